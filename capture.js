@@ -0,0 +1,142 @@
+// Feeds the frames of a camera (a MediaStream, MediaStreamTrack or <video>) to a MosseTrackerJS, frame by frame.
+//
+//   import init from "./pkg/mosse.js";
+//   import { TrackedStream } from "./capture.js";
+//
+//   await init();
+//   const stream = await navigator.mediaDevices.getUserMedia({ video: true });
+//   const tracked = new TrackedStream(stream, { windowSize: 64, onPrediction: (prediction) => draw(prediction) });
+//   tracked.start();
+//   canvas.onclick = (event) => tracked.setTarget(event.offsetX, event.offsetY);
+//
+// Where the browser has MediaStreamTrackProcessor (Chrome, Edge), the frames come as VideoFrames straight from the
+// track. Elsewhere they are drawn from a <video>, once per new video frame (requestVideoFrameCallback) or, failing
+// that, once per animation frame in which the video moved on. The frames are tracked on the main thread; see the
+// Web Worker section of the README for moving the tracking off it.
+
+import { MosseTrackerJS } from "./pkg/mosse.js";
+
+export class TrackedStream {
+  constructor(source, { windowSize = 64, onPrediction = () => {}, onFrame = () => {} } = {}) {
+    this.source = source;
+    this.windowSize = windowSize;
+    // called with the prediction of every tracked frame (a plain object, see PredictionJS), and its timestamp in
+    // milliseconds
+    this.onPrediction = onPrediction;
+    // called with every frame (an ImageData), tracked or not, e.g. to draw it
+    this.onFrame = onFrame;
+    this.tracker = null;
+    this.target = null;
+    this.running = false;
+  }
+
+  // start pulling frames, until stop()
+  async start() {
+    if (this.running) return;
+    this.running = true;
+    const track = videoTrack(this.source);
+    if (track && "MediaStreamTrackProcessor" in globalThis) {
+      await this.readTrack(track);
+    } else {
+      await this.readVideo(await videoElement(this.source));
+    }
+  }
+
+  stop() {
+    this.running = false;
+    this.reader?.cancel();
+  }
+
+  // train on the target centered on (x, y) of the next frame, and track it from there
+  setTarget(x, y) {
+    this.target = { x: Math.round(x), y: Math.round(y) };
+  }
+
+  // forget the target
+  clearTarget() {
+    this.tracker?.free();
+    this.tracker = null;
+    this.target = null;
+  }
+
+  async readTrack(track) {
+    const processor = new MediaStreamTrackProcessor({ track });
+    this.reader = processor.readable.getReader();
+    while (this.running) {
+      const { value: frame, done } = await this.reader.read();
+      if (done) break;
+      try {
+        this.feed(frame, frame.displayWidth, frame.displayHeight, frame.timestamp / 1000);
+      } finally {
+        frame.close();
+      }
+    }
+  }
+
+  async readVideo(video) {
+    const next = (callback) =>
+      "requestVideoFrameCallback" in video
+        ? video.requestVideoFrameCallback((now, metadata) => callback(metadata.mediaTime * 1000))
+        : requestAnimationFrame(() => callback(video.currentTime * 1000));
+    let last = null;
+    const loop = (time) => {
+      if (!this.running) return;
+      if (video.videoWidth && video.videoHeight && time !== last) {
+        last = time;
+        this.feed(video, video.videoWidth, video.videoHeight, time);
+      }
+      next(loop);
+    };
+    next(loop);
+  }
+
+  // track one frame (a VideoFrame, or a <video> at its current frame)
+  feed(image, width, height, time) {
+    if (!this.canvas || this.canvas.width !== width || this.canvas.height !== height) {
+      // a tracker only takes the frame size it was made for: when the camera changes it, the target is lost
+      this.canvas = new OffscreenCanvas(width, height);
+      this.context = this.canvas.getContext("2d", { willReadFrequently: true });
+      this.tracker?.free();
+      this.tracker = null;
+    }
+    this.context.drawImage(image, 0, 0, width, height);
+    const pixels = this.context.getImageData(0, 0, width, height);
+    this.onFrame(pixels);
+
+    if (this.target) {
+      this.tracker?.free();
+      this.tracker = new MosseTrackerJS(width, height, this.windowSize);
+      this.tracker.train(pixels.data, this.target.x, this.target.y);
+      this.target = null;
+    } else if (this.tracker) {
+      const tracked = this.tracker.track(pixels.data);
+      const { x, y, width, height, angle, psr, confidence, lost, occluded, frame_index } = tracked;
+      tracked.free();
+      this.onPrediction({ x, y, width, height, angle, psr, confidence, lost, occluded, frame_index }, time);
+    }
+  }
+}
+
+function videoTrack(source) {
+  if (typeof MediaStream !== "undefined" && source instanceof MediaStream) {
+    return source.getVideoTracks()[0];
+  }
+  if (typeof MediaStreamTrack !== "undefined" && source instanceof MediaStreamTrack) {
+    return source;
+  }
+  if (source?.srcObject instanceof MediaStream) {
+    return source.srcObject.getVideoTracks()[0];
+  }
+  return null;
+}
+
+// a playing <video> of the source
+async function videoElement(source) {
+  if (source instanceof HTMLVideoElement) return source;
+  const video = document.createElement("video");
+  video.muted = true;
+  video.playsInline = true;
+  video.srcObject = source instanceof MediaStream ? source : new MediaStream([source]);
+  await video.play();
+  return video;
+}
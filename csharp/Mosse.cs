@@ -0,0 +1,163 @@
+// The tracker for C# (and Unity), on the C API of the crate (include/mosse.h). The native library is the one built
+// with `cargo build --release --features ffi`: libmosse.so, libmosse.dylib or mosse.dll, which Unity picks up from
+// Assets/Plugins.
+
+using System;
+using System.Runtime.InteropServices;
+
+namespace Mosse
+{
+    public enum TrackingStatus
+    {
+        Tracking = 0,
+        Occluded = 1,
+        Lost = 2,
+    }
+
+    // a prediction of the tracker (see MossePrediction in include/mosse.h)
+    [StructLayout(LayoutKind.Sequential)]
+    public struct Prediction
+    {
+        // the (sub-pixel) center of the target in the frame
+        public float X;
+        public float Y;
+        // the size of the target in the frame
+        public float Width;
+        public float Height;
+        // clockwise, in radians
+        public float Angle;
+        public float Psr;
+        public float Confidence;
+        public TrackingStatus Status;
+        public uint FrameIndex;
+    }
+
+    public class MosseException : Exception
+    {
+        public MosseException(string message) : base(message) { }
+    }
+
+    // A tracker for one target in frames of width x height. Frames are 8-bit pixels, with rows `stride` bytes apart:
+    // 1 byte per pixel for grayscale, 3 for RGB and 4 for RGBA (such as the Color32 of Unity).
+    public sealed class Tracker : IDisposable
+    {
+        const string Library = "mosse";
+
+        [DllImport(Library)]
+        static extern IntPtr mosse_new(uint width, uint height, uint windowSize);
+
+        [DllImport(Library)]
+        static extern int mosse_train(IntPtr handle, IntPtr data, UIntPtr length, UIntPtr stride,
+            UIntPtr bytesPerPixel, uint x, uint y);
+
+        [DllImport(Library)]
+        static extern int mosse_track(IntPtr handle, IntPtr data, UIntPtr length, UIntPtr stride,
+            UIntPtr bytesPerPixel, out Prediction prediction);
+
+        [DllImport(Library)]
+        static extern IntPtr mosse_last_error(IntPtr handle);
+
+        [DllImport(Library)]
+        static extern void mosse_free(IntPtr handle);
+
+        IntPtr handle;
+
+        public int Width { get; }
+        public int Height { get; }
+
+        public Tracker(int width, int height, int windowSize = 64)
+        {
+            handle = mosse_new((uint)width, (uint)height, (uint)windowSize);
+            if (handle == IntPtr.Zero)
+            {
+                throw new MosseException($"no tracker for a window of {windowSize} in frames of {width}x{height}");
+            }
+            Width = width;
+            Height = height;
+        }
+
+        // learn the target centered on (x, y), in a frame of `length` bytes at `data`
+        public void Train(IntPtr data, int length, int stride, int bytesPerPixel, int x, int y)
+        {
+            Check(mosse_train(Handle, data, (UIntPtr)length, (UIntPtr)stride, (UIntPtr)bytesPerPixel,
+                (uint)x, (uint)y));
+        }
+
+        // locate the target in the next frame, and learn from it
+        public Prediction Track(IntPtr data, int length, int stride, int bytesPerPixel)
+        {
+            Check(mosse_track(Handle, data, (UIntPtr)length, (UIntPtr)stride, (UIntPtr)bytesPerPixel,
+                out var prediction));
+            return prediction;
+        }
+
+        // the same for frames in managed arrays (of bytes, or of pixel structs such as Color32)
+        public void Train<T>(T[] pixels, int bytesPerPixel, int x, int y) where T : struct
+        {
+            using (var pinned = new Pinned(pixels))
+            {
+                Train(pinned.Address, pinned.Length, Width * bytesPerPixel, bytesPerPixel, x, y);
+            }
+        }
+
+        public Prediction Track<T>(T[] pixels, int bytesPerPixel) where T : struct
+        {
+            using (var pinned = new Pinned(pixels))
+            {
+                return Track(pinned.Address, pinned.Length, Width * bytesPerPixel, bytesPerPixel);
+            }
+        }
+
+        public void Dispose()
+        {
+            if (handle != IntPtr.Zero)
+            {
+                mosse_free(handle);
+                handle = IntPtr.Zero;
+            }
+            GC.SuppressFinalize(this);
+        }
+
+        ~Tracker()
+        {
+            Dispose();
+        }
+
+        IntPtr Handle
+        {
+            get
+            {
+                if (handle == IntPtr.Zero)
+                {
+                    throw new ObjectDisposedException(nameof(Tracker));
+                }
+                return handle;
+            }
+        }
+
+        void Check(int status)
+        {
+            if (status != 0)
+            {
+                throw new MosseException(Marshal.PtrToStringAnsi(mosse_last_error(handle)));
+            }
+        }
+
+        // an array that the garbage collector doesn't move while the tracker reads it
+        readonly struct Pinned : IDisposable
+        {
+            readonly GCHandle pin;
+
+            public Pinned(Array array)
+            {
+                pin = GCHandle.Alloc(array, GCHandleType.Pinned);
+                Length = Buffer.ByteLength(array);
+            }
+
+            public IntPtr Address => pin.AddrOfPinnedObject();
+            public int Length { get; }
+
+            public void Dispose() => pin.Free();
+        }
+    }
+}
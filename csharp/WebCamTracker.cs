@@ -0,0 +1,68 @@
+// A Unity example: tracks the region in the middle of the webcam image from the moment the space bar is pressed,
+// and moves `marker` (e.g. a UI element over a RawImage showing the webcam) along with it.
+//
+// Copy this file and Mosse.cs into Assets/, and the native library into Assets/Plugins/.
+
+using Mosse;
+using UnityEngine;
+
+public class WebCamTracker : MonoBehaviour
+{
+    public int windowSize = 64;
+    // follows the target, in the coordinates of the webcam image (the origin at its bottom left)
+    public RectTransform marker;
+
+    WebCamTexture webcam;
+    Tracker tracker;
+    Color32[] pixels;
+
+    void Start()
+    {
+        webcam = new WebCamTexture();
+        webcam.Play();
+    }
+
+    void Update()
+    {
+        // the size of the webcam image is only known once the first frame is in
+        if (!webcam.didUpdateThisFrame || webcam.width < windowSize)
+        {
+            return;
+        }
+        if (pixels == null || pixels.Length != webcam.width * webcam.height)
+        {
+            pixels = new Color32[webcam.width * webcam.height];
+            tracker?.Dispose();
+            tracker = null;
+        }
+        webcam.GetPixels32(pixels);
+
+        if (Input.GetKeyDown(KeyCode.Space))
+        {
+            tracker?.Dispose();
+            tracker = new Tracker(webcam.width, webcam.height, windowSize);
+            tracker.Train(pixels, 4, webcam.width / 2, webcam.height / 2);
+            return;
+        }
+        if (tracker == null)
+        {
+            return;
+        }
+
+        // GetPixels32 starts at the bottom row, so the tracker sees the image upside down, which it doesn't mind:
+        // its y counts from the bottom, as in Unity
+        var prediction = tracker.Track(pixels, 4);
+        if (marker != null)
+        {
+            marker.anchoredPosition = new Vector2(prediction.X, prediction.Y);
+            marker.sizeDelta = new Vector2(prediction.Width, prediction.Height);
+            marker.gameObject.SetActive(prediction.Status != TrackingStatus.Lost);
+        }
+    }
+
+    void OnDestroy()
+    {
+        tracker?.Dispose();
+        webcam?.Stop();
+    }
+}
@@ -19,7 +19,7 @@ fn main() {
     // remove the first element from the list of arguments, which is the call to the binary
     let inputfiles = &args[1..];
 
-    if inputfiles.len() == 0 {
+    if inputfiles.is_empty() {
         panic!("no input files specified");
     }
     let mut images = inputfiles.iter().map(|path| image::open(path).unwrap());
@@ -30,12 +30,13 @@ fn main() {
     let window_size = 64; //size of the tracking window
     let psr_thresh = 7.0; // how high the psr must be before prediction is considered succesful.
     let settings = MosseTrackerSettings {
-        window_size: window_size,
+        window_size,
         width,
         height,
         regularization: 0.001,
         learning_rate: 0.05,
         psr_threshold: psr_thresh,
+        ..Default::default()
     };
     let desperation_threshold = 3; // how many frames the tracker should try to re-acquire the target until we consider it failed
     let mut multi_tracker = MultiMosseTracker::new(settings, desperation_threshold);
@@ -53,10 +54,11 @@ fn main() {
     ];
 
     // Add all the targets  on the first image to the multitracker
-    let first_img = first.to_luma8();
     for (i, coords) in target_coords.into_iter().enumerate() {
         let start = Instant::now();
-        multi_tracker.add_or_replace_target(i as u32, coords, &first_img);
+        multi_tracker
+            .add_or_replace_target(i as u32, coords, &first)
+            .expect("the targets lie inside the first frame");
         println!(
             "Added object on initial frame to multi-tracker in {} ms",
             start.elapsed().as_millis()
@@ -69,7 +71,9 @@ fn main() {
 
         // track the objects on the new frame
         let start = Instant::now();
-        let predictions = multi_tracker.track(&dyn_img.to_luma8());
+        let predictions = multi_tracker
+            .track(&dyn_img)
+            .expect("all frames have the size of the first one");
 
         println!(
             "Processed sample image no. {} in {} ms. Active trackers: {}.",
@@ -93,13 +97,11 @@ fn main() {
                 pred.location.0 as i32,
                 pred.location.1 as i32,
             );
+            let bbox = pred.bbox();
             draw_hollow_rect_mut(
                 &mut img_copy,
-                Rect::at(
-                    pred.location.0.saturating_sub(window_size / 2) as i32,
-                    pred.location.1.saturating_sub(window_size / 2) as i32,
-                )
-                .of_size(window_size, window_size),
+                Rect::at(bbox.x as i32, bbox.y as i32)
+                    .of_size(bbox.width as u32, bbox.height as u32),
                 color,
             );
 
@@ -124,7 +126,9 @@ fn main() {
                 &mut img_copy,
                 color,
                 (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2) + FONT_SCALE as u32).try_into().unwrap(),
+                (pred.location.1 - (window_size / 2) + FONT_SCALE as u32)
+                    .try_into()
+                    .unwrap(),
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("PSR: {:.2}", pred.psr),
@@ -0,0 +1,50 @@
+// Tracks a region of a live camera, and prints where it is in every frame:
+//
+//   cargo run --release --features camera --example track_camera -- /dev/video0 [<x> <y> <size>]
+//
+// The target is the size x size square centered on (x, y) of the first frame (by default, 64 x 64 pixels in the
+// middle of the view): hold it there when the example starts. Stop with Ctrl+C.
+
+use mosse::video::VideoFrames;
+use mosse::{MosseTracker, TrackingStatus};
+use std::env;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 && args.len() != 5 {
+        panic!("usage: track_camera <device> [<x> <y> <size>]");
+    }
+    let mut frames = VideoFrames::camera(&args[1]).expect("the camera can be opened");
+    let (width, height) = frames.dimensions();
+    let number = |i: usize| -> u32 { args[i].parse().expect("x, y and size are numbers") };
+    let (center, size) = match args.len() {
+        5 => ((number(2), number(3)), number(4)),
+        _ => ((width / 2, height / 2), 64),
+    };
+
+    let mut tracker = MosseTracker::builder()
+        .frame_size(width, height)
+        .window_size(size)
+        .build()
+        .expect("the window fits the frames of the camera");
+    let first = frames.next().expect("the camera sends frames").unwrap();
+    tracker
+        .train(&first, center)
+        .expect("the target is inside the frame");
+
+    for frame in frames {
+        let prediction = tracker
+            .track(&frame.expect("the camera keeps sending frames"))
+            .expect("the frames keep their size");
+        match prediction.status {
+            TrackingStatus::Lost => println!("frame {}: lost", prediction.frame_index),
+            _ => println!(
+                "frame {}: ({:.1}, {:.1}), PSR {:.1}",
+                prediction.frame_index,
+                prediction.position.0,
+                prediction.position.1,
+                prediction.psr
+            ),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Tracks a target through a video file, and prints where it is in every frame:
+//
+//   cargo run --release --features video --example track_video -- video.mp4 <x> <y> <width> <height> [output]
+//
+// with the box of the target in the first frame (its top left corner and size). With an output file (e.g. out.mp4 or
+// out.gif), it writes the video there with the target drawn in.
+
+use mosse::video::{track_video, track_video_annotated};
+use mosse::BBox;
+use std::env;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 6 && args.len() != 7 {
+        panic!("usage: track_video <video> <x> <y> <width> <height> [output]");
+    }
+    let number = |i: usize| -> f32 { args[i].parse().expect("the box is four numbers") };
+    let init_box = BBox::from_xywh(number(2), number(3), number(4), number(5));
+
+    let predictions = match args.get(6) {
+        Some(output) => track_video_annotated(&args[1], init_box, output),
+        None => track_video(&args[1], init_box),
+    }
+    .expect("the video can be tracked");
+    for prediction in predictions {
+        let bbox = prediction.bbox();
+        println!(
+            "frame {}: ({:.1}, {:.1}) {:.1}x{:.1}, PSR {:.1}{}",
+            prediction.frame_index,
+            bbox.x,
+            bbox.y,
+            bbox.width,
+            bbox.height,
+            prediction.psr,
+            if prediction.status == mosse::TrackingStatus::Lost {
+                " (lost)"
+            } else {
+                ""
+            }
+        );
+    }
+}
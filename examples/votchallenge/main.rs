@@ -2,7 +2,7 @@ mod trax_protocol;
 
 use std::io::stdin;
 
-use mosse::{MosseTrackerSettings, MultiMosseTracker};
+use mosse::{BBox, MosseTrackerSettings, MultiMosseTracker};
 
 use crate::trax_protocol::{
     ChannelType, Image, ImageType, Region, RegionType, TraxMessageFromClient, TraxMessageFromServer,
@@ -13,7 +13,7 @@ pub enum ServerState {
     Introduction,
     Initialization,
     Reporting {
-        multi_tracker: MultiMosseTracker,
+        multi_tracker: Box<MultiMosseTracker>,
         first_region: Region,
     },
     Termination,
@@ -74,12 +74,13 @@ impl MosseTraxServer {
         let window_size = f64::max(region.width, region.height) as u32; // size of the tracking window
         let psr_thresh = 7.0; // how high the psr must be before prediction is considered succesful.
         let settings = MosseTrackerSettings {
-            window_size: window_size,
+            window_size,
             width,
             height,
             regularization: 0.001,
             learning_rate: 0.05,
             psr_threshold: psr_thresh,
+            ..Default::default()
         };
 
         // FIXME: Could I get away with a single MosseTracker here? This would make things simpler,
@@ -87,14 +88,19 @@ impl MosseTraxServer {
         let desperation_threshold = 300000; // how many frames the tracker should try to re-acquire the target until we consider it failed
         let mut multi_tracker = MultiMosseTracker::new(settings, desperation_threshold);
 
-        let coords = (
-            (region.x + region.width / 2.) as u32,
-            (region.y + region.height / 2.) as u32,
-        );
-        multi_tracker.add_or_replace_target(0, coords, &first.to_luma8());
+        let coords = BBox::from_xywh(
+            region.x as f32,
+            region.y as f32,
+            region.width as f32,
+            region.height as f32,
+        )
+        .pixel_center();
+        multi_tracker
+            .add_or_replace_target(0, coords, &first)
+            .expect("the initial region lies inside the frame");
 
         self.state = ServerState::Reporting {
-            multi_tracker,
+            multi_tracker: Box::new(multi_tracker),
             first_region: region.clone(),
         };
 
@@ -122,19 +128,19 @@ impl MosseTraxServer {
         };
 
         let frame = &images[0].open().unwrap();
-        let predictions = multi_tracker.track(&frame.to_luma8());
+        let predictions = multi_tracker
+            .track(frame)
+            .expect("all frames of a sequence have the same size");
         assert_eq!(predictions.len(), 1);
         let (_obj_id, pred) = &predictions[0];
 
+        let bbox = BBox::around(
+            pred.location,
+            (first_region.width as f32, first_region.height as f32),
+        );
         let region = Region {
-            x: pred
-                .location
-                .0
-                .saturating_sub((first_region.width / 2.) as u32) as f64,
-            y: pred
-                .location
-                .1
-                .saturating_sub((first_region.height / 2.) as u32) as f64,
+            x: bbox.x.max(0.0) as f64,
+            y: bbox.y.max(0.0) as f64,
             height: first_region.height,
             width: first_region.width,
         };
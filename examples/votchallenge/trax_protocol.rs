@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-///! This module implements the trax protocol as described in https://trax.readthedocs.io/en/latest/protocol.html
+//! This module implements the trax protocol as described in https://trax.readthedocs.io/en/latest/protocol.html
 // FIXME: split this out into its own crate?
 use std::{fmt::Display, path::PathBuf, str::FromStr};
 
@@ -171,8 +171,8 @@ impl FromStr for Region {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let [x, y, width, height]: [f64; 4] = s
-            .split(|c| c == ',' || c == '\t')
-            .map(|n| f64::from_str(n))
+            .split([',', '\t'])
+            .map(f64::from_str)
             .collect::<Result<Vec<_>, _>>()?
             .try_into()
             .map_err(|v| anyhow::anyhow!("{v:?} could not be coerced into a [f64; 4]"))?;
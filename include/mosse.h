@@ -0,0 +1,75 @@
+#ifndef MOSSE_H
+#define MOSSE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define MOSSE_OK 0
+
+// the function failed: mosse_last_error() says why
+#define MOSSE_ERROR -1
+
+// the values of MossePrediction::status (see TrackingStatus)
+#define MOSSE_TRACKING 0
+
+#define MOSSE_OCCLUDED 1
+
+#define MOSSE_LOST 2
+
+typedef struct MosseHandle MosseHandle;
+
+// a Prediction for C
+typedef struct MossePrediction {
+  // the (sub-pixel) center of the target in the frame
+  float x;
+  float y;
+  // the size of the target in the frame
+  float width;
+  float height;
+  // clockwise, in radians
+  float angle;
+  float psr;
+  float confidence;
+  // MOSSE_TRACKING, MOSSE_OCCLUDED or MOSSE_LOST
+  int32_t status;
+  uint32_t frame_index;
+} MossePrediction;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// a tracker for width x height frames, with a window of window_size (null for settings out of range)
+MosseHandle *mosse_new(uint32_t width, uint32_t height, uint32_t window_size);
+
+// train on the target centered on (x, y)
+int32_t mosse_train(MosseHandle *handle,
+                    const uint8_t *data,
+                    size_t length,
+                    size_t stride,
+                    size_t bytes_per_pixel,
+                    uint32_t x,
+                    uint32_t y);
+
+// locate the target in the next frame, and learn from it
+int32_t mosse_track(MosseHandle *handle,
+                    const uint8_t *data,
+                    size_t length,
+                    size_t stride,
+                    size_t bytes_per_pixel,
+                    MossePrediction *prediction);
+
+// Why the last call on the handle failed (an empty string if none did). The string belongs to the handle, and lives
+// until the next call on it.
+const char *mosse_last_error(const MosseHandle *handle);
+
+void mosse_free(MosseHandle *handle);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // MOSSE_H
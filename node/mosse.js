@@ -0,0 +1,26 @@
+// The tracker for Node.js and Electron, as a native addon of the crate (see src/node.rs).
+//
+// Build the library first, with `cargo build --release --features node`. It is looked up in $MOSSE_LIBRARY, then in
+// target/release of the repository.
+//
+//   const { Tracker } = require("./node/mosse.js");
+//   const tracker = new Tracker(width, height, 64);
+//   tracker.train(firstFrame, x, y);
+//   const prediction = tracker.track(nextFrame);  // { x, y, width, height, psr, confidence, status, frameIndex }
+
+"use strict";
+
+const path = require("path");
+
+function libraryPath() {
+  if (process.env.MOSSE_LIBRARY) {
+    return process.env.MOSSE_LIBRARY;
+  }
+  const name = { win32: "mosse.dll", darwin: "libmosse.dylib" }[process.platform] || "libmosse.so";
+  return path.join(__dirname, "..", "target", "release", name);
+}
+
+// an addon is any shared library with the N-API entry point, whatever its extension
+const addon = { exports: {} };
+process.dlopen(addon, libraryPath());
+module.exports = addon.exports;
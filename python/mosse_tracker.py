@@ -0,0 +1,140 @@
+"""The tracker for Python, on the C API of the crate (include/mosse.h).
+
+Build the library first, with `cargo build --release --features ffi`. It is looked up in $MOSSE_LIBRARY, then in
+target/release of the repository, then on the library path.
+
+Frames are 8-bit numpy arrays (or anything else with the buffer protocol) of height x width (grayscale), or
+height x width x channels (RGB, RGBA). Views with padded rows, such as slices of a larger frame, are tracked
+without a copy.
+
+    tracker = mosse_tracker.Tracker(width, height, window_size=64)
+    tracker.train(frame, (x, y))
+    prediction = tracker.track(next_frame)  # {"x": ..., "y": ..., "psr": ..., "status": "tracking", ...}
+"""
+
+import ctypes
+import ctypes.util
+import os
+import sys
+
+__all__ = ["Tracker", "MosseError"]
+
+_STATUS = {0: "tracking", 1: "occluded", 2: "lost"}
+
+
+class MosseError(Exception):
+    pass
+
+
+class _Prediction(ctypes.Structure):
+    _fields_ = [
+        ("x", ctypes.c_float),
+        ("y", ctypes.c_float),
+        ("width", ctypes.c_float),
+        ("height", ctypes.c_float),
+        ("angle", ctypes.c_float),
+        ("psr", ctypes.c_float),
+        ("confidence", ctypes.c_float),
+        ("status", ctypes.c_int32),
+        ("frame_index", ctypes.c_uint32),
+    ]
+
+
+def _library_path():
+    if "MOSSE_LIBRARY" in os.environ:
+        return os.environ["MOSSE_LIBRARY"]
+    name = {"win32": "mosse.dll", "darwin": "libmosse.dylib"}.get(sys.platform, "libmosse.so")
+    built = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "target", "release", name)
+    if os.path.exists(built):
+        return built
+    return ctypes.util.find_library("mosse") or name
+
+
+def _load():
+    library = ctypes.CDLL(_library_path())
+    frame = [ctypes.c_void_p, ctypes.c_void_p, ctypes.c_size_t, ctypes.c_size_t, ctypes.c_size_t]
+    library.mosse_new.argtypes = [ctypes.c_uint32, ctypes.c_uint32, ctypes.c_uint32]
+    library.mosse_new.restype = ctypes.c_void_p
+    library.mosse_train.argtypes = frame + [ctypes.c_uint32, ctypes.c_uint32]
+    library.mosse_train.restype = ctypes.c_int32
+    library.mosse_track.argtypes = frame + [ctypes.POINTER(_Prediction)]
+    library.mosse_track.restype = ctypes.c_int32
+    library.mosse_last_error.argtypes = [ctypes.c_void_p]
+    library.mosse_last_error.restype = ctypes.c_char_p
+    library.mosse_free.argtypes = [ctypes.c_void_p]
+    library.mosse_free.restype = None
+    return library
+
+
+_library = _load()
+
+
+def _frame(frame):
+    """(keep-alive, address, length, stride, bytes per pixel, (width, height)) of a frame"""
+    view = memoryview(frame)
+    if view.itemsize != 1:
+        raise MosseError("frames are 8-bit, got items of %d bytes" % view.itemsize)
+    if view.ndim == 2:
+        (height, width), (stride, bytes_per_pixel) = view.shape, view.strides
+    elif view.ndim == 3:
+        (height, width, channels), (stride, bytes_per_pixel, step) = view.shape, view.strides
+        if step != 1 or channels not in (1, 3, 4):
+            raise MosseError("expected 1, 3 or 4 channels next to each other")
+    else:
+        raise MosseError("expected a frame of height x width (x channels), got %d dimensions" % view.ndim)
+    if stride <= 0 or bytes_per_pixel <= 0:
+        raise MosseError("frames with negative strides are not supported")
+    length = (height - 1) * stride + width * bytes_per_pixel
+
+    interface = getattr(frame, "__array_interface__", None)
+    if interface is not None:
+        return frame, interface["data"][0], length, stride, bytes_per_pixel, (width, height)
+    # other buffers are read as one block, which needs a copy if they can't be written
+    if not view.c_contiguous:
+        raise MosseError("buffers other than numpy arrays need to be contiguous")
+    if view.readonly:
+        view = memoryview(bytearray(view))
+    block = (ctypes.c_uint8 * view.nbytes).from_buffer(view.cast("B"))
+    return block, ctypes.addressof(block), length, stride, bytes_per_pixel, (width, height)
+
+
+class Tracker:
+    """A MOSSE tracker for one target in frames of width x height."""
+
+    def __init__(self, width, height, window_size=64):
+        self.size = (width, height)
+        self._handle = _library.mosse_new(width, height, window_size)
+        if not self._handle:
+            raise MosseError("no tracker for a window of %d in frames of %dx%d" % (window_size, width, height))
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _library.mosse_free(self._handle)
+            self._handle = None
+
+    def train(self, frame, center):
+        """learn the target centered on center (x, y)"""
+        alive, address, length, stride, bytes_per_pixel, size = self._frame(frame)
+        x, y = center
+        self._check(_library.mosse_train(self._handle, address, length, stride, bytes_per_pixel, x, y))
+
+    def track(self, frame):
+        """locate the target in the next frame and learn from it, as a dict"""
+        alive, address, length, stride, bytes_per_pixel, size = self._frame(frame)
+        prediction = _Prediction()
+        self._check(
+            _library.mosse_track(self._handle, address, length, stride, bytes_per_pixel, ctypes.byref(prediction))
+        )
+        result = {name: getattr(prediction, name) for name, _ in _Prediction._fields_}
+        result["status"] = _STATUS[prediction.status]
+        return result
+
+    def _frame(self, frame):
+        described = _frame(frame)
+        if described[-1] != self.size:
+            raise MosseError("expected a frame of %dx%d, got one of %dx%d" % (self.size + described[-1]))
+        return described
+
+    def _check(self, status):
+        if status != 0:
+            raise MosseError(_library.mosse_last_error(self._handle).decode())
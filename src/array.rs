@@ -0,0 +1,129 @@
+// ndarray arrays in and out of the trackers (with the ndarray feature):
+//
+// - a 2D array (height x width, indexed [y, x]) of u8, u16 or f32 is a frame. The tracker copies the windows it
+//   looks at out of the array where it is, with any strides, so a view of part of a larger array (or a transposed
+//   one) tracks as well. u16 and f32 values map onto the gray values as in an ImageBuffer of them (see DeepSample).
+// - MosseTracker::response_array() views the response map to the last frame where it is, and filter_array() is
+//   the filter in the window.
+//
+//   let prediction = tracker.track(&frame.view())?;
+//   let response = tracker.response_array().unwrap();
+
+use crate::frame::to_gray;
+use crate::{utils, DeepSample, FrameSource, MosseTracker};
+use image::{GrayImage, Luma};
+use ndarray::{Array2, ArrayBase, ArrayView2, Data, Ix2};
+
+// the samples of the arrays that are frames
+pub trait ArraySample: Copy + Send + Sync + 'static {
+    fn gray(self) -> u8;
+}
+
+impl ArraySample for u8 {
+    fn gray(self) -> u8 {
+        self
+    }
+}
+
+impl<S: DeepSample> ArraySample for S {
+    fn gray(self) -> u8 {
+        to_gray(self.value(), S::RANGE)
+    }
+}
+
+impl<S, A> FrameSource for ArrayBase<S, Ix2>
+where
+    S: Data<Elem = A> + Sync,
+    A: ArraySample,
+{
+    type Pixel = Luma<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        let (height, width) = self.dim();
+        (width as u32, height as u32)
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut GrayImage) {
+        let (left, top, inside_width, inside_height) =
+            utils::crop_region(self.dimensions(), width, height, center);
+        if output.dimensions() != (width, height)
+            || (inside_width, inside_height) != (width, height)
+        {
+            *output = GrayImage::new(width, height);
+        }
+        for y in 0..inside_height {
+            for x in 0..inside_width {
+                let value = self[[(top + y) as usize, (left + x) as usize]];
+                output.put_pixel(x, y, Luma([value.gray()]));
+            }
+        }
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        let (width, height) = self.dimensions();
+        GrayImage::from_fn(width, height, |x, y| {
+            Luma([self[[y as usize, x as usize]].gray()])
+        })
+    }
+}
+
+impl MosseTracker {
+    // the response map as a window_size() x window_size() array (None until the first frame has been tracked)
+    pub fn response_array(&self) -> Option<ArrayView2<'_, f32>> {
+        let size = self.window_size() as usize;
+        ArrayView2::from_shape((size, size), self.response_map()).ok()
+    }
+
+    // the filter in the window, as filter_image() shows it but without stretching its values
+    pub fn filter_array(&self) -> Array2<f32> {
+        let size = self.window_size() as usize;
+        Array2::from_shape_vec((size, size), self.filter_template())
+            .expect("one value per window pixel")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+    use ndarray::{s, Array2};
+
+    fn array(center: (u32, u32)) -> Array2<u8> {
+        let frame = frame(center);
+        Array2::from_shape_vec((96, 128), frame.into_raw()).unwrap()
+    }
+
+    #[test]
+    fn arrays_track_like_image_buffers() {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        let mut array_tracker = MosseTracker::new(&test_settings(128, 96));
+        assert_eq!(array_tracker.response_array(), None);
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+        array_tracker.train(&array((60, 50)), (60, 50)).unwrap();
+
+        for center in [(63, 48), (66, 47)] {
+            let prediction = tracker.track(&frame(center)).unwrap();
+            // a view of the frame in a larger array
+            let mut larger = Array2::zeros((100, 140));
+            larger.slice_mut(s![2..98, 5..133]).assign(&array(center));
+            let view = larger.slice(s![2..98, 5..133]);
+            assert_eq!(view.to_luma(), frame(center));
+            assert_eq!(array_tracker.track(&view).unwrap(), prediction);
+        }
+        assert_eq!(
+            array_tracker.response_array().unwrap().as_slice().unwrap(),
+            tracker.response_map()
+        );
+        assert_eq!(array_tracker.filter_array().dim(), (32, 32));
+        assert_eq!(
+            array_tracker.filter_array().as_slice().unwrap(),
+            tracker.filter_template()
+        );
+
+        // f32 frames from 0.0 to 1.0, and transposed views
+        let floats = array((66, 47)).mapv(|value| value as f32 / 255.0);
+        assert_eq!(floats.to_luma(), frame((66, 47)));
+        let transposed = floats.t().to_owned();
+        assert_eq!(transposed.t().to_luma(), frame((66, 47)));
+    }
+}
@@ -0,0 +1,131 @@
+// An axis-aligned bounding box in frame pixels, and its conversions between the usual formats:
+// top left corner plus size (xywh, as in OTB and VOT ground truth), center plus size (cxcywh, as the trackers see
+// a target) and two corners (x1y1x2y2, as most drawing APIs want it).
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BBox {
+    // the top left corner
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl BBox {
+    pub fn from_xywh(x: f32, y: f32, width: f32, height: f32) -> BBox {
+        BBox {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_cxcywh(cx: f32, cy: f32, width: f32, height: f32) -> BBox {
+        BBox::from_xywh(cx - width / 2.0, cy - height / 2.0, width, height)
+    }
+
+    // from the top left and the bottom right corner, in either order
+    pub fn from_x1y1x2y2(x1: f32, y1: f32, x2: f32, y2: f32) -> BBox {
+        BBox::from_xywh(x1.min(x2), y1.min(y2), (x2 - x1).abs(), (y2 - y1).abs())
+    }
+
+    // a box of `size` (width, height) around a pixel center, like the ones the trackers take
+    pub fn around(center: (u32, u32), size: (f32, f32)) -> BBox {
+        BBox::from_cxcywh(center.0 as f32, center.1 as f32, size.0, size.1)
+    }
+
+    pub fn xywh(&self) -> [f32; 4] {
+        [self.x, self.y, self.width, self.height]
+    }
+
+    pub fn cxcywh(&self) -> [f32; 4] {
+        let (cx, cy) = self.center();
+        [cx, cy, self.width, self.height]
+    }
+
+    pub fn x1y1x2y2(&self) -> [f32; 4] {
+        [self.x, self.y, self.x + self.width, self.y + self.height]
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    // the center rounded to the pixel grid (and to 0 at the top and left), e.g. to train a tracker on
+    pub fn pixel_center(&self) -> (u32, u32) {
+        let (cx, cy) = self.center();
+        (cx.round().max(0.0) as u32, cy.round().max(0.0) as u32)
+    }
+
+    pub fn area(&self) -> f32 {
+        self.width.max(0.0) * self.height.max(0.0)
+    }
+
+    // the part of the box inside a width x height frame (empty if they don't overlap)
+    pub fn clamp(&self, width: u32, height: u32) -> BBox {
+        let [x1, y1, x2, y2] = self.x1y1x2y2();
+        let (x1, y1) = (x1.clamp(0.0, width as f32), y1.clamp(0.0, height as f32));
+        let (x2, y2) = (x2.clamp(x1, width as f32), y2.clamp(y1, height as f32));
+        BBox::from_xywh(x1, y1, x2 - x1, y2 - y1)
+    }
+
+    // the box grown (factor > 1) or shrunk (factor < 1) around its center
+    pub fn scale(&self, factor: f32) -> BBox {
+        let (cx, cy) = self.center();
+        BBox::from_cxcywh(cx, cy, self.width * factor, self.height * factor)
+    }
+
+    // the same box in a frame resized by (sx, sy), e.g. to map boxes from a downscaled frame back to the original
+    pub fn resize(&self, sx: f32, sy: f32) -> BBox {
+        BBox::from_xywh(self.x * sx, self.y * sy, self.width * sx, self.height * sy)
+    }
+
+    // intersection over union: the overlap of two boxes, from 0 (disjoint) to 1 (identical)
+    pub fn iou(&self, other: &BBox) -> f32 {
+        let [ax1, ay1, ax2, ay2] = self.x1y1x2y2();
+        let [bx1, by1, bx2, by2] = other.x1y1x2y2();
+        let intersection =
+            (ax2.min(bx2) - ax1.max(bx1)).max(0.0) * (ay2.min(by2) - ay1.max(by1)).max(0.0);
+        let union = self.area() + other.area() - intersection;
+        if union > 0.0 {
+            intersection / union
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_convert() {
+        let bbox = BBox::from_xywh(10.0, 20.0, 30.0, 40.0);
+        assert_eq!(bbox.cxcywh(), [25.0, 40.0, 30.0, 40.0]);
+        assert_eq!(bbox.x1y1x2y2(), [10.0, 20.0, 40.0, 60.0]);
+        assert_eq!(BBox::from_cxcywh(25.0, 40.0, 30.0, 40.0), bbox);
+        assert_eq!(BBox::from_x1y1x2y2(40.0, 60.0, 10.0, 20.0), bbox);
+        assert_eq!(BBox::around((25, 40), (30.0, 40.0)), bbox);
+        assert_eq!(bbox.pixel_center(), (25, 40));
+    }
+
+    #[test]
+    fn clamps_scales_and_overlaps() {
+        let bbox = BBox::from_xywh(-10.0, 50.0, 40.0, 40.0);
+        assert_eq!(bbox.clamp(100, 80), BBox::from_xywh(0.0, 50.0, 30.0, 30.0));
+        assert_eq!(bbox.clamp(100, 40).area(), 0.0);
+
+        assert_eq!(bbox.scale(0.5), BBox::from_xywh(0.0, 60.0, 20.0, 20.0));
+        assert_eq!(
+            bbox.resize(2.0, 0.5),
+            BBox::from_xywh(-20.0, 25.0, 80.0, 20.0)
+        );
+
+        let other = BBox::from_xywh(10.0, 50.0, 40.0, 40.0);
+        assert_eq!(bbox.iou(&bbox), 1.0);
+        assert!((bbox.iou(&other) - 800.0 / 2400.0).abs() < 1e-6);
+        assert_eq!(bbox.iou(&BBox::from_xywh(100.0, 0.0, 5.0, 5.0)), 0.0);
+    }
+}
@@ -0,0 +1,856 @@
+// Tracks targets for other programs over HTTP (with the server feature), e.g. as a sidecar of an application that
+// isn't written in Rust:
+//
+//   mosse-server --listen 127.0.0.1:8080
+//   curl -X POST --data-binary @first.jpg 'localhost:8080/sessions?box=120,734,48,32'   -> {"session":1}
+//   curl -X POST --data-binary @next.jpg localhost:8080/sessions/1/track               -> {"id":1,"frame":1,...}
+//   curl -X DELETE localhost:8080/sessions/1
+//
+// Every session is a tracker of one target. Sessions are tracked concurrently, each on the thread of the connection
+// its frame came in on. Responses are JSON, or protobuf for clients that accept application/x-protobuf (see
+// proto/mosse_server.proto). Run it with --help for the details.
+
+use image::{DynamicImage, GrayImage};
+use mosse::export::TrajectoryWriter;
+use mosse::{BBox, MosseTracker, Prediction, TrackingStatus};
+use prost::Message;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: mosse-server [--listen <address>] [--max-sessions <count>] [--idle-timeout <seconds>]
+                    [--max-connections <count>]
+
+Serves trackers over HTTP/1.1 on the address (127.0.0.1:8080 by default). Frames are the bodies of the requests: an
+image (JPEG or PNG), or raw 8-bit grayscale pixels with the width and height in the query (?width=640&height=480).
+At most 64 sessions run at once (--max-sessions), and sessions without a frame for 300 seconds end (--idle-timeout).
+At most 64 connections are served at once (--max-connections), and a connection that sends nothing for 30 seconds
+is closed.
+
+  POST   /sessions?box=<x>,<y>,<width>,<height>[&window=<pixels>]
+         starts tracking the target in the box of the frame: its top left corner and size, in pixels. The window is
+         the larger side of the box by default. Returns {\"session\":<id>}.
+  POST   /sessions/<id>/track
+         tracks the target in the next frame, which is as large as the first one. Returns the prediction:
+         {\"id\":<id>,\"frame\":<n>,\"x\":...,\"y\":...,\"width\":...,\"height\":...,\"psr\":...,\"confidence\":...,\"status\":...}
+         with the status one of tracking, occluded and lost.
+  DELETE /sessions/<id>
+         ends the session.
+
+Errors are {\"error\":<message>}, with status 400 (a bad request or frame), 404 (no such session) or 503 (as many
+sessions or connections as there may be).
+
+Requests with the header Accept: application/x-protobuf get the responses as protobuf messages instead, those of
+proto/mosse_server.proto. There is no gRPC service.";
+
+// the largest frame a request takes, in bytes
+const MAX_BODY: usize = 64 << 20;
+// the longest request line or header, in bytes, and the most headers a request has
+const MAX_LINE: usize = 8 << 10;
+const MAX_HEADERS: usize = 100;
+// how long a connection may take to send (a part of) a request, or to take a response
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Session {
+    tracker: MosseTracker,
+    // the frames tracked so far
+    frames: u32,
+    last_used: Instant,
+}
+
+struct Sessions {
+    sessions: Mutex<HashMap<u32, Arc<Mutex<Session>>>>,
+    next_id: AtomicU32,
+    max_sessions: usize,
+    // how long a session lives without frames
+    idle_timeout: Duration,
+}
+
+impl Sessions {
+    fn new(max_sessions: usize, idle_timeout: Duration) -> Sessions {
+        Sessions {
+            sessions: Mutex::new(HashMap::new()),
+            next_id: AtomicU32::new(0),
+            max_sessions,
+            idle_timeout,
+        }
+    }
+
+    // the id of a new session of `tracker`, or None if there are as many sessions as there may be
+    fn start(&self, tracker: MosseTracker) -> Option<u32> {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= self.max_sessions {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let session = Session {
+            tracker,
+            frames: 0,
+            last_used: Instant::now(),
+        };
+        sessions.insert(id, Arc::new(Mutex::new(session)));
+        Some(id)
+    }
+
+    fn get(&self, id: u32) -> Option<Arc<Mutex<Session>>> {
+        self.sessions.lock().unwrap().get(&id).cloned()
+    }
+
+    fn end(&self, id: u32) -> bool {
+        self.sessions.lock().unwrap().remove(&id).is_some()
+    }
+
+    // end the sessions that have been idle for too long (but not those tracking right now)
+    fn expire(&self) {
+        self.sessions.lock().unwrap().retain(|_, session| {
+            session.try_lock().map_or(true, |session| {
+                session.last_used.elapsed() < self.idle_timeout
+            })
+        });
+    }
+}
+
+// the connections being served, counted down again when one is done
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    // None if there are `max` connections already
+    fn open(connections: &Arc<AtomicUsize>, max: usize) -> Option<Connection> {
+        if connections.fetch_add(1, Ordering::SeqCst) >= max {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Connection(Arc::clone(connections)))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    body: Vec<u8>,
+    keep_alive: bool,
+    // whether the client takes protobuf responses
+    protobuf: bool,
+}
+
+// the protobuf messages of the responses (see proto/mosse_server.proto)
+mod protobuf {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Session {
+        #[prost(uint32, tag = "1")]
+        pub session: u32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Status {
+        Tracking = 0,
+        Occluded = 1,
+        Lost = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Prediction {
+        #[prost(uint32, tag = "1")]
+        pub id: u32,
+        #[prost(uint32, tag = "2")]
+        pub frame: u32,
+        #[prost(float, tag = "3")]
+        pub x: f32,
+        #[prost(float, tag = "4")]
+        pub y: f32,
+        #[prost(float, tag = "5")]
+        pub width: f32,
+        #[prost(float, tag = "6")]
+        pub height: f32,
+        #[prost(float, tag = "7")]
+        pub psr: f32,
+        #[prost(float, tag = "8")]
+        pub confidence: f32,
+        #[prost(enumeration = "Status", tag = "9")]
+        pub status: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Error {
+        #[prost(string, tag = "1")]
+        pub error: String,
+    }
+}
+
+enum Body {
+    Session(u32),
+    // the prediction of session `id` in its frame `frame`
+    Prediction {
+        id: u32,
+        frame: u32,
+        prediction: Prediction,
+    },
+    Empty,
+    Error(String),
+}
+
+struct Response {
+    status: &'static str,
+    body: Body,
+}
+
+impl Response {
+    fn ok(status: &'static str, body: Body) -> Response {
+        Response { status, body }
+    }
+
+    fn error(status: &'static str, message: impl ToString) -> Response {
+        Response {
+            status,
+            body: Body::Error(message.to_string()),
+        }
+    }
+
+    fn json(&self) -> String {
+        match &self.body {
+            Body::Session(id) => format!("{{\"session\":{}}}", id),
+            Body::Prediction {
+                id,
+                frame,
+                prediction,
+            } => {
+                let mut json = TrajectoryWriter::json_lines(Vec::new());
+                json.write(*id, *frame, prediction)
+                    .expect("writing to memory doesn't fail");
+                String::from_utf8_lossy(&json.into_inner())
+                    .trim_end()
+                    .to_string()
+            }
+            Body::Empty => "{}".to_string(),
+            Body::Error(message) => format!("{{\"error\":{}}}", json_string(message)),
+        }
+    }
+
+    fn protobuf(&self) -> Vec<u8> {
+        match &self.body {
+            Body::Session(id) => protobuf::Session { session: *id }.encode_to_vec(),
+            Body::Prediction {
+                id,
+                frame,
+                prediction,
+            } => {
+                let [x, y, width, height] = prediction.bbox().xywh();
+                let status = match prediction.status {
+                    TrackingStatus::Tracking => protobuf::Status::Tracking,
+                    TrackingStatus::Occluded => protobuf::Status::Occluded,
+                    TrackingStatus::Lost => protobuf::Status::Lost,
+                };
+                protobuf::Prediction {
+                    id: *id,
+                    frame: *frame,
+                    x,
+                    y,
+                    width,
+                    height,
+                    psr: prediction.psr,
+                    confidence: prediction.confidence,
+                    status: status as i32,
+                }
+                .encode_to_vec()
+            }
+            Body::Empty => Vec::new(),
+            Body::Error(message) => protobuf::Error {
+                error: message.clone(),
+            }
+            .encode_to_vec(),
+        }
+    }
+}
+
+// a JSON string of `text`, with quotes, backslashes and control characters escaped
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn main() {
+    let mut address = "127.0.0.1:8080".to_string();
+    let (mut max_sessions, mut idle_timeout, mut max_connections) = (64, 300, 64);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(value) => value,
+            None => usage_error(&format!("{} needs a value", arg)),
+        };
+        let mut number = || {
+            let value = value();
+            match value.parse() {
+                Ok(number) => number,
+                Err(_) => usage_error(&format!("{} takes a number, not {}", arg, value)),
+            }
+        };
+        match arg.as_str() {
+            "--listen" => address = value(),
+            "--max-sessions" => max_sessions = number() as usize,
+            "--idle-timeout" => idle_timeout = number(),
+            "--max-connections" => max_connections = number() as usize,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => usage_error(&format!("unknown argument {}", arg)),
+        }
+    }
+
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("mosse-server: can't listen on {}: {}", address, error);
+            process::exit(1);
+        }
+    };
+    eprintln!("mosse-server: listening on {}", address);
+    let sessions = Arc::new(Sessions::new(
+        max_sessions,
+        Duration::from_secs(idle_timeout),
+    ));
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = stream.and_then(|stream| {
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            Ok(stream)
+        });
+        match stream {
+            Ok(mut stream) => match Connection::open(&connections, max_connections) {
+                Some(connection) => {
+                    let sessions = Arc::clone(&sessions);
+                    thread::spawn(move || {
+                        let _connection = connection;
+                        if let Err(error) = serve(stream, &sessions) {
+                            eprintln!("mosse-server: {}", error);
+                        }
+                    });
+                }
+                None => {
+                    let response =
+                        Response::error("503 Service Unavailable", "too many connections");
+                    // the client may be gone already
+                    let _ = write_response(&mut stream, &response, false, false);
+                }
+            },
+            Err(error) => eprintln!("mosse-server: {}", error),
+        }
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+// the requests of a connection, one after the other
+fn serve(stream: TcpStream, sessions: &Sessions) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let (response, keep_alive, protobuf) = match read_request(&mut reader) {
+            Ok(Some(request)) => (
+                handle(&request, sessions),
+                request.keep_alive,
+                request.protobuf,
+            ),
+            // the client is done, or went quiet
+            Ok(None) => return Ok(()),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(())
+            }
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                (Response::error("400 Bad Request", error), false, false)
+            }
+            Err(error) => return Err(error),
+        };
+        write_response(&mut writer, &response, keep_alive, protobuf)?;
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+fn write_response(
+    writer: &mut impl Write,
+    response: &Response,
+    keep_alive: bool,
+    protobuf: bool,
+) -> io::Result<()> {
+    let (content_type, body) = match protobuf {
+        true => ("application/x-protobuf", response.protobuf()),
+        false => ("application/json", response.json().into_bytes()),
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+        response.status,
+        content_type,
+        body.len(),
+        if keep_alive { "keep-alive" } else { "close" },
+    )?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// a line of the request head into `line`, of at most MAX_LINE bytes. 0 at the end of the stream.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let read = Read::take(&mut *reader, MAX_LINE as u64).read_line(line)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(invalid("a line of the request is too long"));
+    }
+    Ok(read)
+}
+
+// the pairs of a query string, with their keys and values decoded
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+// `text` with its %XX escapes, and + for spaces, decoded. None if an escape is malformed, or the result no UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if read_line(reader, &mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => return Err(invalid("malformed request line")),
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = parse_query(query).ok_or_else(|| invalid("malformed query"))?;
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        body: Vec::new(),
+        // the default of HTTP/1.1, but not of HTTP/1.0
+        keep_alive: version == "HTTP/1.1",
+        protobuf: false,
+    };
+
+    let mut length = 0;
+    for headers in 0.. {
+        if headers > MAX_HEADERS {
+            return Err(invalid("the request has too many headers"));
+        }
+        let mut header = String::new();
+        if read_line(reader, &mut header)? == 0 {
+            return Err(invalid("the request ends in its headers"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid("malformed header"))?;
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => {
+                length = value
+                    .parse()
+                    .map_err(|_| invalid("malformed Content-Length"))?
+            }
+            "transfer-encoding" => {
+                return Err(invalid(
+                    "chunked bodies are not supported: send a Content-Length",
+                ))
+            }
+            "connection" => request.keep_alive = !value.eq_ignore_ascii_case("close"),
+            "accept" => request.protobuf = value.contains("application/x-protobuf"),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY {
+        return Err(invalid("the frame is too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(Some(request))
+}
+
+fn handle(request: &Request, sessions: &Sessions) -> Response {
+    sessions.expire();
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let session = |id: &str| {
+        let id = id.parse::<u32>().ok()?;
+        Some((id, sessions.get(id)?))
+    };
+    match (request.method.as_str(), &segments[..]) {
+        ("POST", ["sessions"]) => match start(request) {
+            Ok(tracker) => match sessions.start(tracker) {
+                Some(id) => Response::ok("201 Created", Body::Session(id)),
+                None => Response::error("503 Service Unavailable", "too many sessions"),
+            },
+            Err(message) => Response::error("400 Bad Request", message),
+        },
+        ("POST", ["sessions", id, "track"]) => match session(id) {
+            Some((id, session)) => match track(request, id, &mut session.lock().unwrap()) {
+                Ok(prediction) => Response::ok("200 OK", prediction),
+                Err(message) => Response::error("400 Bad Request", message),
+            },
+            None => Response::error("404 Not Found", "no such session"),
+        },
+        ("DELETE", ["sessions", id]) => match id.parse().is_ok_and(|id| sessions.end(id)) {
+            true => Response::ok("200 OK", Body::Empty),
+            false => Response::error("404 Not Found", "no such session"),
+        },
+        _ => Response::error(
+            "404 Not Found",
+            "no such endpoint (see mosse-server --help)",
+        ),
+    }
+}
+
+// a tracker trained on the target in the frame of the request
+fn start(request: &Request) -> Result<MosseTracker, String> {
+    let numbers = request
+        .query
+        .get("box")
+        .ok_or("the box of the target is missing (?box=x,y,width,height)")?
+        .split(',')
+        .map(|n| n.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "the box is four numbers")?;
+    let init_box = match numbers[..] {
+        [x, y, width, height] => BBox::from_xywh(x, y, width, height),
+        _ => return Err("the box is four numbers".to_string()),
+    };
+    let window = match request.query.get("window") {
+        Some(window) => window.parse().map_err(|_| "the window is a number")?,
+        None => init_box.width.max(init_box.height).round() as u32,
+    };
+
+    let frame = frame(request)?;
+    let mut tracker = MosseTracker::builder()
+        .frame_size(frame.width(), frame.height())
+        .window_size(window)
+        .build()
+        .map_err(|error| error.to_string())?;
+    tracker
+        .retrain(&frame, init_box)
+        .map_err(|error| error.to_string())?;
+    Ok(tracker)
+}
+
+// the prediction of the tracker of session `id` in the frame of the request
+fn track(request: &Request, id: u32, session: &mut Session) -> Result<Body, String> {
+    let frame = frame(request)?;
+    let prediction = session
+        .tracker
+        .track(&frame)
+        .map_err(|error| error.to_string())?;
+    session.frames += 1;
+    session.last_used = Instant::now();
+    Ok(Body::Prediction {
+        id,
+        frame: session.frames,
+        prediction,
+    })
+}
+
+// the frame in the body of the request: raw grayscale with a width and height in the query, an image without
+fn frame(request: &Request) -> Result<DynamicImage, String> {
+    match (request.query.get("width"), request.query.get("height")) {
+        (Some(width), Some(height)) => {
+            let (width, height) = match (width.parse(), height.parse()) {
+                (Ok(width), Ok(height)) => (width, height),
+                _ => return Err("the width and height are numbers".to_string()),
+            };
+            if request.body.len() != width as usize * height as usize {
+                return Err(format!(
+                    "a raw frame of {}x{} is {} bytes, not {}",
+                    width,
+                    height,
+                    width as usize * height as usize,
+                    request.body.len()
+                ));
+            }
+            GrayImage::from_raw(width, height, request.body.clone())
+                .map(DynamicImage::ImageLuma8)
+                .ok_or_else(|| "the frame is empty".to_string())
+        }
+        _ => image::load_from_memory(&request.body)
+            .map_err(|error| format!("the frame is no image: {}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, target: &str, body: Vec<u8>) -> Request {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: parse_query(query).unwrap(),
+            body,
+            keep_alive: true,
+            protobuf: false,
+        }
+    }
+
+    // a raw 128x96 frame with a bright square around `center`
+    fn frame(center: (u32, u32)) -> Vec<u8> {
+        GrayImage::from_fn(128, 96, |x, y| {
+            match x.abs_diff(center.0) <= 6 && y.abs_diff(center.1) <= 6 {
+                true => image::Luma([200]),
+                false => image::Luma([20]),
+            }
+        })
+        .into_raw()
+    }
+
+    #[test]
+    fn parses_requests() {
+        let parse = |text: &[u8]| read_request(&mut &text[..]);
+
+        let request = parse(
+            b"POST /sessions/1/track?width=2&height=1 HTTP/1.1\r\nContent-Length: 2\r\n\r\nab\
+              DELETE /sessions/1 HTTP/1.0\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/sessions/1/track");
+        assert_eq!(request.query["width"], "2");
+        assert_eq!(request.body, b"ab");
+        assert!(request.keep_alive);
+        assert!(!request.protobuf);
+
+        // query values are percent-decoded
+        let request = parse(
+            b"POST /sessions?box=44%2C34,32,32&name=a+b%21 HTTP/1.1\r\nAccept: application/x-protobuf\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.query["box"], "44,34,32,32");
+        assert_eq!(request.query["name"], "a b!");
+        assert!(request.protobuf);
+
+        // without a Content-Length, a request has no body
+        let request = parse(b"POST /sessions HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert!(request.body.is_empty());
+        assert!(!request.keep_alive);
+
+        assert!(parse(b"").unwrap().is_none());
+        let invalid = |text: &[u8]| parse(text).err().map(|error| error.kind());
+        assert_eq!(invalid(b"GET\r\n\r\n"), Some(io::ErrorKind::InvalidData));
+        assert_eq!(
+            invalid(b"GET / HTTP/1.1\r\nno header\r\n\r\n"),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            invalid(
+                format!(
+                    "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                    MAX_BODY + 1
+                )
+                .as_bytes()
+            ),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            invalid(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            invalid(b"GET /?box=%2 HTTP/1.1\r\n\r\n"),
+            Some(io::ErrorKind::InvalidData)
+        );
+        // lines and headers are bounded
+        assert_eq!(
+            invalid(format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE)).as_bytes()),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(
+            invalid(
+                format!(
+                    "GET / HTTP/1.1\r\n{}\r\n",
+                    "A: b\r\n".repeat(MAX_HEADERS + 1)
+                )
+                .as_bytes()
+            ),
+            Some(io::ErrorKind::InvalidData)
+        );
+        // a body shorter than its Content-Length
+        assert_eq!(
+            invalid(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nab"),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn runs_sessions() {
+        let sessions = Sessions::new(1, Duration::from_secs(60));
+        let raw = "width=128&height=96";
+
+        let response = handle(
+            &request(
+                "POST",
+                &format!("/sessions?box=44,34,32,32&{}", raw),
+                frame((60, 50)),
+            ),
+            &sessions,
+        );
+        assert_eq!(response.status, "201 Created");
+        assert_eq!(response.json(), "{\"session\":1}");
+        // no more sessions than allowed
+        let response = handle(
+            &request(
+                "POST",
+                &format!("/sessions?box=44,34,32,32&{}", raw),
+                frame((60, 50)),
+            ),
+            &sessions,
+        );
+        assert_eq!(response.status, "503 Service Unavailable");
+
+        let response = handle(
+            &request(
+                "POST",
+                &format!("/sessions/1/track?{}", raw),
+                frame((63, 50)),
+            ),
+            &sessions,
+        );
+        assert_eq!(response.status, "200 OK");
+        let json = response.json();
+        assert!(json.starts_with("{\"id\":1,\"frame\":1,\"x\":"), "{}", json);
+        let message = protobuf::Prediction::decode(&response.protobuf()[..]).unwrap();
+        assert_eq!((message.id, message.frame), (1, 1));
+        assert_eq!(message.status(), protobuf::Status::Tracking);
+        let response = handle(
+            &request("POST", "/sessions/1/track", b"no image".to_vec()),
+            &sessions,
+        );
+        assert_eq!(response.status, "400 Bad Request");
+
+        assert_eq!(
+            handle(&request("DELETE", "/sessions/1", Vec::new()), &sessions).status,
+            "200 OK"
+        );
+        for (method, target) in [
+            ("DELETE", "/sessions/1"),
+            ("POST", "/sessions/1/track"),
+            ("GET", "/"),
+        ] {
+            assert_eq!(
+                handle(&request(method, target, Vec::new()), &sessions).status,
+                "404 Not Found"
+            );
+        }
+
+        // idle sessions end
+        let sessions = Sessions::new(1, Duration::ZERO);
+        let response = handle(
+            &request(
+                "POST",
+                &format!("/sessions?box=44,34,32,32&{}", raw),
+                frame((60, 50)),
+            ),
+            &sessions,
+        );
+        assert_eq!(response.status, "201 Created");
+        let response = handle(
+            &request(
+                "POST",
+                &format!("/sessions/1/track?{}", raw),
+                frame((63, 50)),
+            ),
+            &sessions,
+        );
+        assert_eq!(response.status, "404 Not Found");
+    }
+
+    #[test]
+    fn bounds_the_connections() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let first = Connection::open(&connections, 2).unwrap();
+        let second = Connection::open(&connections, 2).unwrap();
+        assert!(Connection::open(&connections, 2).is_none());
+        drop(first);
+        assert!(Connection::open(&connections, 2).is_some());
+        drop(second);
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn escapes_error_messages() {
+        let response = Response::error("400 Bad Request", "a \"bad\"\\frame\nat line 1\u{1}");
+        assert_eq!(
+            response.json(),
+            "{\"error\":\"a \\\"bad\\\"\\\\frame\\nat line 1\\u0001\"}"
+        );
+    }
+}
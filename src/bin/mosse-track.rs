@@ -0,0 +1,323 @@
+// Tracks a target through a video or a sequence of images, and writes its box in every frame as CSV, in the format of
+// export::TrajectoryWriter (the target is id 0):
+//
+//   mosse-track --box 120,734,48,32 ./testdata/traffic/img0*.png > boxes.csv
+//   mosse-track --box 120,734,48,32 --annotate out/ ./testdata/traffic.mp4    (with the video feature)
+//
+// Run it with --help for all options.
+
+use image::DynamicImage;
+use mosse::draw::Overlay;
+use mosse::export::TrajectoryWriter;
+use mosse::{BBox, MosseTracker, Prediction, TrackingStatus};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str =
+    "usage: mosse-track --box <x>,<y>,<width>,<height> [options] <video | images...>
+
+The box is the target in the first frame: its top left corner and size, in pixels.
+The images are the frames in order. Videos take the video feature, and ffmpeg on the PATH.
+
+options:
+  --window <pixels>          the size of the tracking window (the larger side of the box by default)
+  --learning-rate <rate>     how fast the filter adapts to the target, in (0, 1] (0.05)
+  --regularization <value>   keeps the filter from dividing by zero (0.001)
+  --psr-threshold <psr>      the PSR below which the target counts as lost (7)
+  --scale                    adapt to changes in the size of the target
+  --output <file>            write the CSV there instead of to stdout
+  --annotate <directory>     write the frames with the box, its confidence and its trail drawn in to the directory,
+                             as PNG";
+
+struct Options {
+    init_box: BBox,
+    window: Option<u32>,
+    learning_rate: Option<f32>,
+    regularization: Option<f32>,
+    psr_threshold: Option<f32>,
+    scale: bool,
+    output: Option<PathBuf>,
+    annotate: Option<PathBuf>,
+    inputs: Vec<PathBuf>,
+}
+
+fn main() {
+    let options = match parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(error) = run(options) {
+        eprintln!("mosse-track: {}", error);
+        process::exit(1);
+    }
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        init_box: BBox::from_xywh(0.0, 0.0, 0.0, 0.0),
+        window: None,
+        learning_rate: None,
+        regularization: None,
+        psr_threshold: None,
+        scale: false,
+        output: None,
+        annotate: None,
+        inputs: Vec::new(),
+    };
+    let mut init_box = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--box" => {
+                let numbers = value()?
+                    .split(',')
+                    .map(|n| n.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| "the box is four numbers".to_string())?;
+                match numbers[..] {
+                    [x, y, width, height] => init_box = Some(BBox::from_xywh(x, y, width, height)),
+                    _ => return Err("the box is four numbers".to_string()),
+                }
+            }
+            "--window" => options.window = Some(number(&arg, value()?)?),
+            "--learning-rate" => options.learning_rate = Some(number(&arg, value()?)?),
+            "--regularization" => options.regularization = Some(number(&arg, value()?)?),
+            "--psr-threshold" => options.psr_threshold = Some(number(&arg, value()?)?),
+            "--scale" => options.scale = true,
+            "--output" => options.output = Some(value()?.into()),
+            "--annotate" => options.annotate = Some(value()?.into()),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => options.inputs.push(arg.into()),
+        }
+    }
+    options.init_box = init_box.ok_or("the box of the target is missing (--box)")?;
+    if options.inputs.is_empty() {
+        return Err("no video or images to track".to_string());
+    }
+    Ok(options)
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} takes a number, not {}", option, value))
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let frames = frames(&options.inputs)?;
+    let csv: Box<dyn Write> = match &options.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    track(&options, frames, csv)
+}
+
+// track the target through the frames, and write its box in every frame to `csv`
+fn track(options: &Options, mut frames: Frames, csv: impl Write) -> Result<(), Box<dyn Error>> {
+    let first = frames.next().ok_or("there are no frames")??;
+
+    let init_box = options.init_box;
+    let window = options
+        .window
+        .unwrap_or(init_box.width.max(init_box.height).round() as u32);
+    let mut builder = MosseTracker::builder()
+        .frame_size(first.width(), first.height())
+        .window_size(window);
+    if let Some(rate) = options.learning_rate {
+        builder = builder.learning_rate(rate);
+    }
+    if let Some(regularization) = options.regularization {
+        builder = builder.regularization(regularization);
+    }
+    if let Some(threshold) = options.psr_threshold {
+        builder = builder.psr_threshold(threshold);
+    }
+    let scale = options.scale;
+    let mut tracker = builder
+        .with(|settings| settings.scale_adaptation = scale)
+        .build()?;
+    tracker.retrain(&first, init_box)?;
+
+    // the first frame is the box given: it has no PSR
+    let (width, height) = (init_box.width, init_box.height);
+    let given = Prediction {
+        location: init_box.pixel_center(),
+        position: init_box.center(),
+        psr: f32::NAN,
+        apce: f32::NAN,
+        scale: 1.0,
+        aspect_ratio: 1.0,
+        angle: 0.0,
+        size: (width, height),
+        confidence: 1.0,
+        status: TrackingStatus::Tracking,
+        frame_index: 0,
+        velocity: None,
+    };
+    let mut writer = TrajectoryWriter::csv(csv);
+    let mut overlay = Overlay::new();
+    let mut write = |index: u32, frame: &DynamicImage, prediction: Prediction| {
+        writer.write(0, index, &prediction)?;
+        if let Some(directory) = &options.annotate {
+            let mut annotated = frame.to_rgb8();
+            overlay.draw(&mut annotated, &[(0, prediction)]);
+            annotated.save(directory.join(format!("frame_{:05}.png", index)))?;
+        }
+        Ok::<(), Box<dyn Error>>(())
+    };
+    if let Some(directory) = &options.annotate {
+        fs::create_dir_all(directory)?;
+    }
+    write(0, &first, given)?;
+
+    for (index, frame) in (1..).zip(frames) {
+        let frame = frame?;
+        let prediction = tracker.track(&frame)?;
+        write(index, &frame, prediction)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+type Frames = Box<dyn Iterator<Item = Result<DynamicImage, Box<dyn Error>>>>;
+
+// the frames of a video, or of images in order
+fn frames(inputs: &[PathBuf]) -> Result<Frames, Box<dyn Error>> {
+    let is_image = |path: &PathBuf| image::ImageFormat::from_path(path).is_ok();
+    if inputs.len() == 1 && !is_image(&inputs[0]) {
+        return video(&inputs[0]);
+    }
+    let images: Vec<PathBuf> = inputs.to_vec();
+    Ok(Box::new(images.into_iter().map(|path| {
+        image::open(&path).map_err(|error| format!("{}: {}", path.display(), error).into())
+    })))
+}
+
+#[cfg(feature = "video")]
+fn video(path: &Path) -> Result<Frames, Box<dyn Error>> {
+    let frames = mosse::video::VideoFrames::open(path)?;
+    Ok(Box::new(frames.map(|frame| {
+        frame
+            .map(DynamicImage::ImageLuma8)
+            .map_err(|error| error.into())
+    })))
+}
+
+#[cfg(not(feature = "video"))]
+fn video(path: &Path) -> Result<Frames, Box<dyn Error>> {
+    Err(format!(
+        "{} is no image: videos take the video feature (cargo install --features video)",
+        path.display()
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(String::from)
+    }
+
+    // a 128x96 frame with a textured square around `center`
+    fn frame(center: (u32, u32)) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(128, 96, |x, y| {
+            let (dx, dy) = (x as i32 - center.0 as i32, y as i32 - center.1 as i32);
+            match dx.abs() <= 6 && dy.abs() <= 6 {
+                true => Luma([(150 + 8 * dx + 4 * dy) as u8]),
+                false => Luma([20]),
+            }
+        }))
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let options = parse(args(
+            "--box 44,34,32,32 --window 48 --scale --output out.csv a.png b.png",
+        ))
+        .unwrap();
+        assert_eq!(options.init_box.xywh(), [44.0, 34.0, 32.0, 32.0]);
+        assert_eq!(options.window, Some(48));
+        assert!(options.scale);
+        assert_eq!(options.output, Some(PathBuf::from("out.csv")));
+        assert_eq!(
+            options.inputs,
+            vec![PathBuf::from("a.png"), PathBuf::from("b.png")]
+        );
+
+        // --help is the empty error
+        assert_eq!(parse(args("--help")).err().unwrap(), "");
+        assert_eq!(
+            parse(args("a.png")).err().unwrap(),
+            "the box of the target is missing (--box)"
+        );
+        assert_eq!(
+            parse(args("--box 1,2,3 a.png")).err().unwrap(),
+            "the box is four numbers"
+        );
+        assert_eq!(
+            parse(args("--box 1,2,3,4 --window big a.png"))
+                .err()
+                .unwrap(),
+            "--window takes a number, not big"
+        );
+        assert_eq!(
+            parse(args("--box 1,2,3,4 --psr-threshold")).err().unwrap(),
+            "--psr-threshold needs a value"
+        );
+        assert_eq!(
+            parse(args("--box 1,2,3,4")).err().unwrap(),
+            "no video or images to track"
+        );
+    }
+
+    #[test]
+    fn writes_the_box_in_every_frame() {
+        let directory = std::env::temp_dir().join(format!("mosse-track-{}", std::process::id()));
+        let options = parse(args(&format!(
+            "--box 44,34,32,32 --annotate {} frames",
+            directory.display()
+        )))
+        .unwrap();
+        let frames: Frames = Box::new((0..3).map(|i| Ok(frame((60 + 2 * i, 50)))));
+        let mut csv = Vec::new();
+        track(&options, frames, &mut csv).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,frame,x,y,width,height,psr,confidence,status");
+        assert_eq!(lines[1], "0,0,44,34,32,32,NaN,1,tracking");
+        assert_eq!(lines.len(), 4);
+        for (i, line) in lines[2..].iter().enumerate() {
+            let fields: Vec<&str> = line.split(',').collect();
+            assert_eq!(fields[..2], ["0", &(i + 1).to_string()]);
+            let x: f32 = fields[2].parse().unwrap();
+            assert!((x - (44.0 + 2.0 * (i + 1) as f32)).abs() <= 1.0, "{}", line);
+            assert_eq!(fields[8], "tracking");
+        }
+
+        // the box of the first frame, in the color of a tracked target
+        let annotated = image::open(directory.join("frame_00000.png")).unwrap();
+        assert_eq!(
+            annotated.to_rgb8().get_pixel(44, 34),
+            &image::Rgb([0, 255, 0])
+        );
+        for i in 1..3 {
+            assert!(directory.join(format!("frame_{:05}.png", i)).exists());
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
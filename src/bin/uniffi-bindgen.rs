@@ -0,0 +1,8 @@
+// Generates the Kotlin and Swift bindings of the uniffi feature from the built library (see src/mobile.rs):
+//
+//   cargo build --release --features uniffi
+//   cargo run --features uniffi --bin uniffi-bindgen -- generate --library target/release/libmosse.so \
+//       --language kotlin --out-dir bindings
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
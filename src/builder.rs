@@ -0,0 +1,310 @@
+// A builder for MosseTracker, as an alternative to filling in MosseTrackerSettings by hand.
+//
+// The builder starts from the default settings, and build() checks the settings before it creates the tracker:
+// e.g. a forgotten frame size or a learning rate of 5 is reported as a SettingsError instead of producing a
+// tracker that silently loses its target (or panics on the first frame).
+
+use crate::utils::AugmentationPipeline;
+use crate::{
+    Features, MosseTracker, MosseTrackerSettings, Preprocessing, ResponseSigma, WindowFunction,
+};
+use std::error::Error;
+use std::fmt;
+
+// a setting that is out of range (see MosseTrackerSettings::validate)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsError {
+    // the name of the setting (as in MosseTrackerSettings)
+    pub setting: &'static str,
+    // what is wrong with it
+    pub problem: &'static str,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.setting, self.problem)
+    }
+}
+
+impl Error for SettingsError {}
+
+impl MosseTrackerSettings {
+    // check that every setting is in range
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let check = |ok: bool, setting, problem| match ok {
+            true => Ok(()),
+            false => Err(SettingsError { setting, problem }),
+        };
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        let rate = |value: f32| positive(value) && value <= 1.0;
+
+        check(
+            self.width > 0 && self.height > 0,
+            "width/height",
+            "the frame size must be set",
+        )?;
+        check(self.window_size > 0, "window_size", "must be at least 1")?;
+        check(
+            self.window_size <= self.width.min(self.height),
+            "window_size",
+            "must fit inside the frame",
+        )?;
+        check(
+            rate(self.learning_rate),
+            "learning_rate",
+            "must be in (0, 1]",
+        )?;
+        check(
+            positive(self.regularization),
+            "regularization",
+            "must be positive",
+        )?;
+        check(
+            positive(self.scale_regularization),
+            "scale_regularization",
+            "must be positive",
+        )?;
+        check(
+            self.psr_threshold.is_finite(),
+            "psr_threshold",
+            "must be finite",
+        )?;
+        check(
+            self.occlusion_apce_ratio.is_none_or(positive),
+            "occlusion_apce_ratio",
+            "must be positive",
+        )?;
+        check(
+            !self.aspect_ratio_adaptation || self.scale_adaptation,
+            "aspect_ratio_adaptation",
+            "requires scale_adaptation",
+        )?;
+        check(
+            self.padding.is_finite() && self.padding >= 1.0,
+            "padding",
+            "must be at least 1.0",
+        )?;
+        check(rate(self.downscale), "downscale", "must be in (0, 1]")?;
+        check(
+            positive(self.preprocessing.gamma),
+            "preprocessing.gamma",
+            "must be positive",
+        )?;
+        check(
+            self.long_term_learning_rate.is_none_or(rate),
+            "long_term_learning_rate",
+            "must be in (0, 1]",
+        )?;
+        check(
+            self.anchor_interval != Some(0),
+            "anchor_interval",
+            "must be at least 1",
+        )?;
+        check(
+            self.update_interval > 0,
+            "update_interval",
+            "must be at least 1",
+        )?;
+        check(
+            self.ensemble_size > 0,
+            "ensemble_size",
+            "must be at least 1",
+        )?;
+        check(
+            positive(self.response_sigma.pixels(self.window_size)),
+            "response_sigma",
+            "must be positive",
+        )?;
+        check(
+            self.translation_augmentation
+                .is_none_or(|shift| shift < self.window_size),
+            "translation_augmentation",
+            "must be smaller than the window size",
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MosseTrackerBuilder {
+    settings: MosseTrackerSettings,
+}
+
+impl MosseTracker {
+    // a builder that starts from the default settings
+    pub fn builder() -> MosseTrackerBuilder {
+        MosseTrackerBuilder::default()
+    }
+}
+
+impl MosseTrackerBuilder {
+    // the size of the frames the tracker will see (required)
+    pub fn frame_size(mut self, width: u32, height: u32) -> Self {
+        self.settings.width = width;
+        self.settings.height = height;
+        self
+    }
+
+    pub fn window_size(mut self, window_size: u32) -> Self {
+        self.settings.window_size = window_size;
+        self
+    }
+
+    pub fn padding(mut self, padding: f32) -> Self {
+        self.settings.padding = padding;
+        self
+    }
+
+    // see MosseTrackerSettings::downscale
+    pub fn downscale(mut self, factor: f32) -> Self {
+        self.settings.downscale = factor;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: f32) -> Self {
+        self.settings.learning_rate = learning_rate;
+        self
+    }
+
+    // see MosseTrackerSettings::update_interval
+    pub fn update_interval(mut self, frames: u32) -> Self {
+        self.settings.update_interval = frames;
+        self
+    }
+
+    pub fn regularization(mut self, regularization: f32) -> Self {
+        self.settings.regularization = regularization;
+        self
+    }
+
+    pub fn response_sigma(mut self, sigma: ResponseSigma) -> Self {
+        self.settings.response_sigma = sigma;
+        self
+    }
+
+    pub fn psr_threshold(mut self, threshold: f32) -> Self {
+        self.settings.psr_threshold = threshold;
+        self
+    }
+
+    // freeze the filter when the APCE drops below this ratio (see MosseTrackerSettings::occlusion_apce_ratio)
+    pub fn occlusion_apce_ratio(mut self, ratio: f32) -> Self {
+        self.settings.occlusion_apce_ratio = Some(ratio);
+        self
+    }
+
+    pub fn window_function(mut self, window_function: WindowFunction) -> Self {
+        self.settings.window_function = window_function;
+        self
+    }
+
+    pub fn preprocessing(mut self, preprocessing: Preprocessing) -> Self {
+        self.settings.preprocessing = preprocessing;
+        self
+    }
+
+    pub fn features(mut self, features: Features) -> Self {
+        self.settings.features = features;
+        self
+    }
+
+    pub fn affine_augmentation(mut self, enabled: bool) -> Self {
+        self.settings.affine_augmentation = enabled;
+        self
+    }
+
+    pub fn photometric_augmentation(mut self, enabled: bool) -> Self {
+        self.settings.photometric_augmentation = enabled;
+        self
+    }
+
+    // train on copies of the target shifted by up to `max_shift` pixels
+    pub fn translation_augmentation(mut self, max_shift: u32) -> Self {
+        self.settings.translation_augmentation = Some(max_shift);
+        self
+    }
+
+    pub fn occlusion_augmentation(mut self, enabled: bool) -> Self {
+        self.settings.occlusion_augmentation = enabled;
+        self
+    }
+
+    // train on the frames of this pipeline instead (see MosseTrackerSettings::augmentation)
+    pub fn augmentation(mut self, pipeline: AugmentationPipeline) -> Self {
+        self.settings.augmentation = Some(pipeline);
+        self
+    }
+
+    // change any of the other settings, e.g. .with(|settings| settings.redetection = true)
+    pub fn with(mut self, change: impl FnOnce(&mut MosseTrackerSettings)) -> Self {
+        change(&mut self.settings);
+        self
+    }
+
+    // the validated settings, e.g. for a MultiMosseTracker
+    pub fn settings(self) -> Result<MosseTrackerSettings, SettingsError> {
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+
+    pub fn build(self) -> Result<MosseTracker, SettingsError> {
+        Ok(MosseTracker::new(&self.settings()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_validated_trackers() {
+        let settings = MosseTracker::builder()
+            .frame_size(320, 240)
+            .window_size(48)
+            .learning_rate(0.1)
+            .response_sigma(ResponseSigma::Relative(0.1))
+            .translation_augmentation(4)
+            .with(|settings| settings.redetection = true)
+            .settings()
+            .unwrap();
+        assert_eq!((settings.width, settings.height), (320, 240));
+        assert_eq!(settings.window_size, 48);
+        assert_eq!(settings.learning_rate, 0.1);
+        assert_eq!(settings.translation_augmentation, Some(4));
+        assert!(settings.redetection);
+        // everything else keeps its default
+        assert_eq!(
+            settings.psr_threshold,
+            MosseTrackerSettings::default().psr_threshold
+        );
+
+        assert!(MosseTracker::builder().frame_size(100, 100).build().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let error = |builder: MosseTrackerBuilder| builder.build().unwrap_err().setting;
+
+        assert_eq!(error(MosseTracker::builder()), "width/height");
+        let builder = || MosseTracker::builder().frame_size(100, 80);
+        assert_eq!(error(builder().window_size(90)), "window_size");
+        assert_eq!(error(builder().learning_rate(0.0)), "learning_rate");
+        assert_eq!(error(builder().learning_rate(5.0)), "learning_rate");
+        assert_eq!(error(builder().padding(0.5)), "padding");
+        assert_eq!(error(builder().downscale(2.0)), "downscale");
+        assert_eq!(
+            error(builder().response_sigma(ResponseSigma::Absolute(-1.0))),
+            "response_sigma"
+        );
+        assert_eq!(
+            error(builder().with(|settings| settings.aspect_ratio_adaptation = true)),
+            "aspect_ratio_adaptation"
+        );
+
+        let message = builder()
+            .regularization(f32::NAN)
+            .build()
+            .unwrap_err()
+            .to_string();
+        assert_eq!(message, "invalid regularization: must be positive");
+    }
+}
@@ -0,0 +1,157 @@
+// Color Names (CN) features, after van de Weijer et al. (2009), "Learning Color Names for Real-World Applications",
+// as used for tracking by Danelljan et al. (2014), "Adaptive Color Attributes for Real-Time Visual Tracking".
+//
+// Every pixel is described by the probabilities of the 11 basic color names of English. Like the original, the
+// probabilities come from a lookup table of 32 x 32 x 32 quantized RGB values. The table of van de Weijer et al.
+// is learned from images; ours is computed once, by softly assigning every RGB value to a prototype of every
+// color name in CIELAB space (where distances roughly follow perceived color differences).
+
+use std::sync::OnceLock;
+
+// the number of feature channels: black, blue, brown, gray, green, orange, pink, purple, red, white, yellow
+pub const COLOR_NAMES: usize = 11;
+
+// a typical sRGB value of every color name, in the order of COLOR_NAMES
+const PROTOTYPES: [[u8; 3]; COLOR_NAMES] = [
+    [0, 0, 0],
+    [30, 60, 200],
+    [130, 80, 40],
+    [128, 128, 128],
+    [40, 160, 50],
+    [250, 140, 20],
+    [250, 160, 200],
+    [130, 40, 160],
+    [220, 30, 30],
+    [255, 255, 255],
+    [250, 230, 40],
+];
+// the standard deviation (in CIELAB units) of the soft assignment to the prototypes
+const SPREAD: f32 = 20.0;
+// the table has this many levels per RGB channel
+const LEVELS: usize = 32;
+
+// the color name probabilities of an RGB value
+pub fn color_name_probabilities(rgb: [u8; 3]) -> &'static [f32; COLOR_NAMES] {
+    let step = 256 / LEVELS;
+    let (r, g, b) = (
+        rgb[0] as usize / step,
+        rgb[1] as usize / step,
+        rgb[2] as usize / step,
+    );
+    &table()[r + LEVELS * g + LEVELS * LEVELS * b]
+}
+
+// The 11 color name channels of a window, one Vec per channel, row by row. `planes` holds the red, green and blue
+// values of the window; with a single plane, the window is taken to be gray.
+pub fn color_names(planes: &[&[u8]]) -> Vec<Vec<f32>> {
+    let length = planes.first().map_or(0, |plane| plane.len());
+    let channel = |c: usize| planes[c.min(planes.len() - 1)];
+
+    let mut features = vec![vec![0f32; length]; COLOR_NAMES];
+    for i in 0..length {
+        let probabilities = color_name_probabilities([channel(0)[i], channel(1)[i], channel(2)[i]]);
+        for (feature, p) in features.iter_mut().zip(probabilities) {
+            feature[i] = *p;
+        }
+    }
+    features
+}
+
+// the lookup table, indexed by r + 32 * g + 32 * 32 * b (of the quantized RGB values)
+fn table() -> &'static [[f32; COLOR_NAMES]] {
+    static TABLE: OnceLock<Vec<[f32; COLOR_NAMES]>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let prototypes = PROTOTYPES.map(lab);
+        let step = 256 / LEVELS;
+        (0..LEVELS * LEVELS * LEVELS)
+            .map(|i| {
+                // the center of the quantization bin
+                let level = |l: usize| (l * step + step / 2) as u8;
+                let color = lab([
+                    level(i % LEVELS),
+                    level(i / LEVELS % LEVELS),
+                    level(i / (LEVELS * LEVELS)),
+                ]);
+
+                let mut probabilities = prototypes.map(|prototype| {
+                    let distance: f32 = color
+                        .iter()
+                        .zip(prototype)
+                        .map(|(a, b)| (a - b) * (a - b))
+                        .sum();
+                    (-distance / (2.0 * SPREAD * SPREAD)).exp()
+                });
+                let total: f32 = probabilities.iter().sum();
+                if total > 0.0 {
+                    probabilities.iter_mut().for_each(|p| *p /= total);
+                } else {
+                    // far from all prototypes: no idea
+                    probabilities = [1.0 / COLOR_NAMES as f32; COLOR_NAMES];
+                }
+                probabilities
+            })
+            .collect()
+    })
+}
+
+// sRGB to CIELAB (D65 white point)
+fn lab(rgb: [u8; 3]) -> [f32; 3] {
+    let linear = rgb.map(|c| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let [r, g, b] = linear;
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the most likely color name of an RGB value
+    fn name(rgb: [u8; 3]) -> usize {
+        let probabilities = color_name_probabilities(rgb);
+        (0..COLOR_NAMES)
+            .max_by(|a, b| probabilities[*a].total_cmp(&probabilities[*b]))
+            .unwrap()
+    }
+
+    #[test]
+    fn names_basic_colors() {
+        for (i, prototype) in PROTOTYPES.iter().enumerate() {
+            assert_eq!(name(*prototype), i, "{:?}", prototype);
+        }
+        assert_eq!(name([200, 10, 20]), 8);
+        assert_eq!(name([20, 180, 30]), 4);
+        assert_eq!(name([240, 240, 235]), 9);
+
+        let probabilities = color_name_probabilities([90, 200, 255]);
+        assert!((probabilities.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn gray_windows() {
+        let gray = [0u8, 128, 255];
+        let features = color_names(&[&gray]);
+        assert_eq!(features.len(), COLOR_NAMES);
+        assert!(features[0][0] > 0.5);
+        assert!(features[3][1] > 0.5);
+        assert!(features[9][2] > 0.5);
+    }
+}
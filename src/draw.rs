@@ -0,0 +1,190 @@
+// Draws what the trackers see onto the frames, to check them by eye: the box of every target, its id and
+// confidence above it, and the trail of its last positions.
+//
+//   let mut overlay = Overlay::new();
+//   for frame in frames {
+//       let predictions = multi_tracker.track(&frame)?;
+//       let mut annotated = frame.to_rgb8();
+//       overlay.draw(&mut annotated, &predictions);
+//   }
+//
+// Targets are colored by their status: green while tracking, yellow when occluded and red when lost (in gray frames:
+// white, light and dark gray). A MosseTracker's prediction is drawn as a target of any id, e.g. &[(0, prediction)].
+
+use crate::{Prediction, TrackingStatus};
+use image::{Luma, Pixel, Rgb, Rgba};
+use imageproc::drawing::{draw_hollow_rect_mut, draw_line_segment_mut, draw_text_mut, Canvas};
+use imageproc::rect::Rect;
+use rusttype::{Font, Scale};
+use std::collections::{HashMap, VecDeque};
+
+// the pixels the overlay draws in, and their color for every status of a target
+pub trait OverlayColor: Pixel<Subpixel = u8> + 'static {
+    fn color(status: TrackingStatus) -> Self;
+}
+
+impl OverlayColor for Luma<u8> {
+    fn color(status: TrackingStatus) -> Self {
+        match status {
+            TrackingStatus::Tracking => Luma([255]),
+            TrackingStatus::Occluded => Luma([170]),
+            TrackingStatus::Lost => Luma([85]),
+        }
+    }
+}
+
+impl OverlayColor for Rgb<u8> {
+    fn color(status: TrackingStatus) -> Self {
+        match status {
+            TrackingStatus::Tracking => Rgb([0, 255, 0]),
+            TrackingStatus::Occluded => Rgb([255, 255, 0]),
+            TrackingStatus::Lost => Rgb([255, 0, 0]),
+        }
+    }
+}
+
+impl OverlayColor for Rgba<u8> {
+    fn color(status: TrackingStatus) -> Self {
+        let Rgb([r, g, b]) = Rgb::color(status);
+        Rgba([r, g, b, 255])
+    }
+}
+
+pub struct Overlay {
+    font: Font<'static>,
+    // the height of the labels in pixels
+    font_size: f32,
+    // the positions a trail is long
+    trail_length: usize,
+    // the last positions of every target, the latest at the back
+    trails: HashMap<u32, VecDeque<(f32, f32)>>,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Overlay::new()
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        let font = Font::try_from_bytes(include_bytes!("../examples/Arial.ttf") as &[u8])
+            .expect("the bundled font is valid");
+        Overlay {
+            font,
+            font_size: 12.0,
+            trail_length: 30,
+            trails: HashMap::new(),
+        }
+    }
+
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    // 0 draws no trails
+    pub fn trail_length(mut self, trail_length: usize) -> Self {
+        self.trail_length = trail_length;
+        self
+    }
+
+    // Draw the predictions of a frame onto it, as MultiMosseTracker::track() returns them. The trails grow with
+    // every frame drawn; the trail of a target that is not in the predictions is dropped.
+    pub fn draw<C>(&mut self, canvas: &mut C, predictions: &[(u32, Prediction)])
+    where
+        C: Canvas,
+        C::Pixel: OverlayColor,
+    {
+        self.trails
+            .retain(|id, _| predictions.iter().any(|(other, _)| other == id));
+        for (id, prediction) in predictions {
+            let color = C::Pixel::color(prediction.status);
+
+            let trail = self.trails.entry(*id).or_default();
+            trail.push_back(prediction.position);
+            while trail.len() > self.trail_length {
+                trail.pop_front();
+            }
+            for (from, to) in trail.iter().zip(trail.iter().skip(1)) {
+                draw_line_segment_mut(canvas, *from, *to, color);
+            }
+
+            let [x, y, width, height] = prediction.bbox().xywh();
+            let (left, top) = (x.round() as i32, y.round() as i32);
+            if width >= 1.0 && height >= 1.0 {
+                let rect = Rect::at(left, top).of_size(width.round() as u32, height.round() as u32);
+                draw_hollow_rect_mut(canvas, rect, color);
+            }
+
+            // above the box, or inside it at the top of the frame
+            let label_top = match top - self.font_size as i32 - 1 {
+                above if above >= 0 => above,
+                _ => top + 2,
+            };
+            draw_text_mut(
+                canvas,
+                color,
+                left + 1,
+                label_top,
+                Scale::uniform(self.font_size),
+                &self.font,
+                &format!("#{} {:.2}", id, prediction.confidence),
+            );
+        }
+    }
+
+    // forget the trails, e.g. at a cut in a video
+    pub fn clear(&mut self) {
+        self.trails.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::prediction;
+    use image::{GrayImage, RgbImage};
+
+    #[test]
+    fn draws_boxes_labels_and_trails() {
+        let mut overlay = Overlay::new().trail_length(3);
+        for x in [40.0, 50.0, 60.0] {
+            let mut frame = RgbImage::new(128, 96);
+            overlay.draw(
+                &mut frame,
+                &[
+                    (1, prediction((x, 50.0), TrackingStatus::Tracking)),
+                    (2, prediction((100.0, 10.0), TrackingStatus::Lost)),
+                ],
+            );
+            if x == 60.0 {
+                // the corners of the boxes
+                assert_eq!(frame.get_pixel(44, 38), &Rgb([0, 255, 0]));
+                assert_eq!(frame.get_pixel(75, 61), &Rgb([0, 255, 0]));
+                assert_eq!(frame.get_pixel(84, 0), &Rgb([255, 0, 0]));
+                // the trail from 40 to 60
+                assert_eq!(frame.get_pixel(42, 50), &Rgb([0, 255, 0]));
+                // the label above the first box, inside the second one at the top of the frame (blended
+                // into the black frame at its edges)
+                let label = |x: std::ops::Range<u32>, y: std::ops::Range<u32>, channel: usize| {
+                    x.flat_map(|x| y.clone().map(move |y| (x, y)))
+                        .any(|(x, y)| frame.get_pixel(x, y)[channel] > 128)
+                };
+                assert!(label(45..75, 24..37, 1));
+                assert!(label(85..115, 2..15, 0));
+            }
+        }
+        assert_eq!(overlay.trails[&1].len(), 3);
+
+        // the second target is gone, and so is its trail
+        let mut frame = GrayImage::new(128, 96);
+        overlay.draw(
+            &mut frame,
+            &[(1, prediction((70.0, 50.0), TrackingStatus::Occluded))],
+        );
+        assert_eq!(frame.get_pixel(54, 38), &Luma([170]));
+        assert_eq!(overlay.trails[&1].len(), 3);
+        assert!(!overlay.trails.contains_key(&2));
+    }
+}
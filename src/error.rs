@@ -0,0 +1,92 @@
+// The errors of the trackers.
+
+use crate::builder::SettingsError;
+use std::{fmt, io};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    // a setting is out of range (see MosseTrackerSettings::validate)
+    Settings(SettingsError),
+    // the tracker was set up for frames of `expected` (width, height), but got one of `actual`
+    FrameSize {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    // the (padded) tracking window is empty, or larger than the frame
+    WindowSize {
+        window: u32,
+        frame: (u32, u32),
+    },
+    // a TrackerState doesn't fit the tracker it is restored into: it was taken from a tracker with other settings
+    IncompatibleState(&'static str),
+    // the target center lies outside the frame
+    TargetOutsideFrame {
+        center: (u32, u32),
+        frame: (u32, u32),
+    },
+    // a raw pixel buffer doesn't hold the frame it is said to (see frame::FrameView)
+    FrameBuffer(&'static str),
+    // reading or writing a tracker file failed (see MosseTracker::save)
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+    // a tracker file is broken or of an unknown version, or the tracker can't be written to one
+    Format(&'static str),
+    // the tracker has a setup the operation doesn't support (e.g. see fixed::FixedCorrelator::from_tracker)
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Settings(error) => error.fmt(f),
+            Error::FrameSize { expected, actual } => write!(
+                f,
+                "expected a frame of {}x{}, got one of {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            Error::WindowSize { window, frame } => write!(
+                f,
+                "a window of {}x{} doesn't fit a frame of {}x{}",
+                window, window, frame.0, frame.1
+            ),
+            Error::IncompatibleState(problem) => {
+                write!(f, "the state was taken with other settings: {}", problem)
+            }
+            Error::TargetOutsideFrame { center, frame } => write!(
+                f,
+                "the target center ({}, {}) lies outside the frame of {}x{}",
+                center.0, center.1, frame.0, frame.1
+            ),
+            Error::FrameBuffer(problem) => write!(f, "invalid frame buffer: {}", problem),
+            Error::Io { message, .. } => write!(f, "{}", message),
+            Error::Format(problem) => write!(f, "tracker file: {}", problem),
+            Error::Unsupported(problem) => write!(f, "not supported: {}", problem),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Settings(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<SettingsError> for Error {
+    fn from(error: SettingsError) -> Self {
+        Error::Settings(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
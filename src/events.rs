@@ -0,0 +1,181 @@
+// Events in the life of a track, for applications that would rather be told than poll every prediction: register
+// a callback with MosseTracker::on_event() (or MultiMosseTracker::on_event()).
+//
+// The events mark changes: a target that stays lost is reported lost once, not on every frame.
+
+use crate::{Prediction, TrackingStatus};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackerEvent {
+    // the PSR dropped to the threshold (see TrackingStatus::Lost)
+    TargetLost,
+    // the target is found, but with a confidence below LOW_CONFIDENCE (see Prediction::confidence)
+    LowConfidence,
+    // the target is found again after it was lost
+    Reacquired,
+    // the scale of the target changed by at least SCALE_CHANGE since it was last reported (initially 1.0)
+    ScaleChanged { from: f32, to: f32 },
+}
+
+pub const LOW_CONFIDENCE: f32 = 0.25;
+pub const SCALE_CHANGE: f32 = 0.1;
+
+// called with every event, and the prediction that caused it
+pub type EventCallback = Box<dyn FnMut(TrackerEvent, &Prediction) + Send>;
+
+// what the events of the last prediction were about
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EventState {
+    lost: bool,
+    low_confidence: bool,
+    reported_scale: f32,
+}
+
+impl Default for EventState {
+    fn default() -> Self {
+        EventState {
+            lost: false,
+            low_confidence: false,
+            reported_scale: 1.0,
+        }
+    }
+}
+
+impl EventState {
+    // the events of a new prediction
+    pub(crate) fn observe(&mut self, prediction: &Prediction) -> Vec<TrackerEvent> {
+        let mut events = Vec::new();
+
+        let lost = prediction.status == TrackingStatus::Lost;
+        if lost && !self.lost {
+            events.push(TrackerEvent::TargetLost);
+        } else if !lost && self.lost {
+            events.push(TrackerEvent::Reacquired);
+        }
+        let low_confidence = !lost && prediction.confidence < LOW_CONFIDENCE;
+        if low_confidence && !self.low_confidence {
+            events.push(TrackerEvent::LowConfidence);
+        }
+        self.lost = lost;
+        self.low_confidence = low_confidence;
+
+        if (prediction.scale / self.reported_scale - 1.0).abs() >= SCALE_CHANGE {
+            events.push(TrackerEvent::ScaleChanged {
+                from: self.reported_scale,
+                to: prediction.scale,
+            });
+            self.reported_scale = prediction.scale;
+        }
+        events
+    }
+}
+
+// called with the ID of the target, every event and the prediction that caused it
+pub type MultiEventCallback = Box<dyn FnMut(u32, TrackerEvent, &Prediction) + Send>;
+
+// the callbacks of a MultiMosseTracker, shared with the trackers of its targets
+#[derive(Clone, Default)]
+pub(crate) struct SharedCallbacks(Arc<Mutex<Vec<MultiEventCallback>>>);
+
+impl SharedCallbacks {
+    pub(crate) fn push(&self, callback: MultiEventCallback) {
+        self.lock().push(callback);
+    }
+
+    // a callback for the tracker of target `id`, that calls all callbacks (including the ones registered later)
+    pub(crate) fn for_target(&self, id: u32) -> EventCallback {
+        let callbacks = self.clone();
+        Box::new(move |event, prediction| {
+            for callback in callbacks.lock().iter_mut() {
+                callback(id, event, prediction);
+            }
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<MultiEventCallback>> {
+        // a callback that panicked leaves the others intact
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Debug for SharedCallbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} callbacks", self.lock().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+    use crate::{MosseTracker, MultiMosseTracker};
+    use image::GrayImage;
+
+    #[test]
+    fn reports_lost_and_reacquired_targets() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        let recorded = events.clone();
+        tracker.on_event(move |event, prediction| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((event, prediction.frame_index))
+        });
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+
+        let blank = GrayImage::new(128, 96);
+        for next in [frame((62, 50)), blank.clone(), blank] {
+            tracker.track_new_frame(&next);
+        }
+        // the lost tracker drifted off, and has to look for the target in the whole frame
+        let back = frame((62, 50));
+        assert_eq!(tracker.track_new_frame(&back).status, TrackingStatus::Lost);
+        assert_eq!(tracker.redetect(&back).unwrap().location, (62, 50));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![(TrackerEvent::TargetLost, 2), (TrackerEvent::Reacquired, 4)]
+        );
+    }
+
+    #[test]
+    fn reports_low_confidence_and_scale_changes() {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+        let mut prediction = tracker.track_new_frame(&frame((60, 50)));
+
+        let mut state = EventState::default();
+        prediction.confidence = 0.1;
+        assert_eq!(
+            state.observe(&prediction),
+            vec![TrackerEvent::LowConfidence]
+        );
+        assert_eq!(state.observe(&prediction), vec![]);
+
+        prediction.confidence = 1.0;
+        prediction.scale = 1.05;
+        assert_eq!(state.observe(&prediction), vec![]);
+        prediction.scale = 1.2;
+        assert_eq!(
+            state.observe(&prediction),
+            vec![TrackerEvent::ScaleChanged { from: 1.0, to: 1.2 }]
+        );
+    }
+
+    #[test]
+    fn multi_trackers_report_the_target() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut multi = MultiMosseTracker::new(test_settings(128, 96), 5);
+        let recorded = events.clone();
+        multi.on_event(move |id, event, _| recorded.lock().unwrap().push((id, event)));
+        multi
+            .add_or_replace_target(7, (60, 50), &frame((60, 50)))
+            .unwrap();
+        multi.track(&GrayImage::new(128, 96)).unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![(7, TrackerEvent::TargetLost)]);
+    }
+}
@@ -0,0 +1,156 @@
+// The predictions of a tracker, frame by frame, as CSV or JSON Lines (one JSON object per line) for post-processing:
+//
+//   id,frame,x,y,width,height,psr,confidence,status
+//   {"id":0,"frame":1,"x":24.5,"y":34.0,"width":32.0,"height":32.0,"psr":21.3,"confidence":1.0,"status":"tracking"}
+//
+// The box is the one of Prediction::bbox() (its top left corner and size), and the status one of "tracking",
+// "occluded" and "lost". Frames are numbered by the caller, e.g. from 0 for the first frame of a video.
+
+use crate::{Error, Prediction, TrackingStatus};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Debug)]
+pub struct TrajectoryWriter<W: Write> {
+    writer: W,
+    format: Format,
+    // whether the CSV header is written yet
+    started: bool,
+}
+
+impl<W: Write> TrajectoryWriter<W> {
+    pub fn new(writer: W, format: Format) -> Self {
+        TrajectoryWriter {
+            writer,
+            format,
+            started: false,
+        }
+    }
+
+    pub fn csv(writer: W) -> Self {
+        TrajectoryWriter::new(writer, Format::Csv)
+    }
+
+    pub fn json_lines(writer: W) -> Self {
+        TrajectoryWriter::new(writer, Format::JsonLines)
+    }
+
+    // the prediction of a MosseTracker (or of the target `id` of a MultiMosseTracker) in frame `frame`
+    pub fn write(&mut self, id: u32, frame: u32, prediction: &Prediction) -> Result<(), Error> {
+        let [x, y, width, height] = prediction.bbox().xywh();
+        let status = match prediction.status {
+            TrackingStatus::Tracking => "tracking",
+            TrackingStatus::Occluded => "occluded",
+            TrackingStatus::Lost => "lost",
+        };
+        match self.format {
+            Format::Csv => {
+                if !self.started {
+                    writeln!(
+                        self.writer,
+                        "id,frame,x,y,width,height,psr,confidence,status"
+                    )?;
+                    self.started = true;
+                }
+                writeln!(
+                    self.writer,
+                    "{},{},{},{},{},{},{},{},{}",
+                    id, frame, x, y, width, height, prediction.psr, prediction.confidence, status
+                )?;
+            }
+            Format::JsonLines => {
+                writeln!(
+                    self.writer,
+                    "{{\"id\":{},\"frame\":{},\"x\":{},\"y\":{},\"width\":{},\"height\":{},\"psr\":{},\
+                     \"confidence\":{},\"status\":\"{}\"}}",
+                    id,
+                    frame,
+                    json_number(x),
+                    json_number(y),
+                    json_number(width),
+                    json_number(height),
+                    json_number(prediction.psr),
+                    json_number(prediction.confidence),
+                    status
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // the predictions of all targets of a MultiMosseTracker in frame `frame`, as MultiMosseTracker::track() returns
+    // them
+    pub fn write_frame(
+        &mut self,
+        frame: u32,
+        predictions: &[(u32, Prediction)],
+    ) -> Result<(), Error> {
+        for (id, prediction) in predictions {
+            self.write(*id, frame, prediction)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// JSON has no NaN or infinity: those are null
+fn json_number(value: f32) -> String {
+    if value.is_finite() {
+        format!("{:?}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::prediction;
+
+    #[test]
+    fn writes_csv_and_json_lines() {
+        let predictions = [
+            (1, prediction((40.0, 50.0), TrackingStatus::Tracking)),
+            (
+                4,
+                Prediction {
+                    psr: f32::NAN,
+                    ..prediction((80.5, 20.0), TrackingStatus::Lost)
+                },
+            ),
+        ];
+
+        let mut csv = TrajectoryWriter::csv(Vec::new());
+        csv.write_frame(7, &predictions).unwrap();
+        csv.write(1, 8, &predictions[0].1).unwrap();
+        assert_eq!(
+            String::from_utf8(csv.into_inner()).unwrap(),
+            "id,frame,x,y,width,height,psr,confidence,status\n\
+             1,7,24,38,32,24,20,0.75,tracking\n\
+             4,7,64.5,8,32,24,NaN,0.75,lost\n\
+             1,8,24,38,32,24,20,0.75,tracking\n"
+        );
+
+        let mut json = TrajectoryWriter::json_lines(Vec::new());
+        json.write_frame(7, &predictions).unwrap();
+        assert_eq!(
+            String::from_utf8(json.into_inner()).unwrap(),
+            "{\"id\":1,\"frame\":7,\"x\":24.0,\"y\":38.0,\"width\":32.0,\"height\":24.0,\"psr\":20.0,\
+             \"confidence\":0.75,\"status\":\"tracking\"}\n\
+             {\"id\":4,\"frame\":7,\"x\":64.5,\"y\":8.0,\"width\":32.0,\"height\":24.0,\"psr\":null,\
+             \"confidence\":0.75,\"status\":\"lost\"}\n"
+        );
+    }
+}
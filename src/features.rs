@@ -0,0 +1,196 @@
+// The feature channels the correlation filter works on.
+//
+// The window of every frame is split into planes, one per channel of the frame (see utils::channel_planes).
+// A feature extractor turns those planes into one or more feature channels, which are preprocessed like the
+// pixel values of a grayscale window (see utils::Preprocessing). MosseTracker learns one filter per feature channel.
+//
+// Anything that implements Preprocessor can take the place of the built-in features and preprocessing
+// (see MosseTrackerSettings::preprocessor).
+
+use crate::color_names::{color_names, COLOR_NAMES};
+use crate::hog::{hog, HOG_CHANNELS};
+use crate::utils::{normalize, pixel_values, preprocess_planes, Preprocessing};
+use image::GrayImage;
+use std::fmt::Debug;
+
+// turns the planes (channels) of a window into the feature channels the filter is learned on
+pub trait Preprocessor: Debug + Send + Sync {
+    // the number of feature channels of a window with `planes` channels
+    fn channel_count(&self, planes: usize) -> usize;
+
+    // The feature channels of the planes of a window, one after the other (each row by row, like the planes).
+    // `mask` holds the weights of the window function (one per pixel): multiply every feature channel with it,
+    // or the circular correlation sees a hard edge where the window wraps around.
+    fn preprocess(&self, planes: &[GrayImage], mask: &[f32]) -> Vec<f32>;
+}
+
+// the built-in preprocessing: the features, preprocessed with the steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StandardPreprocessor {
+    pub features: Features,
+    pub steps: Preprocessing,
+}
+
+impl Preprocessor for StandardPreprocessor {
+    fn channel_count(&self, planes: usize) -> usize {
+        self.features.channel_count(planes)
+    }
+
+    fn preprocess(&self, planes: &[GrayImage], mask: &[f32]) -> Vec<f32> {
+        self.features.extract(planes, &self.steps, mask)
+    }
+}
+
+// which features the filter is learned on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Features {
+    // the pixel values of every channel of the frame (the features of MOSSE)
+    #[default]
+    Intensity,
+    // the magnitude of the Sobel gradient of every channel: one feature channel per channel of the frame.
+    // Gradients are far less sensitive to changes in lighting than the pixel values themselves.
+    GradientMagnitude,
+    // the horizontal and the vertical Sobel gradient of every channel: two feature channels per channel of the frame
+    Gradients,
+    // the 31 FHOG channels (see hog::hog), for cells of cell_size x cell_size pixels around every pixel.
+    // Much more discriminative than the pixel values, at the cost of 31 filters. Cells of 4 pixels are common.
+    Hog {
+        cell_size: u32,
+    },
+    // the probabilities of the 11 basic color names (see color_names::color_names), for color frames.
+    // Cheap and robust against small changes in lighting. Grayscale frames only tell black, gray and white apart.
+    ColorNames,
+}
+
+impl Features {
+    // the number of feature channels of a window with `planes` channels
+    pub fn channel_count(&self, planes: usize) -> usize {
+        match self {
+            Features::Intensity | Features::GradientMagnitude => planes,
+            Features::Gradients => 2 * planes,
+            Features::Hog { .. } => HOG_CHANNELS,
+            Features::ColorNames => COLOR_NAMES,
+        }
+    }
+
+    // The preprocessed feature channels of the planes of a window, one after the other.
+    // The gradients are taken after the equalization and the log transform (if enabled), and the other
+    // preprocessing steps are applied to every feature channel separately. Color names skip the equalization
+    // and the log transform, as both would change the colors.
+    pub fn extract(&self, planes: &[GrayImage], steps: &Preprocessing, mask: &[f32]) -> Vec<f32> {
+        match self {
+            Features::Intensity => return preprocess_planes(planes, steps, mask),
+            Features::ColorNames => {
+                let planes: Vec<&[u8]> = planes
+                    .iter()
+                    .map(|plane| plane.as_raw().as_slice())
+                    .collect();
+                let mut features = Vec::with_capacity(COLOR_NAMES * mask.len());
+                for mut channel in color_names(&planes) {
+                    normalize(&mut channel, steps, mask);
+                    features.extend(channel);
+                }
+                return features;
+            }
+            _ => {}
+        }
+
+        let values = planes.iter().map(|plane| pixel_values(plane, steps));
+
+        let mut features = Vec::with_capacity(self.channel_count(planes.len()) * mask.len());
+        if let Features::Hog { cell_size } = *self {
+            let (width, height) = planes.first().map_or((0, 0), |plane| plane.dimensions());
+            for mut channel in hog(&values.collect::<Vec<_>>(), width, height, cell_size) {
+                normalize(&mut channel, steps, mask);
+                features.extend(channel);
+            }
+            return features;
+        }
+
+        for (plane, values) in planes.iter().zip(values) {
+            let (gx, gy) = sobel(&values, plane.width(), plane.height());
+            let channels = match self {
+                Features::Gradients => vec![gx, gy],
+                _ => vec![gx
+                    .iter()
+                    .zip(&gy)
+                    .map(|(x, y)| (x * x + y * y).sqrt())
+                    .collect()],
+            };
+            for mut channel in channels {
+                normalize(&mut channel, steps, mask);
+                features.extend(channel);
+            }
+        }
+        features
+    }
+}
+
+// The horizontal and vertical Sobel gradients of a width x height image (row by row).
+// The edges of the image are repeated beyond its border.
+pub fn sobel(values: &[f32], width: u32, height: u32) -> (Vec<f32>, Vec<f32>) {
+    let (width, height) = (width as i64, height as i64);
+    let at =
+        |x: i64, y: i64| values[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+
+    let mut gx = Vec::with_capacity(values.len());
+    let mut gy = Vec::with_capacity(values.len());
+    for y in 0..height {
+        for x in 0..width {
+            gx.push(
+                at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x - 1, y)
+                    - at(x - 1, y + 1),
+            );
+            gy.push(
+                at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x, y - 1)
+                    - at(x + 1, y - 1),
+            );
+        }
+    }
+    (gx, gy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::WindowFunction;
+    use image::Luma;
+
+    #[test]
+    fn sobel_finds_edges() {
+        // a vertical edge between x = 3 and x = 4
+        let values: Vec<f32> = (0..64)
+            .map(|i| if i % 8 < 4 { 0.0 } else { 10.0 })
+            .collect();
+        let (gx, gy) = sobel(&values, 8, 8);
+        assert_eq!(gx[2 * 8 + 3], 40.0);
+        assert_eq!(gx[2 * 8 + 4], 40.0);
+        assert_eq!(gx[2 * 8 + 1], 0.0);
+        assert!(gy.iter().all(|g| *g == 0.0));
+    }
+
+    #[test]
+    fn gradient_channels() {
+        let plane = GrayImage::from_fn(16, 16, |x, y| Luma([(x * 10 + y * 3) as u8]));
+        let mask = WindowFunction::None.weights(16, 16);
+        let steps = Preprocessing::default();
+
+        let planes = [plane.clone(), plane];
+        let intensity = Features::Intensity.extract(&planes[..1], &steps, &mask);
+        assert_eq!(intensity.len(), 256);
+        let magnitude = Features::GradientMagnitude.extract(&planes, &steps, &mask);
+        assert_eq!(magnitude.len(), 2 * 256);
+        let gradients = Features::Gradients.extract(&planes[..1], &steps, &mask);
+        assert_eq!(gradients.len(), Features::Gradients.channel_count(1) * 256);
+
+        // every feature channel is normalized separately
+        for channel in gradients.chunks(256) {
+            let norm: f32 = channel.iter().map(|v| v * v).sum::<f32>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-4, "norm {}", norm);
+        }
+    }
+}
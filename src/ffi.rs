@@ -0,0 +1,246 @@
+// The C API of the tracker (with the ffi feature), declared in include/mosse.h, for C and C++ pipelines such as the
+// plugins of GStreamer or DeepStream. A MosseHandle is a MosseTracker behind a pointer: mosse_new() makes one, and
+// mosse_free() frees it.
+//
+// The functions track raw 8-bit frames where they are, with rows `stride` bytes apart (see frame::FrameView). With
+// 1 or 2 bytes per pixel, the first byte of every pixel is its gray value (GRAY8, or the Y of YUY2). With 3 or more,
+// the first three bytes are RGB (RGB, RGBA, RGBx), which the tracker turns into grayscale.
+//
+// Safety: every pointer is either null or valid for what the function reads or writes: `handle` comes from
+// mosse_new() and isn't freed yet, `data` points to `length` bytes, and `prediction` to a MossePrediction. A handle
+// isn't thread-safe: one thread at a time may use it.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::frame::RawFrame;
+use crate::{Error, MosseTracker, Prediction, TrackingStatus};
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::slice;
+
+pub const MOSSE_OK: i32 = 0;
+// the function failed: mosse_last_error() says why
+pub const MOSSE_ERROR: i32 = -1;
+
+// the values of MossePrediction::status (see TrackingStatus)
+pub const MOSSE_TRACKING: i32 = 0;
+pub const MOSSE_OCCLUDED: i32 = 1;
+pub const MOSSE_LOST: i32 = 2;
+
+pub struct MosseHandle {
+    tracker: MosseTracker,
+    // the message of the last error, for mosse_last_error()
+    error: CString,
+}
+
+// a Prediction for C
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MossePrediction {
+    // the (sub-pixel) center of the target in the frame
+    pub x: f32,
+    pub y: f32,
+    // the size of the target in the frame
+    pub width: f32,
+    pub height: f32,
+    // clockwise, in radians
+    pub angle: f32,
+    pub psr: f32,
+    pub confidence: f32,
+    // MOSSE_TRACKING, MOSSE_OCCLUDED or MOSSE_LOST
+    pub status: i32,
+    pub frame_index: u32,
+}
+
+impl From<&Prediction> for MossePrediction {
+    fn from(prediction: &Prediction) -> Self {
+        MossePrediction {
+            x: prediction.position.0,
+            y: prediction.position.1,
+            width: prediction.size.0,
+            height: prediction.size.1,
+            angle: prediction.angle,
+            psr: prediction.psr,
+            confidence: prediction.confidence,
+            status: match prediction.status {
+                TrackingStatus::Tracking => MOSSE_TRACKING,
+                TrackingStatus::Occluded => MOSSE_OCCLUDED,
+                TrackingStatus::Lost => MOSSE_LOST,
+            },
+            frame_index: prediction.frame_index,
+        }
+    }
+}
+
+// a tracker for width x height frames, with a window of window_size (null for settings out of range)
+#[no_mangle]
+pub extern "C" fn mosse_new(width: u32, height: u32, window_size: u32) -> *mut MosseHandle {
+    match MosseTracker::builder()
+        .frame_size(width, height)
+        .window_size(window_size)
+        .build()
+    {
+        Ok(tracker) => Box::into_raw(Box::new(MosseHandle {
+            tracker,
+            error: CString::default(),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// train on the target centered on (x, y)
+#[no_mangle]
+pub unsafe extern "C" fn mosse_train(
+    handle: *mut MosseHandle,
+    data: *const u8,
+    length: usize,
+    stride: usize,
+    bytes_per_pixel: usize,
+    x: u32,
+    y: u32,
+) -> i32 {
+    let handle = match handle.as_mut() {
+        Some(handle) => handle,
+        None => return MOSSE_ERROR,
+    };
+    let result = raw_frame(&handle.tracker, data, length, stride, bytes_per_pixel)
+        .and_then(|frame| handle.tracker.train(&frame, (x, y)));
+    handle.result(result)
+}
+
+// locate the target in the next frame, and learn from it
+#[no_mangle]
+pub unsafe extern "C" fn mosse_track(
+    handle: *mut MosseHandle,
+    data: *const u8,
+    length: usize,
+    stride: usize,
+    bytes_per_pixel: usize,
+    prediction: *mut MossePrediction,
+) -> i32 {
+    let handle = match handle.as_mut() {
+        Some(handle) => handle,
+        None => return MOSSE_ERROR,
+    };
+    if prediction.is_null() {
+        return handle.result(Err(Error::FrameBuffer("the prediction is null")));
+    }
+    let result = raw_frame(&handle.tracker, data, length, stride, bytes_per_pixel)
+        .and_then(|frame| handle.tracker.track(&frame));
+    let result = result.map(|tracked| *prediction = MossePrediction::from(&tracked));
+    handle.result(result)
+}
+
+// Why the last call on the handle failed (an empty string if none did). The string belongs to the handle, and lives
+// until the next call on it.
+#[no_mangle]
+pub unsafe extern "C" fn mosse_last_error(handle: *const MosseHandle) -> *const c_char {
+    match handle.as_ref() {
+        Some(handle) => handle.error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mosse_free(handle: *mut MosseHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+impl MosseHandle {
+    fn result(&mut self, result: Result<(), Error>) -> i32 {
+        match result {
+            Ok(()) => {
+                self.error = CString::default();
+                MOSSE_OK
+            }
+            Err(error) => {
+                // the messages have no NUL bytes
+                self.error = CString::new(error.to_string()).unwrap_or_default();
+                MOSSE_ERROR
+            }
+        }
+    }
+}
+
+// the frame at `data`, of the size the tracker was made for
+unsafe fn raw_frame<'a>(
+    tracker: &MosseTracker,
+    data: *const u8,
+    length: usize,
+    stride: usize,
+    bytes_per_pixel: usize,
+) -> Result<RawFrame<'a>, Error> {
+    if data.is_null() {
+        return Err(Error::FrameBuffer("the frame is null"));
+    }
+    let data = slice::from_raw_parts(data, length);
+    let settings = tracker.settings();
+    RawFrame::new(
+        data,
+        settings.width,
+        settings.height,
+        stride,
+        bytes_per_pixel,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    // the shared frame as RGBx, with rows padded to 520 bytes
+    fn frame(center: (u32, u32)) -> Vec<u8> {
+        let gray = crate::test_util::frame(center);
+        let mut data = vec![0; 520 * gray.height() as usize];
+        for (x, y, pixel) in gray.enumerate_pixels() {
+            let start = y as usize * 520 + x as usize * 4;
+            data[start..start + 3].copy_from_slice(&[pixel[0]; 3]);
+        }
+        data
+    }
+
+    #[test]
+    fn tracks_through_the_c_api() {
+        assert!(mosse_new(128, 96, 0).is_null());
+        let handle = mosse_new(128, 96, 32);
+        assert!(!handle.is_null());
+        unsafe {
+            let first = frame((60, 50));
+            let status = mosse_train(handle, first.as_ptr(), first.len(), 520, 4, 60, 50);
+            assert_eq!(status, MOSSE_OK);
+
+            let next = frame((63, 51));
+            let mut prediction = MossePrediction::default();
+            let status = mosse_track(handle, next.as_ptr(), next.len(), 520, 4, &mut prediction);
+            assert_eq!(status, MOSSE_OK);
+            assert_eq!(prediction.status, MOSSE_TRACKING);
+            assert_eq!(prediction.frame_index, 1);
+            assert!((prediction.x - 63.0).abs() <= 1.0, "{:?}", prediction);
+            assert!((prediction.y - 51.0).abs() <= 1.0, "{:?}", prediction);
+
+            // a buffer too short for the frame fails, and says why
+            let status = mosse_track(handle, next.as_ptr(), 1000, 520, 4, &mut prediction);
+            assert_eq!(status, MOSSE_ERROR);
+            let error = CStr::from_ptr(mosse_last_error(handle));
+            assert_eq!(
+                error.to_str().unwrap(),
+                Error::FrameBuffer("the buffer is too small for the frame").to_string()
+            );
+
+            mosse_free(handle);
+            assert_eq!(
+                mosse_track(
+                    ptr::null_mut(),
+                    next.as_ptr(),
+                    next.len(),
+                    520,
+                    4,
+                    &mut prediction
+                ),
+                MOSSE_ERROR
+            );
+        }
+    }
+}
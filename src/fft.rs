@@ -0,0 +1,324 @@
+// The FFT plans of all trackers, planned once per length and shared. Planning is far more expensive than running a
+// plan for large windows, and a multi-tracker makes a tracker (of the same window size) for every target it adds.
+//
+// With the fftw feature, the plans are FFTW's instead of rustfft's. FFTW keeps what it learned planning a length
+// (its wisdom) for the next plan of that length, so plans are made for every tracker but only measured once.
+
+use rustfft::num_complex::Complex;
+#[cfg(not(feature = "fftw"))]
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::PI;
+use std::fmt::Debug;
+#[cfg(not(feature = "fftw"))]
+use std::sync::{Arc, OnceLock};
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(not(feature = "fftw"))]
+static PLANNER: OnceLock<Mutex<FftPlanner<f32>>> = OnceLock::new();
+
+#[cfg(not(feature = "fftw"))]
+fn planner() -> MutexGuard<'static, FftPlanner<f32>> {
+    PLANNER
+        .get_or_init(|| Mutex::new(FftPlanner::new()))
+        .lock()
+        // the planner holds nothing a panic could leave half done
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// A planned FFT, with the scratch space it needs. rustfft's process() allocates the scratch space on every call,
+// which is an allocation per FFT per frame, so the scratch space is allocated once here and reused instead.
+#[cfg(not(feature = "fftw"))]
+pub(crate) struct Plan {
+    fft: Arc<dyn Fft<f32>>,
+    scratch: Mutex<Vec<Complex<f32>>>,
+}
+
+#[cfg(not(feature = "fftw"))]
+impl Plan {
+    fn new(fft: Arc<dyn Fft<f32>>) -> Self {
+        let scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+        Plan {
+            fft,
+            scratch: Mutex::new(scratch),
+        }
+    }
+
+    // transform `buffer` in place, in chunks of len() values
+    pub(crate) fn process(&self, buffer: &mut [Complex<f32>]) {
+        let mut scratch = lock(&self.scratch);
+        self.fft.process_with_scratch(buffer, &mut scratch);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.fft.len()
+    }
+}
+
+// every clone has scratch space of its own
+#[cfg(not(feature = "fftw"))]
+impl Clone for Plan {
+    fn clone(&self) -> Self {
+        Plan::new(self.fft.clone())
+    }
+}
+
+// the forward and the inverse FFT of `length` values
+#[cfg(not(feature = "fftw"))]
+pub(crate) fn plans(length: usize) -> (Plan, Plan) {
+    let mut planner = planner();
+    (
+        Plan::new(planner.plan_fft_forward(length)),
+        Plan::new(planner.plan_fft_inverse(length)),
+    )
+}
+
+// An FFTW plan, with the buffers it was planned for: FFTW runs a plan on buffers of the alignment it was planned
+// with, so the values are copied through them. Like rustfft, FFTW doesn't normalize.
+#[cfg(feature = "fftw")]
+pub(crate) struct Plan {
+    length: usize,
+    sign: fftw::types::Sign,
+    fftw: Mutex<Fftw>,
+}
+
+#[cfg(feature = "fftw")]
+struct Fftw {
+    plan: fftw::plan::C2CPlan32,
+    input: fftw::array::AlignedVec<Complex<f32>>,
+    output: fftw::array::AlignedVec<Complex<f32>>,
+}
+
+#[cfg(feature = "fftw")]
+impl Plan {
+    fn new(length: usize, sign: fftw::types::Sign) -> Self {
+        use fftw::plan::C2CPlan;
+        let mut input = fftw::array::AlignedVec::new(length);
+        let mut output = fftw::array::AlignedVec::new(length);
+        // measuring overwrites the buffers, which hold nothing yet
+        let plan = fftw::plan::C2CPlan32::new(
+            &[length],
+            &mut input,
+            &mut output,
+            sign,
+            fftw::types::Flag::MEASURE,
+        )
+        .expect("FFTW plans any length");
+        Plan {
+            length,
+            sign,
+            fftw: Mutex::new(Fftw {
+                plan,
+                input,
+                output,
+            }),
+        }
+    }
+
+    // transform `buffer` in place, in chunks of len() values
+    pub(crate) fn process(&self, buffer: &mut [Complex<f32>]) {
+        use fftw::plan::C2CPlan;
+        let mut guard = lock(&self.fftw);
+        let fftw = &mut *guard;
+        for chunk in buffer.chunks_exact_mut(self.length) {
+            fftw.input.copy_from_slice(chunk);
+            fftw.plan
+                .c2c(&mut fftw.input, &mut fftw.output)
+                .expect("the buffers are the ones planned for");
+            chunk.copy_from_slice(&fftw.output);
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.length
+    }
+}
+
+#[cfg(feature = "fftw")]
+impl Clone for Plan {
+    fn clone(&self) -> Self {
+        Plan::new(self.length, self.sign)
+    }
+}
+
+#[cfg(feature = "fftw")]
+pub(crate) fn plans(length: usize) -> (Plan, Plan) {
+    (
+        Plan::new(length, fftw::types::Sign::Forward),
+        Plan::new(length, fftw::types::Sign::Backward),
+    )
+}
+
+impl Debug for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Plan({})", self.len())
+    }
+}
+
+fn lock<T>(buffer: &Mutex<T>) -> MutexGuard<'_, T> {
+    // a buffer is overwritten before it is read, so a panic can't leave anything half done
+    buffer
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// The forward FFT of real values (the windows, kernels, ...), done as an FFT of half the length: the even values
+// are taken as the real parts and the odd ones as the imaginary parts, and the two halves are untangled afterwards.
+// It gives the whole spectrum, as the filters work on whole spectra.
+pub(crate) struct RealFft {
+    length: usize,
+    // the FFT of half the length, or of the whole length for odd lengths
+    fft: Plan,
+    // exp(-2 pi i k / length) for k < length / 2
+    twiddles: Vec<Complex<f32>>,
+    // the values packed into complex ones, reused from one call to the next
+    packed: Mutex<Vec<Complex<f32>>>,
+}
+
+impl RealFft {
+    pub(crate) fn new(length: usize) -> Self {
+        let packed = Mutex::default();
+        if length % 2 == 1 {
+            return RealFft {
+                length,
+                fft: plans(length).0,
+                twiddles: Vec::new(),
+                packed,
+            };
+        }
+        let half = length / 2;
+        RealFft {
+            length,
+            fft: plans(half).0,
+            twiddles: (0..half)
+                .map(|k| Complex::from_polar(1.0, -2.0 * PI * k as f32 / length as f32))
+                .collect(),
+            packed,
+        }
+    }
+
+    // the spectrum of every `length` values of `input`, one after the other (e.g. one window per channel)
+    pub(crate) fn process(&self, input: &[f32]) -> Vec<Complex<f32>> {
+        let mut spectrum = Vec::new();
+        self.process_into(input, &mut spectrum);
+        spectrum
+    }
+
+    // see process(), writing the spectrum into `spectrum` (resized to fit)
+    pub(crate) fn process_into(&self, input: &[f32], spectrum: &mut Vec<Complex<f32>>) {
+        spectrum.clear();
+        if self.twiddles.is_empty() {
+            spectrum.extend(input.iter().map(|&x| Complex::new(x, 0.0)));
+            self.fft.process(spectrum);
+            return;
+        }
+
+        let half = self.length / 2;
+        let mut packed = lock(&self.packed);
+        packed.clear();
+        packed.extend(
+            input
+                .chunks_exact(2)
+                .map(|pair| Complex::new(pair[0], pair[1])),
+        );
+        self.fft.process(&mut packed);
+
+        spectrum.resize(input.len(), Complex::new(0.0, 0.0));
+        for (z, x) in packed
+            .chunks_exact(half)
+            .zip(spectrum.chunks_exact_mut(self.length))
+        {
+            for k in 0..half {
+                let mirrored = z[(half - k) % half].conj();
+                // the spectra of the even and of the odd values
+                let even = (z[k] + mirrored) * 0.5;
+                let odd = (z[k] - mirrored) * Complex::new(0.0, -0.5);
+                let odd = self.twiddles[k] * odd;
+                x[k] = even + odd;
+                x[k + half] = even - odd;
+            }
+        }
+    }
+}
+
+impl Clone for RealFft {
+    fn clone(&self) -> Self {
+        RealFft {
+            length: self.length,
+            fft: self.fft.clone(),
+            twiddles: self.twiddles.clone(),
+            packed: Mutex::default(),
+        }
+    }
+}
+
+impl Debug for RealFft {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RealFft({})", self.length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "fftw"))]
+    #[test]
+    fn plans_are_shared() {
+        let (forward, inverse) = plans(24 * 24);
+        let (again, _) = plans(24 * 24);
+        assert!(Arc::ptr_eq(&forward.fft, &again.fft));
+        assert_eq!(forward.len(), 576);
+        assert_eq!(inverse.len(), 576);
+        assert!(!Arc::ptr_eq(&forward.fft, &plans(25 * 25).0.fft));
+    }
+
+    #[test]
+    fn real_ffts_match_complex_ffts() {
+        for length in [16, 24, 9] {
+            // two windows, one after the other
+            let input: Vec<f32> = (0..2 * length)
+                .map(|i| ((i * 7) % 11) as f32 - 3.5)
+                .collect();
+            let mut expected: Vec<Complex<f32>> =
+                input.iter().map(|&x| Complex::new(x, 0.0)).collect();
+            plans(length).0.process(&mut expected);
+
+            let real_fft = RealFft::new(length);
+            let spectrum = real_fft.process(&input);
+            assert_eq!(spectrum.len(), expected.len());
+            for (a, b) in spectrum.iter().zip(&expected) {
+                assert!((a - b).norm() < 1e-3, "{} != {}", a, b);
+            }
+
+            // a reused buffer (and the reused packing buffer) gives the same spectrum
+            let mut reused = vec![Complex::new(1.0, 1.0); 3];
+            real_fft.process_into(&input[..length], &mut reused);
+            real_fft.clone().process_into(&input, &mut reused);
+            assert_eq!(reused, spectrum);
+        }
+    }
+
+    #[cfg(feature = "fftw")]
+    #[test]
+    fn fftw_matches_rustfft() {
+        for length in [16, 24, 9] {
+            let input: Vec<Complex<f32>> = (0..2 * length)
+                .map(|i| Complex::new(((i * 7) % 11) as f32 - 3.5, (i % 3) as f32))
+                .collect();
+            let mut planner = rustfft::FftPlanner::new();
+            let (forward, inverse) = plans(length);
+            assert_eq!(forward.len(), length);
+            for (plan, fft) in [
+                (forward, planner.plan_fft_forward(length)),
+                (inverse.clone(), planner.plan_fft_inverse(length)),
+            ] {
+                let (mut actual, mut expected) = (input.clone(), input.clone());
+                plan.process(&mut actual);
+                fft.process(&mut expected);
+                for (a, b) in actual.iter().zip(&expected) {
+                    assert!((a - b).norm() < 1e-3, "{} != {}", a, b);
+                }
+            }
+        }
+    }
+}
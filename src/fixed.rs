@@ -0,0 +1,525 @@
+// Fixed-point (Q15) preprocessing and correlation, for targets without an FPU.
+//
+// A filter is learned in floating point as usual (e.g. on the host, or on the first frames), and converted with
+// FixedCorrelator::from_tracker(). From there on, every frame only takes integer math: the window is preprocessed
+// in Q15, transformed with a radix-2 FFT in Q15 (with a shared exponent per block, so small windows keep their
+// precision), multiplied with the filter and transformed back, and the peak of the response is the new center.
+//
+// Only the setup (the twiddle factors, the window function and the filter) uses floats. The tracker loses some
+// accuracy against the float one, mostly in the sub-pixel position, which is not estimated at all here.
+//
+// After the setup, FixedCorrelator::track_in() tracks without touching the heap: the frame is a borrowed buffer,
+// and the window and its spectrum live in FixedBuffers of a size fixed at compile time, which may be statics.
+// NOTE: the crate itself still needs std, so this is the math for a port, not a no_std build.
+
+use crate::features::Features;
+use crate::utils::{crop_into, crop_region, Equalization};
+use crate::{index_to_coords, Error, FrameView, MosseTracker};
+use image::{GrayImage, Luma};
+use rustfft::num_complex::Complex;
+
+// a number in [-1, 1) as a multiple of 2^-15
+pub type Q15 = i16;
+
+const ONE: i32 = 1 << 15;
+
+pub fn to_q15(value: f32) -> Q15 {
+    (value * ONE as f32)
+        .round()
+        .clamp(i16::MIN as f32, i16::MAX as f32) as Q15
+}
+
+pub fn from_q15(value: Q15) -> f32 {
+    value as f32 / ONE as f32
+}
+
+// a * b, rounded
+fn multiply(a: i32, b: i32) -> i32 {
+    (a * b + (1 << 14)) >> 15
+}
+
+fn multiply_complex(a: Complex<i32>, b: Complex<i32>) -> Complex<i32> {
+    Complex::new(
+        multiply(a.re, b.re) - multiply(a.im, b.im),
+        multiply(a.re, b.im) + multiply(a.im, b.re),
+    )
+}
+
+// ln(value) in Q12, for value >= 1. log2 is the position of the highest bit plus the fraction below it, which is
+// found one bit at a time by squaring.
+fn ln_q12(value: u32) -> i32 {
+    let whole = 31 - value.leading_zeros();
+    // value / 2^whole in Q30, in [1, 2)
+    let mut x = ((value as u64) << 30) >> whole;
+    let mut fraction = 0;
+    for bit in (0..12).rev() {
+        x = (x * x) >> 30;
+        if x >= 2 << 30 {
+            x >>= 1;
+            fraction |= 1 << bit;
+        }
+    }
+    let log2 = ((whole as i64) << 12) | fraction;
+    // ln(2) in Q16
+    ((log2 * 45426) >> 16) as i32
+}
+
+// The preprocessing of utils::preprocess() in fixed point: the log transform and mean subtraction (both optional,
+// as in Preprocessing), and the window. Instead of a unit norm, the values are scaled to fill the Q15 range: only the
+// peak of the response matters, and its location doesn't change with the scale.
+pub fn preprocess_q15(
+    window: &GrayImage,
+    weights: &[Q15],
+    log_transform: bool,
+    mean_subtraction: bool,
+) -> Vec<Q15> {
+    let mut values = vec![Complex::new(0, 0); window.as_raw().len()];
+    preprocess_q15_into(
+        window.as_raw(),
+        weights,
+        log_transform,
+        mean_subtraction,
+        &mut values,
+    );
+    values.iter().map(|v| v.re as Q15).collect()
+}
+
+// preprocess_q15() without allocating: the values of the pixels of `window` go into the real parts of `output`
+// (of as many values), ready for the FFT
+pub fn preprocess_q15_into(
+    window: &[u8],
+    weights: &[Q15],
+    log_transform: bool,
+    mean_subtraction: bool,
+    output: &mut [Complex<i32>],
+) {
+    assert_eq!(window.len(), output.len());
+    if window.is_empty() {
+        return;
+    }
+    for (output, &pixel) in output.iter_mut().zip(window) {
+        let value = if log_transform {
+            ln_q12(pixel as u32 + 1)
+        } else {
+            pixel as i32
+        };
+        *output = Complex::new(value, 0);
+    }
+
+    if mean_subtraction {
+        let mean = output.iter().map(|v| v.re as i64).sum::<i64>() / output.len() as i64;
+        output.iter_mut().for_each(|v| v.re -= mean as i32);
+    }
+    let peak = output.iter().map(|v| v.re.abs()).max().unwrap_or(0).max(1);
+    for (value, &w) in output.iter_mut().zip(weights) {
+        // scaled to [-2^14, 2^14], leaving headroom for the rounding
+        let v = ((value.re as i64) << 14) / peak as i64;
+        value.re = multiply(v as i32, w as i32) as Q15 as i32;
+    }
+}
+
+// A radix-2 FFT of a power of two length in Q15. Every stage halves the values when they might overflow, and
+// counts it in the exponent: the spectrum is the returned values times 2^exponent.
+#[derive(Debug, Clone)]
+pub struct FixedFft {
+    length: usize,
+    // exp(-2 pi i k / length) for k < length / 2
+    twiddles: Vec<Complex<i32>>,
+}
+
+impl FixedFft {
+    // None unless `length` is a power of two
+    pub fn new(length: usize) -> Option<Self> {
+        if !length.is_power_of_two() {
+            return None;
+        }
+        let twiddles = (0..length / 2)
+            .map(|k| {
+                let angle = -2.0 * std::f32::consts::PI * k as f32 / length as f32;
+                Complex::new(to_q15(angle.cos()) as i32, to_q15(angle.sin()) as i32)
+            })
+            .collect();
+        Some(FixedFft { length, twiddles })
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    // transform `values` (of len() values) in place, and return the exponent of the result
+    pub fn forward(&self, values: &mut [Complex<i32>]) -> u32 {
+        self.process(values, false)
+    }
+
+    // the inverse transform, without dividing by the length (like rustfft)
+    pub fn inverse(&self, values: &mut [Complex<i32>]) -> u32 {
+        self.process(values, true)
+    }
+
+    fn process(&self, values: &mut [Complex<i32>], inverse: bool) -> u32 {
+        assert_eq!(values.len(), self.length);
+        let bits = self.length.trailing_zeros();
+        for i in 0..self.length {
+            let j = i
+                .reverse_bits()
+                .checked_shr(usize::BITS - bits)
+                .unwrap_or(0);
+            if i < j {
+                values.swap(i, j);
+            }
+        }
+
+        let mut exponent = 0;
+        let mut size = 2;
+        while size <= self.length {
+            // a butterfly can grow the largest value by a factor of up to 1 + sqrt(2): halve everything first
+            // until that stays within Q15
+            let largest = values
+                .iter()
+                .map(|v| v.re.abs().max(v.im.abs()))
+                .max()
+                .unwrap_or(0);
+            let shift = (32 - largest.leading_zeros()).saturating_sub(13);
+            if shift > 0 {
+                values
+                    .iter_mut()
+                    .for_each(|v| *v = Complex::new(v.re >> shift, v.im >> shift));
+                exponent += shift;
+            }
+
+            let half = size / 2;
+            let step = self.length / size;
+            for start in (0..self.length).step_by(size) {
+                for k in 0..half {
+                    let twiddle = self.twiddles[k * step];
+                    let twiddle = if inverse { twiddle.conj() } else { twiddle };
+                    let t = multiply_complex(twiddle, values[start + k + half]);
+                    let u = values[start + k];
+                    values[start + k] = u + t;
+                    values[start + k + half] = u - t;
+                }
+            }
+            size *= 2;
+        }
+        exponent
+    }
+}
+
+// A trained filter in Q15, that finds the target in the windows of later frames with integer math only.
+#[derive(Debug, Clone)]
+pub struct FixedCorrelator {
+    window_size: u32,
+    log_transform: bool,
+    mean_subtraction: bool,
+    // the window function
+    weights: Vec<Q15>,
+    // the filter H*, scaled to fill the Q15 range
+    filter: Vec<Complex<i32>>,
+    fft: FixedFft,
+}
+
+impl FixedCorrelator {
+    // The filter of a trained tracker in Q15. Only the plain MOSSE setup converts: a grayscale filter on the pixel
+    // values, without equalization, gamma correction or downscaling, and a window of a power of two pixels
+    // (e.g. a window_size of 32 or 64, without padding).
+    pub fn from_tracker(tracker: &MosseTracker) -> Result<Self, Error> {
+        let settings = tracker.settings();
+        if settings.preprocessor.is_some() || settings.features != Features::Intensity {
+            return Err(Error::Unsupported(
+                "only the pixel values are fixed-point features",
+            ));
+        }
+        let steps = settings.preprocessing;
+        if steps.equalization != Equalization::None || steps.gamma != 1.0 {
+            return Err(Error::Unsupported(
+                "equalization and gamma correction have no fixed-point version",
+            ));
+        }
+        if settings.downscale != 1.0 {
+            return Err(Error::Unsupported(
+                "the fixed-point correlator tracks on the frames as they are",
+            ));
+        }
+        let length = tracker.target.len();
+        if tracker.filter.len() != length {
+            return Err(Error::Unsupported(
+                "only grayscale filters have a fixed-point version",
+            ));
+        }
+        let fft = FixedFft::new(length).ok_or(Error::Unsupported(
+            "the fixed-point FFT needs a window of a power of two pixels",
+        ))?;
+
+        let filter = tracker.filter.get();
+        let largest = filter
+            .iter()
+            .map(|h| h.re.abs().max(h.im.abs()))
+            .fold(f32::EPSILON, f32::max);
+        let scale = 0.5 / largest;
+        Ok(FixedCorrelator {
+            window_size: tracker.window_size,
+            log_transform: steps.log_transform,
+            mean_subtraction: steps.mean_subtraction,
+            weights: tracker
+                .feature_weights()
+                .iter()
+                .map(|&w| to_q15(w))
+                .collect(),
+            filter: filter
+                .iter()
+                .map(|h| Complex::new(to_q15(h.re * scale) as i32, to_q15(h.im * scale) as i32))
+                .collect(),
+            fft,
+        })
+    }
+
+    pub fn window_size(&self) -> u32 {
+        self.window_size
+    }
+
+    // the offset (x, y) of the target from the center of `window` (of window_size x window_size pixels)
+    pub fn locate(&self, window: &GrayImage) -> (i32, i32) {
+        let mut spectrum = vec![Complex::new(0, 0); self.fft.len()];
+        self.locate_in(window.as_raw(), &mut spectrum)
+    }
+
+    // locate() on the pixels of a window, with `spectrum` (of as many values) for the math: nothing is allocated
+    pub fn locate_in(&self, window: &[u8], spectrum: &mut [Complex<i32>]) -> (i32, i32) {
+        preprocess_q15_into(
+            window,
+            &self.weights,
+            self.log_transform,
+            self.mean_subtraction,
+            spectrum,
+        );
+        self.fft.forward(spectrum);
+
+        // F H*, at the precision of the larger of the two
+        spectrum
+            .iter_mut()
+            .zip(&self.filter)
+            .for_each(|(f, h)| *f = multiply_complex(*f, *h));
+        let largest = spectrum
+            .iter()
+            .map(|v| v.re.abs().max(v.im.abs()))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let shift = 14 - (31 - largest.leading_zeros() as i32);
+        if shift > 0 {
+            spectrum
+                .iter_mut()
+                .for_each(|v| *v = Complex::new(v.re << shift, v.im << shift));
+        }
+        self.fft.inverse(spectrum);
+
+        let peak = spectrum
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, v)| v.re)
+            .map_or(0, |(i, _)| i);
+        let (x, y) = index_to_coords(self.window_size, peak as u32);
+        let half = (self.window_size / 2) as i32;
+        (x as i32 - half, y as i32 - half)
+    }
+
+    // the new center of the target, that was at `center` in the last frame
+    pub fn track(&self, frame: &GrayImage, center: (u32, u32)) -> (u32, u32) {
+        let mut window = GrayImage::new(self.window_size, self.window_size);
+        crop_into(
+            frame,
+            self.window_size,
+            self.window_size,
+            center,
+            &mut window,
+        );
+        let (dx, dy) = self.locate(&window);
+        let (width, height) = frame.dimensions();
+        (
+            (center.0 as i32 + dx).clamp(0, width as i32 - 1) as u32,
+            (center.1 as i32 + dy).clamp(0, height as i32 - 1) as u32,
+        )
+    }
+
+    // track() on a frame of width x height gray values, with rows `stride` bytes apart (e.g. straight from the
+    // DMA buffer of a camera), in the static buffers of `buffers`: no allocation at all.
+    // An error if the buffers don't hold window_size x window_size pixels, or the frame doesn't fit its buffer.
+    pub fn track_in<const PIXELS: usize>(
+        &self,
+        frame: &[u8],
+        (width, height): (u32, u32),
+        stride: usize,
+        center: (u32, u32),
+        buffers: &mut FixedBuffers<PIXELS>,
+    ) -> Result<(u32, u32), Error> {
+        if PIXELS != self.fft.len() {
+            return Err(Error::Unsupported("the buffers don't fit the window"));
+        }
+        FrameView::<Luma<u8>>::new(frame, width, height, stride, 1)?;
+        let size = self.window_size;
+        // the window as in utils::crop_into: the part inside the frame at its top left, zeros around it
+        let (left, top, inside_width, inside_height) =
+            crop_region((width, height), size, size, center);
+        buffers.window.fill(0);
+        for y in 0..inside_height as usize {
+            let start = (top as usize + y) * stride + left as usize;
+            let row = y * size as usize;
+            buffers.window[row..row + inside_width as usize]
+                .copy_from_slice(&frame[start..start + inside_width as usize]);
+        }
+
+        let (dx, dy) = self.locate_in(&buffers.window, &mut buffers.spectrum);
+        Ok((
+            (center.0 as i32 + dx).clamp(0, width as i32 - 1) as u32,
+            (center.1 as i32 + dy).clamp(0, height as i32 - 1) as u32,
+        ))
+    }
+}
+
+// The memory FixedCorrelator::track_in() works in, for a window of PIXELS pixels (window_size squared). new() is
+// const, so the buffers can be a static (e.g. behind the mutex of a critical section) instead of taking up the stack.
+#[derive(Debug, Clone)]
+pub struct FixedBuffers<const PIXELS: usize> {
+    window: [u8; PIXELS],
+    spectrum: [Complex<i32>; PIXELS],
+}
+
+impl<const PIXELS: usize> FixedBuffers<PIXELS> {
+    pub const fn new() -> Self {
+        FixedBuffers {
+            window: [0; PIXELS],
+            spectrum: [Complex::new(0, 0); PIXELS],
+        }
+    }
+}
+
+impl<const PIXELS: usize> Default for FixedBuffers<PIXELS> {
+    fn default() -> Self {
+        FixedBuffers::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+    use crate::utils::Preprocessing;
+    use crate::MosseTrackerSettings;
+
+    #[test]
+    fn fixed_point_math_is_close() {
+        for value in [1u32, 2, 21, 150, 256] {
+            let ln = ln_q12(value) as f32 / 4096.0;
+            assert!(
+                (ln - (value as f32).ln()).abs() < 2e-3,
+                "ln({}) = {}",
+                value,
+                ln
+            );
+        }
+
+        let fft = FixedFft::new(16).unwrap();
+        assert!(FixedFft::new(24).is_none());
+        let input: Vec<f32> = (0..16).map(|i| ((i * 5) % 7) as f32 / 8.0 - 0.4).collect();
+        let mut values: Vec<Complex<i32>> = input
+            .iter()
+            .map(|&v| Complex::new(to_q15(v) as i32, 0))
+            .collect();
+        let exponent = fft.forward(&mut values);
+        for (k, value) in values.iter().enumerate() {
+            let expected: Complex<f32> = input
+                .iter()
+                .enumerate()
+                .map(|(n, &x)| {
+                    let angle = -2.0 * std::f32::consts::PI * (k * n) as f32 / 16.0;
+                    Complex::new(x * angle.cos(), x * angle.sin())
+                })
+                .sum();
+            let scale = (1 << exponent) as f32 / ONE as f32;
+            let actual = Complex::new(value.re as f32 * scale, value.im as f32 * scale);
+            assert!(
+                (actual - expected).norm() < 0.01,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn tracks_like_the_float_tracker() {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+        let fixed = FixedCorrelator::from_tracker(&tracker).unwrap();
+
+        let mut center = (60, 50);
+        for i in 1..=5 {
+            let next = frame((60 + 2 * i, 50 - i));
+            center = fixed.track(&next, center);
+            assert_eq!(center, tracker.track_new_frame(&next).location);
+        }
+        assert_eq!(center, (70, 45));
+
+        // the same in static buffers, on a frame with padded rows
+        let mut buffers = FixedBuffers::<{ 32 * 32 }>::new();
+        let next = frame((72, 44));
+        let mut padded = vec![0; 140 * 96];
+        for (row, pixels) in padded.chunks_mut(140).zip(next.rows()) {
+            row[..128].copy_from_slice(&pixels.map(|p| p[0]).collect::<Vec<_>>());
+        }
+        let expected = fixed.track(&next, center);
+        assert_eq!(
+            fixed.track_in(&padded, (128, 96), 140, center, &mut buffers),
+            Ok(expected)
+        );
+        assert_eq!(expected, (72, 44));
+
+        // frames that don't fit their buffer, and buffers that don't fit the window, are errors
+        for (length, stride) in [(140 * 95, 140), (140 * 96, 127)] {
+            assert!(matches!(
+                fixed.track_in(&padded[..length], (128, 96), stride, center, &mut buffers),
+                Err(Error::FrameBuffer(_))
+            ));
+        }
+        assert!(matches!(
+            fixed.track_in(
+                &padded,
+                (128, 96),
+                140,
+                center,
+                &mut FixedBuffers::<{ 16 * 16 }>::new()
+            ),
+            Err(Error::Unsupported(_))
+        ));
+
+        // as the float tracker does, without mean subtraction
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            preprocessing: Preprocessing {
+                mean_subtraction: false,
+                ..Default::default()
+            },
+            ..test_settings(128, 96)
+        });
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+        let fixed = FixedCorrelator::from_tracker(&tracker).unwrap();
+        let mut center = (60, 50);
+        for i in 1..=3 {
+            let next = frame((60 + 2 * i, 50 + i));
+            center = fixed.track(&next, center);
+            assert_eq!(center, tracker.track_new_frame(&next).location);
+        }
+
+        let not_a_power_of_two = MosseTracker::new(&MosseTrackerSettings {
+            window_size: 24,
+            ..test_settings(128, 96)
+        });
+        assert!(matches!(
+            FixedCorrelator::from_tracker(&not_a_power_of_two),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,570 @@
+// The frames MosseTracker works on: ImageBuffers, or raw pixel buffers borrowed from a camera, V4L2 or FFmpeg.
+// A DynamicImage (e.g. straight from image::open) is tracked in grayscale, and Grayscale tracks any other frame
+// in grayscale, e.g. an RgbImage that would otherwise get a filter per color channel. 16-bit and floating point
+// grayscale frames (e.g. of thermal and scientific cameras) are tracked in a range of their values (see DeepSample).
+//
+// A FrameView tracks such a buffer where it is, without copying it into an ImageBuffer first: the tracker only
+// copies the windows it looks at out of the frame (as it does for any FrameSource).
+
+use crate::{utils, Error, Frame, FramePixel};
+use image::{imageops, DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Pixel};
+use std::marker::PhantomData;
+
+// a frame the tracker can crop its windows out of. The targets of a MultiMosseTracker may be tracked in parallel,
+// which all crop from the same frame.
+pub trait FrameSource: Sync {
+    type Pixel: FramePixel;
+
+    // (width, height)
+    fn dimensions(&self) -> (u32, u32);
+
+    // see utils::crop_into
+    fn crop_into(
+        &self,
+        width: u32,
+        height: u32,
+        center: (u32, u32),
+        output: &mut Frame<Self::Pixel>,
+    );
+
+    // the frame in grayscale
+    fn to_luma(&self) -> GrayImage;
+}
+
+impl<P: FramePixel> FrameSource for Frame<P> {
+    type Pixel = P;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut Frame<P>) {
+        utils::crop_into(self, width, height, center, output);
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        imageops::grayscale(self)
+    }
+}
+
+// Converted to 8-bit grayscale pixel by pixel, as in DynamicImage::to_luma8(): 16-bit and float images keep their
+// full range, instead of being cut off at 255.
+impl FrameSource for DynamicImage {
+    type Pixel = Luma<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        GenericImageView::dimensions(self)
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut GrayImage) {
+        let (left, top, inside_width, inside_height) =
+            utils::crop_region(GenericImageView::dimensions(self), width, height, center);
+        if output.dimensions() != (width, height)
+            || (inside_width, inside_height) != (width, height)
+        {
+            *output = GrayImage::new(width, height);
+        }
+        for y in 0..inside_height {
+            for x in 0..inside_width {
+                output.put_pixel(x, y, self.get_pixel(left + x, top + y).to_luma());
+            }
+        }
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        self.to_luma8()
+    }
+}
+
+// The samples of grayscale frames deeper than 8 bits: u16 (e.g. 12 to 16-bit cameras) and f32 (e.g. radiometric
+// temperatures). The features work on 8-bit windows, so the samples are mapped onto the 256 gray values from a
+// range that is the same in every frame, as the filter learns from frame to frame: an ImageBuffer maps the full
+// range of its type (0 to 65535, and 0.0 to 1.0 for f32, as DynamicImage::to_luma8() does), and a DeepFrame the
+// range it is given.
+pub trait DeepSample: image::Primitive + Send + Sync + 'static {
+    // the values mapped to black and white
+    const RANGE: (f32, f32);
+
+    fn value(self) -> f32;
+}
+
+impl DeepSample for u16 {
+    const RANGE: (f32, f32) = (0.0, 65535.0);
+
+    fn value(self) -> f32 {
+        self as f32
+    }
+}
+
+impl DeepSample for f32 {
+    const RANGE: (f32, f32) = (0.0, 1.0);
+
+    fn value(self) -> f32 {
+        self
+    }
+}
+
+// the value as a gray value, from black at the low end of the range to white at the high end (values outside it
+// are clamped, and NaN is black)
+pub(crate) fn to_gray(value: f32, (low, high): (f32, f32)) -> u8 {
+    match high > low {
+        true => ((value - low) / (high - low) * 255.0).round() as u8,
+        false => 0,
+    }
+}
+
+fn crop_deep<S: DeepSample>(
+    frame: &ImageBuffer<Luma<S>, Vec<S>>,
+    range: (f32, f32),
+    width: u32,
+    height: u32,
+    center: (u32, u32),
+    output: &mut GrayImage,
+) {
+    let (left, top, inside_width, inside_height) =
+        utils::crop_region(frame.dimensions(), width, height, center);
+    if output.dimensions() != (width, height) || (inside_width, inside_height) != (width, height) {
+        *output = GrayImage::new(width, height);
+    }
+    for y in 0..inside_height {
+        for x in 0..inside_width {
+            let value = frame.get_pixel(left + x, top + y)[0].value();
+            output.put_pixel(x, y, Luma([to_gray(value, range)]));
+        }
+    }
+}
+
+fn deep_to_luma<S: DeepSample>(
+    frame: &ImageBuffer<Luma<S>, Vec<S>>,
+    range: (f32, f32),
+) -> GrayImage {
+    GrayImage::from_fn(frame.width(), frame.height(), |x, y| {
+        Luma([to_gray(frame.get_pixel(x, y)[0].value(), range)])
+    })
+}
+
+impl<S: DeepSample> FrameSource for ImageBuffer<Luma<S>, Vec<S>> {
+    type Pixel = Luma<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut GrayImage) {
+        crop_deep(self, S::RANGE, width, height, center, output);
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        deep_to_luma(self, S::RANGE)
+    }
+}
+
+// A deep frame whose samples from `low` to `high` are mapped onto the 256 gray values, e.g. the 40 counts a warm
+// target stands out from its background on a thermal camera, which would be less than a gray value of the full
+// range. Fit the range to the first frame, and track every frame with it:
+//
+//   let range = DeepFrame::fitted(&first).range();
+//   tracker.train(&DeepFrame::new(&first, range), center)?;
+//   tracker.track(&DeepFrame::new(&next, range))?;
+#[derive(Debug, Clone, Copy)]
+pub struct DeepFrame<'a, S: DeepSample> {
+    frame: &'a ImageBuffer<Luma<S>, Vec<S>>,
+    range: (f32, f32),
+}
+
+impl<'a, S: DeepSample> DeepFrame<'a, S> {
+    pub fn new(frame: &'a ImageBuffer<Luma<S>, Vec<S>>, range: (f32, f32)) -> Self {
+        DeepFrame { frame, range }
+    }
+
+    // the frame, with the range of its (finite) values
+    pub fn fitted(frame: &'a ImageBuffer<Luma<S>, Vec<S>>) -> Self {
+        let finite = frame
+            .pixels()
+            .map(|p| p[0].value())
+            .filter(|v| v.is_finite());
+        let range = finite.fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), v| {
+            (low.min(v), high.max(v))
+        });
+        DeepFrame { frame, range }
+    }
+
+    // (low, high)
+    pub fn range(&self) -> (f32, f32) {
+        self.range
+    }
+}
+
+impl<S: DeepSample> FrameSource for DeepFrame<'_, S> {
+    type Pixel = Luma<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.frame.dimensions()
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut GrayImage) {
+        crop_deep(self.frame, self.range, width, height, center, output);
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        deep_to_luma(self.frame, self.range)
+    }
+}
+
+// any frame, tracked in grayscale
+#[derive(Debug, Clone, Copy)]
+pub struct Grayscale<'a, F: FrameSource>(pub &'a F);
+
+impl<F: FrameSource> FrameSource for Grayscale<'_, F> {
+    type Pixel = Luma<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.0.dimensions()
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut GrayImage) {
+        let mut window = Frame::new(width, height);
+        self.0.crop_into(width, height, center, &mut window);
+        *output = imageops::grayscale(&window);
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        self.0.to_luma()
+    }
+}
+
+// A width x height frame of P pixels in a borrowed buffer. Rows start `stride` bytes apart, and pixels
+// `bytes_per_pixel` bytes apart: rows may be padded (as in most camera buffers), and pixels may carry bytes the
+// tracker ignores after their P::CHANNEL_COUNT channels (e.g. the X of RGBX, tracked as Rgb<u8>).
+#[derive(Debug, Clone, Copy)]
+pub struct FrameView<'a, P: FramePixel> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    bytes_per_pixel: usize,
+    pixel: PhantomData<P>,
+}
+
+impl<'a, P: FramePixel> FrameView<'a, P> {
+    pub fn new(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        bytes_per_pixel: usize,
+    ) -> Result<FrameView<'a, P>, Error> {
+        if bytes_per_pixel < P::CHANNEL_COUNT as usize {
+            return Err(Error::FrameBuffer("fewer bytes per pixel than channels"));
+        }
+        let row = width as usize * bytes_per_pixel;
+        if stride < row {
+            return Err(Error::FrameBuffer("the stride is shorter than a row"));
+        }
+        let length = match height {
+            0 => 0,
+            height => (height as usize - 1) * stride + row,
+        };
+        if data.len() < length {
+            return Err(Error::FrameBuffer("the buffer is too small for the frame"));
+        }
+        Ok(FrameView {
+            data,
+            width,
+            height,
+            stride,
+            bytes_per_pixel,
+            pixel: PhantomData,
+        })
+    }
+
+    // a buffer of rows without padding, and pixels without extra bytes (like the one of an ImageBuffer)
+    pub fn packed(data: &'a [u8], width: u32, height: u32) -> Result<FrameView<'a, P>, Error> {
+        let channels = P::CHANNEL_COUNT as usize;
+        FrameView::new(data, width, height, width as usize * channels, channels)
+    }
+
+    // the channels of the pixel at (x, y)
+    pub fn pixel(&self, x: u32, y: u32) -> &'a [u8] {
+        let start = y as usize * self.stride + x as usize * self.bytes_per_pixel;
+        &self.data[start..start + P::CHANNEL_COUNT as usize]
+    }
+
+    // a copy of the frame in an ImageBuffer
+    pub fn to_frame(&self) -> Frame<P> {
+        Frame::from_fn(self.width, self.height, |x, y| {
+            *P::from_slice(self.pixel(x, y))
+        })
+    }
+}
+
+impl<P: FramePixel> FrameSource for FrameView<'_, P> {
+    type Pixel = P;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // the same window as utils::crop_into, copied row by row (or pixel by pixel, when they have extra bytes)
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut Frame<P>) {
+        let (left, top, inside_width, inside_height) =
+            utils::crop_region(self.dimensions(), width, height, center);
+        if output.dimensions() != (width, height)
+            || (inside_width, inside_height) != (width, height)
+        {
+            *output = Frame::new(width, height);
+        }
+
+        let channels = P::CHANNEL_COUNT as usize;
+        let row = width as usize * channels;
+        let inside_row = inside_width as usize * channels;
+        for y in 0..inside_height as usize {
+            let start = (top as usize + y) * self.stride + left as usize * self.bytes_per_pixel;
+            let input_row = &self.data[start..start + inside_width as usize * self.bytes_per_pixel];
+            let output_row = &mut (**output)[y * row..y * row + inside_row];
+            if self.bytes_per_pixel == channels {
+                output_row.copy_from_slice(input_row);
+            } else {
+                for (output, input) in output_row
+                    .chunks_exact_mut(channels)
+                    .zip(input_row.chunks_exact(self.bytes_per_pixel))
+                {
+                    output.copy_from_slice(&input[..channels]);
+                }
+            }
+        }
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        GrayImage::from_fn(self.width, self.height, |x, y| {
+            P::from_slice(self.pixel(x, y)).to_luma()
+        })
+    }
+}
+
+// A frame of raw 8-bit pixels, tracked in grayscale, as the bindings take them: with 1 or 2 bytes per pixel, the
+// first byte of every pixel is its gray value (GRAY8, or the Y of YUY2). With 3 or more, the first three bytes are
+// RGB (RGB, RGBA, RGBx).
+#[cfg(any(feature = "ffi", feature = "node", feature = "uniffi"))]
+pub(crate) enum RawFrame<'a> {
+    Luma(FrameView<'a, Luma<u8>>),
+    Rgb(FrameView<'a, image::Rgb<u8>>),
+}
+
+#[cfg(any(feature = "ffi", feature = "node", feature = "uniffi"))]
+impl<'a> RawFrame<'a> {
+    pub(crate) fn new(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        bytes_per_pixel: usize,
+    ) -> Result<RawFrame<'a>, Error> {
+        match bytes_per_pixel {
+            1 | 2 => {
+                FrameView::new(data, width, height, stride, bytes_per_pixel).map(RawFrame::Luma)
+            }
+            _ => FrameView::new(data, width, height, stride, bytes_per_pixel).map(RawFrame::Rgb),
+        }
+    }
+}
+
+#[cfg(any(feature = "ffi", feature = "node", feature = "uniffi"))]
+impl FrameSource for RawFrame<'_> {
+    type Pixel = Luma<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            RawFrame::Luma(frame) => frame.dimensions(),
+            RawFrame::Rgb(frame) => frame.dimensions(),
+        }
+    }
+
+    fn crop_into(&self, width: u32, height: u32, center: (u32, u32), output: &mut GrayImage) {
+        match self {
+            RawFrame::Luma(frame) => frame.crop_into(width, height, center, output),
+            RawFrame::Rgb(frame) => Grayscale(frame).crop_into(width, height, center, output),
+        }
+    }
+
+    fn to_luma(&self) -> GrayImage {
+        match self {
+            RawFrame::Luma(frame) => frame.to_luma(),
+            RawFrame::Rgb(frame) => frame.to_luma(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_settings;
+    use crate::{MosseTracker, MosseTrackerSettings};
+    use image::{Rgb, RgbImage};
+
+    fn frame(center: (u32, u32)) -> RgbImage {
+        RgbImage::from_fn(128, 96, |x, y| {
+            let (dx, dy) = (x as i32 - center.0 as i32, y as i32 - center.1 as i32);
+            if dx.abs() <= 6 && dy.abs() <= 6 {
+                Rgb([(150 + 8 * dx) as u8, (150 + 4 * dy) as u8, 60])
+            } else {
+                Rgb([20, 30, 40])
+            }
+        })
+    }
+
+    // the frame as RGBX, in rows padded to 520 bytes
+    fn padded_rgbx(frame: &RgbImage) -> Vec<u8> {
+        let mut buffer = vec![0xAA; 520 * frame.height() as usize];
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            let start = y as usize * 520 + x as usize * 4;
+            buffer[start..start + 3].copy_from_slice(&pixel.0);
+        }
+        buffer
+    }
+
+    #[test]
+    fn views_track_like_image_buffers() {
+        let settings = MosseTrackerSettings {
+            width: 128,
+            height: 96,
+            window_size: 32,
+            scale_adaptation: true,
+            ..Default::default()
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        let mut view_tracker = MosseTracker::new(&settings);
+
+        let first = frame((60, 50));
+        let buffer = padded_rgbx(&first);
+        let view = FrameView::<Rgb<u8>>::new(&buffer, 128, 96, 520, 4).unwrap();
+        assert_eq!(view.to_frame(), first);
+        tracker.train(&first, (60, 50)).unwrap();
+        view_tracker.train(&view, (60, 50)).unwrap();
+
+        for center in [(63, 48), (66, 47)] {
+            let next = frame(center);
+            let buffer = padded_rgbx(&next);
+            let view = FrameView::<Rgb<u8>>::new(&buffer, 128, 96, 520, 4).unwrap();
+            let (pred, view_pred) = (
+                tracker.track_new_frame(&next),
+                view_tracker.track_new_frame(&view),
+            );
+            assert_eq!(pred.position, view_pred.position);
+            assert_eq!(pred.scale, view_pred.scale);
+            tracker.update(&next).unwrap();
+            view_tracker.update(&view).unwrap();
+        }
+        assert_eq!(tracker.state(), view_tracker.state());
+    }
+
+    #[test]
+    fn dynamic_images_are_tracked_in_grayscale() {
+        let settings = MosseTrackerSettings {
+            width: 128,
+            height: 96,
+            window_size: 32,
+            ..Default::default()
+        };
+        let gray = |center| imageops::grayscale(&frame(center));
+        // a 16-bit frame uses the full range: cutting it to 8 bits would leave it white
+        let deep = |center| {
+            let gray: GrayImage = gray(center);
+            DynamicImage::ImageLuma16(image::ImageBuffer::from_fn(128, 96, |x, y| {
+                Luma([gray.get_pixel(x, y)[0] as u16 * 257])
+            }))
+        };
+        let mut window = GrayImage::new(32, 32);
+        deep((60, 50)).crop_into(32, 32, (60, 50), &mut window);
+        assert_eq!(
+            window,
+            imageops::crop_imm(&gray((60, 50)), 44, 34, 32, 32).to_image()
+        );
+        assert_eq!(FrameSource::to_luma(&deep((60, 50))), gray((60, 50)));
+
+        let mut tracker = MosseTracker::new(&settings);
+        let mut deep_tracker = MosseTracker::new(&settings);
+        let mut rgb_tracker = MosseTracker::new(&settings);
+        tracker.train(&gray((60, 50)), (60, 50)).unwrap();
+        deep_tracker.train(&deep((60, 50)), (60, 50)).unwrap();
+        rgb_tracker
+            .train(&Grayscale(&frame((60, 50))), (60, 50))
+            .unwrap();
+        let pred = tracker.track_new_frame(&gray((63, 48)));
+        assert_eq!(pred.location, (63, 48));
+        let deep_pred = deep_tracker.track_new_frame(&deep((63, 48)));
+        let rgb_pred = rgb_tracker.track_new_frame(&Grayscale(&frame((63, 48))));
+        assert_eq!(deep_pred.position, pred.position);
+        assert_eq!(rgb_pred.position, pred.position);
+    }
+
+    #[test]
+    fn deep_frames_keep_their_contrast() {
+        // a warm target on a warm background, 40 counts apart in a 16-bit range: less than one 8-bit gray value
+        let thermal = |center: (u32, u32), background: u16| {
+            ImageBuffer::from_fn(128, 96, |x, y| {
+                let (dx, dy) = (x as i32 - center.0 as i32, y as i32 - center.1 as i32);
+                if dx.abs() <= 6 && dy.abs() <= 6 {
+                    Luma([(30020 + 2 * dx + dy) as u16])
+                } else {
+                    Luma([background])
+                }
+            })
+        };
+        let first = thermal((60, 50), 30000);
+        let range = DeepFrame::fitted(&first).range();
+        assert_eq!(range, (30000.0, 30038.0));
+        let mut window = GrayImage::new(32, 32);
+        DeepFrame::new(&first, range).crop_into(32, 32, (60, 50), &mut window);
+        let values: Vec<u8> = window.pixels().map(|p| p[0]).collect();
+        assert_eq!(values.iter().min(), Some(&0));
+        assert_eq!(values.iter().max(), Some(&255));
+        // in the full range of u16, the target is gone
+        first.crop_into(32, 32, (60, 50), &mut window);
+        assert!(window.pixels().all(|p| p[0] == 117));
+
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker
+            .train(&DeepFrame::new(&first, range), (60, 50))
+            .unwrap();
+        for center in [(63, 48), (66, 47), (69, 49)] {
+            // the background warms up: the range stays, so the target keeps the gray values it was learned with
+            let next = thermal(center, 30004);
+            let view = DeepFrame::new(&next, range);
+            view.crop_into(32, 32, center, &mut window);
+            assert_eq!(window.get_pixel(0, 0)[0], to_gray(30004.0, range));
+            assert_eq!(tracker.track_new_frame(&view).location, center);
+            tracker.update(&view).unwrap();
+        }
+
+        // f32 maps 0.0 to 1.0 by default, as image does, and values outside a range are clamped
+        let radiometric: ImageBuffer<Luma<f32>, Vec<f32>> =
+            ImageBuffer::from_fn(128, 96, |x, _| Luma([x as f32 / 127.0]));
+        let gray = FrameSource::to_luma(&radiometric);
+        assert_eq!(
+            (gray.get_pixel(0, 0)[0], gray.get_pixel(127, 0)[0]),
+            (0, 255)
+        );
+        let gray = DeepFrame::new(&radiometric, (0.25, 0.5)).to_luma();
+        assert_eq!(
+            (
+                gray.get_pixel(0, 0)[0],
+                gray.get_pixel(48, 0)[0],
+                gray.get_pixel(127, 0)[0]
+            ),
+            (0, 131, 255)
+        );
+    }
+
+    #[test]
+    fn rejects_short_buffers() {
+        let buffer = vec![0; 100];
+        assert!(FrameView::<Rgb<u8>>::packed(&buffer, 10, 3).is_ok());
+        assert_eq!(
+            FrameView::<Rgb<u8>>::packed(&buffer, 10, 4).unwrap_err(),
+            Error::FrameBuffer("the buffer is too small for the frame")
+        );
+        assert!(FrameView::<Rgb<u8>>::new(&buffer, 10, 3, 20, 3).is_err());
+        assert!(FrameView::<Rgb<u8>>::new(&buffer, 10, 3, 40, 2).is_err());
+    }
+}
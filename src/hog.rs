@@ -0,0 +1,176 @@
+// Histogram of oriented gradients (HOG), in the variant of Felzenszwalb et al. (2010), "Object Detection with
+// Discriminatively Trained Part-Based Models" (FHOG).
+//
+// FHOG describes a cell of cell_size x cell_size pixels with 31 values: a histogram of 18 contrast-sensitive
+// orientations, one of 9 contrast-insensitive orientations, and 4 texture features, all normalized against the
+// gradient energy of the four blocks of 2x2 cells around the cell.
+// Correlation trackers usually compute these on a grid of cells, which shrinks the window by the cell size.
+// Here the cells are dense: every pixel gets the features of the cell centered on it, so that the filter keeps
+// working at pixel resolution (and the rest of the tracker doesn't notice the difference).
+
+use crate::features::sobel;
+
+// the number of feature channels
+pub const HOG_CHANNELS: usize = 31;
+
+// the number of contrast-insensitive orientations (there are twice as many contrast-sensitive ones)
+const ORIENTATIONS: usize = 9;
+// the normalized histograms are truncated at this value
+const TRUNCATION: f32 = 0.2;
+// the weight of the texture features (1 / sqrt(18))
+const TEXTURE_WEIGHT: f32 = 0.2357;
+
+// The 31 FHOG channels of a width x height window, one Vec per channel, row by row. `planes` holds the values
+// of every channel of the window (row by row); every pixel takes its gradient from the channel where it is strongest.
+pub fn hog(planes: &[Vec<f32>], width: u32, height: u32, cell_size: u32) -> Vec<Vec<f32>> {
+    let length = (width * height) as usize;
+    let (w, h) = (width as i64, height as i64);
+    let cell = cell_size.max(1) as i64;
+
+    // the strongest gradient of every pixel over all channels
+    let mut gradients = vec![(0f32, 0f32); length];
+    for plane in planes {
+        let (gx, gy) = sobel(plane, width, height);
+        for (gradient, (x, y)) in gradients.iter_mut().zip(gx.into_iter().zip(gy)) {
+            if x * x + y * y > gradient.0 * gradient.0 + gradient.1 * gradient.1 {
+                *gradient = (x, y);
+            }
+        }
+    }
+
+    // the contrast-sensitive orientation histogram of every pixel: its gradient magnitude, shared between
+    // the two orientations nearest to its gradient
+    let signed = 2 * ORIENTATIONS;
+    let mut histograms = vec![vec![0f32; length]; signed];
+    for (i, &(x, y)) in gradients.iter().enumerate() {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude == 0.0 {
+            continue;
+        }
+        let angle = y.atan2(x).rem_euclid(2.0 * std::f32::consts::PI);
+        let position = angle / (2.0 * std::f32::consts::PI) * signed as f32;
+        let first = position.floor() as usize % signed;
+        let weight = position - position.floor();
+        histograms[first][i] += magnitude * (1.0 - weight);
+        histograms[(first + 1) % signed][i] += magnitude * weight;
+    }
+
+    // the histogram of the cell around every pixel
+    let cells: Vec<Vec<f32>> = histograms
+        .iter()
+        .map(|histogram| box_sum(histogram, w, h, cell))
+        .collect();
+
+    // the gradient energy of every cell, and the normalization factors of the four blocks around it
+    let energy: Vec<f32> = (0..length)
+        .map(|i| {
+            (0..ORIENTATIONS)
+                .map(|o| {
+                    let unsigned = cells[o][i] + cells[o + ORIENTATIONS][i];
+                    unsigned * unsigned
+                })
+                .sum()
+        })
+        .collect();
+    let at = |x: i64, y: i64| energy[(y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize];
+    let blocks = [(-cell, -cell), (cell, -cell), (-cell, cell), (cell, cell)];
+
+    let mut features = vec![vec![0f32; length]; HOG_CHANNELS];
+    for i in 0..length {
+        let (x, y) = (i as i64 % w, i as i64 / w);
+        let norms = blocks.map(|(dx, dy)| {
+            let block = at(x, y) + at(x + dx, y) + at(x, y + dy) + at(x + dx, y + dy);
+            1.0 / (block + f32::EPSILON).sqrt()
+        });
+
+        let mut texture = [0f32; 4];
+        for o in 0..signed {
+            let mut sum = 0.0;
+            for (k, norm) in norms.iter().enumerate() {
+                let value = (cells[o][i] * norm).min(TRUNCATION);
+                sum += value;
+                texture[k] += value;
+            }
+            features[o][i] = 0.5 * sum;
+        }
+        for o in 0..ORIENTATIONS {
+            let unsigned = cells[o][i] + cells[o + ORIENTATIONS][i];
+            features[signed + o][i] = 0.5
+                * norms
+                    .iter()
+                    .map(|norm| (unsigned * norm).min(TRUNCATION))
+                    .sum::<f32>();
+        }
+        for (k, value) in texture.iter().enumerate() {
+            features[signed + ORIENTATIONS + k][i] = TEXTURE_WEIGHT * value;
+        }
+    }
+    features
+}
+
+// the sum of the values in the `size` x `size` box around every pixel of a width x height image (clipped at its edges)
+fn box_sum(values: &[f32], width: i64, height: i64, size: i64) -> Vec<f32> {
+    // integral[(y * (width + 1)) + x] is the sum of all values above and left of (x, y)
+    let stride = width + 1;
+    let mut integral = vec![0f32; (stride * (height + 1)) as usize];
+    for y in 0..height {
+        let mut row = 0.0;
+        for x in 0..width {
+            row += values[(y * width + x) as usize];
+            integral[((y + 1) * stride + x + 1) as usize] =
+                integral[(y * stride + x + 1) as usize] + row;
+        }
+    }
+
+    let sum = |x: i64, y: i64| integral[(y * stride + x) as usize];
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            let (left, top) = ((x - size / 2).max(0), (y - size / 2).max(0));
+            let (right, bottom) = (
+                (x - size / 2 + size).min(width),
+                (y - size / 2 + size).min(height),
+            );
+            sum(right, bottom) - sum(left, bottom) - sum(right, top) + sum(left, top)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_sums() {
+        let values = vec![1.0; 25];
+        let sums = box_sum(&values, 5, 5, 3);
+        assert_eq!(sums[2 * 5 + 2], 9.0);
+        assert_eq!(sums[0], 4.0);
+        assert_eq!(sums[5 + 4], 6.0);
+    }
+
+    #[test]
+    fn edges_vote_for_their_orientation() {
+        // a vertical edge: dark on the left, bright on the right
+        let plane: Vec<f32> = (0..32 * 32)
+            .map(|i| if i % 32 < 16 { 10.0 } else { 200.0 })
+            .collect();
+        let features = hog(&[plane], 32, 32, 4);
+        assert_eq!(features.len(), HOG_CHANNELS);
+
+        // at the edge, the orientation pointing right (0 radians) wins, among the signed and the unsigned ones
+        let at_edge = 10 * 32 + 16;
+        let strongest = |channels: std::ops::Range<usize>| {
+            channels
+                .max_by(|a, b| features[*a][at_edge].total_cmp(&features[*b][at_edge]))
+                .unwrap()
+        };
+        assert_eq!(strongest(0..18), 0);
+        assert_eq!(strongest(18..27), 18);
+        assert!(features[0][at_edge] > 0.0 && features[0][at_edge] <= 2.0 * TRUNCATION);
+
+        // far from the edge there are no gradients at all
+        assert!(features.iter().all(|channel| channel[10 * 32 + 2] == 0.0));
+        assert!(features.iter().flatten().all(|v| v.is_finite()));
+    }
+}
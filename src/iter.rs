@@ -0,0 +1,125 @@
+// Tracking a stream of frames as an iterator: frames.track_with(&mut tracker) predicts where the target is in
+// every frame, lets the tracker learn from it unless the target is lost (see Tracker::track()), and yields the
+// index of the frame in the stream with the prediction.
+
+use crate::{Error, Frame, FramePixel, Tracker};
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+
+// for iterators of frames (or references to frames), e.g. a Vec<GrayImage> or the decoded frames of a video
+pub trait TrackFrames<P: FramePixel>: Iterator + Sized
+where
+    Self::Item: Borrow<Frame<P>>,
+{
+    // track the frames with a trained tracker
+    fn track_with<T: Tracker<P>>(self, tracker: &mut T) -> Tracked<'_, Self, T, P> {
+        Tracked {
+            frames: self,
+            tracker,
+            index: 0,
+            pixel: PhantomData,
+        }
+    }
+}
+
+impl<P: FramePixel, I: Iterator> TrackFrames<P> for I where I::Item: Borrow<Frame<P>> {}
+
+// the iterator of TrackFrames::track_with(). Yields an error for a frame the tracker can't learn from (e.g. one
+// of another size), and carries on with the next one.
+#[derive(Debug)]
+pub struct Tracked<'t, I, T, P> {
+    frames: I,
+    tracker: &'t mut T,
+    index: usize,
+    pixel: PhantomData<P>,
+}
+
+impl<I, T, P> Iterator for Tracked<'_, I, T, P>
+where
+    P: FramePixel,
+    I: Iterator,
+    I::Item: Borrow<Frame<P>>,
+    T: Tracker<P>,
+{
+    type Item = Result<(usize, T::Output), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frames.next()?;
+        let frame = frame.borrow();
+        let index = self.index;
+        self.index += 1;
+
+        Some(self.tracker.track(frame).map(|output| (index, output)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+    use crate::{MosseTracker, TrackingStatus};
+    use image::GrayImage;
+
+    #[test]
+    fn tracks_streams_of_frames() {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker.train(&frame((40, 50)), (40, 50)).unwrap();
+
+        let frames: Vec<GrayImage> = (1..=5).map(|i| frame((40 + 3 * i, 50))).collect();
+        let locations: Vec<(usize, (u32, u32))> = frames
+            .iter()
+            .track_with(&mut tracker)
+            .map(|tracked| tracked.map(|(index, pred)| (index, pred.location)))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            locations,
+            vec![
+                (0, (43, 50)),
+                (1, (46, 50)),
+                (2, (49, 50)),
+                (3, (52, 50)),
+                (4, (55, 50))
+            ]
+        );
+
+        // a frame of the wrong size is an error, and the stream goes on
+        let frames = vec![GrayImage::new(64, 64), frame((58, 50))];
+        let mut tracked = frames.into_iter().track_with(&mut tracker);
+        assert!(tracked.next().unwrap().is_err());
+        assert_eq!(tracked.next().unwrap().unwrap().1.location, (58, 50));
+        assert!(tracked.next().is_none());
+    }
+
+    #[test]
+    fn lost_targets_are_not_learned_from() {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker.train(&frame((40, 50)), (40, 50)).unwrap();
+        let state = tracker.state();
+
+        // the target is gone in the second frame, and back in the third
+        let frames = [frame((40, 50)), GrayImage::new(128, 96), frame((41, 50))];
+        let statuses: Vec<TrackingStatus> = frames
+            .iter()
+            .take(2)
+            .track_with(&mut tracker)
+            .map(|tracked| tracked.unwrap().1.status)
+            .collect();
+        assert_eq!(statuses, [TrackingStatus::Tracking, TrackingStatus::Lost]);
+        let learned = tracker.state();
+        assert_ne!(learned.filter, state.filter);
+
+        // nothing was learned from the empty frame
+        let mut reference = MosseTracker::new(&test_settings(128, 96));
+        reference.train(&frame((40, 50)), (40, 50)).unwrap();
+        reference.track(&frames[0]).unwrap();
+        assert_eq!(learned.filter, reference.state().filter);
+
+        let mut tracked = frames[2..].iter().track_with(&mut tracker);
+        assert_eq!(tracked.next().unwrap().unwrap().1.location, (41, 50));
+    }
+}
@@ -0,0 +1,160 @@
+// A constant-velocity Kalman filter for the target center.
+// The state is (x, y, vx, vy) in frame pixels, and the tracker measures (x, y) once per frame (dt = 1).
+
+type Matrix4 = [[f32; 4]; 4];
+
+// variance of the random acceleration between frames, and of the tracker's position measurements (in px^2)
+pub const DEFAULT_PROCESS_NOISE: f32 = 0.5;
+pub const DEFAULT_MEASUREMENT_NOISE: f32 = 4.0;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KalmanFilter {
+    pub(crate) state: [f32; 4],
+    pub(crate) covariance: Matrix4,
+    pub(crate) process_noise: f32,
+    pub(crate) measurement_noise: f32,
+}
+
+impl KalmanFilter {
+    // start at rest at `position`
+    pub fn new(position: (f32, f32), process_noise: f32, measurement_noise: f32) -> KalmanFilter {
+        // we know where the target is, but not how fast it is moving
+        let mut covariance = [[0.0; 4]; 4];
+        covariance[0][0] = measurement_noise;
+        covariance[1][1] = measurement_noise;
+        covariance[2][2] = 100.0;
+        covariance[3][3] = 100.0;
+
+        KalmanFilter {
+            state: [position.0, position.1, 0.0, 0.0],
+            covariance,
+            process_noise,
+            measurement_noise,
+        }
+    }
+
+    pub fn position(&self) -> (f32, f32) {
+        (self.state[0], self.state[1])
+    }
+
+    // in pixels per frame
+    pub fn velocity(&self) -> (f32, f32) {
+        (self.state[2], self.state[3])
+    }
+
+    // advance the state by one frame, and return the predicted position
+    pub fn predict(&mut self) -> (f32, f32) {
+        self.state[0] += self.state[2];
+        self.state[1] += self.state[3];
+
+        // P = F P F^T + Q, with F = [[I, I], [0, I]]
+        let p = self.covariance;
+        let mut fp = p;
+        for j in 0..4 {
+            fp[0][j] = p[0][j] + p[2][j];
+            fp[1][j] = p[1][j] + p[3][j];
+        }
+        let mut fpf = fp;
+        for row in fpf.iter_mut() {
+            row[0] += row[2];
+            row[1] += row[3];
+        }
+        // the random acceleration mostly affects the velocity
+        fpf[0][0] += self.process_noise / 4.0;
+        fpf[1][1] += self.process_noise / 4.0;
+        fpf[2][2] += self.process_noise;
+        fpf[3][3] += self.process_noise;
+        self.covariance = fpf;
+
+        self.position()
+    }
+
+    // fold a measured position into the state, and return the corrected position
+    pub fn correct(&mut self, measurement: (f32, f32)) -> (f32, f32) {
+        let p = self.covariance;
+
+        // innovation covariance S = H P H^T + R, with H selecting the position
+        let s = [
+            [p[0][0] + self.measurement_noise, p[0][1]],
+            [p[1][0], p[1][1] + self.measurement_noise],
+        ];
+        let det = s[0][0] * s[1][1] - s[0][1] * s[1][0];
+        if det.abs() < f32::EPSILON {
+            return self.position();
+        }
+        let s_inv = [
+            [s[1][1] / det, -s[0][1] / det],
+            [-s[1][0] / det, s[0][0] / det],
+        ];
+
+        // Kalman gain K = P H^T S^-1 (4x2)
+        let mut gain = [[0.0; 2]; 4];
+        for (i, row) in gain.iter_mut().enumerate() {
+            for (j, k) in row.iter_mut().enumerate() {
+                *k = p[i][0] * s_inv[0][j] + p[i][1] * s_inv[1][j];
+            }
+        }
+
+        let innovation = [measurement.0 - self.state[0], measurement.1 - self.state[1]];
+        for (x, k) in self.state.iter_mut().zip(&gain) {
+            *x += k[0] * innovation[0] + k[1] * innovation[1];
+        }
+
+        // P = (I - K H) P
+        let mut corrected = p;
+        for i in 0..4 {
+            for j in 0..4 {
+                corrected[i][j] = p[i][j] - gain[i][0] * p[0][j] - gain[i][1] * p[1][j];
+            }
+        }
+        self.covariance = corrected;
+
+        self.position()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learns_constant_velocity() {
+        let mut kf = KalmanFilter::new(
+            (10.0, 20.0),
+            DEFAULT_PROCESS_NOISE,
+            DEFAULT_MEASUREMENT_NOISE,
+        );
+        for i in 1..=30 {
+            kf.predict();
+            kf.correct((10.0 + 2.0 * i as f32, 20.0 - i as f32));
+        }
+        let (vx, vy) = kf.velocity();
+        assert!((vx - 2.0).abs() < 0.1, "vx = {}", vx);
+        assert!((vy + 1.0).abs() < 0.1, "vy = {}", vy);
+
+        // without measurements, the target keeps moving
+        let (x, y) = kf.predict();
+        assert!((x - 72.0).abs() < 0.5, "x = {}", x);
+        assert!((y + 11.0).abs() < 0.5, "y = {}", y);
+    }
+
+    #[test]
+    fn smooths_noisy_measurements() {
+        let mut kf = KalmanFilter::new(
+            (50.0, 50.0),
+            DEFAULT_PROCESS_NOISE,
+            DEFAULT_MEASUREMENT_NOISE,
+        );
+        let mut max_error: f32 = 0.0;
+        for i in 0..50 {
+            kf.predict();
+            let jitter = if i % 2 == 0 { 2.0 } else { -2.0 };
+            let (x, _) = kf.correct((50.0 + jitter, 50.0));
+            if i > 10 {
+                max_error = max_error.max((x - 50.0).abs());
+            }
+        }
+        assert!(max_error < 2.0, "max error {}", max_error);
+    }
+}
@@ -0,0 +1,348 @@
+// Kernelized correlation filter (KCF), after Henriques et al. (2015),
+// "High-Speed Tracking with Kernelized Correlation Filters".
+//
+// Where MOSSE learns a linear filter, KCF learns a kernel ridge regression over all circular shifts of the
+// training window. With a Gaussian kernel, both training and detection still boil down to elementwise
+// operations in the Fourier domain.
+
+use crate::utils::{preprocess_with, Preprocessing, WindowFunction};
+use crate::{
+    build_target, check_center, check_frame_size, compute_apce, compute_psr, confidence, fft,
+    find_peak, padded_window_size, window_crop, Error, Prediction, Tracker, TrackingStatus,
+    DEFAULT_RESPONSE_SIGMA,
+};
+use image::GrayImage;
+use rustfft::num_complex::Complex;
+use std::fmt::Debug;
+
+#[derive(Debug)]
+pub struct KcfTrackerSettings {
+    pub width: u32,
+    pub height: u32,
+    pub window_size: u32,
+    // see MosseTrackerSettings::padding. KCF relies on background context, so it defaults to 2.0.
+    pub padding: f32,
+    pub learning_rate: f32,
+    pub regularization: f32,
+    // bandwidth of the Gaussian kernel
+    pub kernel_sigma: f32,
+    // see MosseTrackerSettings::psr_threshold
+    pub psr_threshold: f32,
+    // see MosseTrackerSettings::window_function
+    pub window_function: WindowFunction,
+    // see MosseTrackerSettings::preprocessing
+    pub preprocessing: Preprocessing,
+}
+
+impl Default for KcfTrackerSettings {
+    // NOTE: the frame dimensions have no sensible default and should always be set by the caller.
+    fn default() -> Self {
+        KcfTrackerSettings {
+            width: 0,
+            height: 0,
+            window_size: 64,
+            padding: 2.0,
+            learning_rate: 0.075,
+            regularization: 0.0001,
+            kernel_sigma: 0.5,
+            psr_threshold: 7.0,
+            window_function: WindowFunction::Cosine,
+            preprocessing: Preprocessing::default(),
+        }
+    }
+}
+
+pub struct KcfTracker {
+    // the learned dual coefficients (alpha) and the appearance model they belong to (x), both in the Fourier domain
+    model_alphaf: Vec<Complex<f32>>,
+    model_xf: Vec<Complex<f32>>,
+
+    frame_width: u32,
+    frame_height: u32,
+    window_size: u32,
+    target_window_size: u32,
+    current_target_center: (u32, u32),
+
+    // FFT of the desired response: a Gaussian peak centered on the window
+    target: Vec<Complex<f32>>,
+
+    eta: f32,
+    regularization: f32,
+    kernel_sigma: f32,
+    psr_threshold: f32,
+    window_weights: Vec<f32>,
+    preprocessing: Preprocessing,
+
+    pub last_psr: f32,
+    pub last_apce: f32,
+    frames_tracked: u32,
+
+    real_fft: fft::RealFft,
+    inv_fft: fft::Plan,
+}
+
+impl Debug for KcfTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KcfTracker")
+            .field("model_alphaf", &self.model_alphaf)
+            .field("model_xf", &self.model_xf)
+            .field("frame_width", &self.frame_width)
+            .field("frame_height", &self.frame_height)
+            .field("window_size", &self.window_size)
+            .field("target_window_size", &self.target_window_size)
+            .field("current_target_center", &self.current_target_center)
+            .field("eta", &self.eta)
+            .field("regularization", &self.regularization)
+            .field("kernel_sigma", &self.kernel_sigma)
+            .field("psr_threshold", &self.psr_threshold)
+            .field("window_weights", &self.window_weights)
+            .field("preprocessing", &self.preprocessing)
+            .field("last_psr", &self.last_psr)
+            .field("last_apce", &self.last_apce)
+            .field("frames_tracked", &self.frames_tracked)
+            .finish()
+    }
+}
+
+impl KcfTracker {
+    pub fn new(settings: &KcfTrackerSettings) -> KcfTracker {
+        let window_size = padded_window_size(
+            settings.window_size,
+            settings.padding,
+            settings.width,
+            settings.height,
+        );
+        let length = (window_size * window_size) as usize;
+
+        let (fft, inv_fft) = fft::plans(length);
+
+        let mut target: Vec<Complex<f32>> =
+            build_target(window_size, window_size, DEFAULT_RESPONSE_SIGMA)
+                .into_iter()
+                .map(|p| Complex::new(p, 0.0))
+                .collect();
+        fft.process(&mut target);
+
+        KcfTracker {
+            model_alphaf: vec![Complex::new(0.0, 0.0); length],
+            model_xf: vec![Complex::new(0.0, 0.0); length],
+            frame_width: settings.width,
+            frame_height: settings.height,
+            window_size,
+            target_window_size: settings.window_size,
+            current_target_center: (0, 0),
+            target,
+            eta: settings.learning_rate,
+            regularization: settings.regularization,
+            kernel_sigma: settings.kernel_sigma,
+            psr_threshold: settings.psr_threshold,
+            window_weights: settings.window_function.weights(window_size, window_size),
+            preprocessing: settings.preprocessing,
+            last_psr: 0.0,
+            last_apce: 0.0,
+            frames_tracked: 0,
+            real_fft: fft::RealFft::new(length),
+            inv_fft,
+        }
+    }
+
+    // the preprocessed window around `center`, in the Fourier domain
+    fn features(&self, frame: &GrayImage, center: (u32, u32)) -> Vec<Complex<f32>> {
+        let window = window_crop(frame, self.window_size, self.window_size, center);
+        self.real_fft.process(&preprocess_with(
+            &window,
+            &self.preprocessing,
+            &self.window_weights,
+        ))
+    }
+
+    // the Gaussian kernel between x and every circular shift of z, in the Fourier domain
+    fn gaussian_correlation(&self, xf: &[Complex<f32>], zf: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        // rustfft doesn't normalize, so by Parseval |x|^2 = sum(|X|^2) / N
+        let n = xf.len() as f32;
+        let xx: f32 = xf.iter().map(|x| x.norm_sqr()).sum::<f32>() / n;
+        let zz: f32 = zf.iter().map(|z| z.norm_sqr()).sum::<f32>() / n;
+
+        // cross-correlation of x and z, for all shifts at once
+        let mut xz: Vec<Complex<f32>> = xf.iter().zip(zf).map(|(x, z)| x.conj() * z).collect();
+        self.inv_fft.process(&mut xz);
+
+        // NOTE: preprocessing normalizes the windows to unit norm (unless disabled), so the squared distances are in [0, 4]
+        let sigma2 = self.kernel_sigma * self.kernel_sigma;
+        let kernel: Vec<f32> = xz
+            .iter()
+            .map(|c| {
+                let distance = (xx + zz - 2.0 * c.re / n).max(0.0);
+                (-distance / sigma2).exp()
+            })
+            .collect();
+        self.real_fft.process(&kernel)
+    }
+
+    // see MosseTracker::check_frame()
+    fn check_frame(&self, frame: &GrayImage) -> Result<(), Error> {
+        check_frame_size(
+            frame.dimensions(),
+            (self.frame_width, self.frame_height),
+            self.window_size,
+        )
+    }
+
+    // fit the dual coefficients to the window around the current center, and blend them in with rate `eta`
+    fn learn(&mut self, frame: &GrayImage, eta: f32) {
+        let xf = self.features(frame, self.current_target_center);
+        let kf = self.gaussian_correlation(&xf, &xf);
+
+        let alphaf = self
+            .target
+            .iter()
+            .zip(&kf)
+            .map(|(y, k)| y / (k + self.regularization));
+
+        self.model_alphaf
+            .iter_mut()
+            .zip(alphaf)
+            .for_each(|(prev, new)| *prev = eta * new + (1.0 - eta) * *prev);
+        self.model_xf
+            .iter_mut()
+            .zip(xf)
+            .for_each(|(prev, new)| *prev = eta * new + (1.0 - eta) * *prev);
+    }
+}
+
+impl Tracker for KcfTracker {
+    // the center of the target
+    type Target = (u32, u32);
+    type Output = Prediction;
+
+    fn train(&mut self, frame: &GrayImage, target_center: (u32, u32)) -> Result<(), Error> {
+        self.check_frame(frame)?;
+        check_center(frame.dimensions(), target_center)?;
+        self.current_target_center = target_center;
+        self.frames_tracked = 0;
+        self.learn(frame, 1.0);
+        Ok(())
+    }
+
+    fn predict(&mut self, frame: &GrayImage) -> Prediction {
+        self.frames_tracked += 1;
+        let center = self.current_target_center;
+        let zf = self.features(frame, center);
+        let kzf = self.gaussian_correlation(&self.model_xf, &zf);
+
+        let mut response: Vec<Complex<f32>> = self
+            .model_alphaf
+            .iter()
+            .zip(&kzf)
+            .map(|(a, k)| a * k)
+            .collect();
+        self.inv_fft.process(&mut response);
+
+        let ((px, py), max, (x_offset, y_offset)) = find_peak(&response, self.window_size);
+        let window_half = (self.window_size / 2) as i32;
+        let target_half = (self.target_window_size / 2) as i32;
+        let x_max = (self.frame_width as i32 - target_half).max(target_half);
+        let y_max = (self.frame_height as i32 - target_half).max(target_half);
+
+        let dx = px as i32 - window_half;
+        let dy = py as i32 - window_half;
+        let new_x = (center.0 as i32 + dx).clamp(target_half, x_max);
+        let new_y = (center.1 as i32 + dy).clamp(target_half, y_max);
+        let position = (
+            (center.0 as f32 + dx as f32 + x_offset).clamp(target_half as f32, x_max as f32),
+            (center.1 as f32 + dy as f32 + y_offset).clamp(target_half as f32, y_max as f32),
+        );
+
+        self.current_target_center = (new_x as u32, new_y as u32);
+        self.last_psr = compute_psr(&response, self.window_size, self.window_size, max, (px, py));
+        self.last_apce = compute_apce(&response, max);
+
+        Prediction {
+            location: self.current_target_center,
+            position,
+            psr: self.last_psr,
+            apce: self.last_apce,
+            scale: 1.0,
+            aspect_ratio: 1.0,
+            angle: 0.0,
+            size: (
+                self.target_window_size as f32,
+                self.target_window_size as f32,
+            ),
+            confidence: confidence(self.last_psr, self.psr_threshold),
+            status: match self.last_psr > self.psr_threshold {
+                true => TrackingStatus::Tracking,
+                false => TrackingStatus::Lost,
+            },
+            frame_index: self.frames_tracked,
+            velocity: None,
+        }
+    }
+
+    fn update(&mut self, frame: &GrayImage) -> Result<(), Error> {
+        self.check_frame(frame)?;
+        let eta = self.eta;
+        self.learn(frame, eta);
+        Ok(())
+    }
+
+    // see MosseTracker::track(): a lost target is not learned from
+    fn track(&mut self, frame: &GrayImage) -> Result<Prediction, Error> {
+        self.check_frame(frame)?;
+        let prediction = self.predict(frame);
+        if prediction.status != TrackingStatus::Lost {
+            self.update(frame)?;
+        }
+        Ok(prediction)
+    }
+
+    fn reset(&mut self) {
+        self.model_alphaf.fill(Complex::new(0.0, 0.0));
+        self.model_xf.fill(Complex::new(0.0, 0.0));
+        self.current_target_center = (0, 0);
+        self.last_psr = 0.0;
+        self.last_apce = 0.0;
+        self.frames_tracked = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    fn frame_with_target(center: (u32, u32)) -> GrayImage {
+        GrayImage::from_fn(160, 120, |x, y| {
+            let dx = x as i32 - center.0 as i32;
+            let dy = y as i32 - center.1 as i32;
+            if dx.abs() <= 8 && dy.abs() <= 8 {
+                Luma([(140 + 6 * dx + 3 * dy) as u8])
+            } else {
+                Luma([30])
+            }
+        })
+    }
+
+    #[test]
+    fn follows_moving_target() {
+        let settings = KcfTrackerSettings {
+            width: 160,
+            height: 120,
+            window_size: 32,
+            ..Default::default()
+        };
+        let mut tracker = KcfTracker::new(&settings);
+        assert_eq!(tracker.window_size, 64);
+        tracker
+            .train(&frame_with_target((60, 60)), (60, 60))
+            .unwrap();
+
+        for i in 1..=5 {
+            let frame = frame_with_target((60 + 4 * i, 60 - 2 * i));
+            let pred = tracker.predict(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 4 * i, 60 - 2 * i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
+}
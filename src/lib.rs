@@ -2,26 +2,86 @@ extern crate image;
 extern crate imageproc;
 extern crate rustfft;
 
-use image::{imageops, GrayImage, ImageBuffer, Luma};
-use imageproc::geometric_transformations::Projection;
-use imageproc::geometric_transformations::{rotate_about_center, warp, Interpolation};
+use image::{imageops, GrayImage, ImageBuffer, Luma, Pixel};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use rustfft::num_complex::Complex;
 use rustfft::num_traits::Zero;
-use rustfft::{Fft, FftPlanner};
 use std::cmp::Ordering;
 use std::f32;
 use std::fmt::Debug;
 use std::sync::Arc;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(feature = "ndarray")]
+pub mod array;
+pub mod bbox;
+pub mod builder;
+pub mod color_names;
+pub mod draw;
+mod error;
+pub mod events;
+pub mod export;
+pub mod features;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fft;
+pub mod fixed;
+pub mod frame;
+pub mod hog;
+pub mod iter;
+pub mod kalman;
+pub mod kcf;
+mod log_polar;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+pub mod mot;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod otb;
+mod persist;
+pub mod profile;
+#[cfg(feature = "rerun")]
+pub mod recording;
+mod scale;
+mod simd;
+mod spectrum;
+pub mod state;
+pub mod utils;
+#[cfg(feature = "video")]
+pub mod video;
+pub mod vot;
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
 pub mod wasm;
 
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+pub use bbox::BBox;
+pub use builder::{MosseTrackerBuilder, SettingsError};
+pub use error::Error;
+pub use events::TrackerEvent;
+use events::{EventCallback, EventState, SharedCallbacks};
+use features::StandardPreprocessor;
+pub use features::{Features, Preprocessor};
+pub use frame::{DeepFrame, DeepSample, FrameSource, FrameView, Grayscale};
+pub use iter::TrackFrames;
+use kalman::KalmanFilter;
+use log_polar::LogPolarFilter;
+pub use profile::StageTimings;
+use profile::{Profiler, Stage};
+use scale::{ScaleAxis, ScaleFilter};
+use spectrum::Spectrum;
+pub use state::TrackerState;
+use state::{FilterState, ShapeFilterState};
+use utils::{augment_planes, channel_planes, AugmentationPipeline};
+pub use utils::{Preprocessing, WindowFunction};
+
 // TODO: use constant declarations wherever possible
 // TODO: refactor the unwrap statement into match statements wherever we can't be certain a result exists.
 // TODO: behaviour at edge of frame: target may not leave frame, but filter will screw up anyway due to cropping. Move target coord freely within template?
 // TODO: improve initial filter quality: additional affine perturbations, like scaling (zooming)?
 // TODO: 11x11 window around peak for PSR calculation is arbitrary and seems biased towards larger video feeds?
-// TODO: make k (number of perturbarions) a hyperparameter. k = 0 should not be allowed as it is senseless.
 // TODO: FFT objects may be thread safe (Arc), but are they blocking during concurrent calls? See https://docs.rs/crate/rustfft/2.1.0/source/examples/concurrency.rs
 // TODO: Double check: prevent division by zero (everywhere)? Or use div_checked? Inf is not acceptable!!
 
@@ -36,43 +96,16 @@ pub mod wasm;
 // TODO: update routine: benchmark initialization of Gaussian peak on target coordinates.
 // TODO: in general: remove allocating functions by reusing buffers where possible (such as self.prev's)
 
-fn preprocess(image: &GrayImage) -> Vec<f32> {
-    let mut prepped: Vec<f32> = image
-        .pixels()
-        // convert the pixel to u8 and then to f32
-        .map(|p| p[0] as f32)
-        // add 1, and take the natural logarithm
-        .map(|p| (p + 1.0).ln())
-        .collect();
-
-    // normalize to mean = 0 (subtract image-wide mean from each pixel)
-    let sum: f32 = prepped.iter().sum();
-    let mean: f32 = sum / prepped.len() as f32;
-    prepped.iter_mut().for_each(|p| *p = *p - mean);
-
-    // normalize to norm = 1, if possible
-    let u: f32 = prepped.iter().map(|a| a * a).sum();
-    let norm = u.sqrt();
-    if norm != 0.0 {
-        prepped.iter_mut().for_each(|e| *e = *e / norm)
-    }
+type Identifier = u32;
 
-    // multiply each pixel by a cosine window
-    let (width, height) = image.dimensions();
-    let mut position = 0;
-    for i in 0..width {
-        for j in 0..height {
-            let cww = ((f32::consts::PI * i as f32) / (width - 1) as f32).sin();
-            let cwh = ((f32::consts::PI * j as f32) / (height - 1) as f32).sin();
-            prepped[position] = cww.min(cwh) * prepped[position];
-            position += 1;
-        }
-    }
+// The pixel types the tracker works on: any number of 8-bit channels, e.g. Luma<u8> for grayscale or Rgb<u8> for
+// color frames. MosseTracker learns one filter per channel (see Features) and sums their responses.
+pub trait FramePixel: Pixel<Subpixel = u8> + Send + Sync + 'static {}
 
-    return prepped;
-}
+impl<P> FramePixel for P where P: Pixel<Subpixel = u8> + Send + Sync + 'static {}
 
-type Identifier = u32;
+// a frame (or window) of FramePixels
+pub type Frame<P> = ImageBuffer<P, Vec<u8>>;
 
 #[derive(Debug)]
 pub struct MultiMosseTracker {
@@ -84,24 +117,109 @@ pub struct MultiMosseTracker {
 
     // how many times a tracker is allowed to fail the PSR threshold
     desperation_level: u32,
+
+    // see on_event()
+    event_callbacks: SharedCallbacks,
+
+    // how add_target() picks IDs, and the next ID it hands out under IdPolicy::Monotonic
+    id_policy: IdPolicy,
+    next_id: Identifier,
+}
+
+// how MultiMosseTracker::add_target() picks the IDs of new targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdPolicy {
+    // count up from 0: an ID is never handed out twice, not even after its target is dropped
+    #[default]
+    Monotonic,
+    // the lowest ID not in use: the IDs of dropped targets come back (e.g. for a fixed number of slots)
+    Recycle,
 }
 
 impl MultiMosseTracker {
     pub fn new(settings: MosseTrackerSettings, desperation_level: u32) -> MultiMosseTracker {
-        return MultiMosseTracker {
+        MultiMosseTracker {
             trackers: Vec::new(),
-            settings: settings,
-            desperation_level: desperation_level,
-        };
+            settings,
+            desperation_level,
+            event_callbacks: SharedCallbacks::default(),
+            id_policy: IdPolicy::default(),
+            next_id: 0,
+        }
+    }
+
+    pub fn with_id_policy(mut self, id_policy: IdPolicy) -> MultiMosseTracker {
+        self.id_policy = id_policy;
+        self
+    }
+
+    // add a target with a new ID (see IdPolicy), and return the ID
+    pub fn add_target<F: FrameSource>(
+        &mut self,
+        coords: (u32, u32),
+        frame: &F,
+    ) -> Result<Identifier, Error> {
+        let id = self.new_id();
+        self.add_or_replace_target(id, coords, frame)?;
+        Ok(id)
+    }
+
+    // see add_target(), for a target with its own settings (e.g. a larger window for a larger target)
+    pub fn add_target_with<F: FrameSource>(
+        &mut self,
+        settings: &MosseTrackerSettings,
+        coords: (u32, u32),
+        frame: &F,
+    ) -> Result<Identifier, Error> {
+        let id = self.new_id();
+        self.add_or_replace_target_with(id, settings, coords, frame)?;
+        Ok(id)
     }
 
-    pub fn add_or_replace_target(&mut self, id: Identifier, coords: (u32, u32), frame: &GrayImage) {
+    fn new_id(&self) -> Identifier {
+        match self.id_policy {
+            IdPolicy::Monotonic => self.next_id,
+            IdPolicy::Recycle => (0..)
+                .find(|id| !self.contains(*id))
+                .expect("fewer targets than IDs"),
+        }
+    }
+
+    pub fn add_or_replace_target<F: FrameSource>(
+        &mut self,
+        id: Identifier,
+        coords: (u32, u32),
+        frame: &F,
+    ) -> Result<(), Error> {
         // Add a target by specifying its coords and a new ID.
         // Specify an existing ID to replace an existing tracked target.
 
         // create a new tracker for this target and train it
         let mut new_tracker = MosseTracker::new(&self.settings);
-        new_tracker.train(frame, coords);
+        new_tracker.train(frame, coords)?;
+        self.insert(id, new_tracker);
+        Ok(())
+    }
+
+    // see add_or_replace_target(), for a target with its own settings. The redetection and the PSR threshold of
+    // these settings apply to the target, instead of the ones of the multi-tracker.
+    pub fn add_or_replace_target_with<F: FrameSource>(
+        &mut self,
+        id: Identifier,
+        settings: &MosseTrackerSettings,
+        coords: (u32, u32),
+        frame: &F,
+    ) -> Result<(), Error> {
+        settings.validate()?;
+        let mut new_tracker = MosseTracker::new(settings);
+        new_tracker.train(frame, coords)?;
+        self.insert(id, new_tracker);
+        Ok(())
+    }
+
+    // add a trained tracker, or replace the one with the same ID
+    fn insert(&mut self, id: Identifier, mut new_tracker: MosseTracker) {
+        new_tracker.on_event(self.event_callbacks.for_target(id));
 
         match self.trackers.iter_mut().find(|tracker| tracker.0 == id) {
             Some(tuple) => {
@@ -111,49 +229,291 @@ impl MultiMosseTracker {
             // add the tracker to the map
             _ => self.trackers.push((id, 0, new_tracker)),
         };
+        // IDs of targets added by hand are never handed out (again) by add_target()
+        self.next_id = self.next_id.max(id.saturating_add(1));
+    }
+
+    // the tracker of the target with this ID
+    pub fn get(&self, id: Identifier) -> Option<&MosseTracker> {
+        self.trackers
+            .iter()
+            .find(|(tracker_id, _, _)| *tracker_id == id)
+            .map(|(_, _, tracker)| tracker)
+    }
+
+    pub fn get_mut(&mut self, id: Identifier) -> Option<&mut MosseTracker> {
+        self.trackers
+            .iter_mut()
+            .find(|(tracker_id, _, _)| *tracker_id == id)
+            .map(|(_, _, tracker)| tracker)
+    }
+
+    pub fn contains(&self, id: Identifier) -> bool {
+        self.get(id).is_some()
+    }
+
+    // the IDs of all targets, in the order they were added
+    pub fn ids(&self) -> impl Iterator<Item = Identifier> + '_ {
+        self.trackers.iter().map(|(id, _, _)| *id)
+    }
+
+    // stop tracking the target with this ID, and return its tracker
+    pub fn remove(&mut self, id: Identifier) -> Option<MosseTracker> {
+        let index = self
+            .trackers
+            .iter()
+            .position(|(tracker_id, _, _)| *tracker_id == id)?;
+        Some(self.trackers.remove(index).2)
+    }
+
+    // keep only the targets `keep` returns true for (e.g. the ones still inside a region of interest)
+    pub fn retain(&mut self, mut keep: impl FnMut(Identifier, &MosseTracker) -> bool) {
+        self.trackers.retain(|(id, _, tracker)| keep(*id, tracker));
     }
 
-    pub fn track(&mut self, frame: &GrayImage) -> Vec<(Identifier, Prediction)> {
-        let mut predictions: Vec<(Identifier, Prediction)> = Vec::new();
-        for (id, death_watch, tracker) in &mut self.trackers {
-            // compute the location of the object in the new frame and save it
-            let pred = tracker.track_new_frame(frame);
-            predictions.push((*id, pred));
+    // locate every target in a new frame, and learn from the targets that were found (see Tracker::predict()
+    // and Tracker::update()). Targets that fail the PSR threshold too often are dropped.
+    pub fn track<F: FrameSource>(
+        &mut self,
+        frame: &F,
+    ) -> Result<Vec<(Identifier, Prediction)>, Error> {
+        self.update_all(frame)
+    }
+
+    // See track(). The work the targets share is done once for the frame instead of once per target: for color
+    // frames, that's the grayscale frame the scale, aspect ratio and log-polar filters sample.
+    // NOTE: the windows of the targets are preprocessed (and transformed) each on their own, even where they
+    // overlap: the preprocessing of a pixel depends on the rest of its window.
+    pub fn update_all<F: FrameSource>(
+        &mut self,
+        frame: &F,
+    ) -> Result<Vec<(Identifier, Prediction)>, Error> {
+        let shape_estimators = self
+            .trackers
+            .iter()
+            .filter(|(_, _, tracker)| tracker.estimates_shape())
+            .count();
+        if F::Pixel::CHANNEL_COUNT > 1 && shape_estimators > 1 {
+            let grayscale = frame.to_luma();
+            let predictions = self.predict_targets(frame, &grayscale);
+            self.update_targets(frame, &grayscale)?;
+            Ok(predictions)
+        } else {
+            let grayscale = Grayscale(frame);
+            let predictions = self.predict_targets(frame, &grayscale);
+            self.update_targets(frame, &grayscale)?;
+            Ok(predictions)
+        }
+    }
+
+    // see Tracker::predict(), with `grayscale` the frame in grayscale
+    fn predict_targets<F: FrameSource, G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &F,
+        grayscale: &G,
+    ) -> Vec<(Identifier, Prediction)> {
+        // the targets are tracked independently of each other, so with rayon they are tracked in parallel
+        #[cfg(feature = "rayon")]
+        let trackers = self.trackers.par_iter_mut();
+        #[cfg(not(feature = "rayon"))]
+        let trackers = self.trackers.iter_mut();
+
+        trackers
+            .map(|(id, _, tracker)| {
+                // compute the location of the object in the new frame (or re-detect it) and save it
+                (*id, tracker.track_new_frame_with(frame, grayscale))
+            })
+            .collect()
+    }
+
+    // see Tracker::update(), with `grayscale` the frame in grayscale
+    fn update_targets<F: FrameSource, G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &F,
+        grayscale: &G,
+    ) -> Result<(), Error> {
+        // see predict_targets()
+        #[cfg(feature = "rayon")]
+        let trackers = self.trackers.par_iter_mut();
+        #[cfg(not(feature = "rayon"))]
+        let mut trackers = self.trackers.iter_mut();
 
-            // if the tracker made the PSR threshold, update it.
+        trackers.try_for_each(|(_, death_watch, tracker)| {
+            // if the tracker made the PSR threshold, update it (unless the target is occluded, see update()).
             // if not, we increment its death ticker.
-            if tracker.last_psr > self.settings.psr_threshold {
-                tracker.update(frame);
+            if tracker.last_psr > tracker.settings().psr_threshold {
+                tracker.update_with(frame, grayscale)?;
                 *death_watch = 0u32;
             } else {
                 *death_watch += 1;
             }
-        }
+            Ok::<(), Error>(())
+        })?;
 
         // prune all filters with an expired death ticker
         let level = &self.desperation_level;
         self.trackers
             .retain(|(_id, death_count, _tracker)| death_count < level);
-
-        return predictions;
+        Ok(())
     }
 
     pub fn dump_filter_reals(&self) -> Vec<GrayImage> {
-        return self.trackers.iter().map(|t| t.2.dump_filter().0).collect();
+        self.trackers.iter().map(|t| t.2.dump_filter().0).collect()
+    }
+
+    // the spatial filter of every target (see MosseTracker::filter_image())
+    pub fn filter_images(&self) -> Vec<(Identifier, GrayImage)> {
+        self.trackers
+            .iter()
+            .map(|(id, _, tracker)| (*id, tracker.filter_image()))
+            .collect()
     }
 
     pub fn size(&self) -> usize {
         self.trackers.len()
     }
+
+    // call `callback` with the ID of the target, every event of its track (see events::TrackerEvent) and the
+    // prediction that caused it
+    pub fn on_event(
+        &mut self,
+        callback: impl FnMut(Identifier, TrackerEvent, &Prediction) + Send + 'static,
+    ) {
+        self.event_callbacks.push(Box::new(callback));
+    }
+}
+
+impl<P: FramePixel> Tracker<P> for MultiMosseTracker {
+    // the ID of the target (replacing the target with that ID, if any) and its center
+    type Target = (Identifier, (u32, u32));
+    type Output = Vec<(Identifier, Prediction)>;
+
+    fn train(&mut self, frame: &Frame<P>, (id, center): Self::Target) -> Result<(), Error> {
+        self.add_or_replace_target(id, center, frame)
+    }
+
+    fn predict(&mut self, frame: &Frame<P>) -> Self::Output {
+        self.predict_targets(frame, &Grayscale(frame))
+    }
+
+    fn update(&mut self, frame: &Frame<P>) -> Result<(), Error> {
+        self.update_targets(frame, &Grayscale(frame))
+    }
+
+    fn track(&mut self, frame: &Frame<P>) -> Result<Self::Output, Error> {
+        MultiMosseTracker::track(self, frame)
+    }
+
+    // drop all targets
+    fn reset(&mut self) {
+        self.trackers.clear();
+    }
+}
+
+// A tracker of frames of P pixels. Implemented by MosseTracker and kcf::KcfTracker (for a single target, given by
+// its center) and by MultiMosseTracker (for any number of targets, each with an ID), so that integration code can
+// switch between algorithms, and tests can stand in a mock.
+pub trait Tracker<P: FramePixel = Luma<u8>> {
+    // what train() needs to know about a target
+    type Target;
+    // what predict() reports about the target(s)
+    type Output;
+
+    // learn the appearance of a target
+    fn train(&mut self, frame: &Frame<P>, target: Self::Target) -> Result<(), Error>;
+
+    // locate the target(s) in a new frame, without learning from it
+    fn predict(&mut self, frame: &Frame<P>) -> Self::Output;
+
+    // blend the appearance of the target(s) at the last predicted location(s) into the model
+    fn update(&mut self, frame: &Frame<P>) -> Result<(), Error>;
+
+    // locate the target(s) in a new frame, and learn from the ones that were found. By default predict() and
+    // update(), for trackers whose update() leaves lost targets alone.
+    fn track(&mut self, frame: &Frame<P>) -> Result<Self::Output, Error> {
+        let output = self.predict(frame);
+        self.update(frame)?;
+        Ok(output)
+    }
+
+    // forget all that was learned: the tracker needs training again before it can predict
+    fn reset(&mut self);
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Prediction {
+    // the predicted center of the target in the frame
     pub location: (u32, u32),
+    // the same center, refined to sub-pixel precision by interpolating the correlation peak
+    pub position: (f32, f32),
+    // peak-to-sidelobe ratio of the correlation response: the tracker's confidence in this prediction.
+    // Bolme et al. report values between 20 and 60 for good tracks, and below 7 when the target is lost.
     pub psr: f32,
+    // average peak-to-correlation energy of the response (Wang et al., 2017).
+    // Drops sharply when the response map turns multi-modal, e.g. under occlusion.
+    pub apce: f32,
+    // size of the target relative to the window size it was trained with (always 1.0 without scale adaptation)
+    pub scale: f32,
+    // width/height ratio of the target relative to the one it was trained with (always 1.0 without aspect ratio adaptation)
+    pub aspect_ratio: f32,
+    // in-plane rotation of the target since training, clockwise in radians (always 0.0 without rotation estimation)
+    pub angle: f32,
+    // the size (width, height) of the target in the frame: the window size, times the scale and aspect ratio
+    pub size: (f32, f32),
+    // the PSR mapped to [0, 1]: 0 at (or below) the PSR threshold, 1 at a PSR of 20 and above
+    pub confidence: f32,
+    // whether the tracker found the target
+    pub status: TrackingStatus,
+    // the number of frames tracked since the tracker was trained (1 for the first tracked frame)
+    pub frame_index: u32,
+    // the velocity of the target in frame pixels per frame (only with a motion model)
+    pub velocity: Option<(f32, f32)>,
+}
+
+// what a prediction says about the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum TrackingStatus {
+    // the tracker found the target
+    Tracking,
+    // the tracker found the target, but its response is much weaker than usual: something covers part of it.
+    // The filter doesn't learn from the frame (see MosseTrackerSettings::occlusion_apce_ratio).
+    Occluded,
+    // the PSR is not above the threshold: the tracker lost the target, and the location is a guess
+    Lost,
+}
+
+impl Prediction {
+    // the box of the target around its (sub-pixel) position. NOTE: it ignores the angle.
+    pub fn bbox(&self) -> BBox {
+        BBox::from_cxcywh(self.position.0, self.position.1, self.size.0, self.size.1)
+    }
+}
+
+// the PSR mapped to [0, 1]: ramps up linearly from 0 at the PSR threshold to 1 at FULL_CONFIDENCE_PSR
+pub(crate) fn confidence(psr: f32, psr_threshold: f32) -> f32 {
+    let span = FULL_CONFIDENCE_PSR - psr_threshold;
+    if span > 0.0 {
+        ((psr - psr_threshold) / span).clamp(0.0, 1.0)
+    } else if psr > psr_threshold {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+// the long-term, anchor and ensemble filters
+impl FilterState {
+    // blend the window spectrum (Fi) into the top and bottom parts at learning rate `eta`.
+    // NOTE: the filter itself is left alone.
+    fn learn(&mut self, target: &[Complex<f32>], spectrum: &[Complex<f32>], eta: f32) {
+        simd::learn(&mut self.top, &mut self.bottom, target, spectrum, eta);
+    }
 }
 
 pub struct MosseTracker {
-    filter: Vec<Complex<f32>>,
+    // one filter per channel of the frames it was trained on, one after the other
+    filter: Spectrum,
 
     // constants frame height
     frame_width: u32,
@@ -161,26 +521,115 @@ pub struct MosseTracker {
 
     // stores dimensions of tracking window and its center
     // window is square for now, this variable contains the size of the square edge
+    // NOTE: with padding, the tracking window is larger than the target itself (target_window_size)
     window_size: u32,
+    target_window_size: u32,
     current_target_center: (u32, u32), // represents center in frame
+    // the windows are cut from the frame downscaled by this factor (see MosseTrackerSettings::downscale)
+    downscale: f32,
+
+    // the size of the target relative to the window size, and the filter that estimates it (if enabled)
+    current_scale: f32,
+    scale_filter: Option<ScaleFilter>,
+
+    // the target is current_stretch times wider and 1/current_stretch times higher than at the current scale alone,
+    // and the filter that estimates that stretch (if enabled)
+    current_stretch: f32,
+    aspect_filter: Option<ScaleFilter>,
+    // estimates the change in size and in-plane rotation of the target together (if enabled)
+    log_polar_filter: Option<LogPolarFilter>,
 
-    // the 'target' (G). A single Gaussian peak centered at the tracking window.
+    // the in-plane rotation of the target, and whether we estimate it
+    current_angle: f32,
+    rotation_estimation: bool,
+
+    // whether the support of the filter is restricted to the reliable pixels of the target (CSR-DCF)
+    spatial_reliability: bool,
+    // whether the support of the filter is restricted to the target box, while it learns from the whole window (BACF)
+    background_aware: bool,
+    // the flat-top mask that replaces the cosine window, and how far from the window center a peak may be
+    // (see MosseTrackerSettings::boundary_masking)
+    boundary_mask: Option<Vec<f32>>,
+    search_radius: u32,
+    // the weights of the window function (see MosseTrackerSettings::window_function)
+    window_weights: Vec<f32>,
+    // turns the windows into the feature channels of the filter
+    preprocessor: Arc<dyn Preprocessor>,
+
+    // the motion model of the target center (if enabled)
+    motion_model: Option<KalmanFilter>,
+    use_motion_model: bool,
+
+    // the 'target' (G). A single Gaussian peak centered at the tracking window, with a standard deviation of
+    // response_sigma window pixels.
     target: Vec<Complex<f32>>,
+    response_sigma: f32,
 
     // constants: learning rate and PSR threshold
     eta: f32,
     regularization: f32, // not super important for MOSSE: see paper fig 4.
+    psr_threshold: f32,
+    adaptive_learning_rate: bool,
 
     // the previous Ai and Bi
-    last_top: Vec<Complex<f32>>,
-    last_bottom: Vec<Complex<f32>>,
+    last_top: Spectrum,
+    last_bottom: Spectrum,
+
+    // the conservatively updated long-term filter that validates this (short-term) one (if enabled)
+    long_term: Option<FilterState>,
+    long_term_learning_rate: Option<f32>,
+
+    // the other members of the filter ensemble, next to the filter itself (empty without an ensemble)
+    ensemble: Vec<FilterState>,
+    ensemble_size: usize,
+
+    initialization: FilterInitialization,
+    // the perturbations of the training window that train() learns the initial filter from
+    augmentation: AugmentationPipeline,
+
+    // the filter as trained on the first frame, and how often (in frames) we check for drift against it (if enabled)
+    anchor: Option<FilterState>,
+    anchor_interval: Option<u32>,
+    frames_since_anchor: u32,
+
+    // the real part of the last response map (see response_map())
+    last_response: Vec<f32>,
+    // the spectrum of the last window learned from, a buffer reused from frame to frame
+    spectrum: Vec<Complex<f32>>,
 
     // the previous psr
     pub last_psr: f32,
 
+    // the previous apce
+    pub last_apce: f32,
+
+    // occlusion detection: the running mean APCE over the frames we learned from, and whether the last frame was occluded
+    occlusion_apce_ratio: Option<f32>,
+    mean_apce: f32,
+    learned_frames: u32,
+    occluded: bool,
+
+    // the number of frames tracked since training
+    frames_tracked: u32,
+
+    // whether update() leaves the filter alone (see pause_learning())
+    learning_paused: bool,
+    // the filter learns from every this many tracked frames (see MosseTrackerSettings::update_interval)
+    update_interval: u32,
+
+    // see on_event()
+    events: EventState,
+    event_callbacks: Vec<EventCallback>,
+    // see enable_profiling()
+    profiler: Profiler,
+
     // thread-safe FFT objects containing precomputed parameters for this input data size.
-    fft: Arc<dyn Fft<f32>>,
-    inv_fft: Arc<dyn Fft<f32>>,
+    fft: fft::Plan,
+    real_fft: fft::RealFft,
+    inv_fft: fft::Plan,
+
+    // the settings the tracker was made with
+    settings: MosseTrackerSettings,
 }
 
 impl Debug for MosseTracker {
@@ -190,13 +639,53 @@ impl Debug for MosseTracker {
             .field("frame_width", &self.frame_width)
             .field("frame_height", &self.frame_height)
             .field("window_size", &self.window_size)
+            .field("target_window_size", &self.target_window_size)
+            .field("downscale", &self.downscale)
             .field("current_target_center", &self.current_target_center)
+            .field("current_scale", &self.current_scale)
+            .field("scale_filter", &self.scale_filter)
+            .field("current_stretch", &self.current_stretch)
+            .field("aspect_filter", &self.aspect_filter)
+            .field("log_polar_filter", &self.log_polar_filter)
+            .field("current_angle", &self.current_angle)
+            .field("rotation_estimation", &self.rotation_estimation)
+            .field("spatial_reliability", &self.spatial_reliability)
+            .field("background_aware", &self.background_aware)
+            .field("boundary_mask", &self.boundary_mask)
+            .field("search_radius", &self.search_radius)
+            .field("window_weights", &self.window_weights)
+            .field("preprocessor", &self.preprocessor)
+            .field("motion_model", &self.motion_model)
             .field("target", &self.target)
+            .field("response_sigma", &self.response_sigma)
             .field("eta", &self.eta)
             .field("regularization", &self.regularization)
+            .field("psr_threshold", &self.psr_threshold)
+            .field("adaptive_learning_rate", &self.adaptive_learning_rate)
             .field("last_top", &self.last_top)
             .field("last_bottom", &self.last_bottom)
+            .field("long_term", &self.long_term)
+            .field("long_term_learning_rate", &self.long_term_learning_rate)
+            .field("ensemble", &self.ensemble)
+            .field("ensemble_size", &self.ensemble_size)
+            .field("initialization", &self.initialization)
+            .field("augmentation", &self.augmentation)
+            .field("anchor", &self.anchor)
+            .field("anchor_interval", &self.anchor_interval)
+            .field("frames_since_anchor", &self.frames_since_anchor)
+            .field("last_response", &self.last_response)
             .field("last_psr", &self.last_psr)
+            .field("last_apce", &self.last_apce)
+            .field("occlusion_apce_ratio", &self.occlusion_apce_ratio)
+            .field("mean_apce", &self.mean_apce)
+            .field("learned_frames", &self.learned_frames)
+            .field("frames_tracked", &self.frames_tracked)
+            .field("learning_paused", &self.learning_paused)
+            .field("update_interval", &self.update_interval)
+            .field("events", &self.events)
+            .field("event_callbacks", &self.event_callbacks.len())
+            .field("profiler", &self.profiler)
+            .field("occluded", &self.occluded)
             // These fields don't implement Debug, so I can't use the #[derive(Debug)] impl.
             // .field("fft", &self.fft)
             // .field("inv_fft", &self.inv_fft)
@@ -204,157 +693,596 @@ impl Debug for MosseTracker {
     }
 }
 
-#[derive(Debug)]
+// the standard deviation of the Gaussian peak the filter is trained to respond with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseSigma {
+    // in window pixels
+    Absolute(f32),
+    // as a fraction of the window size (without padding), e.g. 0.1 for a tenth of the target size
+    Relative(f32),
+}
+
+impl ResponseSigma {
+    // the standard deviation in window pixels, for a target of `window_size`
+    pub fn pixels(&self, window_size: u32) -> f32 {
+        match *self {
+            ResponseSigma::Absolute(sigma) => sigma,
+            ResponseSigma::Relative(fraction) => fraction * window_size as f32,
+        }
+    }
+}
+
+impl Default for ResponseSigma {
+    fn default() -> Self {
+        ResponseSigma::Absolute(DEFAULT_RESPONSE_SIGMA)
+    }
+}
+
+// how train() combines the (perturbed) training frames into the initial filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterInitialization {
+    // MOSSE: divide the sum of all tops by the sum of all bottoms
+    #[default]
+    Mosse,
+    // ASEF (Bolme et al., 2009): average the exact filters of the individual training frames
+    Asef,
+}
+
+#[derive(Debug, Clone)]
 pub struct MosseTrackerSettings {
     pub width: u32,
     pub height: u32,
     pub window_size: u32,
     pub learning_rate: f32,
     pub psr_threshold: f32,
+    // lambda in H* = Ai / (Bi + lambda). Larger values give a smoother, more stable filter at the cost of a less sharp peak:
+    // noisy sensors (e.g. thermal cameras) want more than clean webcam footage.
     pub regularization: f32,
+    // scale the learning rate by the confidence (PSR) of the last prediction:
+    // confident frames update at the full learning rate, frames near the PSR threshold barely update.
+    pub adaptive_learning_rate: bool,
+    // freeze filter updates while the target is occluded. A frame counts as occluded when its APCE drops
+    // below this fraction of the average APCE of the frames the filter learned from. None disables detection.
+    pub occlusion_apce_ratio: Option<f32>,
+    // when a target fails the PSR threshold, sweep the filter over the whole frame to re-acquire it
+    pub redetection: bool,
+    // estimate changes in target size with a separate scale filter (DSST), and grow/shrink the window accordingly
+    pub scale_adaptation: bool,
+    // lambda of the scale filter (see regularization). It is much larger than the translation filter's,
+    // as raw pixel features are very sensitive to small misalignments of the target.
+    pub scale_regularization: f32,
+    // also estimate changes in the width/height ratio of the target, by stretching the window along one axis
+    // and squeezing it along the other (requires scale_adaptation)
+    pub aspect_ratio_adaptation: bool,
+    // estimate the in-plane rotation of the target every frame, by correlating a few rotated versions of the window
+    pub rotation_estimation: bool,
+    // estimate changes in size and in-plane rotation of the target together, with one correlation filter on a
+    // log-polar resampling of the target (where both become shifts). A cheaper alternative to scale_adaptation
+    // plus rotation_estimation, that also copes with larger rotations.
+    pub log_polar_estimation: bool,
+    // run a constant-velocity Kalman filter on the target center: it picks the search center every frame,
+    // smooths the reported positions, and stands in for the correlation filter when its response is weak
+    pub motion_model: bool,
+    // the filter is trained on (and searches) a window of `padding` times the window size around the target,
+    // so that fast-moving targets stay inside it between frames. 1.0 means no padding; 2.0 to 2.5 is common.
+    // The padded window is capped at the frame size.
+    pub padding: f32,
+    // restrict the filter to the pixels of the target box that look like foreground (CSR-DCF spatial reliability map),
+    // so that the background inside the box of a non-rectangular target doesn't end up in the filter
+    pub spatial_reliability: bool,
+    // learn the filter from the whole (padded) window, but restrict its support to the target box (BACF).
+    // The background around the target then only serves as negative examples, which reduces boundary effects
+    // and helps against clutter. Only useful with padding (2.0 or more).
+    pub background_aware: bool,
+    // Reduce the boundary effects of the circular correlation: the filter learns from the padded window with
+    // a flat-top mask (that leaves the target box alone and fades out the rest towards the edges) instead of
+    // the cosine window, and the peak of the response is only searched where the target box stays inside
+    // the window. Otherwise, the wrap-around at the edges of the window can produce peaks that pull the
+    // tracker onto busy backgrounds. Only useful with padding (2.0 or more).
+    pub boundary_masking: bool,
+    // the window function that fades out the edges of the window before the correlation (see WindowFunction).
+    // Boundary masking replaces it.
+    pub window_function: WindowFunction,
+    // the steps of preprocessing the windows go through before the correlation (see utils::Preprocessing)
+    pub preprocessing: Preprocessing,
+    // the features the filter is learned on, e.g. gradients instead of the pixel values (see features::Features).
+    // The tracker learns one filter per feature channel.
+    pub features: Features,
+    // your own preprocessing of the windows, which replaces the features and preprocessing settings above
+    // (see features::Preprocessor). None uses those.
+    pub preprocessor: Option<Arc<dyn Preprocessor>>,
+    // keep a second filter that is updated at this (much lower) learning rate, and only from confident frames.
+    // Every frame, it validates the regular filter: when the two disagree on the target position, or the regular
+    // filter is not confident (PSR below 20), and the long-term filter is more confident, the tracker goes with the
+    // long-term filter and restores the regular filter from it. None disables the long-term filter.
+    pub long_term_learning_rate: Option<f32>,
+    // every this many frames, correlate the filter of the first frame (the anchor) around the prediction.
+    // If the anchor confidently puts the target elsewhere, the learned filter has drifted: the prediction moves
+    // to the anchor's, and the anchor is blended back into the learned filter. None disables drift correction.
+    pub anchor_interval: Option<u32>,
+    // the number of filters in the ensemble. Each filter after the first learns from the unperturbed training frame
+    // and its own subset of the perturbed ones, and their responses are fused (weighted by their PSR) before
+    // locating the peak. 1 means just the one filter.
+    pub ensemble_size: usize,
+    // see FilterInitialization. NOTE: ensemble members are always initialized the MOSSE way.
+    pub initialization: FilterInitialization,
+    // the width of the desired response. A small sigma gives a sharp peak that tracks tightly,
+    // a larger one is more forgiving with fast or erratic motion.
+    pub response_sigma: ResponseSigma,
+    // also train the initial filter on sheared and slightly warped (perspective) copies of the target,
+    // so that it generalizes to changes in viewpoint
+    pub affine_augmentation: bool,
+    // also train the initial filter on brighter, darker, higher and lower contrast, and noisy copies of the target,
+    // so that it tolerates changes in lighting
+    pub photometric_augmentation: bool,
+    // also train the initial filter on a few copies of the target shifted by up to this many pixels, with the desired
+    // response shifted along. This teaches the filter to respond off-center correctly, which helps when the initial
+    // box is slightly misplaced. None disables translation augmentation.
+    pub translation_augmentation: Option<u32>,
+    // also train the initial filter on copies of the target with a random rectangle pasted over part of it,
+    // so that it tolerates partial occlusion
+    pub occlusion_augmentation: bool,
+    // the perturbations of the training window that train() learns the initial filter from, next to the window itself.
+    // None trains on the default rotations and scales, plus whatever the *_augmentation settings above enable;
+    // a pipeline replaces all of those (see utils::AugmentationPipeline).
+    pub augmentation: Option<AugmentationPipeline>,
+    // track on the frames downscaled by this factor, e.g. 0.5 for a quarter of the pixels: the window covers the
+    // same part of the frame with fewer pixels, which makes every step of the tracker cheaper. The window size
+    // stays in frame pixels, and so do the predictions; the other sizes in pixels (an absolute response_sigma,
+    // translation_augmentation) are in the pixels of the downscaled frame. 1.0 tracks on the frames as they are.
+    pub downscale: f32,
+    // the filter is correlated with every frame, but only learns from every this many: update() skips the others.
+    // Trades accuracy for throughput, as learning costs about as much as tracking. Which frames are skipped only
+    // depends on the number of frames tracked since training. 1 learns from every frame.
+    pub update_interval: u32,
+}
+
+impl Default for MosseTrackerSettings {
+    // NOTE: the frame dimensions have no sensible default and should always be set by the caller.
+    fn default() -> Self {
+        MosseTrackerSettings {
+            width: 0,
+            height: 0,
+            window_size: 64,
+            learning_rate: 0.05,
+            psr_threshold: 7.0,
+            regularization: 0.001,
+            adaptive_learning_rate: false,
+            occlusion_apce_ratio: None,
+            redetection: false,
+            scale_adaptation: false,
+            scale_regularization: scale::DEFAULT_SCALE_REGULARIZATION,
+            aspect_ratio_adaptation: false,
+            rotation_estimation: false,
+            log_polar_estimation: false,
+            motion_model: false,
+            padding: 1.0,
+            spatial_reliability: false,
+            background_aware: false,
+            boundary_masking: false,
+            window_function: WindowFunction::Cosine,
+            preprocessing: Preprocessing::default(),
+            features: Features::Intensity,
+            preprocessor: None,
+            long_term_learning_rate: None,
+            anchor_interval: None,
+            ensemble_size: 1,
+            initialization: FilterInitialization::Mosse,
+            response_sigma: ResponseSigma::default(),
+            affine_augmentation: false,
+            photometric_augmentation: false,
+            translation_augmentation: None,
+            occlusion_augmentation: false,
+            augmentation: None,
+            downscale: 1.0,
+            update_interval: 1,
+        }
+    }
+}
+
+// the standard deviation of the desired response (in window pixels), unless configured otherwise.
+// A very sharp peak, about as narrow as the one this tracker always trained with. Bolme et al. use 2.0 for 64x64 windows.
+const DEFAULT_RESPONSE_SIGMA: f32 = 0.6;
+
+// the training perturbations of the *_augmentation settings
+fn default_augmentation(settings: &MosseTrackerSettings) -> AugmentationPipeline {
+    use utils::*;
+
+    let mut pipeline = AugmentationPipeline::new()
+        .with(Rotations(TRAINING_ROTATIONS.to_vec()))
+        .with(Scales(TRAINING_SCALES.to_vec()));
+    if settings.affine_augmentation {
+        pipeline = pipeline
+            .with(Shears(TRAINING_SHEARS.to_vec()))
+            .with(Tilts(TRAINING_TILTS.to_vec()));
+    }
+    if settings.photometric_augmentation {
+        pipeline = pipeline
+            .with(Brightness(TRAINING_BRIGHTNESS.to_vec()))
+            .with(Contrast(TRAINING_CONTRASTS.to_vec()))
+            .with(Noise {
+                stddevs: TRAINING_NOISE.to_vec(),
+                seed: 0,
+            });
+    }
+    if settings.occlusion_augmentation {
+        pipeline = pipeline.with(Occlusions {
+            count: TRAINING_OCCLUSIONS,
+            seed: 0,
+        });
+    }
+    if let Some(max_shift) = settings.translation_augmentation {
+        pipeline = pipeline.with(Translations {
+            count: TRAINING_TRANSLATIONS,
+            max_shift,
+            seed: 0,
+        });
+    }
+    pipeline
 }
 
+// the smallest edge (in frame pixels) scale adaptation may shrink the window to
+const MIN_WINDOW_SIZE: f32 = 8.0;
+
+// the most aspect ratio adaptation may stretch (or squeeze) the window along an axis, i.e. the aspect ratio
+// stays within [1/MAX_STRETCH^2, MAX_STRETCH^2]
+const MAX_STRETCH: f32 = 2.0;
+
+// rotation estimation tries the current angle, and this many steps of ROTATION_STEP radians to either side
+const ROTATION_CANDIDATES: i32 = 2;
+const ROTATION_STEP: f32 = 0.05;
+
+// PSR at which the adaptive learning rate reaches the full learning rate (lower bound of 'good' tracks in Bolme et al.).
+// The long-term filter only learns from frames at least this confident.
+const FULL_CONFIDENCE_PSR: f32 = 20.0;
+
+// distance (in frame pixels) between the short-term and long-term predictions beyond which they disagree
+const LONG_TERM_DISAGREEMENT: f32 = 2.0;
+
+// distance (in frame pixels) between the prediction and the anchor's beyond which the filter has drifted,
+// and how much of the anchor is blended back into the filter when it has
+const ANCHOR_DRIFT: f32 = 1.0;
+const ANCHOR_BLEND: f32 = 0.5;
+
 #[allow(non_snake_case)]
 impl MosseTracker {
     pub fn new(settings: &MosseTrackerSettings) -> MosseTracker {
-        // parameterize the FFT objects
-        let mut planner = FftPlanner::new();
-        let mut inv_planner = FftPlanner::new();
+        // NOTE: we initialize the FFTs based on the size of the (padded) window, in pixels of the downscaled frame
+        let (target_window_size, window_size) = window_sizes(settings);
+        let length = (window_size * window_size) as usize;
+        let (fft, inv_fft) = fft::plans(length);
 
-        // NOTE: we initialize the FFTs based on the size of the window
-        let length = (settings.window_size * settings.window_size) as usize;
-        let fft = planner.plan_fft_forward(length);
-        let inv_fft = inv_planner.plan_fft_inverse(length);
+        // boundary masking needs room around the target
+        let boundary_masking = settings.boundary_masking && window_size > target_window_size;
 
         // initialize the filter and its top and bottom parts with zeroes.
-        let filter = vec![Complex::zero(); length];
-        let top = vec![Complex::zero(); length];
-        let bottom = vec![Complex::zero(); length];
+        let filter = Spectrum::zeros(length);
+        let top = Spectrum::zeros(length);
+        let bottom = Spectrum::zeros(length);
 
         // initialize the target output map (G), with a compact Gaussian peak centered on the target object.
         // In the Bolme paper, this map is called gi.
-        let mut target: Vec<Complex<f32>> =
-            build_target(settings.window_size, settings.window_size)
-                .into_iter()
-                .map(|p| Complex::new(p as f32, 0.0))
-                .collect();
+        let sigma = settings.response_sigma.pixels(target_window_size);
+        let mut target: Vec<Complex<f32>> = build_target(window_size, window_size, sigma)
+            .into_iter()
+            .map(|p| Complex::new(p, 0.0))
+            .collect();
         fft.process(&mut target);
 
-        return MosseTracker {
+        MosseTracker {
             filter,
             last_top: top,
             last_bottom: bottom,
+            long_term: None,
+            long_term_learning_rate: settings.long_term_learning_rate,
+            ensemble: Vec::new(),
+            ensemble_size: settings.ensemble_size,
+            initialization: settings.initialization,
+            augmentation: settings
+                .augmentation
+                .clone()
+                .unwrap_or_else(|| default_augmentation(settings)),
+            anchor: None,
+            anchor_interval: settings.anchor_interval,
+            frames_since_anchor: 0,
+            last_response: Vec::new(),
+            spectrum: Vec::new(),
             last_psr: 0.0,
+            last_apce: 0.0,
+            occlusion_apce_ratio: settings.occlusion_apce_ratio,
+            mean_apce: 0.0,
+            learned_frames: 0,
+            frames_tracked: 0,
+            learning_paused: false,
+            update_interval: settings.update_interval.max(1),
+            events: EventState::default(),
+            event_callbacks: Vec::new(),
+            profiler: Profiler::default(),
+            occluded: false,
             eta: settings.learning_rate,
             regularization: settings.regularization,
+            psr_threshold: settings.psr_threshold,
+            adaptive_learning_rate: settings.adaptive_learning_rate,
             target,
+            response_sigma: sigma,
             fft,
+            real_fft: fft::RealFft::new(length),
             inv_fft,
+            settings: settings.clone(),
             frame_width: settings.width,
             frame_height: settings.height,
-            window_size: settings.window_size,
+            window_size,
+            target_window_size,
             current_target_center: (0, 0),
-        };
+            downscale: settings.downscale,
+            current_scale: 1.0,
+            scale_filter: settings.scale_adaptation.then(|| {
+                ScaleFilter::new(
+                    settings.learning_rate,
+                    settings.scale_regularization,
+                    ScaleAxis::Uniform,
+                )
+            }),
+            current_stretch: 1.0,
+            aspect_filter: (settings.scale_adaptation && settings.aspect_ratio_adaptation).then(
+                || {
+                    ScaleFilter::new(
+                        settings.learning_rate,
+                        settings.scale_regularization,
+                        ScaleAxis::Aspect,
+                    )
+                },
+            ),
+            log_polar_filter: settings
+                .log_polar_estimation
+                .then(|| LogPolarFilter::new(settings.learning_rate, settings.regularization)),
+            current_angle: 0.0,
+            rotation_estimation: settings.rotation_estimation,
+            spatial_reliability: settings.spatial_reliability,
+            background_aware: settings.background_aware,
+            boundary_mask: boundary_masking.then(|| boundary_mask(window_size, target_window_size)),
+            search_radius: if boundary_masking {
+                (window_size - target_window_size) / 2
+            } else {
+                window_size
+            },
+            window_weights: settings.window_function.weights(window_size, window_size),
+            preprocessor: settings.preprocessor.clone().unwrap_or_else(|| {
+                Arc::new(StandardPreprocessor {
+                    features: settings.features,
+                    steps: settings.preprocessing,
+                })
+            }),
+            motion_model: None,
+            use_motion_model: settings.motion_model,
+        }
     }
 
     fn compute_2dfft(&self, imagedata: Vec<f32>) -> Vec<Complex<f32>> {
-        let mut buffer: Vec<Complex<f32>> = imagedata
-            .into_iter()
-            .map(|p| Complex::new(p as f32, 0.0))
-            .collect();
+        let start = self.profiler.start();
+        // the windows are real, so half-length FFTs do
+        let spectrum = self.real_fft.process(&imagedata);
+        self.profiler.record(Stage::Fft, start);
+        spectrum
+    }
 
-        // fft.process() CONSUMES the input buffer as scratch space, make sure it is not reused
-        self.fft.process(&mut buffer);
+    // Train a new filter on the first frame in which the object occurs.
+    // The tracker learns one filter per channel of the frame, so it should track frames of the same pixel type.
+    pub fn train<F: FrameSource>(
+        &mut self,
+        input_frame: &F,
+        target_center: (u32, u32),
+    ) -> Result<(), Error> {
+        self.train_on_frames(&[(input_frame, target_center)])
+    }
+
+    // Forget the target and everything learned about it. The buffers and FFT plans are kept for the next train().
+    pub fn reset(&mut self) {
+        for buffer in [&mut self.filter, &mut self.last_top, &mut self.last_bottom] {
+            buffer.update(|values| values.fill(Complex::zero()));
+        }
+        self.long_term = None;
+        self.ensemble.clear();
+        self.anchor = None;
+        self.frames_since_anchor = 0;
+        self.motion_model = None;
+        self.last_response.clear();
+        self.last_psr = 0.0;
+        self.last_apce = 0.0;
+        self.mean_apce = 0.0;
+        self.learned_frames = 0;
+        self.occluded = false;
+        self.frames_tracked = 0;
+        self.events = EventState::default();
+        self.current_target_center = (0, 0);
+        self.current_scale = 1.0;
+        self.current_stretch = 1.0;
+        self.current_angle = 0.0;
+    }
 
-        return buffer;
+    // Forget the target and train on a new one in `bbox` (e.g. a box the user redrew), reusing the buffers and FFT
+    // plans. Only the center of the box is used: the window size stays the one of the settings.
+    // The tracker is left alone if the frame or the box doesn't fit.
+    pub fn retrain<F: FrameSource>(&mut self, frame: &F, bbox: BBox) -> Result<(), Error> {
+        let center = bbox.pixel_center();
+        self.check_frame(frame)?;
+        check_center(frame.dimensions(), center)?;
+        self.reset();
+        self.train(frame, center)
     }
 
-    // Train a new filter on the first frame in which the object occurs
-    pub fn train(&mut self, input_frame: &GrayImage, target_center: (u32, u32)) {
+    // Train a new filter on several frames in which the object occurs, each with the target center in it
+    // (e.g. the first few frames of a labeled clip). Every frame is augmented (see MosseTrackerSettings::augmentation);
+    // with an empty augmentation pipeline, the filter learns from the real frames only.
+    // Tracking continues from the last frame. Does nothing without frames.
+    pub fn train_on_frames<F: FrameSource>(
+        &mut self,
+        frames: &[(&F, (u32, u32))],
+    ) -> Result<(), Error> {
+        for &(frame, center) in frames {
+            self.check_frame(frame)?;
+            check_center(frame.dimensions(), center)?;
+        }
+        let target_center = match frames.last() {
+            Some(&(_, center)) => center,
+            None => return Ok(()),
+        };
+
         // store the target center as the current
         self.current_target_center = target_center;
+        self.current_scale = 1.0;
+        self.current_stretch = 1.0;
+        self.current_angle = 0.0;
+        self.frames_tracked = 0;
+        // the training counts as frame 0 of the profile
+        self.profiler.next_frame(0);
+        self.events = EventState::default();
+        self.last_response.clear();
+        self.motion_model = self.use_motion_model.then(|| {
+            KalmanFilter::new(
+                (target_center.0 as f32, target_center.1 as f32),
+                kalman::DEFAULT_PROCESS_NOISE,
+                kalman::DEFAULT_MEASUREMENT_NOISE,
+            )
+        });
 
-        // cut out the training template by cropping
-        let window = &window_crop(
-            input_frame,
-            self.window_size,
-            self.window_size,
-            target_center,
-        );
+        // the shape filters (scale, aspect ratio, log-polar) learn from the grayscale frames
+        let target_size = self.target_size();
+        if self.estimates_shape() {
+            for (i, &(frame, center)) in frames.iter().enumerate() {
+                let frame = &Grayscale(frame);
+                for filter in [&mut self.scale_filter, &mut self.aspect_filter]
+                    .into_iter()
+                    .flatten()
+                {
+                    if i == 0 {
+                        filter.train(frame, center, target_size);
+                    } else {
+                        filter.update(frame, center, target_size);
+                    }
+                }
+                if let Some(filter) = &mut self.log_polar_filter {
+                    if i == 0 {
+                        filter.train(frame, center, target_size, 0.0);
+                    } else {
+                        filter.update(frame, center, target_size, 0.0);
+                    }
+                }
+            }
+        }
+
+        // cut out the training templates by cropping
+        let windows: Vec<Frame<F::Pixel>> = frames
+            .iter()
+            .map(|&(frame, center)| self.crop_window(frame, center, 0.0))
+            .collect();
+        let window = &windows[windows.len() - 1];
 
         #[cfg(debug_assertions)]
         {
-            window.save("WINDOW.png").unwrap();
+            // a debugging aid only: never fail training over it
+            let _ = imageops::grayscale(window).save("WINDOW.png");
         }
 
-        // build an iterator that produces training frames that have been slightly rotated according to a theta value.
-        let rotated_frames = [
-            0.02, -0.02, 0.05, -0.05, 0.07, -0.07, 0.09, -0.09, 1.1, -1.1, 1.3, -1.3, 1.5, -1.5,
-            2.0, -2.0,
-        ]
-        .iter()
-        .map(|rad| {
-            // Rotate an image clockwise about its center by theta radians.
-            let training_frame =
-                rotate_about_center(window, *rad, Interpolation::Nearest, Luma([0]));
-
-            #[cfg(debug_assertions)]
-            {
-                training_frame
-                    .save(format!("training_frame_rotated_theta_{}.png", rad))
-                    .unwrap();
-            }
+        // the unperturbed training frames, each followed by its perturbed ones (as one image per channel)
+        let training_frames: Vec<_> = windows
+            .iter()
+            .flat_map(|window| {
+                let planes = channel_planes(window);
+                let perturbed = augment_planes(&self.augmentation, &planes);
+                std::iter::once((planes, (0, 0), true)).chain(
+                    perturbed
+                        .into_iter()
+                        .map(|(planes, shift)| (planes, shift, false)),
+                )
+            })
+            .collect();
 
-            return training_frame;
-        });
+        // the spectra of the training frames (Fi), and the desired outputs of the shifted ones, which are
+        // computed in parallel with rayon. The filters learn from them in order, so the result is the same.
+        let spectra: Vec<_> = {
+            let (preprocessor, weights, real_fft) =
+                (&self.preprocessor, self.feature_weights(), &self.real_fft);
+            let (window_size, sigma) = (self.window_size, self.response_sigma);
 
-        // build an iterator that produces training frames that have been slightly scaled to various degrees ('zoomed')
-        let scaled_frames = [0.8, 0.9, 1.1, 1.2].into_iter().map(|scalefactor| {
-            let scale = Projection::scale(scalefactor, scalefactor);
+            #[cfg(feature = "rayon")]
+            let training_frames = training_frames.into_par_iter();
+            #[cfg(not(feature = "rayon"))]
+            let training_frames = training_frames.into_iter();
 
-            let scaled_training_frame = warp(&window, &scale, Interpolation::Nearest, Luma([0]));
+            training_frames
+                .map(|(planes, shift, unperturbed)| {
+                    // calculate the 2D FFT of the preprocessed frame: FFT(fi) = Fi
+                    let Fi = real_fft.process(&preprocessor.preprocess(&planes, weights));
+                    let target = (shift != (0, 0))
+                        .then(|| shifted_target(window_size, sigma, real_fft, shift));
+                    (Fi, target, unperturbed)
+                })
+                .collect()
+        };
 
-            #[cfg(debug_assertions)]
-            {
-                scaled_training_frame
-                    .save(format!("training_frame_scaled_{}.png", scalefactor))
-                    .unwrap();
-            }
+        // one filter per feature channel, learned from scratch
+        let channels = self
+            .preprocessor
+            .channel_count(F::Pixel::CHANNEL_COUNT as usize);
+        let length = channels * self.target.len();
+        let mut last_top = vec![Complex::zero(); length];
+        let mut last_bottom = vec![Complex::zero(); length];
 
-            return scaled_training_frame;
-        });
+        let members = self.ensemble_size.saturating_sub(1);
+        let empty = FilterState {
+            top: vec![Complex::zero(); length],
+            bottom: vec![Complex::zero(); length],
+            filter: vec![Complex::zero(); length],
+        };
+        let mut ensemble = vec![empty; members];
+        let mut member_frame_counts = vec![0; members];
 
-        // Chain these iterators together.
-        // Note that we add the initial, unperturbed training frame as first in line.
-        let training_frames = std::iter::once(window)
-            .cloned()
-            .chain(rotated_frames)
-            .chain(scaled_frames);
-        // TODO: scaling is not ready yet
-        // .chain(scaled_frames);
+        // the sum of the exact filters of the individual training frames (ASEF)
+        let mut exact_filters = vec![Complex::zero(); length];
 
         let mut training_frame_count = 0;
-        for training_frame in training_frames {
-            // preprocess the training frame using preprocess()
-            let vectorized = preprocess(&training_frame);
-
-            // calculate the 2D FFT of the preprocessed frame: FFT(fi) = Fi
-            let Fi = self.compute_2dfft(vectorized);
+        let mut perturbed_frame_count = 0;
+        for (Fi, shifted_target, unperturbed) in spectra {
+            // the desired output for this frame (G)
+            let target = shifted_target.as_ref().unwrap_or(&self.target);
 
             //  compute the complex conjugate of Fi, Fi*.
             let Fi_star: Vec<Complex<f32>> = Fi.iter().map(|e| e.conj()).collect();
 
-            // compute the initial filter
-            let top = self.target.iter().zip(Fi_star.iter()).map(|(g, f)| g * f);
+            // compute the initial filter (every channel has the same desired output)
+            let top = target
+                .iter()
+                .cycle()
+                .zip(Fi_star.iter())
+                .map(|(g, f)| g * f);
             let bottom = Fi.iter().zip(Fi_star.iter()).map(|(f, f_star)| f * f_star);
 
+            if self.initialization == FilterInitialization::Asef {
+                exact_filters
+                    .iter_mut()
+                    .zip(top.clone().zip(bottom.clone()))
+                    .for_each(|(sum, (a, b))| *sum += a / (b + self.regularization));
+            }
+
+            // all ensemble members see the unperturbed frames, and the perturbed ones are dealt out among them
+            for (m, member) in ensemble.iter_mut().enumerate() {
+                if unperturbed || perturbed_frame_count % members == m {
+                    member.learn(target, &Fi, 1.0 / (member_frame_counts[m] + 1) as f32);
+                    member_frame_counts[m] += 1;
+                }
+            }
+            if !unperturbed {
+                perturbed_frame_count += 1;
+            }
+
             // // add the values to the running sum
-            self.last_top
+            last_top
                 .iter_mut()
                 .zip(top)
                 .for_each(|(running, new)| *running += new);
 
-            self.last_bottom
+            last_bottom
                 .iter_mut()
                 .zip(bottom)
                 .for_each(|(running, new)| *running += new);
@@ -363,22 +1291,52 @@ impl MosseTracker {
         }
 
         // divide the values of the top and bottom filters by the number of training perturbations used
-        self.last_top
+        last_top
             .iter_mut()
             .for_each(|e| *e /= training_frame_count as f32);
 
-        self.last_bottom
+        last_bottom
             .iter_mut()
             .for_each(|e| *e /= training_frame_count as f32);
+        self.last_top = Spectrum::from(last_top);
+        self.last_bottom = Spectrum::from(last_bottom);
 
         // compute the filter by dividing Ai and Bi elementwise
-        // note that we add a small quantity to avoid dividing by zero, which would yield NaN's.
-        self.filter = self
-            .last_top
-            .iter()
-            .zip(&self.last_bottom)
-            .map(|(a, b)| a / b + self.regularization)
-            .collect();
+        self.compute_filter();
+
+        if self.initialization == FilterInitialization::Asef {
+            let filter: Vec<Complex<f32>> = exact_filters
+                .iter()
+                .map(|h| h / training_frame_count as f32)
+                .collect();
+            // rebalance the top so that it still divides into the filter, and updates pick up from there
+            self.last_top = filter
+                .iter()
+                .zip(self.last_bottom.get().iter())
+                .map(|(h, b)| h * (b + self.regularization))
+                .collect::<Vec<_>>()
+                .into();
+            self.filter = Spectrum::from(filter);
+        }
+
+        let mask = self.filter_mask(window);
+        if let Some(mask) = &mask {
+            self.constrain_filter(mask);
+        }
+
+        for member in ensemble.iter_mut() {
+            member.filter = divide_filter(&member.top, &member.bottom, self.regularization);
+            if let Some(mask) = &mask {
+                member.filter =
+                    self.constrained_filter(&member.top, &member.bottom, &member.filter, mask);
+            }
+        }
+        self.ensemble = ensemble;
+
+        // the long-term and anchor filters start out from the same training frames
+        self.long_term = self.long_term_learning_rate.map(|_| self.snapshot());
+        self.anchor = self.anchor_interval.map(|_| self.snapshot());
+        self.frames_since_anchor = 0;
 
         #[cfg(debug_assertions)]
         {
@@ -387,48 +1345,200 @@ impl MosseTracker {
                 self.current_target_center.0, self.current_target_center.1
             );
         }
+        Ok(())
+    }
+
+    // the FFT of the desired output for a window that has been shifted by `shift` (x, y) pixels
+    #[cfg(test)]
+    fn shifted_target(&self, shift: (i32, i32)) -> Vec<Complex<f32>> {
+        shifted_target(self.window_size, self.response_sigma, &self.real_fft, shift)
+    }
+
+    // the size of a window pixel in frame pixels, along x and y
+    fn window_scales(&self) -> (f32, f32) {
+        (
+            self.current_scale * self.current_stretch / self.downscale,
+            self.current_scale / self.current_stretch / self.downscale,
+        )
+    }
+
+    // the current size of the target in the frame
+    fn target_size(&self) -> (f32, f32) {
+        let (x_scale, y_scale) = self.window_scales();
+        let size = self.target_window_size as f32;
+        (size * x_scale, size * y_scale)
+    }
+
+    // whether any of the filters that estimate the shape of the target (scale, aspect ratio, log-polar) is enabled
+    fn estimates_shape(&self) -> bool {
+        self.scale_filter.is_some()
+            || self.aspect_filter.is_some()
+            || self.log_polar_filter.is_some()
+    }
+
+    // cut out the window around `center`, at the current scale, resized to the window size of the filter.
+    // The window is rotated by -angle, so that a target rotated clockwise by `angle` appears upright.
+    fn crop_window<F: FrameSource>(
+        &self,
+        frame: &F,
+        center: (u32, u32),
+        angle: f32,
+    ) -> Frame<F::Pixel> {
+        let start = self.profiler.start();
+        let (x_scale, y_scale) = self.window_scales();
+        let window = if x_scale == 1.0 && y_scale == 1.0 {
+            window_crop(frame, self.window_size, self.window_size, center)
+        } else {
+            scaled_window_crop(
+                frame,
+                (self.window_size as f32 * x_scale).round() as u32,
+                (self.window_size as f32 * y_scale).round() as u32,
+                center,
+                self.window_size,
+                self.window_size,
+            )
+        };
+
+        let window = if angle == 0.0 {
+            window
+        } else {
+            rotate_about_center(&window, -angle, Interpolation::Bilinear, black())
+        };
+        self.profiler.record(Stage::Crop, start);
+        window
     }
 
-    pub fn track_new_frame(&mut self, frame: &GrayImage) -> Prediction {
+    // the weights the features of the window are multiplied with
+    fn feature_weights(&self) -> &[f32] {
+        self.boundary_mask
+            .as_deref()
+            .unwrap_or(&self.window_weights)
+    }
+
+    // the preprocessed feature channels of a window, given as one plane per channel (see features::Preprocessor)
+    fn window_features(&self, planes: &[GrayImage]) -> Vec<f32> {
+        let start = self.profiler.start();
+        let features = self.preprocessor.preprocess(planes, self.feature_weights());
+        self.profiler.record(Stage::Preprocess, start);
+        features
+    }
+
+    // the FFT of the preprocessed window centered on `center`: Fi (one window per channel, one after the other)
+    fn window_spectrum<F: FrameSource>(
+        &self,
+        frame: &F,
+        center: (u32, u32),
+        angle: f32,
+    ) -> Vec<Complex<f32>> {
         // cut out the training template by cropping
-        let window = window_crop(
-            frame,
-            self.window_size,
-            self.window_size,
-            self.current_target_center,
-        );
+        let window = self.crop_window(frame, center, angle);
 
-        // preprocess the image using preprocess()
-        let vectorized = preprocess(&window);
+        // compute the preprocessed features of the image
+        let vectorized = self.window_features(&channel_planes(&window));
 
         // calculate the 2D FFT of the preprocessed image: FFT(fi) = Fi
-        let Fi = self.compute_2dfft(vectorized);
+        self.compute_2dfft(vectorized)
+    }
 
-        // elementwise multiplication of F with filter H gives Gi
-        let mut corr_map_gi: Vec<Complex<f32>> =
-            Fi.iter().zip(&self.filter).map(|(a, b)| a * b).collect();
+    // correlate `filter` with the window spectrum Fi. Returns the (complex) response map gi.
+    fn correlate(&self, filter: &[Complex<f32>], Fi: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        // elementwise multiplication of F with filter H gives Gi, for every channel.
+        // The responses of the channels are summed (in the Fourier domain, which saves an inverse FFT per channel).
+        let start = self.profiler.start();
+        let product = simd::multiply_spectra(Fi, filter);
+        let mut corr_map_gi = sum_channels(&product, self.target.len());
+        self.profiler.record(Stage::Correlation, start);
 
         // NOTE: Gi is garbage after this call
+        let start = self.profiler.start();
         self.inv_fft.process(&mut corr_map_gi);
+        self.profiler.record(Stage::Fft, start);
 
-        // find the max value of the filtered image 'gi', along with the position of the maximum
-        let (maxind, max_complex) = corr_map_gi
-            .iter()
-            .enumerate()
-            .max_by(|a, b| {
-                // filtered (gi) is still complex at this point, we only care about the real part
-                a.1.re.partial_cmp(&b.1.re).unwrap_or(Ordering::Equal)
-            })
-            .unwrap(); // we can unwrap the result of max_by(), as we are sure filtered.len() > 0
+        corr_map_gi
+    }
+
+    // the responses of the filter and the ensemble members, weighted by their PSR
+    fn fused_response(&self, Fi: &[Complex<f32>]) -> Vec<Complex<f32>> {
+        let filter = self.filter.get();
+        let filters =
+            std::iter::once(&filter[..]).chain(self.ensemble.iter().map(|m| &m.filter[..]));
 
-        // convert the array index of the max to the coordinates in the window
-        let max_coord_in_window = index_to_coords(self.window_size, maxind as u32);
+        let mut fused = vec![Complex::zero(); self.target.len()];
+        let mut total_weight = 0.0;
+        for filter in filters {
+            let response = self.correlate(filter, Fi);
+            let (maxpos, max, _) = find_peak(&response, self.window_size);
+            let weight = compute_psr(&response, self.window_size, self.window_size, max, maxpos)
+                .max(0.0)
+                // a member that responds to nothing at all still gets an (equally small) say
+                + f32::EPSILON;
+            fused
+                .iter_mut()
+                .zip(&response)
+                .for_each(|(fused, r)| *fused += weight * r);
+            total_weight += weight;
+        }
+        fused.iter_mut().for_each(|fused| *fused /= total_weight);
+
+        fused
+    }
+
+    // locate the target in the window centered on `center`, assuming it is rotated by `angle`,
+    // without changing the tracker state. Also returns the response map the prediction was made from.
+    fn evaluate<F: FrameSource>(
+        &self,
+        frame: &F,
+        center: (u32, u32),
+        angle: f32,
+    ) -> (Prediction, Vec<Complex<f32>>) {
+        let Fi = self.window_spectrum(frame, center, angle);
+        let corr_map_gi = if self.ensemble.is_empty() {
+            self.correlate(&self.filter.get(), &Fi)
+        } else {
+            self.fused_response(&Fi)
+        };
+        (self.locate(&corr_map_gi, center, angle), corr_map_gi)
+    }
 
+    // see evaluate(), with a filter other than the current one
+    fn evaluate_filter<F: FrameSource>(
+        &self,
+        filter: &[Complex<f32>],
+        frame: &F,
+        center: (u32, u32),
+        angle: f32,
+    ) -> (Prediction, Vec<Complex<f32>>) {
+        let Fi = self.window_spectrum(frame, center, angle);
+        let corr_map_gi = self.correlate(filter, &Fi);
+        (self.locate(&corr_map_gi, center, angle), corr_map_gi)
+    }
+
+    // turn the response map of the window centered on `center` into a prediction
+    fn locate(&self, corr_map_gi: &[Complex<f32>], center: (u32, u32), angle: f32) -> Prediction {
+        let (max_coord_in_window, max, (x_offset, y_offset)) =
+            find_peak_within(corr_map_gi, self.window_size, self.search_radius);
+        let (px, py) = max_coord_in_window;
+
+        // the window may be a rotated and resized crop of the frame,
+        // so the shift is rotated and scaled back to frame pixels
         let window_half = (self.window_size / 2) as i32;
-        let x_delta = max_coord_in_window.0 as i32 - window_half;
-        let y_delta = max_coord_in_window.1 as i32 - window_half;
-        let x_max = self.frame_width as i32 - window_half;
-        let y_max = self.frame_height as i32 - window_half;
+        let (sin, cos) = angle.sin_cos();
+        let (x_scale, y_scale) = self.window_scales();
+        let to_frame = |dx: f32, dy: f32| {
+            let dx = dx * x_scale;
+            let dy = dy * y_scale;
+            (dx * cos - dy * sin, dx * sin + dy * cos)
+        };
+        let dx = (px as i32 - window_half) as f32;
+        let dy = (py as i32 - window_half) as f32;
+        let (x_delta, y_delta) = to_frame(dx, dy);
+        let (x_delta, y_delta) = (x_delta.round() as i32, y_delta.round() as i32);
+        let (x_subpixel_delta, y_subpixel_delta) = to_frame(dx + x_offset, dy + y_offset);
+
+        let (target_width, target_height) = self.target_size();
+        let (x_half, y_half) = ((target_width / 2.0) as i32, (target_height / 2.0) as i32);
+        let x_max = self.frame_width as i32 - x_half;
+        let y_max = self.frame_height as i32 - y_half;
 
         #[cfg(debug_assertions)]
         {
@@ -439,245 +1549,2845 @@ impl MosseTracker {
         }
 
         // compute the max coord in the frame by looking at the shift of the window center
-        let new_x = (self.current_target_center.0 as i32 + x_delta)
-            .min(x_max)
-            .max(window_half);
-
-        let new_y = (self.current_target_center.1 as i32 + y_delta)
-            .min(y_max)
-            .max(window_half);
-
-        self.current_target_center = (new_x as u32, new_y as u32);
+        let new_x = (center.0 as i32 + x_delta).min(x_max).max(x_half);
+        let new_y = (center.1 as i32 + y_delta).min(y_max).max(y_half);
+        let position = (
+            (center.0 as f32 + x_subpixel_delta).clamp(x_half as f32, x_max.max(x_half) as f32),
+            (center.1 as f32 + y_subpixel_delta).clamp(y_half as f32, y_max.max(y_half) as f32),
+        );
 
         // compute PSR
         // Note that we re-use the computed max and its coordinate for downstream simplicity
-        self.last_psr = compute_psr(
-            &corr_map_gi,
+        let psr = compute_psr(
+            corr_map_gi,
             self.window_size,
             self.window_size,
-            max_complex.re,
+            max,
             max_coord_in_window,
         );
 
-        return Prediction {
-            location: self.current_target_center,
-            psr: self.last_psr,
+        let apce = compute_apce(corr_map_gi, max);
+
+        Prediction {
+            location: (new_x as u32, new_y as u32),
+            position,
+            psr,
+            apce,
+            scale: self.current_scale,
+            aspect_ratio: self.current_stretch * self.current_stretch,
+            angle,
+            size: (target_width, target_height),
+            confidence: confidence(psr, self.psr_threshold),
+            status: TrackingStatus::Tracking,
+            frame_index: self.frames_tracked,
+            velocity: None,
+        }
+    }
+
+    // move the tracker state to a new prediction
+    fn accept(&mut self, prediction: &mut Prediction) {
+        self.current_target_center = prediction.location;
+        self.current_angle = prediction.angle;
+        self.last_psr = prediction.psr;
+        self.last_apce = prediction.apce;
+
+        // flag the target as occluded when the response quality is well below what we have learned from so far
+        self.occluded = match self.occlusion_apce_ratio {
+            Some(ratio) => self.learned_frames > 0 && self.last_apce < ratio * self.mean_apce,
+            None => false,
+        };
+
+        prediction.status = if prediction.psr <= self.psr_threshold {
+            TrackingStatus::Lost
+        } else if self.occluded {
+            TrackingStatus::Occluded
+        } else {
+            TrackingStatus::Tracking
         };
+        prediction.velocity = self.velocity();
     }
 
-    // update the filter
-    fn update(&mut self, frame: &GrayImage) {
-        // cut out the training template by cropping
-        let window = window_crop(
-            frame,
+    // Locate the target in a new frame, and move the tracker there. With redetection, a target that is lost is
+    // searched for in the whole frame (see redetect()). The filter doesn't learn from the frame until update() (or
+    // see track()), so the application can decide which frames it learns from.
+    pub fn track_new_frame<F: FrameSource>(&mut self, frame: &F) -> Prediction {
+        self.track_new_frame_with(frame, &Grayscale(frame))
+    }
+
+    // see track_new_frame(), with `grayscale` the frame in grayscale (for the shape filters)
+    fn track_new_frame_with<F: FrameSource, G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &F,
+        grayscale: &G,
+    ) -> Prediction {
+        let prediction = self.track_frame(frame, grayscale);
+        self.emit_events(&prediction);
+
+        // the target got lost: try to find it anywhere in the frame
+        if self.settings.redetection && prediction.status == TrackingStatus::Lost {
+            if let Some(redetected) = self.redetect(frame) {
+                return redetected;
+            }
+        }
+        prediction
+    }
+
+    // see track_new_frame()
+    pub fn predict<F: FrameSource>(&mut self, frame: &F) -> Prediction {
+        self.track_new_frame(frame)
+    }
+
+    // locate the target in a new frame, and learn from it (see update()) unless the target is lost: the window is
+    // then mostly background, which the filter would drift onto
+    pub fn track<F: FrameSource>(&mut self, frame: &F) -> Result<Prediction, Error> {
+        self.check_frame(frame)?;
+        let prediction = self.track_new_frame(frame);
+        if prediction.status == TrackingStatus::Lost {
+            // the frame is done
+            self.profiler.report();
+        } else {
+            self.update(frame)?;
+        }
+        Ok(prediction)
+    }
+
+    // Where the target would be found in `frame`, without changing the tracker at all: it doesn't move, learn,
+    // count the frame or report events. The scale, aspect ratio and angle stay the current ones.
+    pub fn peek<F: FrameSource>(&self, frame: &F) -> Prediction {
+        let center = match &self.motion_model {
+            Some(motion_model) => self.clamp_to_frame(motion_model.clone().predict()),
+            None => self.current_target_center,
+        };
+        let (mut prediction, _) = self.evaluate(frame, center, self.current_angle);
+        prediction.frame_index += 1;
+        prediction.status = match prediction.psr > self.psr_threshold {
+            true => TrackingStatus::Tracking,
+            false => TrackingStatus::Lost,
+        };
+        prediction.velocity = self.velocity();
+        prediction
+    }
+
+    // call `callback` with every event of the track (see events::TrackerEvent) and the prediction that caused it
+    pub fn on_event(&mut self, callback: impl FnMut(TrackerEvent, &Prediction) + Send + 'static) {
+        self.event_callbacks.push(Box::new(callback));
+    }
+
+    // Time the stages of tracking every frame (see profile::StageTimings), until disable_profiling().
+    // For finding out where the time goes: profiling reads the clock a few times per stage.
+    pub fn enable_profiling(&mut self) {
+        self.profiler.set_enabled(true);
+    }
+
+    pub fn disable_profiling(&mut self) {
+        self.profiler.set_enabled(false);
+    }
+
+    // the timings of the current frame (so far), or None when not profiling
+    pub fn timings(&self) -> Option<StageTimings> {
+        self.profiler.timings()
+    }
+
+    // call `callback` with the timings of every frame, once update() is done with it (or the next frame started).
+    // Enables profiling.
+    pub fn on_timings(&mut self, callback: impl FnMut(&StageTimings) + Send + 'static) {
+        self.profiler.push(Box::new(callback));
+        self.enable_profiling();
+    }
+
+    fn emit_events(&mut self, prediction: &Prediction) {
+        for event in self.events.observe(prediction) {
+            for callback in &mut self.event_callbacks {
+                callback(event, prediction);
+            }
+        }
+    }
+
+    fn track_frame<F: FrameSource, G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &F,
+        grayscale: &G,
+    ) -> Prediction {
+        self.frames_tracked += 1;
+        self.profiler.next_frame(self.frames_tracked);
+
+        // let the motion model predict where to look for the target
+        let center = match &mut self.motion_model {
+            Some(motion_model) => {
+                let predicted = motion_model.predict();
+                self.clamp_to_frame(predicted)
+            }
+            None => self.current_target_center,
+        };
+
+        let (mut prediction, mut response) = if self.rotation_estimation {
+            // try a small bank of rotations around the current angle, and keep the one the filter responds to best
+            (-ROTATION_CANDIDATES..=ROTATION_CANDIDATES)
+                .map(|step| {
+                    let angle = self.current_angle + step as f32 * ROTATION_STEP;
+                    self.evaluate(frame, center, angle)
+                })
+                .max_by(|a, b| a.0.psr.partial_cmp(&b.0.psr).unwrap_or(Ordering::Equal))
+                .unwrap() // the range of candidates is never empty
+        } else {
+            self.evaluate(frame, center, self.current_angle)
+        };
+
+        // validate the prediction with the long-term filter, and recover from it if it knows better
+        if let Some(long_term) = &self.long_term {
+            let (long, long_response) =
+                self.evaluate_filter(&long_term.filter, frame, center, prediction.angle);
+            let dx = long.position.0 - prediction.position.0;
+            let dy = long.position.1 - prediction.position.1;
+            let disagree = dx * dx + dy * dy > LONG_TERM_DISAGREEMENT * LONG_TERM_DISAGREEMENT;
+            if long.psr > prediction.psr && (disagree || prediction.psr < FULL_CONFIDENCE_PSR) {
+                self.last_top = Spectrum::from(long_term.top.clone());
+                self.last_bottom = Spectrum::from(long_term.bottom.clone());
+                self.filter = Spectrum::from(long_term.filter.clone());
+                prediction = long;
+                response = long_response;
+            }
+        }
+
+        // check for drift against the anchor every so often
+        if let (Some(anchor), Some(interval)) = (&self.anchor, self.anchor_interval) {
+            self.frames_since_anchor += 1;
+            if self.frames_since_anchor >= interval {
+                self.frames_since_anchor = 0;
+                let (anchored, _) = self.evaluate_filter(
+                    &anchor.filter,
+                    frame,
+                    prediction.location,
+                    prediction.angle,
+                );
+                let dx = anchored.position.0 - prediction.position.0;
+                let dy = anchored.position.1 - prediction.position.1;
+                if anchored.psr > self.psr_threshold
+                    && dx * dx + dy * dy > ANCHOR_DRIFT * ANCHOR_DRIFT
+                {
+                    self.last_top
+                        .update(|top| blend(top, &anchor.top, ANCHOR_BLEND));
+                    self.last_bottom
+                        .update(|bottom| blend(bottom, &anchor.bottom, ANCHOR_BLEND));
+                    self.filter
+                        .update(|filter| blend(filter, &anchor.filter, ANCHOR_BLEND));
+                    prediction.location = anchored.location;
+                    prediction.position = anchored.position;
+                }
+            }
+        }
+
+        // smooth confident measurements, and fall back to the motion model when the response is weak
+        if let Some(motion_model) = &mut self.motion_model {
+            let position = if prediction.psr > self.psr_threshold {
+                motion_model.correct(prediction.position)
+            } else {
+                motion_model.position()
+            };
+            prediction.position = position;
+            prediction.location = self.clamp_to_frame(position);
+        }
+        self.accept(&mut prediction);
+        self.last_response.clear();
+        self.last_response.extend(response.iter().map(|r| r.re));
+
+        // with the new location known, estimate the change in size of the target (on the grayscale frame).
+        // A lost target keeps its shape: the windows around a weak response hold little more than rounding noise.
+        if !self.estimates_shape() || prediction.psr <= self.psr_threshold {
+            return prediction;
+        }
+        let frame = grayscale;
+        if let Some(scale_filter) = &self.scale_filter {
+            let factor = scale_filter.estimate(frame, prediction.location, self.target_size());
+            self.current_scale = self.clamp_scale(self.current_scale * factor);
+            prediction.scale = self.current_scale;
+        }
+        if let Some(aspect_filter) = &self.aspect_filter {
+            let factor = aspect_filter.estimate(frame, prediction.location, self.target_size());
+            self.current_stretch =
+                (self.current_stretch * factor).clamp(1.0 / MAX_STRETCH, MAX_STRETCH);
+            prediction.aspect_ratio = self.current_stretch * self.current_stretch;
+        }
+        if let Some(log_polar_filter) = &self.log_polar_filter {
+            let (factor, angle) = log_polar_filter.estimate(
+                frame,
+                prediction.location,
+                self.target_size(),
+                self.current_angle,
+            );
+            self.current_scale = self.clamp_scale(self.current_scale * factor);
+            self.current_angle += angle;
+            prediction.scale = self.current_scale;
+            prediction.angle = self.current_angle;
+        }
+        prediction.size = self.target_size();
+
+        prediction
+    }
+
+    // the window can't grow beyond the frame, or shrink to nothing
+    fn clamp_scale(&self, scale: f32) -> f32 {
+        let max_scale = self.frame_width.min(self.frame_height) as f32 * self.downscale
+            / self.window_size as f32;
+        let min_scale = (MIN_WINDOW_SIZE / self.target_window_size as f32).min(1.0);
+        scale.clamp(min_scale, max_scale.max(min_scale))
+    }
+
+    // the current box of the target in the frame
+    pub fn target_box(&self) -> BBox {
+        BBox::around(self.current_target_center, self.target_size())
+    }
+
+    // the velocity of the target in pixels per frame, as estimated by the motion model (if enabled)
+    pub fn velocity(&self) -> Option<(f32, f32)> {
+        self.motion_model.as_ref().map(|m| m.velocity())
+    }
+
+    // round a position to the pixel grid, inside the frame
+    fn clamp_to_frame(&self, position: (f32, f32)) -> (u32, u32) {
+        (
+            (position.0.round().max(0.0) as u32).min(self.frame_width.saturating_sub(1)),
+            (position.1.round().max(0.0) as u32).min(self.frame_height.saturating_sub(1)),
+        )
+    }
+
+    // Sweep the filter over the whole frame to re-acquire a lost target.
+    // Windows are laid out on a grid with a stride of half the window size, and the candidate with the highest PSR wins.
+    // The tracker only moves to that candidate (and returns it) if its PSR makes the threshold.
+    pub fn redetect<F: FrameSource>(&mut self, frame: &F) -> Option<Prediction> {
+        // the window in frame pixels
+        let window = (self.window_size as f32 / self.downscale).round() as u32;
+        let stride = (window / 2).max(1);
+        let xs = grid_positions(self.frame_width, window, stride);
+        let ys = grid_positions(self.frame_height, window, stride);
+
+        let best = xs
+            .iter()
+            .flat_map(|x| ys.iter().map(move |y| (*x, *y)))
+            .map(|center| self.evaluate(frame, center, self.current_angle))
+            .max_by(|a, b| a.0.psr.partial_cmp(&b.0.psr).unwrap_or(Ordering::Equal))?;
+
+        let (mut best, response) = best;
+        if best.psr > self.psr_threshold {
+            self.accept(&mut best);
+            self.last_response.clear();
+            self.last_response.extend(response.iter().map(|r| r.re));
+            self.emit_events(&best);
+            Some(best)
+        } else {
+            None
+        }
+    }
+
+    // the learning rate that the next update will use.
+    // With the adaptive learning rate enabled this ramps up linearly from 0 at the PSR threshold
+    // to the configured learning rate at a PSR of 20.
+    pub fn learning_rate(&self) -> f32 {
+        if !self.adaptive_learning_rate {
+            return self.eta;
+        }
+
+        self.eta * confidence(self.last_psr, self.psr_threshold)
+    }
+
+    // The correlation response to the last tracked frame: window_size() x window_size() values, row by row.
+    // Its center is where the tracker searched for the target, and its peak is where it found it.
+    // Empty until the first frame has been tracked.
+    pub fn response_map(&self) -> &[f32] {
+        &self.last_response
+    }
+
+    // the response map, stretched to the full range of gray values (None until the first frame has been tracked)
+    pub fn response_image(&self) -> Option<GrayImage> {
+        if self.last_response.is_empty() {
+            return None;
+        }
+
+        Some(to_normalized_imgbuf(
+            &self.last_response,
             self.window_size,
             self.window_size,
-            self.current_target_center,
+        ))
+    }
+
+    // the edge size of the (padded) window the tracker searches, and of its response map
+    pub fn window_size(&self) -> u32 {
+        self.window_size
+    }
+
+    // whether the last tracked frame was flagged as occluded (see MosseTrackerSettings::occlusion_apce_ratio)
+    pub fn is_occluded(&self) -> bool {
+        self.occluded
+    }
+
+    // Stop learning from new frames until resume_learning(): update() leaves all filters alone, while tracking goes
+    // on as before. For when the application knows better than the tracker that a frame is bad (e.g. an external
+    // occlusion or motion blur detector). Neither reset() nor training resumes learning.
+    pub fn pause_learning(&mut self) {
+        self.learning_paused = true;
+    }
+
+    pub fn resume_learning(&mut self) {
+        self.learning_paused = false;
+    }
+
+    pub fn is_learning_paused(&self) -> bool {
+        self.learning_paused
+    }
+
+    // update the filter (see MosseTrackerSettings::update_interval for the frames that are skipped)
+    pub fn update<F: FrameSource>(&mut self, frame: &F) -> Result<(), Error> {
+        self.update_with(frame, &Grayscale(frame))
+    }
+
+    // see update(), with `grayscale` the frame in grayscale (for the shape filters)
+    fn update_with<F: FrameSource, G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &F,
+        grayscale: &G,
+    ) -> Result<(), Error> {
+        self.check_frame(frame)?;
+
+        // freeze the filter while the target is occluded, so we don't learn the occluder
+        let frozen = self.occluded || self.learning_paused;
+        if !frozen && self.frames_tracked.is_multiple_of(self.update_interval) {
+            self.learn(frame, grayscale, self.learning_rate());
+        }
+        // the frame is done
+        self.profiler.report();
+        Ok(())
+    }
+
+    // Move the tracker to the target at `center` (e.g. where a user dragged it): tracking continues from there,
+    // with the filter as it is. See blend_template() to also learn what the target looks like there.
+    pub fn correct(&mut self, center: (u32, u32)) -> Result<(), Error> {
+        check_center((self.frame_width, self.frame_height), center)?;
+        self.current_target_center = center;
+        self.occluded = false;
+        // the motion model starts over at the new position: the velocity it estimated led it astray
+        if self.motion_model.is_some() {
+            self.motion_model = Some(KalmanFilter::new(
+                (center.0 as f32, center.1 as f32),
+                kalman::DEFAULT_PROCESS_NOISE,
+                kalman::DEFAULT_MEASUREMENT_NOISE,
+            ));
+        }
+        Ok(())
+    }
+
+    // see correct(). With scale (and aspect ratio) adaptation, the tracker also takes the size (and aspect ratio)
+    // of the box, within the limits of the adaptation.
+    pub fn correct_box(&mut self, bbox: BBox) -> Result<(), Error> {
+        self.correct(bbox.pixel_center())?;
+        if self.estimates_shape() && bbox.area() > 0.0 {
+            let scale =
+                (bbox.width * bbox.height).sqrt() * self.downscale / self.target_window_size as f32;
+            self.current_scale = self.clamp_scale(scale);
+            if self.aspect_filter.is_some() {
+                self.current_stretch = (bbox.width / bbox.height)
+                    .sqrt()
+                    .clamp(1.0 / MAX_STRETCH, MAX_STRETCH);
+            }
+        }
+        Ok(())
+    }
+
+    // Blend the window at the current position of the target into the filter with `weight` (from 0 to 1, where 1
+    // forgets everything learned so far), e.g. after a correction. Unlike update(), this learns even while the
+    // target is occluded or learning is paused.
+    pub fn blend_template<F: FrameSource>(&mut self, frame: &F, weight: f32) -> Result<(), Error> {
+        self.check_frame(frame)?;
+        self.learn(frame, &Grayscale(frame), weight.clamp(0.0, 1.0));
+        Ok(())
+    }
+
+    // learn the window at the current position of the target, at learning rate `eta`
+    fn learn<F: FrameSource, G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &F,
+        grayscale: &G,
+        eta: f32,
+    ) {
+        // cut out the training template by cropping
+        let window = self.crop_window(frame, self.current_target_center, self.current_angle);
+
+        // compute the preprocessed features of the image
+        let vectorized = self.window_features(&channel_planes(&window));
+
+        // calculate the 2D FFT of the preprocessed image: FFT(fi) = Fi, into the buffer of the last frame
+        let start = self.profiler.start();
+        let mut new_Fi = std::mem::take(&mut self.spectrum);
+        self.real_fft.process_into(&vectorized, &mut new_Fi);
+        self.profiler.record(Stage::Fft, start);
+
+        //// Update the filter using the prediction
+        let start = self.profiler.start();
+        // compute Ai (top) and Bi (bottom) using F*, G, and the learning rate (see paper)
+        Spectrum::learn(
+            &mut self.last_top,
+            &mut self.last_bottom,
+            &self.target,
+            &new_Fi,
+            eta,
+        );
+
+        // compute the new filter H* by dividing Ai and Bi elementwise
+        self.compute_filter();
+
+        let mask = self.filter_mask(&window);
+        if let Some(mask) = &mask {
+            self.constrain_filter(mask);
+        }
+
+        // the long-term filter only learns from confident frames
+        if self.last_psr >= FULL_CONFIDENCE_PSR {
+            if let (Some(long_term), Some(eta)) =
+                (&mut self.long_term, self.long_term_learning_rate)
+            {
+                long_term.learn(&self.target, &new_Fi, eta);
+                long_term.filter =
+                    divide_filter(&long_term.top, &long_term.bottom, self.regularization);
+            }
+        }
+
+        // the ensemble members all learn from the new frame, like the filter itself
+        let mut ensemble = std::mem::take(&mut self.ensemble);
+        for member in ensemble.iter_mut() {
+            member.learn(&self.target, &new_Fi, eta);
+            member.filter = divide_filter(&member.top, &member.bottom, self.regularization);
+            if let Some(mask) = &mask {
+                member.filter =
+                    self.constrained_filter(&member.top, &member.bottom, &member.filter, mask);
+            }
+        }
+        self.ensemble = ensemble;
+        self.spectrum = new_Fi;
+        self.profiler.record(Stage::Update, start);
+
+        // the shape filters learn from the grayscale frame
+        if self.estimates_shape() {
+            let target_size = self.target_size();
+            let frame = grayscale;
+            if let Some(scale_filter) = &mut self.scale_filter {
+                scale_filter.update(frame, self.current_target_center, target_size);
+            }
+            if let Some(aspect_filter) = &mut self.aspect_filter {
+                aspect_filter.update(frame, self.current_target_center, target_size);
+            }
+            if let Some(log_polar_filter) = &mut self.log_polar_filter {
+                log_polar_filter.update(
+                    frame,
+                    self.current_target_center,
+                    target_size,
+                    self.current_angle,
+                );
+            }
+        }
+
+        // keep track of the response quality of the frames we learned from
+        self.learned_frames += 1;
+        self.mean_apce += (self.last_apce - self.mean_apce) / self.learned_frames as f32;
+    }
+
+    // whether the tracker can work on `frame`: it has the size the tracker was set up for, and the window fits in it
+    fn check_frame<F: FrameSource>(&self, frame: &F) -> Result<(), Error> {
+        check_frame_size(
+            frame.dimensions(),
+            (self.frame_width, self.frame_height),
+            self.window_size,
+        )
+    }
+
+    fn snapshot(&self) -> FilterState {
+        FilterState {
+            top: self.last_top.to_vec(),
+            bottom: self.last_bottom.to_vec(),
+            filter: self.filter.to_vec(),
+        }
+    }
+
+    pub fn settings(&self) -> &MosseTrackerSettings {
+        &self.settings
+    }
+
+    // everything the tracker has learned, to restore it later (see state::TrackerState)
+    pub fn state(&self) -> TrackerState {
+        TrackerState {
+            frame_size: (self.frame_width, self.frame_height),
+            window_size: self.window_size,
+            filter: self.snapshot(),
+            long_term: self.long_term.clone(),
+            anchor: self.anchor.clone(),
+            frames_since_anchor: self.frames_since_anchor,
+            ensemble: self.ensemble.clone(),
+            center: self.current_target_center,
+            scale: self.current_scale,
+            stretch: self.current_stretch,
+            angle: self.current_angle,
+            scale_filter: self.scale_filter.as_ref().map(|f| f.state()),
+            aspect_filter: self.aspect_filter.as_ref().map(|f| f.state()),
+            log_polar_filter: self.log_polar_filter.as_ref().map(|f| f.state()),
+            motion_model: self.motion_model.clone(),
+            last_psr: self.last_psr,
+            last_apce: self.last_apce,
+            mean_apce: self.mean_apce,
+            learned_frames: self.learned_frames,
+            frames_tracked: self.frames_tracked,
+            occluded: self.occluded,
+        }
+    }
+
+    // A tracker with `settings` that continues from a state taken with the same settings. The settings and the size
+    // of the state are checked before the tracker is made, so that a state that doesn't fit them is an error rather
+    // than a tracker of whatever size the settings ask for.
+    pub fn from_state(
+        settings: &MosseTrackerSettings,
+        state: TrackerState,
+    ) -> Result<MosseTracker, Error> {
+        settings.validate()?;
+        let frame_size = (settings.width, settings.height);
+        if state.frame_size != frame_size {
+            return Err(Error::FrameSize {
+                expected: frame_size,
+                actual: state.frame_size,
+            });
+        }
+        let (_, window_size) = window_sizes(settings);
+        if state.window_size != window_size {
+            return Err(Error::IncompatibleState("window size"));
+        }
+        // the filter holds a spectrum of the window for every feature channel
+        let window_length = window_size as usize * window_size as usize;
+        let length = state.filter.filter.len();
+        if length == 0 || !length.is_multiple_of(window_length) {
+            return Err(Error::IncompatibleState("filter size"));
+        }
+
+        let mut tracker = MosseTracker::new(settings);
+        tracker.restore_state(state)?;
+        Ok(tracker)
+    }
+
+    // Continue from a state taken from a tracker with the same settings. The state is checked against the settings
+    // of this tracker first, and the tracker is left alone if it doesn't fit.
+    pub fn restore_state(&mut self, state: TrackerState) -> Result<(), Error> {
+        let frame_size = (self.frame_width, self.frame_height);
+        if state.frame_size != frame_size {
+            return Err(Error::FrameSize {
+                expected: frame_size,
+                actual: state.frame_size,
+            });
+        }
+        if state.window_size != self.window_size {
+            return Err(Error::IncompatibleState("window size"));
+        }
+
+        // every filter holds the same number of windows
+        let length = state.filter.filter.len();
+        let filters = std::iter::once(&state.filter)
+            .chain(&state.long_term)
+            .chain(&state.anchor)
+            .chain(&state.ensemble);
+        let fits = length > 0
+            && length.is_multiple_of(self.target.len())
+            && filters.into_iter().all(|f| {
+                f.top.len() == length && f.bottom.len() == length && f.filter.len() == length
+            });
+        if !fits {
+            return Err(Error::IncompatibleState("filter size"));
+        }
+        if state.long_term.is_some() != self.long_term_learning_rate.is_some()
+            || state.anchor.is_some() != self.anchor_interval.is_some()
+            || state.ensemble.len() != self.ensemble_size.saturating_sub(1)
+        {
+            return Err(Error::IncompatibleState(
+                "long-term, anchor or ensemble filters",
+            ));
+        }
+        if state.motion_model.is_some() != self.use_motion_model {
+            return Err(Error::IncompatibleState("motion model"));
+        }
+
+        // the shape filters
+        let fits = |filter: bool,
+                    state: &Option<ShapeFilterState>,
+                    fits: fn(&ShapeFilterState) -> bool| {
+            match state {
+                Some(state) => filter && fits(state),
+                None => !filter,
+            }
+        };
+        if !fits(
+            self.scale_filter.is_some(),
+            &state.scale_filter,
+            ScaleFilter::fits,
+        ) || !fits(
+            self.aspect_filter.is_some(),
+            &state.aspect_filter,
+            ScaleFilter::fits,
+        ) || !fits(
+            self.log_polar_filter.is_some(),
+            &state.log_polar_filter,
+            LogPolarFilter::fits,
+        ) {
+            return Err(Error::IncompatibleState(
+                "scale, aspect ratio or log-polar filter",
+            ));
+        }
+        if let (Some(filter), Some(state)) = (&mut self.scale_filter, state.scale_filter) {
+            filter.restore(state);
+        }
+        if let (Some(filter), Some(state)) = (&mut self.aspect_filter, state.aspect_filter) {
+            filter.restore(state);
+        }
+        if let (Some(filter), Some(state)) = (&mut self.log_polar_filter, state.log_polar_filter) {
+            filter.restore(state);
+        }
+
+        let FilterState {
+            top,
+            bottom,
+            filter,
+        } = state.filter;
+        self.last_top = Spectrum::from(top);
+        self.last_bottom = Spectrum::from(bottom);
+        self.filter = Spectrum::from(filter);
+        self.long_term = state.long_term;
+        self.anchor = state.anchor;
+        self.frames_since_anchor = state.frames_since_anchor;
+        self.ensemble = state.ensemble;
+        self.current_target_center = state.center;
+        self.current_scale = state.scale;
+        self.current_stretch = state.stretch;
+        self.current_angle = state.angle;
+        self.motion_model = state.motion_model;
+        self.last_psr = state.last_psr;
+        self.last_apce = state.last_apce;
+        self.mean_apce = state.mean_apce;
+        self.learned_frames = state.learned_frames;
+        self.frames_tracked = state.frames_tracked;
+        self.occluded = state.occluded;
+        self.last_response.clear();
+        Ok(())
+    }
+
+    // H* = Ai / (Bi + regularization).
+    // The regularization keeps frequencies with (next to) no energy in the training frames from blowing up the filter,
+    // and avoids dividing by zero, which would yield NaN's.
+    fn compute_filter(&mut self) {
+        let (top, bottom) = (self.last_top.get(), self.last_bottom.get());
+        self.filter
+            .update(|filter| simd::divide_spectra_into(&top, &bottom, self.regularization, filter));
+    }
+
+    // the mask the spatial support of the filter is restricted to, in window coordinates (if any).
+    // All channels share the mask: the spatial reliability map is computed on the grayscale window.
+    fn filter_mask<P: FramePixel>(&self, window: &Frame<P>) -> Option<Vec<f32>> {
+        if self.spatial_reliability {
+            Some(reliability_mask(
+                &imageops::grayscale(window),
+                self.target_window_size,
+            ))
+        } else if self.background_aware {
+            Some(box_mask(self.window_size, self.target_window_size))
+        } else {
+            None
+        }
+    }
+
+    // Restrict the spatial support of the filter to the pixels where `mask` is set.
+    // Simply cropping the filter makes it fit the training frames less well, so we alternate between refitting
+    // the filter to the training frames (while staying close to the cropped filter) and cropping it again.
+    fn constrain_filter(&mut self, mask: &[f32]) {
+        let filter = self.constrained_filter(
+            &self.last_top.get(),
+            &self.last_bottom.get(),
+            &self.filter.get(),
+            mask,
         );
+        self.filter = Spectrum::from(filter);
+    }
+
+    // see constrain_filter(), for any filter and its top and bottom parts
+    fn constrained_filter(
+        &self,
+        top: &[Complex<f32>],
+        bottom: &[Complex<f32>],
+        filter: &[Complex<f32>],
+        mask: &[f32],
+    ) -> Vec<Complex<f32>> {
+        let mut constrained = self.project_filter(filter, mask);
+        for _ in 0..CONSTRAINT_ITERATIONS {
+            let refitted: Vec<Complex<f32>> = top
+                .iter()
+                .zip(bottom)
+                .zip(&constrained)
+                .map(|((a, b), c)| {
+                    (a + CONSTRAINT_PENALTY * c) / (b + self.regularization + CONSTRAINT_PENALTY)
+                })
+                .collect();
+            constrained = self.project_filter(&refitted, mask);
+        }
+        constrained
+    }
+
+    // zero the filter outside of `mask` (in window coordinates), in the spatial domain
+    fn project_filter(&self, filter: &[Complex<f32>], mask: &[f32]) -> Vec<Complex<f32>> {
+        // the filter holds H*, so its spatial template is IFFT(conj(H*)).
+        // The desired output peaks at the window center, which shifts the template circularly by that much.
+        // NOTE: the window is transformed as one flattened signal (see compute_2dfft), so the shift is a flat index too.
+        // The FFTs process every channel separately, and all channels get the same mask.
+        let length = self.target.len();
+        let shift = ((self.window_size / 2) * self.window_size + self.window_size / 2) as usize;
+
+        let mut template: Vec<Complex<f32>> = filter.iter().map(|h| h.conj()).collect();
+        self.inv_fft.process(&mut template);
+        template
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, t)| *t *= mask[(i % length + shift) % length] / length as f32);
+        self.fft.process(&mut template);
+
+        template.iter().map(|t| t.conj()).collect()
+    }
 
-        // preprocess the image using preprocess()
-        let vectorized = preprocess(&window);
+    // The learned filter in the spatial domain, as an image of the window: what the tracker looks for.
+    // The filter is aligned with the window (its center is the target center), and stretched to the full range of
+    // gray values. A healthy filter resembles the target; one that has drifted shows the background.
+    // With several channels, their filters are summed.
+    pub fn filter_image(&self) -> GrayImage {
+        to_normalized_imgbuf(&self.filter_template(), self.window_size, self.window_size)
+    }
+
+    // the filter in the window (as filter_image() shows it), window_size() x window_size() values, row by row
+    pub(crate) fn filter_template(&self) -> Vec<f32> {
+        // see project_filter(): the template of window pixel i is at (i - shift)
+        let length = self.target.len();
+        let shift = ((self.window_size / 2) * self.window_size + self.window_size / 2) as usize;
+
+        let mut template: Vec<Complex<f32>> = sum_channels(&self.filter.get(), length)
+            .iter()
+            .map(|h| h.conj())
+            .collect();
+        self.inv_fft.process(&mut template);
+        (0..length)
+            .map(|i| template[(i + length - shift) % length].re)
+            .collect()
+    }
+
+    // debug method to dump the latest filter to an inspectable image (the sum of the filters of all channels)
+    pub fn dump_filter(&self) -> (GrayImage, GrayImage) {
+        // get the filter out of fourier space
+        // NOTE: input is garbage after this call to inv_fft.process(), so we work on a copy of the filter.
+        let mut h = sum_channels(&self.filter.get(), self.target.len());
+        self.inv_fft.process(&mut h);
+
+        // turn the real and imaginary values of the filter into separate grayscale images
+        let realfilter: Vec<f32> = h.iter().map(|c| c.re).collect();
+        let imfilter: Vec<f32> = h.iter().map(|c| c.im).collect();
+
+        (
+            to_imgbuf(&realfilter, self.window_size, self.window_size),
+            to_imgbuf(&imfilter, self.window_size, self.window_size),
+        )
+    }
+}
+
+fn window_crop<F: FrameSource>(
+    input_frame: &F,
+    window_width: u32,
+    window_height: u32,
+    center: (u32, u32),
+) -> Frame<F::Pixel> {
+    let mut window = Frame::new(window_width, window_height);
+    input_frame.crop_into(window_width, window_height, center, &mut window);
+    window
+}
+
+// crop a window of (crop_width, crop_height) around center, and resize it to (output_width, output_height).
+// The crop is clamped to the frame.
+fn scaled_window_crop<F: FrameSource>(
+    input_frame: &F,
+    crop_width: u32,
+    crop_height: u32,
+    center: (u32, u32),
+    output_width: u32,
+    output_height: u32,
+) -> Frame<F::Pixel> {
+    let (frame_width, frame_height) = input_frame.dimensions();
+    let window = window_crop(
+        input_frame,
+        crop_width.min(frame_width),
+        crop_height.min(frame_height),
+        center,
+    );
+
+    if window.dimensions() == (output_width, output_height) {
+        return window;
+    }
+    imageops::resize(
+        &window,
+        output_width,
+        output_height,
+        imageops::FilterType::Triangle,
+    )
+}
+
+// the black pixel of any pixel type, for the corners that rotating a window uncovers
+fn black<P: FramePixel>() -> P {
+    *P::from_slice(&vec![0; P::CHANNEL_COUNT as usize])
+}
+
+// the sum of the channels of `values`, which holds one window of `length` values per channel, one after the other
+fn sum_channels(values: &[Complex<f32>], length: usize) -> Vec<Complex<f32>> {
+    let mut sum = vec![Complex::zero(); length];
+    for channel in values.chunks(length) {
+        sum.iter_mut().zip(channel).for_each(|(s, v)| *s += v);
+    }
+    sum
+}
+
+// the FFT of the desired output for a window of `window_size` that has been shifted by `shift` (x, y) pixels
+fn shifted_target(
+    window_size: u32,
+    sigma: f32,
+    real_fft: &fft::RealFft,
+    shift: (i32, i32),
+) -> Vec<Complex<f32>> {
+    let center = (
+        (window_size / 2) as f32 + shift.0 as f32,
+        (window_size / 2) as f32 + shift.1 as f32,
+    );
+    real_fft.process(&build_target_at(window_size, window_size, sigma, center))
+}
+
+// H* = Ai / (Bi + regularization), see MosseTracker::compute_filter()
+fn divide_filter(
+    top: &[Complex<f32>],
+    bottom: &[Complex<f32>],
+    regularization: f32,
+) -> Vec<Complex<f32>> {
+    simd::divide_spectra(top, bottom, regularization)
+}
+
+// move `values` towards `towards` by `weight`
+fn blend(values: &mut [Complex<f32>], towards: &[Complex<f32>], weight: f32) {
+    values
+        .iter_mut()
+        .zip(towards)
+        .for_each(|(v, t)| *v = (1.0 - weight) * *v + weight * t);
+}
+
+// the edge of the window the filter works on: the window size with padding, capped at the frame size (if known)
+// see MosseTracker::check_frame()
+fn check_frame_size(
+    actual: (u32, u32),
+    expected: (u32, u32),
+    window_size: u32,
+) -> Result<(), Error> {
+    if actual != expected {
+        return Err(Error::FrameSize { expected, actual });
+    }
+    if window_size == 0 || window_size > actual.0.min(actual.1) {
+        return Err(Error::WindowSize {
+            window: window_size,
+            frame: actual,
+        });
+    }
+    Ok(())
+}
+
+// a target center must lie inside the frame
+fn check_center(frame: (u32, u32), center: (u32, u32)) -> Result<(), Error> {
+    if center.0 >= frame.0 || center.1 >= frame.1 {
+        return Err(Error::TargetOutsideFrame { center, frame });
+    }
+    Ok(())
+}
+
+// the (target, padded) window size of a tracker with `settings`, in pixels of the downscaled frame
+fn window_sizes(settings: &MosseTrackerSettings) -> (u32, u32) {
+    let downscale = settings.downscale;
+    let target_window_size = ((settings.window_size as f32 * downscale).round() as u32).max(1);
+    let window_size = padded_window_size(
+        target_window_size,
+        settings.padding,
+        (settings.width as f32 * downscale) as u32,
+        (settings.height as f32 * downscale) as u32,
+    );
+    (target_window_size, window_size)
+}
+
+fn padded_window_size(window_size: u32, padding: f32, frame_width: u32, frame_height: u32) -> u32 {
+    let padded = (window_size as f32 * padding.max(1.0)).round() as u32;
+    let frame_edge = frame_width.min(frame_height);
+    if frame_edge > 0 {
+        padded.min(frame_edge).max(window_size.min(frame_edge))
+    } else {
+        padded
+    }
+}
+
+impl<P: FramePixel> Tracker<P> for MosseTracker {
+    // the center of the target
+    type Target = (u32, u32);
+    type Output = Prediction;
+
+    fn train(&mut self, frame: &Frame<P>, target_center: (u32, u32)) -> Result<(), Error> {
+        MosseTracker::train(self, frame, target_center)
+    }
+
+    fn predict(&mut self, frame: &Frame<P>) -> Prediction {
+        MosseTracker::track_new_frame(self, frame)
+    }
+
+    fn update(&mut self, frame: &Frame<P>) -> Result<(), Error> {
+        MosseTracker::update(self, frame)
+    }
+
+    fn track(&mut self, frame: &Frame<P>) -> Result<Prediction, Error> {
+        MosseTracker::track(self, frame)
+    }
+
+    fn reset(&mut self) {
+        MosseTracker::reset(self)
+    }
+}
+
+// window centers along one frame axis, `stride` apart, covering the whole axis
+fn grid_positions(frame_length: u32, window_length: u32, stride: u32) -> Vec<u32> {
+    let first = window_length / 2;
+    let last = frame_length
+        .saturating_sub(window_length - first)
+        .max(first);
+    let mut positions: Vec<u32> = (first..=last).step_by(stride as usize).collect();
+    if positions.last() != Some(&last) {
+        positions.push(last);
+    }
+    positions
+}
+
+// number of refit-and-crop rounds when restricting the support of the filter, and how strongly each refit
+// is pulled towards the cropped filter (relative to the average energy per frequency of a training frame, ~1.0)
+const CONSTRAINT_ITERATIONS: usize = 2;
+const CONSTRAINT_PENALTY: f32 = 1.0;
+
+// number of intensity bins of the foreground and background histograms of the spatial reliability map
+const RELIABILITY_BINS: usize = 16;
+// the reliability map falls back to the whole target box when less than this fraction of the box is reliable
+const MIN_RELIABLE_FRACTION: f32 = 0.1;
+
+// CSR-DCF style spatial reliability map of a (square) window with a target of target_size x target_size at its center:
+// 1.0 for the pixels of the target box that are more likely foreground than background, 0.0 everywhere else.
+// The foreground histogram is sampled inside the circle inscribed in the target box, the background histogram outside of it.
+fn reliability_mask(window: &GrayImage, target_size: u32) -> Vec<f32> {
+    let (width, height) = window.dimensions();
+    let (center_x, center_y) = ((width / 2) as f32, (height / 2) as f32);
+    let radius = (target_size as f32 / 2.0).max(1.0);
+    let bin = |p: &Luma<u8>| p[0] as usize * RELIABILITY_BINS / 256;
+
+    let mut foreground = [0f32; RELIABILITY_BINS];
+    let mut background = [0f32; RELIABILITY_BINS];
+    for (x, y, p) in window.enumerate_pixels() {
+        let (dx, dy) = (x as f32 - center_x, y as f32 - center_y);
+        if dx * dx + dy * dy <= radius * radius {
+            foreground[bin(p)] += 1.0;
+        } else {
+            background[bin(p)] += 1.0;
+        }
+    }
+    for histogram in [&mut foreground, &mut background] {
+        let total: f32 = histogram.iter().sum();
+        if total > 0.0 {
+            histogram.iter_mut().for_each(|h| *h /= total);
+        }
+    }
+
+    let target_box = box_mask(width, target_size);
+    let mask: Vec<f32> = window
+        .pixels()
+        .zip(&target_box)
+        .map(|(p, in_box)| {
+            let reliable = *in_box > 0.0 && foreground[bin(p)] > background[bin(p)];
+            if reliable {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    // with too few reliable pixels (e.g. the target looks just like its surroundings), trust the whole box
+    let box_area: f32 = target_box.iter().sum();
+    let reliable_area: f32 = mask.iter().sum();
+    if reliable_area < MIN_RELIABLE_FRACTION * box_area {
+        return target_box;
+    }
+
+    mask
+}
+
+// binary mask of a target of target_size x target_size at the center of a window_size x window_size window
+fn box_mask(window_size: u32, target_size: u32) -> Vec<f32> {
+    let first = window_size / 2 - (target_size / 2).min(window_size / 2);
+    let last = (first + target_size).min(window_size);
+    let inside = |p: u32| (first..last).contains(&p);
+    (0..window_size * window_size)
+        .map(|i| {
+            let (x, y) = index_to_coords(window_size, i);
+            if inside(x) && inside(y) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+// A flat-top window: 1 across a target of target_size x target_size at the center of a window_size x window_size
+// window, tapering off (like a cosine window) to 0 towards the edges of the window.
+// Unlike the cosine window, it leaves the target itself alone.
+fn boundary_mask(window_size: u32, target_size: u32) -> Vec<f32> {
+    let first = window_size / 2 - (target_size / 2).min(window_size / 2);
+    let last = (first + target_size).min(window_size);
+    let taper = |p: u32| {
+        if p < first {
+            (f32::consts::FRAC_PI_2 * p as f32 / first as f32).sin()
+        } else if p >= last {
+            (f32::consts::FRAC_PI_2 * (window_size - 1 - p) as f32 / (window_size - last) as f32)
+                .sin()
+        } else {
+            1.0
+        }
+    };
+    (0..window_size * window_size)
+        .map(|i| {
+            let (x, y) = index_to_coords(window_size, i);
+            taper(x).min(taper(y))
+        })
+        .collect()
+}
+
+// the desired response: a Gaussian peak with a standard deviation of `sigma` pixels, centered on the window
+fn build_target(window_width: u32, window_height: u32, sigma: f32) -> Vec<f32> {
+    let center = ((window_width / 2) as f32, (window_height / 2) as f32);
+    build_target_at(window_width, window_height, sigma, center)
+}
+
+// see build_target(), with the peak at `center` (x, y)
+fn build_target_at(
+    window_width: u32,
+    window_height: u32,
+    sigma: f32,
+    center: (f32, f32),
+) -> Vec<f32> {
+    let mut target_gi = vec![0f32; (window_width * window_height) as usize];
+
+    // NOTE: see ResponseSigma::Relative to let the sigma depend on the window size
+    // (Galoogahi et al. (2015). Correlation Filters with Limited Boundaries)
+    let variance = sigma * sigma;
+
+    // create gaussian peak at the center coordinates
+    let (center_x, center_y) = center;
+    for x in 0..window_width {
+        for y in 0..window_height {
+            let distx: f32 = x as f32 - center_x;
+            let disty: f32 = y as f32 - center_y;
+
+            // apply an (unnormalized) isotropic Gaussian density function
+            target_gi[((y * window_width) + x) as usize] =
+                (-((distx * distx) + (disty * disty)) / (2.0 * variance)).exp()
+        }
+    }
+
+    target_gi
+}
+
+// function for debugging the shape of the target
+// output only depends on the provided window dimensions and sigma
+pub fn dump_target(
+    window_width: u32,
+    window_height: u32,
+    sigma: f32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let trgt = build_target(window_width, window_height, sigma);
+
+    let normalized: Vec<f32> = trgt.iter().map(|a| a * 255.0).collect();
+
+    to_imgbuf(&normalized, window_width, window_height)
+}
+
+// half-width of the window around the correlation peak that is excluded from the sidelobe (11x11 in the Bolme paper)
+const PSR_EXCLUSION_RADIUS: i32 = 5;
+
+// Peak-to-sidelobe ratio: (max - mean_sidelobe) / sd_sidelobe, where the sidelobe is
+// the response map minus an 11x11 window around the peak.
+// Returns 0.0 when the sidelobe is degenerate (empty or flat), so the result is always finite.
+fn compute_psr(
+    predicted: &[Complex<f32>],
+    width: u32,
+    height: u32,
+    max: f32,
+    maxpos: (u32, u32),
+) -> f32 {
+    // uses running updates of standard deviation and mean
+    let mut running_sum = 0.0;
+    let mut running_sd = 0.0;
+    for e in predicted {
+        running_sum += e.re;
+        running_sd += e.re * e.re;
+    }
+
+    // subtract the values of a 11*11 window around the max from the running sd and sum
+    let max_x = maxpos.0 as i32;
+    let max_y = maxpos.1 as i32;
+    let window_left = (max_x - PSR_EXCLUSION_RADIUS).max(0);
+    let window_right = (max_x + PSR_EXCLUSION_RADIUS + 1).min(width as i32);
+    let window_top = (max_y - PSR_EXCLUSION_RADIUS).max(0); // note: named according to CG conventions
+    let window_bottom = (max_y + PSR_EXCLUSION_RADIUS + 1).min(height as i32);
+    let mut excluded = 0;
+    for x in window_left..window_right {
+        for y in window_top..window_bottom {
+            let ind = (y * width as i32 + x) as usize;
+            let val = predicted[ind].re;
+            running_sd -= val * val;
+            running_sum -= val;
+            excluded += 1;
+        }
+    }
+
+    // the window is clipped at the edges of the response map, so we count what we actually excluded
+    let sidelobe_size = predicted.len().saturating_sub(excluded);
+    if sidelobe_size == 0 {
+        return 0.0;
+    }
+    let sidelobe_size = sidelobe_size as f32;
+    let mean_sl = running_sum / sidelobe_size;
+    // clamp at zero: catastrophic cancellation can make the variance slightly negative
+    let sd_sl = ((running_sd / sidelobe_size) - (mean_sl * mean_sl))
+        .max(0.0)
+        .sqrt();
+    let psr = (max - mean_sl) / sd_sl;
+
+    if psr.is_finite() {
+        psr
+    } else {
+        0.0
+    }
+}
+
+// Average peak-to-correlation energy: |max - min|^2 / mean((response - min)^2).
+// Unlike the PSR, this takes the whole response map into account, so it drops as soon as secondary peaks appear.
+// Returns 0.0 for a flat response.
+fn compute_apce(predicted: &[Complex<f32>], max: f32) -> f32 {
+    let min = predicted
+        .iter()
+        .map(|e| e.re)
+        .fold(f32::INFINITY, |a, b| a.min(b));
+
+    let energy: f32 = predicted
+        .iter()
+        .map(|e| (e.re - min) * (e.re - min))
+        .sum::<f32>()
+        / predicted.len() as f32;
+
+    let apce = (max - min) * (max - min) / energy;
+
+    if apce.is_finite() {
+        apce
+    } else {
+        0.0
+    }
+}
+
+// find the max value of the (square) response map, along with the position of the max in the window,
+// and its sub-pixel offset from that position
+fn find_peak(response: &[Complex<f32>], window_size: u32) -> ((u32, u32), f32, (f32, f32)) {
+    find_peak_within(response, window_size, window_size)
+}
+
+// see find_peak(), but only considering the peaks at most `radius` pixels (along either axis) from the window center
+fn find_peak_within(
+    response: &[Complex<f32>],
+    window_size: u32,
+    radius: u32,
+) -> ((u32, u32), f32, (f32, f32)) {
+    let half = window_size / 2;
+    let within = |p: u32| p.abs_diff(half) <= radius;
+    let (maxind, max_complex) = response
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            let (x, y) = index_to_coords(window_size, *i as u32);
+            within(x) && within(y)
+        })
+        .max_by(|a, b| {
+            // the response is still complex at this point, we only care about the real part
+            a.1.re.partial_cmp(&b.1.re).unwrap_or(Ordering::Equal)
+        })
+        .unwrap(); // we can unwrap the result of max_by(), as the window center is always within the radius
+
+    // convert the array index of the max to the coordinates in the window
+    let (px, py) = index_to_coords(window_size, maxind as u32);
+
+    // refine the peak to sub-pixel precision (the response map wraps around at its edges)
+    let at = |x: u32, y: u32| response[(y * window_size + x) as usize].re;
+    let last = window_size - 1;
+    let x_offset = parabolic_offset(
+        at(if px == 0 { last } else { px - 1 }, py),
+        max_complex.re,
+        at(if px == last { 0 } else { px + 1 }, py),
+    );
+    let y_offset = parabolic_offset(
+        at(px, if py == 0 { last } else { py - 1 }),
+        max_complex.re,
+        at(px, if py == last { 0 } else { py + 1 }),
+    );
+
+    ((px, py), max_complex.re, (x_offset, y_offset))
+}
+
+// offset of the vertex of the parabola through three neighbouring samples, relative to the center sample.
+// Returns 0.0 if the center sample is not a local maximum.
+fn parabolic_offset(left: f32, center: f32, right: f32) -> f32 {
+    let curvature = left - 2.0 * center + right;
+    if curvature >= 0.0 {
+        return 0.0;
+    }
+
+    (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+}
+
+fn index_to_coords(width: u32, index: u32) -> (u32, u32) {
+    // modulo/remainder ops are theoretically O(1)
+    // checked_rem returns None if rhs == 0, which would indicate an upstream error (width == 0).
+    let x = index.checked_rem(width).unwrap();
+
+    // checked sub returns None if overflow occurred, which is also a panicable offense.
+    // checked_div returns None if rhs == 0, which would indicate an upstream error (width == 0).
+    let y = (index.checked_sub(x).unwrap()).checked_div(width).unwrap();
+    (x, y)
+}
+
+pub fn to_imgbuf(buf: &[f32], width: u32, height: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    ImageBuffer::from_vec(width, height, buf.iter().map(|c| *c as u8).collect()).unwrap()
+}
+
+// see to_imgbuf(), with the values stretched so that the smallest is black and the largest white
+pub fn to_normalized_imgbuf(
+    buf: &[f32],
+    width: u32,
+    height: u32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let min = buf.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = buf.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let stretched: Vec<f32> = buf
+        .iter()
+        .map(|v| (255.0 * (v - min) / range).round())
+        .collect();
+    to_imgbuf(&stretched, width, height)
+}
+
+// the fixtures the tests of all modules share
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+
+    // a dark frame with a bright, textured square centered on `center`
+    pub(crate) fn synthetic_frame(width: u32, height: u32, center: (u32, u32)) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let dx = x as i32 - center.0 as i32;
+            let dy = y as i32 - center.1 as i32;
+            if dx.abs() <= 6 && dy.abs() <= 6 {
+                Luma([(150 + 8 * dx + 4 * dy) as u8])
+            } else {
+                Luma([20])
+            }
+        })
+    }
+
+    // the synthetic frame of 128x96 that the tests of the modules track in
+    pub(crate) fn frame(center: (u32, u32)) -> GrayImage {
+        synthetic_frame(128, 96, center)
+    }
+
+    // a 32 pixel window in frames of `width` x `height`
+    pub(crate) fn test_settings(width: u32, height: u32) -> MosseTrackerSettings {
+        MosseTrackerSettings {
+            window_size: 32,
+            width,
+            height,
+            regularization: 0.001,
+            learning_rate: 0.05,
+            psr_threshold: 7.0,
+            ..Default::default()
+        }
+    }
+
+    // a prediction of a 32x24 box centered on `position`, as the writers and the overlay take it
+    pub(crate) fn prediction(position: (f32, f32), status: TrackingStatus) -> Prediction {
+        Prediction {
+            location: (position.0 as u32, position.1 as u32),
+            position,
+            psr: 20.0,
+            apce: 1.0,
+            scale: 1.0,
+            aspect_ratio: 1.0,
+            angle: 0.0,
+            size: (32.0, 24.0),
+            confidence: 0.75,
+            status,
+            frame_index: 1,
+            velocity: None,
+        }
+    }
+}
+
+// TODO: below tests are used as a scratch pad and for syntax experiments, not serious unit testing.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_util::{synthetic_frame, test_settings};
+    use imageproc::geometric_transformations::{warp, Projection};
+
+    #[test]
+    fn sanity_test_max_by() {
+        let filtered: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let maxel = filtered
+            .iter()
+            .enumerate()
+            .max_by(|a, b| {
+                // filtered (gi) is still complex at this point, we only care about the real part
+                a.1.partial_cmp(b.1).unwrap_or(Ordering::Equal)
+            })
+            .unwrap();
+        assert_eq!(maxel, (4usize, &5.0f32));
+    }
+
+    #[test]
+    fn am_i_still_sane() {
+        assert_eq!(
+            Complex::new(1.0, -3.0) * Complex::new(2.0, 5.0),
+            Complex::new(17.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn psr_of_single_peak() {
+        let (width, height) = (32, 32);
+        let mut response = vec![Complex::new(0.0, 0.0); (width * height) as usize];
+        // some low-amplitude sidelobe noise
+        for (i, e) in response.iter_mut().enumerate() {
+            e.re = ((i * 7919) % 13) as f32 / 100.0;
+        }
+        response[(16 * width + 16) as usize].re = 5.0;
+
+        let psr = compute_psr(&response, width, height, 5.0, (16, 16));
+        assert!(
+            psr > 20.0,
+            "psr of a sharp peak should be high, got {}",
+            psr
+        );
+    }
+
+    #[test]
+    fn psr_of_flat_response_is_finite() {
+        let response = vec![Complex::new(1.0, 0.0); 16 * 16];
+        assert_eq!(compute_psr(&response, 16, 16, 1.0, (8, 8)), 0.0);
+    }
+
+    #[test]
+    fn apce_drops_with_secondary_peaks() {
+        let mut response = vec![Complex::new(0.0, 0.0); 32 * 32];
+        response[16 * 32 + 16].re = 5.0;
+        let single = compute_apce(&response, 5.0);
+
+        response[4 * 32 + 4].re = 4.0;
+        response[28 * 32 + 8].re = 4.0;
+        let multi = compute_apce(&response, 5.0);
+
+        assert!(single > multi, "apce {} should exceed {}", single, multi);
+        assert_eq!(compute_apce(&[Complex::new(1.0, 0.0); 16], 1.0), 0.0);
+    }
+
+    #[test]
+    fn prediction_carries_psr() {
+        let (width, height) = (96, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (48, 48)), (48, 48))
+            .unwrap();
+
+        let pred = tracker.track_new_frame(&synthetic_frame(width, height, (50, 47)));
+        assert_eq!(pred.location, (50, 47));
+        assert!(pred.psr.is_finite());
+        assert!(
+            pred.psr > 7.0,
+            "psr on a clean target should be high, got {}",
+            pred.psr
+        );
+        assert_eq!(pred.psr, tracker.last_psr);
+        assert!(pred.apce > 0.0);
+        assert_eq!(pred.apce, tracker.last_apce);
+
+        // nothing to see here: confidence should collapse
+        let pred = tracker.track_new_frame(&GrayImage::new(width, height));
+        assert!(
+            pred.psr < 7.0,
+            "psr on an empty frame should be low, got {}",
+            pred.psr
+        );
+    }
+
+    #[test]
+    fn adaptive_learning_rate_follows_psr() {
+        let settings = MosseTrackerSettings {
+            adaptive_learning_rate: true,
+            ..test_settings(64, 64)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+
+        tracker.last_psr = 3.0;
+        assert_eq!(tracker.learning_rate(), 0.0);
+        tracker.last_psr = (settings.psr_threshold + FULL_CONFIDENCE_PSR) / 2.0;
+        assert!((tracker.learning_rate() - settings.learning_rate / 2.0).abs() < 1e-6);
+        tracker.last_psr = 45.0;
+        assert_eq!(tracker.learning_rate(), settings.learning_rate);
+
+        tracker.adaptive_learning_rate = false;
+        tracker.last_psr = 3.0;
+        assert_eq!(tracker.learning_rate(), settings.learning_rate);
+    }
+
+    #[test]
+    fn occlusion_freezes_updates() {
+        let (width, height) = (96, 96);
+        let settings = MosseTrackerSettings {
+            occlusion_apce_ratio: Some(0.5),
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        let clean = synthetic_frame(width, height, (48, 48));
+        tracker.train(&clean, (48, 48)).unwrap();
+        for _ in 0..3 {
+            tracker.track_new_frame(&clean);
+            assert!(!tracker.is_occluded());
+            tracker.update(&clean).unwrap();
+        }
+
+        // cover the target with a flat occluder
+        let mut occluded = clean.clone();
+        for x in 36..60 {
+            for y in 36..60 {
+                occluded.put_pixel(x, y, Luma([90]));
+            }
+        }
+        tracker.track_new_frame(&occluded);
+        assert!(tracker.is_occluded());
+        let filter = tracker.filter.clone();
+        tracker.update(&occluded).unwrap();
+        assert_eq!(tracker.filter, filter);
+
+        // the target reappears and learning resumes
+        tracker.track_new_frame(&clean);
+        assert!(!tracker.is_occluded());
+    }
+
+    #[test]
+    fn training_is_deterministic() {
+        let (width, height) = (128, 96);
+        let settings = MosseTrackerSettings {
+            affine_augmentation: true,
+            photometric_augmentation: true,
+            ..test_settings(width, height)
+        };
+        let frames = [
+            synthetic_frame(width, height, (60, 50)),
+            synthetic_frame(width, height, (62, 49)),
+        ];
+        let train = || {
+            let mut tracker = MosseTracker::new(&settings);
+            tracker
+                .train_on_frames(&[(&frames[0], (60, 50)), (&frames[1], (62, 49))])
+                .unwrap();
+            tracker.state()
+        };
+        // the training frames are augmented and transformed in parallel, but learned from in order
+        assert_eq!(train(), train());
+    }
+
+    #[test]
+    fn trackers_learn_from_every_nth_frame() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            update_interval: 3,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+
+        let mut learned = Vec::new();
+        for (i, center) in [(61, 50), (62, 50), (63, 49), (64, 49), (65, 48), (66, 48)]
+            .into_iter()
+            .enumerate()
+        {
+            let filter = tracker.filter.clone();
+            let prediction = tracker
+                .track(&synthetic_frame(width, height, center))
+                .unwrap();
+            assert_eq!(prediction.location, center, "frame {}", i);
+            learned.push(tracker.filter != filter);
+        }
+        assert_eq!(learned, [false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn paused_trackers_keep_tracking() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+
+        tracker.pause_learning();
+        assert!(tracker.is_learning_paused());
+        let state = tracker.state();
+        let frame = synthetic_frame(width, height, (63, 48));
+        assert_eq!(tracker.track_new_frame(&frame).location, (63, 48));
+        tracker.update(&frame).unwrap();
+        let paused = tracker.state();
+        assert_eq!(paused.filter, state.filter);
+        assert_eq!(paused.learned_frames, state.learned_frames);
+
+        tracker.resume_learning();
+        tracker.update(&frame).unwrap();
+        assert_ne!(tracker.state().filter, state.filter);
+    }
+
+    #[test]
+    fn lost_targets_are_not_learned_from() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        tracker
+            .track(&synthetic_frame(width, height, (61, 50)))
+            .unwrap();
+
+        // the target is gone: nothing but background in the window
+        let state = tracker.state();
+        let prediction = tracker.track(&GrayImage::new(width, height)).unwrap();
+        assert_eq!(prediction.status, TrackingStatus::Lost);
+        let lost = tracker.state();
+        assert_eq!(lost.filter, state.filter);
+        assert_eq!(lost.learned_frames, state.learned_frames);
+
+        // it is still found where it reappears
+        let prediction = tracker
+            .track(&synthetic_frame(width, height, (62, 51)))
+            .unwrap();
+        assert_eq!(prediction.location, (62, 51));
+    }
+
+    #[test]
+    fn corrections_move_the_tracker() {
+        let (width, height) = (160, 96);
+        let settings = MosseTrackerSettings {
+            scale_adaptation: true,
+            motion_model: true,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        tracker
+            .train(&synthetic_frame(width, height, (40, 48)), (40, 48))
+            .unwrap();
+
+        // the target jumped too far to follow, and the user points it out
+        let frame = synthetic_frame(width, height, (110, 50));
+        assert_ne!(tracker.track_new_frame(&frame).location, (110, 50));
+        tracker.correct((108, 49)).unwrap();
+        assert_eq!(tracker.velocity(), Some((0.0, 0.0)));
+        assert_eq!(tracker.track_new_frame(&frame).location, (110, 50));
+        assert!(tracker.correct((width, 10)).is_err());
+
+        // a box also sets the size, and a fresh template replaces the filter
+        tracker
+            .correct_box(BBox::around((110, 50), (40.0, 40.0)))
+            .unwrap();
+        assert_eq!(tracker.target_box(), BBox::around((110, 50), (40.0, 40.0)));
+        tracker.blend_template(&frame, 1.0).unwrap();
+        assert_eq!(tracker.track_new_frame(&frame).location, (110, 50));
+    }
+
+    #[test]
+    fn peeking_leaves_the_tracker_alone() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        let state = tracker.state();
+
+        let frame = synthetic_frame(width, height, (63, 48));
+        let peeked = tracker.peek(&frame);
+        assert_eq!(peeked.location, (63, 48));
+        assert_eq!(peeked.frame_index, 1);
+        assert_eq!(tracker.state(), state);
+
+        // predict() moves the tracker, track() also learns
+        let predicted = tracker.predict(&frame);
+        assert_eq!(predicted.position, peeked.position);
+        assert_eq!(tracker.state().filter, state.filter);
+        assert_eq!(tracker.target_box().pixel_center(), (63, 48));
+        let tracked = tracker
+            .track(&synthetic_frame(width, height, (66, 47)))
+            .unwrap();
+        assert_eq!(tracked.location, (66, 47));
+        assert_ne!(tracker.state().filter, state.filter);
+    }
+
+    #[test]
+    fn grid_covers_frame() {
+        assert_eq!(grid_positions(100, 32, 16), vec![16, 32, 48, 64, 80, 84]);
+        assert_eq!(grid_positions(32, 32, 16), vec![16]);
+    }
+
+    #[test]
+    fn redetect_after_jump() {
+        let (width, height) = (160, 128);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (40, 40)), (40, 40))
+            .unwrap();
+
+        // the target jumps far outside of the tracking window
+        let frame = synthetic_frame(width, height, (120, 90));
+        let lost = tracker.track_new_frame(&frame);
+        assert!(lost.psr < 7.0);
+
+        let found = tracker
+            .redetect(&frame)
+            .expect("target should be re-acquired");
+        assert_eq!(found.location, (120, 90));
+        assert_eq!(tracker.current_target_center, (120, 90));
+    }
+
+    #[test]
+    fn redetection_follows_a_jump() {
+        let (width, height) = (160, 128);
+        let settings = MosseTrackerSettings {
+            redetection: true,
+            ..test_settings(width, height)
+        };
+        let first = synthetic_frame(width, height, (40, 40));
+        // the target jumps far outside of the tracking window
+        let jumped = synthetic_frame(width, height, (120, 90));
+
+        let mut tracker = MosseTracker::new(&settings);
+        tracker.train(&first, (40, 40)).unwrap();
+        let found = tracker.track(&jumped).unwrap();
+        assert_eq!(found.location, (120, 90));
+        assert_eq!(found.status, TrackingStatus::Tracking);
+
+        let mut tracker = MosseTracker::new(&settings);
+        tracker.train(&first, (40, 40)).unwrap();
+        assert_eq!(tracker.track_new_frame(&jumped).location, (120, 90));
+
+        // without redetection the target stays lost
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker.train(&first, (40, 40)).unwrap();
+        assert_eq!(tracker.track(&jumped).unwrap().status, TrackingStatus::Lost);
+
+        let mut multi = MultiMosseTracker::new(settings, 3);
+        let id = multi.add_target((40, 40), &first).unwrap();
+        let predictions = multi.track(&jumped).unwrap();
+        assert_eq!(predictions[0].0, id);
+        assert_eq!(predictions[0].1.location, (120, 90));
+    }
+
+    #[test]
+    fn scale_follows_growing_target() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings {
+            scale_adaptation: true,
+            window_size: 48,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+
+        // a bright disk with a dark core, that grows by 10% after training
+        let disk = |radius: f32| {
+            GrayImage::from_fn(width, height, |x, y| {
+                let dist = ((x as f32 - 64.0).powi(2) + (y as f32 - 64.0).powi(2)).sqrt();
+                if dist < radius * 0.4 {
+                    Luma([60])
+                } else if dist < radius {
+                    Luma([220])
+                } else {
+                    Luma([10])
+                }
+            })
+        };
+        tracker.train(&disk(20.0), (64, 64)).unwrap();
+
+        let grown = disk(22.0);
+        let mut pred = tracker.track_new_frame(&grown);
+        for _ in 0..4 {
+            pred = tracker.track_new_frame(&grown);
+        }
+        assert!(pred.scale > 1.0, "scale should grow, got {}", pred.scale);
+        assert!((pred.location.0 as i32 - 64).abs() <= 2);
+        assert!((pred.location.1 as i32 - 64).abs() <= 2);
+    }
+
+    #[test]
+    fn aspect_ratio_follows_widening_target() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings {
+            scale_adaptation: true,
+            aspect_ratio_adaptation: true,
+            window_size: 48,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+
+        // a bright rectangle with a dark core, that turns wider and flatter after training
+        let rectangle = |half_width: u32, half_height: u32| {
+            GrayImage::from_fn(width, height, |x, y| {
+                let dx = (x as i32 - 64).unsigned_abs();
+                let dy = (y as i32 - 64).unsigned_abs();
+                if dx < half_width * 2 / 5 && dy < half_height * 2 / 5 {
+                    Luma([60])
+                } else if dx < half_width && dy < half_height {
+                    Luma([220])
+                } else {
+                    Luma([10])
+                }
+            })
+        };
+        tracker.train(&rectangle(20, 20), (64, 64)).unwrap();
+
+        let widened = rectangle(23, 17);
+        let mut pred = tracker.track_new_frame(&widened);
+        for _ in 0..4 {
+            pred = tracker.track_new_frame(&widened);
+        }
+        assert!(
+            pred.aspect_ratio > 1.0,
+            "aspect ratio should grow, got {}",
+            pred.aspect_ratio
+        );
+        let (target_width, target_height) = tracker.target_size();
+        assert!(target_width > target_height);
+    }
+
+    #[test]
+    fn rotation_follows_spinning_target() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings {
+            rotation_estimation: true,
+            window_size: 48,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+
+        // an asymmetric, textured bar rotated clockwise by `angle` around the center of the frame
+        let bar = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            GrayImage::from_fn(width, height, |x, y| {
+                let (dx, dy) = (x as f32 - 64.0, y as f32 - 64.0);
+                let u = dx * cos + dy * sin;
+                let v = -dx * sin + dy * cos;
+                if u.abs() <= 16.0 && v.abs() <= 6.0 {
+                    Luma([(150.0 + 5.0 * u + 2.0 * v) as u8])
+                } else {
+                    Luma([20])
+                }
+            })
+        };
+        tracker.train(&bar(0.0), (64, 64)).unwrap();
+
+        let mut pred = tracker.track_new_frame(&bar(0.0));
+        assert_eq!(pred.angle, 0.0);
+        for i in 1..=6 {
+            let frame = bar(i as f32 * 0.05);
+            pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+        }
+        assert!(
+            (pred.angle - 0.3).abs() < 0.11,
+            "angle should follow the target, got {}",
+            pred.angle
+        );
+    }
+
+    #[test]
+    fn log_polar_follows_growing_spinning_target() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings {
+            log_polar_estimation: true,
+            window_size: 48,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+
+        // an asymmetric disc pattern scaled by `factor` and rotated clockwise by `angle` around the center
+        let disc = |factor: f32, angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            GrayImage::from_fn(width, height, |x, y| {
+                let (dx, dy) = ((x as f32 - 64.0) / factor, (y as f32 - 64.0) / factor);
+                let u = dx * cos + dy * sin;
+                let v = -dx * sin + dy * cos;
+                if u * u + v * v > 18.0 * 18.0 {
+                    Luma([20])
+                } else if u > 0.0 && v.abs() < 5.0 {
+                    Luma([230])
+                } else if v > 3.0 {
+                    Luma([150])
+                } else {
+                    Luma([70])
+                }
+            })
+        };
+        tracker.train(&disc(1.0, 0.0), (64, 64)).unwrap();
+
+        let mut pred = tracker.track_new_frame(&disc(1.0, 0.0));
+        assert!(pred.angle.abs() < 0.02 && (pred.scale - 1.0).abs() < 0.02);
+        for i in 1..=6 {
+            let frame = disc(1.03f32.powi(i), i as f32 * 0.1);
+            pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+        }
+        assert!(
+            (pred.angle - 0.6).abs() < 0.1,
+            "angle should follow the target, got {}",
+            pred.angle
+        );
+        assert!(
+            (pred.scale - 1.03f32.powi(6)).abs() < 0.08,
+            "scale should follow the target, got {}",
+            pred.scale
+        );
+    }
+
+    #[test]
+    fn parabolic_peak() {
+        assert_eq!(parabolic_offset(1.0, 2.0, 1.0), 0.0);
+        assert!(parabolic_offset(1.5, 2.0, 1.0) < 0.0);
+        assert!(parabolic_offset(1.0, 2.0, 1.5) > 0.0);
+        // samples of -(x - 0.25)^2 at -1, 0 and 1
+        assert!((parabolic_offset(-1.5625, -0.0625, -0.5625) - 0.25).abs() < 1e-6);
+        // not a maximum
+        assert_eq!(parabolic_offset(1.0, 0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn subpixel_position_is_near_location() {
+        let (width, height) = (96, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (48, 48)), (48, 48))
+            .unwrap();
+
+        let pred = tracker.track_new_frame(&synthetic_frame(width, height, (50, 47)));
+        assert!((pred.position.0 - pred.location.0 as f32).abs() <= 0.5);
+        assert!((pred.position.1 - pred.location.1 as f32).abs() <= 0.5);
+    }
+
+    #[test]
+    fn motion_model_coasts_through_occlusion() {
+        let (width, height) = (160, 96);
+        let settings = MosseTrackerSettings {
+            motion_model: true,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        tracker
+            .train(&synthetic_frame(width, height, (40, 48)), (40, 48))
+            .unwrap();
+
+        // the target moves right at 3 px per frame
+        for i in 1..=12 {
+            let frame = synthetic_frame(width, height, (40 + 3 * i, 48));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert!((pred.location.0 as i32 - (40 + 3 * i) as i32).abs() <= 2);
+        }
+        let (vx, vy) = tracker.velocity().unwrap();
+        assert!(
+            (vx - 3.0).abs() < 0.5 && vy.abs() < 0.5,
+            "velocity {:?}",
+            (vx, vy)
+        );
+
+        // the target disappears, and the motion model keeps predicting its location
+        let pred = tracker.track_new_frame(&GrayImage::new(width, height));
+        assert!(pred.psr < 7.0);
+        assert!(
+            (pred.location.0 as i32 - 79).abs() <= 2,
+            "{:?}",
+            pred.location
+        );
+    }
+
+    #[test]
+    fn padding_keeps_fast_targets_in_view() {
+        let (width, height) = (160, 96);
+        let start = synthetic_frame(width, height, (40, 48));
+        // the target jumps further than half the window size
+        let jumped = synthetic_frame(width, height, (62, 48));
+
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker.train(&start, (40, 48)).unwrap();
+        assert_ne!(tracker.track_new_frame(&jumped).location, (62, 48));
+
+        let settings = MosseTrackerSettings {
+            padding: 2.5,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        assert_eq!(tracker.window_size, 80);
+        tracker.train(&start, (40, 48)).unwrap();
+        assert_eq!(tracker.track_new_frame(&jumped).location, (62, 48));
+
+        // the padded window never exceeds the frame
+        assert_eq!(padded_window_size(32, 4.0, width, height), 96);
+    }
+
+    #[test]
+    fn downscaled_trackers_report_frame_coordinates() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            window_size: 48,
+            downscale: 0.5,
+            ..test_settings(width, height)
+        });
+        assert_eq!(tracker.window_size(), 24);
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+
+        let mut location = (60, 50);
+        for center in [(62, 50), (64, 52), (66, 52)] {
+            let prediction = tracker
+                .track(&synthetic_frame(width, height, center))
+                .unwrap();
+            assert_eq!(prediction.status, TrackingStatus::Tracking);
+            assert_eq!(prediction.size, (48.0, 48.0));
+            // a pixel of the downscaled frame is two frame pixels
+            let (dx, dy) = (
+                prediction.location.0 as i32 - center.0 as i32,
+                prediction.location.1 as i32 - center.1 as i32,
+            );
+            assert!(dx.abs() <= 1 && dy.abs() <= 1, "{:?}", prediction.location);
+            location = prediction.location;
+        }
+        assert_eq!(tracker.target_box().pixel_center(), location);
+    }
+
+    #[test]
+    fn trackers_are_interchangeable() {
+        let (width, height) = (128, 96);
+        let mut trackers: Vec<Box<dyn Tracker<Target = (u32, u32), Output = Prediction>>> = vec![
+            Box::new(MosseTracker::new(&test_settings(width, height))),
+            Box::new(kcf::KcfTracker::new(&kcf::KcfTrackerSettings {
+                width,
+                height,
+                window_size: 32,
+                ..Default::default()
+            })),
+        ];
+
+        for tracker in trackers.iter_mut() {
+            tracker
+                .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+                .unwrap();
+            let frame = synthetic_frame(width, height, (63, 48));
+            let pred = tracker.predict(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (63, 48));
+
+            // after a reset, the tracker learns a new target from scratch
+            tracker.reset();
+            tracker
+                .train(&synthetic_frame(width, height, (40, 40)), (40, 40))
+                .unwrap();
+            let pred = tracker.predict(&synthetic_frame(width, height, (42, 43)));
+            assert_eq!(pred.location, (42, 43));
+        }
+    }
+
+    #[test]
+    fn regularization_keeps_filter_finite() {
+        // a featureless frame has no energy at any frequency, so Ai = Bi = 0 everywhere
+        let mut tracker = MosseTracker::new(&test_settings(64, 64));
+        tracker.train(&GrayImage::new(64, 64), (32, 32)).unwrap();
+        assert!(tracker.filter.get().iter().all(|h| h.is_finite()));
+
+        let frame = synthetic_frame(64, 64, (32, 32));
+        let sharpness = |regularization: f32| {
+            let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+                regularization,
+                ..test_settings(64, 64)
+            });
+            tracker.train(&frame, (32, 32)).unwrap();
+            tracker.track_new_frame(&frame).psr
+        };
+        assert!(sharpness(0.001) > sharpness(10.0));
+    }
+
+    // a textured disc on a striped background
+    fn disc_frame(width: u32, height: u32, center: (u32, u32)) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let dx = x as f32 - center.0 as f32;
+            let dy = y as f32 - center.1 as f32;
+            if dx * dx + dy * dy <= 100.0 {
+                Luma([(200.0 + 2.0 * dx) as u8])
+            } else if (x / 3) % 2 == 0 {
+                Luma([90])
+            } else {
+                Luma([20])
+            }
+        })
+    }
+
+    #[test]
+    fn reliability_mask_follows_the_target_shape() {
+        let window = disc_frame(32, 32, (16, 16));
+        let mask = reliability_mask(&window, 24);
+        let at = |x: usize, y: usize| mask[y * 32 + x];
+
+        assert_eq!(at(16, 16), 1.0);
+        assert_eq!(at(22, 16), 1.0);
+        // the corners of the target box and everything outside of it are background
+        assert_eq!(at(5, 5), 0.0);
+        assert_eq!(at(26, 27), 0.0);
+        assert_eq!(at(1, 16), 0.0);
+
+        // a featureless window falls back to the whole box
+        let mask = reliability_mask(&GrayImage::new(32, 32), 24);
+        assert_eq!(mask.iter().sum::<f32>(), 24.0 * 24.0);
+    }
+
+    #[test]
+    fn spatial_reliability_tracks_round_target() {
+        let (width, height) = (128, 96);
+        let settings = MosseTrackerSettings {
+            spatial_reliability: true,
+            window_size: 24,
+            padding: 1.5,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        tracker
+            .train(&disc_frame(width, height, (50, 48)), (50, 48))
+            .unwrap();
+
+        for i in 1..=6 {
+            let frame = disc_frame(width, height, (50 + 2 * i, 48 - i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (50 + 2 * i, 48 - i));
+        }
+    }
+
+    #[test]
+    fn background_aware_filter_stays_in_target_box() {
+        let (width, height) = (128, 96);
+        let settings = MosseTrackerSettings {
+            background_aware: true,
+            window_size: 24,
+            padding: 2.0,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        assert_eq!(tracker.window_size, 48);
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+
+        // the spatial template (centered on the window, see project_filter()) has no energy outside the box
+        let mut template: Vec<Complex<f32>> =
+            tracker.filter.get().iter().map(|h| h.conj()).collect();
+        tracker.inv_fft.process(&mut template);
+        let target_box = box_mask(48, 24);
+        let shift = 24 * 48 + 24;
+        let outside: f32 = (0..template.len())
+            .filter(|i| target_box[(i + shift) % template.len()] == 0.0)
+            .map(|i| template[i].norm_sqr())
+            .sum();
+        let total: f32 = template.iter().map(|t| t.norm_sqr()).sum();
+        assert!(outside < 1e-6 * total, "{} of {} outside", outside, total);
+
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 + 3 * i, 50 + i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 3 * i, 50 + i));
+        }
+    }
+
+    #[test]
+    fn response_map_peaks_at_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        assert!(tracker.response_map().is_empty());
+        assert!(tracker.response_image().is_none());
+
+        // the target moved by (3, -2) from where the tracker looked for it
+        tracker.track_new_frame(&synthetic_frame(width, height, (63, 48)));
+        let size = tracker.window_size();
+        let response = tracker.response_map();
+        assert_eq!(response.len(), (size * size) as usize);
+        let peak = (0..response.len())
+            .max_by(|a, b| response[*a].partial_cmp(&response[*b]).unwrap())
+            .unwrap();
+        assert_eq!(index_to_coords(size, peak as u32), (16 + 3, 16 - 2));
+
+        let image = tracker.response_image().unwrap();
+        assert_eq!(image.dimensions(), (size, size));
+        assert_eq!(image.get_pixel(16 + 3, 16 - 2)[0], 255);
+    }
+
+    #[test]
+    fn filter_image_resembles_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            background_aware: true,
+            padding: 2.0,
+            window_size: 24,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+
+        let image = tracker.filter_image();
+        assert_eq!(image.dimensions(), (48, 48));
+        let values: Vec<u8> = image.pixels().map(|p| p[0]).collect();
+        assert_eq!(*values.iter().max().unwrap(), 255);
+        assert_eq!(*values.iter().min().unwrap(), 0);
+
+        // the filter is restricted to the target box, so everything outside of it has the same (zero) value
+        let background = image.get_pixel(2, 2)[0];
+        let target_box = box_mask(48, 24);
+        for (value, inside) in values.iter().zip(&target_box) {
+            if *inside == 0.0 {
+                assert!(value.abs_diff(background) <= 1);
+            }
+        }
+        assert!(values.iter().any(|v| v.abs_diff(background) > 50));
+    }
+
+    #[test]
+    fn window_functions() {
+        let at = |weights: &[f32], x: u32, y: u32| weights[(y * 33 + x) as usize];
+        for window in [
+            WindowFunction::Cosine,
+            WindowFunction::Hann,
+            WindowFunction::Tukey(0.5),
+            WindowFunction::Blackman,
+        ] {
+            let weights = window.weights(33, 33);
+            assert_eq!(weights.len(), 33 * 33);
+            assert!((at(&weights, 16, 16) - 1.0).abs() < 1e-5, "{:?}", window);
+            assert!(at(&weights, 0, 16).abs() < 1e-5, "{:?}", window);
+            assert!(at(&weights, 16, 32).abs() < 1e-5, "{:?}", window);
+        }
+
+        // the cosine window takes the minimum of both axes, the others their product
+        let cosine = WindowFunction::Cosine.weights(33, 33);
+        let hann = WindowFunction::Hann.weights(33, 33);
+        assert!((at(&cosine, 8, 4) - at(&cosine, 4, 4)).abs() < 1e-6);
+        assert!(at(&hann, 8, 4) > at(&hann, 4, 4));
+
+        // a Tukey window is flat in the middle
+        let tukey = WindowFunction::Tukey(0.5).weights(33, 33);
+        assert_eq!(at(&tukey, 10, 20), 1.0);
+        assert!(at(&tukey, 4, 20) < 1.0);
+        assert!(WindowFunction::None.weights(8, 4).iter().all(|w| *w == 1.0));
+        assert!(WindowFunction::Tukey(0.0)
+            .weights(8, 4)
+            .iter()
+            .all(|w| *w == 1.0));
+    }
+
+    #[test]
+    fn tracks_with_hann_window() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            window_function: WindowFunction::Hann,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 + 2 * i, 50 + i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 2 * i, 50 + i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
+
+    // a green frame with a square centered on `center` that fades from red to blue
+    fn color_frame(width: u32, height: u32, center: (u32, u32)) -> image::RgbImage {
+        image::RgbImage::from_fn(width, height, |x, y| {
+            let dx = x as i32 - center.0 as i32;
+            let dy = y as i32 - center.1 as i32;
+            if dx.abs() <= 6 && dy.abs() <= 6 {
+                image::Rgb([(120 + 10 * dx) as u8, 60, (120 - 10 * dx + 2 * dy) as u8])
+            } else {
+                image::Rgb([30, 90, 30])
+            }
+        })
+    }
+
+    #[test]
+    fn tracks_color_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&color_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        // one filter per channel
+        assert_eq!(tracker.filter.len(), 3 * tracker.target.len());
+
+        for i in 1..=5 {
+            let frame = color_frame(width, height, (60 + 2 * i, 50 - i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 2 * i, 50 - i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+        assert_eq!(tracker.filter_image().dimensions(), (32, 32));
+    }
+
+    #[test]
+    fn gradient_features_track_target() {
+        let (width, height) = (128, 96);
+        for features in [Features::GradientMagnitude, Features::Gradients] {
+            let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+                features,
+                ..test_settings(width, height)
+            });
+            tracker
+                .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+                .unwrap();
+            assert_eq!(
+                tracker.filter.len(),
+                features.channel_count(1) * tracker.target.len()
+            );
+
+            for i in 1..=5 {
+                // the lighting changes too
+                let frame = imageops::brighten(
+                    &synthetic_frame(width, height, (60 + 2 * i, 50 + i)),
+                    10 * i as i32,
+                );
+                let pred = tracker.track_new_frame(&frame);
+                tracker.update(&frame).unwrap();
+                assert_eq!(pred.location, (60 + 2 * i, 50 + i));
+                assert!(pred.psr > 7.0, "psr {}", pred.psr);
+            }
+        }
+    }
+
+    #[test]
+    fn hog_features_track_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            features: Features::Hog { cell_size: 4 },
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        assert_eq!(
+            tracker.filter.len(),
+            hog::HOG_CHANNELS * tracker.target.len()
+        );
+
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 - 2 * i, 50 + i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 - 2 * i, 50 + i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
+
+    #[test]
+    fn color_names_track_color_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            features: Features::ColorNames,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&color_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        assert_eq!(
+            tracker.filter.len(),
+            color_names::COLOR_NAMES * tracker.target.len()
+        );
+
+        for i in 1..=5 {
+            let frame = color_frame(width, height, (60 + i, 50 + 2 * i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + i, 50 + 2 * i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
+
+    #[test]
+    fn equalization_tracks_dim_target() {
+        let (width, height) = (128, 96);
+        // a dark, low-contrast version of the synthetic frame
+        let dim = |center| {
+            let mut frame = synthetic_frame(width, height, center);
+            frame.pixels_mut().for_each(|p| p[0] = 10 + p[0] / 32);
+            frame
+        };
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            preprocessing: Preprocessing {
+                equalization: utils::Equalization::Adaptive {
+                    tiles: 2,
+                    clip_limit: 40.0,
+                },
+                ..Default::default()
+            },
+            ..test_settings(width, height)
+        });
+        tracker.train(&dim((60, 50)), (60, 50)).unwrap();
+        for i in 1..=5 {
+            let frame = dim((60 + 2 * i, 50 - i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 2 * i, 50 - i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
+
+    #[test]
+    fn custom_preprocessor() {
+        // the pixel values themselves and their squares, scaled to unit norm
+        #[derive(Debug)]
+        struct Squares;
+
+        impl Preprocessor for Squares {
+            fn channel_count(&self, planes: usize) -> usize {
+                2 * planes
+            }
+
+            fn preprocess(&self, planes: &[GrayImage], mask: &[f32]) -> Vec<f32> {
+                let mut features = Vec::new();
+                for plane in planes {
+                    for power in [1, 2] {
+                        let values: Vec<f32> = plane
+                            .pixels()
+                            .zip(mask)
+                            .map(|(p, m)| (p[0] as f32 / 255.0).powi(power) * m)
+                            .collect();
+                        let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt().max(1e-6);
+                        features.extend(values.iter().map(|v| v / norm));
+                    }
+                }
+                features
+            }
+        }
+
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            preprocessor: Some(Arc::new(Squares)),
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        assert_eq!(tracker.filter.len(), 2 * tracker.target.len());
+
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 + i, 50 + i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + i, 50 + i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
+
+    #[test]
+    fn boundary_mask_leaves_target_alone() {
+        let mask = boundary_mask(48, 24);
+        let target_box = box_mask(48, 24);
+        for (m, b) in mask.iter().zip(&target_box) {
+            if *b == 1.0 {
+                assert_eq!(*m, 1.0);
+            }
+        }
+        // zero at the edges, rising towards the box
+        assert_eq!(mask[0], 0.0);
+        assert_eq!(mask[24 * 48 + 47], 0.0);
+        assert!(mask[24 * 48 + 4] < mask[24 * 48 + 8] && mask[24 * 48 + 8] < 1.0);
+
+        // the peak search ignores a larger peak too far from the center
+        let mut response = vec![Complex::new(0.0, 0.0); 48 * 48];
+        response[24 * 48 + 30].re = 1.0;
+        response[24 * 48 + 44].re = 2.0;
+        assert_eq!(find_peak(&response, 48).0, (44, 24));
+        assert_eq!(find_peak_within(&response, 48, 12).0, (30, 24));
+    }
+
+    #[test]
+    fn boundary_masking_tracks_target() {
+        let (width, height) = (128, 96);
+        let settings = MosseTrackerSettings {
+            boundary_masking: true,
+            window_size: 24,
+            padding: 2.0,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        assert_eq!(tracker.search_radius, 12);
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 + 3 * i, 50 + i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 3 * i, 50 + i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
 
-        // calculate the 2D FFT of the preprocessed image: FFT(fi) = Fi
-        let new_Fi = self.compute_2dfft(vectorized);
+        // without padding, there is no room for masking
+        let unpadded = MosseTracker::new(&MosseTrackerSettings {
+            boundary_masking: true,
+            ..test_settings(width, height)
+        });
+        assert!(unpadded.boundary_mask.is_none());
+    }
 
-        //// Update the filter using the prediction
-        //  compute the complex conjugate of Fi, Fi*.
-        let Fi_star: Vec<Complex<f32>> = new_Fi.iter().map(|e| e.conj()).collect();
+    #[test]
+    fn long_term_filter_recovers_from_corruption() {
+        let (width, height) = (128, 96);
+        let checkerboard = GrayImage::from_fn(width, height, |x, y| {
+            let inside = (52..=68).contains(&x) && (42..=58).contains(&y);
+            if inside && (x / 4 + y / 4) % 2 == 0 {
+                Luma([230])
+            } else {
+                Luma([20])
+            }
+        });
 
-        // compute Ai (top) and Bi (bottom) using F*, G, and the learning rate (see paper)
-        let one_minus_eta = 1.0 - self.eta;
+        let run = |long_term_learning_rate: Option<f32>| {
+            let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+                // an aggressive learning rate: one bad frame replaces the appearance model
+                learning_rate: 1.0,
+                long_term_learning_rate,
+                ..test_settings(width, height)
+            });
+            tracker
+                .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+                .unwrap();
 
-        // update the 'top' of the filter update equation
-        self.last_top = self
-            .target
-            .iter()
-            .zip(&Fi_star)
-            .zip(&self.last_top)
-            .map(|((g, f), prev)| self.eta * (g * f) + (one_minus_eta * prev))
-            .collect();
+            // the target gets swapped for something else, and the tracker learns it
+            tracker.track_new_frame(&checkerboard);
+            tracker.update(&checkerboard).unwrap();
 
-        // update the 'bottom' of the filter update equation
-        self.last_bottom = new_Fi
-            .iter()
-            .zip(&Fi_star)
-            .zip(&self.last_bottom)
-            .map(|((f, f_star), prev)| self.eta * (f * f_star) + (one_minus_eta * prev))
-            .collect();
+            tracker.track_new_frame(&synthetic_frame(width, height, (63, 52)))
+        };
 
-        // compute the new filter H* by dividing Ai and Bi elementwise
-        self.filter = self
-            .last_top
-            .iter()
-            .zip(&self.last_bottom)
-            .map(|(a, b)| a / b)
-            .collect();
+        let short_term_only = run(None);
+        let with_long_term = run(Some(0.01));
+        assert_eq!(with_long_term.location, (63, 52));
+        assert!(with_long_term.psr > short_term_only.psr);
     }
 
-    // debug method to dump the latest filter to an inspectable image
-    pub fn dump_filter(
-        &self,
-    ) -> (
-        ImageBuffer<Luma<u8>, Vec<u8>>,
-        ImageBuffer<Luma<u8>, Vec<u8>>,
-    ) {
-        // get the filter out of fourier space
-        // NOTE: input is garbage after this call to inv_fft.process(), so we clone the filter first.
-        let mut h = self.filter.clone();
-        self.inv_fft.process(&mut h);
+    #[test]
+    fn anchor_corrects_drift() {
+        let (width, height) = (128, 96);
+        let run = |anchor_interval: Option<u32>| {
+            let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+                learning_rate: 0.5,
+                anchor_interval,
+                ..test_settings(width, height)
+            });
+            let frame = synthetic_frame(width, height, (60, 50));
+            tracker.train(&frame, (60, 50)).unwrap();
 
-        // turn the real and imaginary values of the filter into separate grayscale images
-        let realfilter = h.iter().map(|c| c.re).collect();
-        let imfilter = h.iter().map(|c| c.im).collect();
+            // let the filter learn a template that is off by a few pixels
+            for _ in 0..4 {
+                tracker.current_target_center = (63, 50);
+                tracker.update(&frame).unwrap();
+            }
 
-        return (
-            to_imgbuf(&realfilter, self.window_size, self.window_size),
-            to_imgbuf(&imfilter, self.window_size, self.window_size),
-        );
-    }
-}
+            tracker.track_new_frame(&synthetic_frame(width, height, (62, 52)))
+        };
 
-fn window_crop(
-    input_frame: &GrayImage,
-    window_width: u32,
-    window_height: u32,
-    center: (u32, u32),
-) -> GrayImage {
-    let window = imageops::crop(
-        &mut input_frame.clone(),
-        center
-            .0
-            .saturating_sub(window_width / 2)
-            .min(input_frame.width() - window_width),
-        center
-            .1
-            .saturating_sub(window_height / 2)
-            .min(input_frame.height() - window_height),
-        window_width,
-        window_height,
-    )
-    .to_image();
+        assert_ne!(run(None).location, (62, 52));
+        assert_eq!(run(Some(1)).location, (62, 52));
+    }
 
-    return window;
-}
+    #[test]
+    fn ensemble_tracks_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            ensemble_size: 3,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        assert_eq!(tracker.ensemble.len(), 2);
+        // the members learned from different perturbations
+        assert_ne!(tracker.ensemble[0].filter, tracker.ensemble[1].filter);
 
-fn build_target(window_width: u32, window_height: u32) -> Vec<f32> {
-    let mut target_gi = vec![0f32; (window_width * window_height) as usize];
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 + 2 * i, 50 + i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 2 * i, 50 + i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
 
-    // Optional: let the sigma depend on the window size (Galoogahi et al. (2015). Correlation Filters with Limited Boundaries)
-    // let sigma = ((window_width * window_height) as f32).sqrt() / 16.0;
-    // let variance = sigma * sigma;
-    let variance = 2.0;
+    #[test]
+    fn asef_initialization_tracks_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            initialization: FilterInitialization::Asef,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
 
-    // create gaussian peak at the center coordinates
-    let center_x = window_width / 2;
-    let center_y = window_height / 2;
-    for x in 0..window_width {
-        for y in 0..window_height {
-            let distx: f32 = x as f32 - center_x as f32;
-            let disty: f32 = y as f32 - center_y as f32;
+        // updates continue from the ASEF filter
+        let rebuilt = divide_filter(&tracker.last_top.get(), &tracker.last_bottom.get(), 0.001);
+        for (h, r) in tracker.filter.get().iter().zip(&rebuilt) {
+            assert!((h - r).norm() <= 1e-3 * h.norm().max(1.0));
+        }
 
-            // apply a crude univariate Gaussian density function
-            target_gi[((y * window_width) + x) as usize] =
-                (-((distx * distx) + (disty * disty) / variance)).exp()
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (60 + 2 * i, 50 - i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 2 * i, 50 - i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
         }
     }
 
-    return target_gi;
-}
+    #[test]
+    fn response_sigma_sets_peak_width() {
+        let sigma = ResponseSigma::Relative(0.1).pixels(32);
+        assert!((sigma - 3.2).abs() < 1e-6);
 
-// function for debugging the shape of the target
-// output only depends on the provided target_coords
-pub fn dump_target(window_width: u32, window_height: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    let trgt = build_target(window_width, window_height);
+        let target = build_target(32, 32, sigma);
+        let at = |x: usize, y: usize| target[y * 32 + x];
+        assert_eq!(at(16, 16), 1.0);
+        // one sigma away from the center, along either axis
+        let one_sigma = (-0.5f32).exp();
+        assert!(at(19, 16) > one_sigma && at(20, 16) < one_sigma);
+        assert!(at(16, 19) > one_sigma && at(16, 20) < one_sigma);
 
-    let normalized = trgt.iter().map(|a| a * 255.0).collect();
+        // a wider response still tracks
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            response_sigma: ResponseSigma::Absolute(2.0),
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        let pred = tracker.track_new_frame(&synthetic_frame(width, height, (63, 49)));
+        assert_eq!(pred.location, (63, 49));
+    }
 
-    return to_imgbuf(&normalized, window_width, window_height);
-}
+    #[test]
+    fn affine_augmentation_tracks_sheared_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            affine_augmentation: true,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
 
-fn compute_psr(
-    predicted: &Vec<Complex<f32>>,
-    width: u32,
-    height: u32,
-    max: f32,
-    maxpos: (u32, u32),
-) -> f32 {
-    // uses running updates of standard deviation and mean
-    let mut running_sum = 0.0;
-    let mut running_sd = 0.0;
-    for e in predicted {
-        running_sum += e.re;
-        running_sd += e.re * e.re;
+        // the same target, seen at a slight slant
+        let frame = synthetic_frame(width, height, (62, 50));
+        let projection =
+            Projection::from_matrix([1.0, 0.08, -4.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]).unwrap();
+        let slanted = warp(&frame, &projection, Interpolation::Bilinear, Luma([20]));
+        let pred = tracker.track_new_frame(&slanted);
+        assert_eq!(pred.location, (62, 50));
+        assert!(pred.psr > 7.0, "psr {}", pred.psr);
     }
 
-    // subtract the values of a 11*11 window around the max from the running sd and sum
-    // TODO: look up: why 11*11, and not something simpler like 12*12?
-    let max_x = maxpos.0 as i32;
-    let max_y = maxpos.1 as i32;
-    let window_left = (max_x - 5).max(0);
-    let window_right = (max_x + 6).min(width as i32);
-    let window_top = (max_y - 5).min(0); // note: named according to CG conventions
-    let window_bottom = (max_y + 6).min(height as i32);
-    for x in window_left..window_right {
-        for y in window_bottom..window_top {
-            let ind = (y * width as i32 + x) as usize;
-            let val = predicted[ind].re;
-            running_sd -= val * val;
-            running_sum -= val;
-        }
+    #[test]
+    fn photometric_augmentation_tracks_darker_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            photometric_augmentation: true,
+            ..test_settings(width, height)
+        });
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+
+        // the same target, after the sun went behind a cloud
+        let darker = imageops::contrast(
+            &imageops::brighten(&synthetic_frame(width, height, (58, 52)), -40),
+            -30.0,
+        );
+        let pred = tracker.track_new_frame(&darker);
+        assert_eq!(pred.location, (58, 52));
+        assert!(pred.psr > 7.0, "psr {}", pred.psr);
     }
 
-    // we need to subtract 11*11 window from predicted.len() to get the sidelobe_size
-    let sidelobe_size = (predicted.len() - (11 * 11)) as f32;
-    let mean_sl = running_sum / sidelobe_size;
-    let sd_sl = ((running_sd / sidelobe_size) - (mean_sl * mean_sl)).sqrt();
-    let psr = (max - mean_sl) / sd_sl;
+    #[test]
+    fn translation_augmentation_tracks_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            translation_augmentation: Some(3),
+            ..test_settings(width, height)
+        });
 
-    return psr;
-}
+        // the shifted responses peak where the shifted target is
+        let mut response = tracker.shifted_target((3, -2));
+        tracker.inv_fft.process(&mut response);
+        let ((x, y), _, _) = find_peak(&response, tracker.window_size);
+        assert_eq!((x, y), (16 + 3, 16 - 2));
 
-fn index_to_coords(width: u32, index: u32) -> (u32, u32) {
-    // modulo/remainder ops are theoretically O(1)
-    // checked_rem returns None if rhs == 0, which would indicate an upstream error (width == 0).
-    let x = index.checked_rem(width).unwrap();
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        for i in 1..=4 {
+            let frame = synthetic_frame(width, height, (60 + 3 * i, 50));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 3 * i, 50));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+    }
 
-    // checked sub returns None if overflow occurred, which is also a panicable offense.
-    // checked_div returns None if rhs == 0, which would indicate an upstream error (width == 0).
-    let y = (index.checked_sub(x).unwrap()).checked_div(width).unwrap();
-    return (x, y);
-}
+    #[test]
+    fn trains_on_several_frames() {
+        let (width, height) = (128, 96);
+        // the target looks different in every frame: its gradient turns around
+        let frame = |center: (u32, u32), turn: i32| {
+            GrayImage::from_fn(width, height, |x, y| {
+                let dx = x as i32 - center.0 as i32;
+                let dy = y as i32 - center.1 as i32;
+                if dx.abs() <= 8 && dy.abs() <= 8 {
+                    Luma([(130 + turn * 6 * dx + (1 - turn.abs()) * 6 * dy) as u8])
+                } else {
+                    Luma([20])
+                }
+            })
+        };
+        let clip = [
+            (frame((50, 40), 1), (50, 40)),
+            (frame((53, 42), 0), (53, 42)),
+            (frame((56, 44), -1), (56, 44)),
+        ];
+        let labeled: Vec<(&GrayImage, (u32, u32))> = clip.iter().map(|(f, c)| (f, *c)).collect();
 
-pub fn to_imgbuf(buf: &Vec<f32>, width: u32, height: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    ImageBuffer::from_vec(width, height, buf.iter().map(|c| *c as u8).collect()).unwrap()
-}
+        let settings = MosseTrackerSettings {
+            augmentation: Some(AugmentationPipeline::new()),
+            ..test_settings(width, height)
+        };
+        let mut single = MosseTracker::new(&settings);
+        single.train(&clip[0].0, clip[0].1).unwrap();
+        let mut multi = MosseTracker::new(&settings);
+        multi.train_on_frames(&labeled).unwrap();
+        assert_eq!(multi.current_target_center, (56, 44));
 
-// TODO: below tests are used as a scratch pad and for syntax experiments, not serious unit testing.
-#[cfg(test)]
-mod tests {
+        // only the tracker that has seen all of them recognizes every appearance
+        let upright = single.track_new_frame(&frame((58, 44), 0));
+        let pred = multi.track_new_frame(&frame((58, 44), 0));
+        assert_eq!(pred.location, (58, 44));
+        assert!(pred.psr > 7.0 && pred.psr > upright.psr, "psr {}", pred.psr);
 
-    use super::*;
+        // without frames, there is nothing to train on
+        multi.train_on_frames::<GrayImage>(&[]).unwrap();
+        assert_eq!(multi.current_target_center, (58, 44));
+    }
 
     #[test]
-    fn sanity_test_max_by() {
-        let filtered: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0, 5.0];
-        let maxel = filtered
-            .iter()
-            .enumerate()
-            .max_by(|a, b| {
-                // filtered (gi) is still complex at this point, we only care about the real part
-                a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal)
-            })
+    fn custom_augmentation_pipeline() {
+        let (width, height) = (128, 96);
+        let settings = MosseTrackerSettings {
+            augmentation: Some(
+                AugmentationPipeline::new()
+                    .with(utils::Rotations(vec![0.02, -0.02, 0.05, -0.05]))
+                    .with(utils::Scales(vec![0.95, 1.05])),
+            ),
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        assert_eq!(tracker.augmentation.len(), 2);
+
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
             .unwrap();
-        assert_eq!(maxel, (4usize, &5.0f32));
+        for i in 1..=4 {
+            let frame = synthetic_frame(width, height, (60 + 2 * i, 50 - i));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.location, (60 + 2 * i, 50 - i));
+            assert!(pred.psr > 7.0, "psr {}", pred.psr);
+        }
+
+        // the default pipeline follows the *_augmentation settings
+        let default = MosseTracker::new(&MosseTrackerSettings {
+            affine_augmentation: true,
+            ..test_settings(width, height)
+        });
+        assert_eq!(default.augmentation.len(), 4);
     }
 
     #[test]
-    fn am_i_still_sane() {
-        assert_eq!(
-            Complex::new(1.0, -3.0) * Complex::new(2.0, 5.0),
-            Complex::new(17.0, -1.0)
-        );
+    fn occlusion_augmentation_tracks_partially_covered_target() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&MosseTrackerSettings {
+            occlusion_augmentation: true,
+            window_size: 48,
+            ..test_settings(width, height)
+        });
+        let frame = |center: (u32, u32)| {
+            GrayImage::from_fn(width, height, |x, y| {
+                let dx = x as i32 - center.0 as i32;
+                let dy = y as i32 - center.1 as i32;
+                if dx.abs() <= 12 && dy.abs() <= 12 {
+                    Luma([(130 + 4 * dx + 3 * dy) as u8])
+                } else {
+                    Luma([20])
+                }
+            })
+        };
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+
+        // a quarter of the target disappears behind something
+        let mut covered = frame((62, 51));
+        for x in 62..76 {
+            for y in 51..65 {
+                covered.put_pixel(x, y, Luma([90]));
+            }
+        }
+        let pred = tracker.track_new_frame(&covered);
+        assert_eq!(pred.location, (62, 51));
+        assert!(pred.psr > 7.0, "psr {}", pred.psr);
     }
 
     #[test]
@@ -692,10 +4402,13 @@ mod tests {
             regularization: 0.001,
             learning_rate: 0.05,
             psr_threshold: 7.0,
+            ..Default::default()
         };
         let mut multi_tracker = MultiMosseTracker::new(settings, 3);
         assert_eq!(multi_tracker.size(), 0);
-        multi_tracker.add_or_replace_target(0, (0, 0), &frame);
+        multi_tracker
+            .add_or_replace_target(0, (0, 0), &frame)
+            .unwrap();
 
         assert_eq!(multi_tracker.size(), 1);
         assert_eq!(
@@ -709,11 +4422,15 @@ mod tests {
             (0, 0)
         );
 
-        multi_tracker.add_or_replace_target(1, (10, 0), &frame);
+        multi_tracker
+            .add_or_replace_target(1, (10, 0), &frame)
+            .unwrap();
 
         assert_eq!(multi_tracker.size(), 2);
 
-        multi_tracker.add_or_replace_target(0, (10, 0), &frame);
+        multi_tracker
+            .add_or_replace_target(0, (10, 0), &frame)
+            .unwrap();
 
         assert_eq!(multi_tracker.size(), 2);
         assert_eq!(
@@ -727,4 +4444,399 @@ mod tests {
             (10, 0)
         );
     }
+
+    #[test]
+    fn multi_tracker_is_a_tracker() {
+        let (width, height) = (128, 96);
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3);
+        Tracker::<Luma<u8>>::train(
+            &mut multi,
+            &synthetic_frame(width, height, (60, 50)),
+            (7, (60, 50)),
+        )
+        .unwrap();
+
+        // predict() locates the targets, and update() learns from them
+        let frame = synthetic_frame(width, height, (63, 48));
+        let predictions = multi.predict(&frame);
+        assert_eq!(predictions.len(), 1);
+        assert_eq!(predictions[0].0, 7);
+        assert_eq!(predictions[0].1.location, (63, 48));
+        multi.update(&frame).unwrap();
+        assert_eq!(multi.size(), 1);
+
+        Tracker::<Luma<u8>>::reset(&mut multi);
+        assert_eq!(multi.size(), 0);
+    }
+
+    #[test]
+    fn multi_tracker_ids_are_stable() {
+        let (width, height) = (160, 96);
+        let frame = synthetic_frame(width, height, (60, 50));
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3);
+        assert_eq!(multi.add_target((60, 50), &frame).unwrap(), 0);
+        multi.add_or_replace_target(5, (60, 50), &frame).unwrap();
+        assert_eq!(multi.add_target((60, 50), &frame).unwrap(), 6);
+        assert_eq!(multi.ids().collect::<Vec<_>>(), vec![0, 5, 6]);
+
+        assert!(multi.remove(5).is_some());
+        assert!(multi.remove(5).is_none());
+        assert!(multi.get(5).is_none());
+        assert_eq!(multi.get(6).unwrap().target_box().pixel_center(), (60, 50));
+        // a removed ID isn't handed out again
+        assert_eq!(multi.add_target((60, 50), &frame).unwrap(), 7);
+
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3)
+            .with_id_policy(IdPolicy::Recycle);
+        for _ in 0..3 {
+            multi.add_target((60, 50), &frame).unwrap();
+        }
+        multi.remove(1);
+        assert_eq!(multi.add_target((60, 50), &frame).unwrap(), 1);
+        assert_eq!(multi.add_target((60, 50), &frame).unwrap(), 3);
+        assert!(multi.add_target((60, 200), &frame).is_err());
+        assert_eq!(multi.size(), 4);
+    }
+
+    #[test]
+    fn targets_come_and_go_mid_sequence() {
+        let (width, height) = (160, 96);
+        // two targets, the second one appearing on the fourth frame
+        let frame = |i: u32| {
+            let mut frame = synthetic_frame(width, height, (40 + 2 * i, 30));
+            if i >= 3 {
+                let second = synthetic_frame(width, height, (110, 60 + i));
+                for (x, y, pixel) in second.enumerate_pixels() {
+                    if x >= 80 && y >= 45 {
+                        frame.put_pixel(x, y, *pixel);
+                    }
+                }
+            }
+            frame
+        };
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3);
+        let first = multi.add_target((40, 30), &frame(0)).unwrap();
+        let mut alone = MosseTracker::new(&test_settings(width, height));
+        alone.train(&frame(0), (40, 30)).unwrap();
+
+        let mut second = None;
+        for i in 1..=8 {
+            if i == 3 {
+                second = Some(multi.add_target((110, 63), &frame(i)).unwrap());
+                continue;
+            }
+            if i == 6 {
+                assert!(multi.remove(second.unwrap()).is_some());
+            }
+            let predictions = multi.track(&frame(i)).unwrap();
+            let expected = alone.track(&frame(i)).unwrap();
+            // the first target is tracked as if it was alone
+            assert_eq!(predictions[0], (first, expected));
+            if (4..6).contains(&i) {
+                assert_eq!(predictions[1].1.location, (110, 60 + i));
+            }
+        }
+
+        multi.retain(|id, _| id != first);
+        assert_eq!(multi.size(), 0);
+    }
+
+    #[test]
+    fn tracks_many_targets() {
+        let (width, height) = (320, 192);
+        // a grid of 4 x 3 targets, all moving to the right
+        let centers = |i: u32| {
+            (0..12)
+                .map(move |t| (40 + 80 * (t % 4) + 2 * i, 32 + 64 * (t / 4)))
+                .collect::<Vec<_>>()
+        };
+        let frame = |i: u32| {
+            let targets: Vec<GrayImage> = centers(i)
+                .into_iter()
+                .map(|center| synthetic_frame(width, height, center))
+                .collect();
+            GrayImage::from_fn(width, height, |x, y| {
+                Luma([targets.iter().map(|t| t.get_pixel(x, y)[0]).max().unwrap()])
+            })
+        };
+
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3);
+        for center in centers(0) {
+            multi.add_target(center, &frame(0)).unwrap();
+        }
+        for i in 1..=4 {
+            let predictions = multi.track(&frame(i)).unwrap();
+            let ids: Vec<Identifier> = predictions.iter().map(|(id, _)| *id).collect();
+            assert_eq!(ids, (0..12).collect::<Vec<_>>());
+            let locations: Vec<(u32, u32)> = predictions.iter().map(|(_, p)| p.location).collect();
+            assert_eq!(locations, centers(i));
+        }
+    }
+
+    #[test]
+    fn targets_share_the_grayscale_frame() {
+        let (width, height) = (160, 96);
+        let frame = |i: u32| {
+            let first = synthetic_frame(width, height, (40 + i, 40));
+            let second = synthetic_frame(width, height, (110, 50 + i));
+            image::RgbImage::from_fn(width, height, |x, y| {
+                let (a, b) = (first.get_pixel(x, y)[0], second.get_pixel(x, y)[0]);
+                image::Rgb([a.max(b), a, b])
+            })
+        };
+        let settings = MosseTrackerSettings {
+            scale_adaptation: true,
+            log_polar_estimation: true,
+            ..test_settings(width, height)
+        };
+        let mut multi = MultiMosseTracker::new(settings.clone(), 3);
+        let mut alone = Vec::new();
+        for center in [(40, 40), (110, 50)] {
+            multi.add_target(center, &frame(0)).unwrap();
+            let mut tracker = MosseTracker::new(&settings);
+            tracker.train(&frame(0), center).unwrap();
+            alone.push(tracker);
+        }
+
+        // the shared grayscale frame is the same as the grayscale windows of the trackers on their own
+        for i in 1..=4 {
+            let predictions = multi.update_all(&frame(i)).unwrap();
+            for ((_, prediction), tracker) in predictions.iter().zip(&mut alone) {
+                assert_eq!(*prediction, tracker.track(&frame(i)).unwrap());
+            }
+            assert_eq!(predictions[1].1.location, (110, 50 + i));
+        }
+    }
+
+    #[test]
+    fn targets_have_their_own_settings() {
+        let (width, height) = (160, 96);
+        let frame = synthetic_frame(width, height, (60, 50));
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3);
+        let small = multi.add_target((60, 50), &frame).unwrap();
+        let large_settings = MosseTrackerSettings {
+            window_size: 48,
+            learning_rate: 0.2,
+            psr_threshold: 1000.0,
+            ..test_settings(width, height)
+        };
+        let large = multi
+            .add_target_with(&large_settings, (60, 50), &frame)
+            .unwrap();
+        assert_eq!(multi.get(small).unwrap().window_size(), 32);
+        assert_eq!(multi.get(large).unwrap().window_size(), 48);
+
+        // the large target fails its own PSR threshold, and only it is dropped after three frames
+        for _ in 0..3 {
+            multi.track(&frame).unwrap();
+        }
+        assert_eq!(multi.ids().collect::<Vec<_>>(), vec![small]);
+
+        let invalid = MosseTrackerSettings {
+            learning_rate: 2.0,
+            ..test_settings(width, height)
+        };
+        assert!(matches!(
+            multi.add_target_with(&invalid, (60, 50), &frame),
+            Err(Error::Settings(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        let frame = synthetic_frame(width, height, (60, 50));
+
+        assert_eq!(
+            tracker.train(&frame, (130, 50)),
+            Err(Error::TargetOutsideFrame {
+                center: (130, 50),
+                frame: (128, 96)
+            })
+        );
+        let small = synthetic_frame(64, 48, (30, 20));
+        assert_eq!(
+            tracker.train(&small, (30, 20)),
+            Err(Error::FrameSize {
+                expected: (128, 96),
+                actual: (64, 48)
+            })
+        );
+
+        // the tracker is still good for a proper target
+        tracker.train(&frame, (60, 50)).unwrap();
+        let error = tracker.update(&small).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected a frame of 128x96, got one of 64x48"
+        );
+        let pred = tracker.track_new_frame(&synthetic_frame(width, height, (62, 51)));
+        assert_eq!(pred.location, (62, 51));
+
+        // the multi-tracker doesn't add targets it can't train on
+        let mut multi = MultiMosseTracker::new(test_settings(width, height), 3);
+        assert!(multi.add_or_replace_target(0, (60, 200), &frame).is_err());
+        assert_eq!(multi.size(), 0);
+    }
+
+    #[test]
+    fn predictions_have_boxes() {
+        let (width, height) = (128, 96);
+        let mut tracker = MosseTracker::new(&test_settings(width, height));
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        assert_eq!(
+            tracker.target_box(),
+            BBox::from_xywh(44.0, 34.0, 32.0, 32.0)
+        );
+
+        let pred = tracker.track_new_frame(&synthetic_frame(width, height, (63, 48)));
+        assert_eq!(pred.size, (32.0, 32.0));
+        let bbox = pred.bbox();
+        assert_eq!(bbox.pixel_center(), (63, 48));
+        assert_eq!((bbox.width, bbox.height), (32.0, 32.0));
+        assert_eq!(tracker.target_box(), BBox::around((63, 48), (32.0, 32.0)));
+    }
+
+    #[test]
+    fn predictions_report_status() {
+        let (width, height) = (160, 96);
+        let settings = MosseTrackerSettings {
+            motion_model: true,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        tracker
+            .train(&synthetic_frame(width, height, (40, 48)), (40, 48))
+            .unwrap();
+        for i in 1..=5 {
+            let frame = synthetic_frame(width, height, (40 + 3 * i, 48));
+            let pred = tracker.track_new_frame(&frame);
+            tracker.update(&frame).unwrap();
+            assert_eq!(pred.frame_index, i);
+            assert_eq!(pred.status, TrackingStatus::Tracking);
+            assert!(pred.confidence > 0.0 && pred.confidence <= 1.0);
+            assert_eq!(pred.velocity, tracker.velocity());
+        }
+
+        let pred = tracker.track_new_frame(&GrayImage::new(width, height));
+        assert_eq!(pred.status, TrackingStatus::Lost);
+        assert_eq!(pred.confidence, 0.0);
+        assert_eq!(pred.frame_index, 6);
+
+        // training starts counting again
+        tracker
+            .train(&synthetic_frame(width, height, (40, 48)), (40, 48))
+            .unwrap();
+        let pred = tracker.track_new_frame(&synthetic_frame(width, height, (40, 48)));
+        assert_eq!(pred.frame_index, 1);
+    }
+
+    #[test]
+    fn retrained_trackers_start_over() {
+        let (width, height) = (128, 96);
+        let settings = MosseTrackerSettings {
+            scale_adaptation: true,
+            motion_model: true,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        let frame = synthetic_frame(width, height, (63, 48));
+        tracker.track_new_frame(&frame);
+        tracker.update(&frame).unwrap();
+
+        // a new box: the tracker forgets the old target, as if it was new
+        let frame = synthetic_frame(width, height, (40, 60));
+        let bbox = BBox::around((40, 60), (20.0, 20.0));
+        tracker.retrain(&frame, bbox).unwrap();
+        let mut fresh = MosseTracker::new(&settings);
+        fresh.train(&frame, (40, 60)).unwrap();
+        assert_eq!(tracker.state(), fresh.state());
+
+        // a box outside the frame leaves the tracker alone
+        let outside = BBox::around((200, 60), (20.0, 20.0));
+        assert!(tracker.retrain(&frame, outside).is_err());
+        assert_eq!(tracker.state(), fresh.state());
+    }
+
+    #[test]
+    fn restored_trackers_continue_where_they_left_off() {
+        let (width, height) = (128, 96);
+        let settings = || MosseTrackerSettings {
+            scale_adaptation: true,
+            motion_model: true,
+            ensemble_size: 2,
+            ..test_settings(width, height)
+        };
+        let mut tracker = MosseTracker::new(&settings());
+        tracker
+            .train(&synthetic_frame(width, height, (60, 50)), (60, 50))
+            .unwrap();
+        let frame = synthetic_frame(width, height, (63, 48));
+        tracker.track_new_frame(&frame);
+        tracker.update(&frame).unwrap();
+
+        let state = tracker.state();
+        let mut restored = MosseTracker::from_state(&settings(), state.clone()).unwrap();
+        assert_eq!(restored.state(), state);
+        for center in [(66, 47), (68, 49)] {
+            let frame = synthetic_frame(width, height, center);
+            let (pred, restored_pred) = (
+                tracker.track_new_frame(&frame),
+                restored.track_new_frame(&frame),
+            );
+            assert_eq!(pred.position, restored_pred.position);
+            assert_eq!(pred.psr, restored_pred.psr);
+        }
+
+        // a state only fits trackers with the settings it was taken with
+        let other = MosseTrackerSettings {
+            ensemble_size: 1,
+            ..settings()
+        };
+        assert!(matches!(
+            MosseTracker::from_state(&other, state.clone()),
+            Err(Error::IncompatibleState(_))
+        ));
+        let other = MosseTrackerSettings {
+            padding: 2.0,
+            ..settings()
+        };
+        assert!(MosseTracker::from_state(&other, state.clone()).is_err());
+
+        // the settings and the size of the state are checked before the tracker is made, so a state of another
+        // window size doesn't allocate the windows the settings ask for
+        let huge = MosseTrackerSettings {
+            width: 60000,
+            height: 60000,
+            window_size: 60000,
+            ..settings()
+        };
+        let mut huge_state = state.clone();
+        huge_state.frame_size = (60000, 60000);
+        assert_eq!(
+            MosseTracker::from_state(&huge, huge_state).unwrap_err(),
+            Error::IncompatibleState("window size")
+        );
+        let invalid = MosseTrackerSettings {
+            learning_rate: 0.0,
+            ..settings()
+        };
+        assert!(matches!(
+            MosseTracker::from_state(&invalid, state),
+            Err(Error::Settings(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_is_serializable() {
+        fn serializable<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+        serializable::<TrackerState>();
+    }
 }
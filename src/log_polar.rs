@@ -0,0 +1,350 @@
+// Joint scale and rotation estimation in log-polar space, in the spirit of Fourier-Mellin registration.
+//
+// The target is resampled on a log-polar grid around its center (see utils::log_polar): columns are exponentially
+// spaced radii, rows are angles. A change in size of the target then shifts it along the columns, and an in-plane
+// rotation shifts it (circularly) along the rows, so a single MOSSE filter on the log-polar patch estimates both.
+
+use crate::state::ShapeFilterState;
+use crate::{build_target, fft, parabolic_offset, utils, FrameSource};
+use image::{GrayImage, Luma};
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use std::f32::consts::PI;
+
+// edge size of the log-polar patch: the number of radii, and of angles
+const LOG_POLAR_SIZE: u32 = 64;
+// the innermost sampled radius, relative to the outermost one (half the target size). The middle of the target is
+// left out, as it is the part most sensitive to errors in the target center.
+const MIN_RADIUS_FRACTION: f32 = 1.0 / 5.0;
+// the standard deviation of the desired response (in patch pixels)
+const LOG_POLAR_SIGMA: f32 = 1.0;
+// the largest change in size (as a factor) and rotation (in radians) between two frames. Only the part of the
+// response within these bounds is searched for the peak, which rules out far-fetched matches (such as a rotation
+// by half a turn of a target that looks alike upside down).
+const MAX_SCALE_CHANGE: f32 = 1.15;
+const MAX_ROTATION: f32 = 0.3;
+
+pub(crate) struct LogPolarFilter {
+    // the 'top' (G * F*) and 'bottom' (F * F*) of the filter, in the Fourier domain
+    top: Vec<Complex<f32>>,
+    bottom: Vec<Complex<f32>>,
+
+    // FFT of the desired output: a Gaussian peak in the middle of the patch
+    target: Vec<Complex<f32>>,
+
+    eta: f32,
+    regularization: f32,
+
+    real_fft: fft::RealFft,
+    inv_fft: fft::Plan,
+}
+
+impl std::fmt::Debug for LogPolarFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogPolarFilter")
+            .field("top", &self.top)
+            .field("bottom", &self.bottom)
+            .field("target", &self.target)
+            .field("eta", &self.eta)
+            .field("regularization", &self.regularization)
+            .finish()
+    }
+}
+
+impl LogPolarFilter {
+    pub(crate) fn new(learning_rate: f32, regularization: f32) -> LogPolarFilter {
+        let length = (LOG_POLAR_SIZE * LOG_POLAR_SIZE) as usize;
+        let (fft, inv_fft) = fft::plans(length);
+
+        let mut target: Vec<Complex<f32>> =
+            build_target(LOG_POLAR_SIZE, LOG_POLAR_SIZE, LOG_POLAR_SIGMA)
+                .into_iter()
+                .map(|p| Complex::new(p, 0.0))
+                .collect();
+        fft.process(&mut target);
+
+        LogPolarFilter {
+            top: Vec::new(),
+            bottom: vec![Complex::zero(); length],
+            target,
+            eta: learning_rate,
+            regularization,
+            real_fft: fft::RealFft::new(length),
+            inv_fft,
+        }
+    }
+
+    // the log-polar patch around a target of `size` (width, height) that is rotated clockwise by `angle`,
+    // preprocessed and in the Fourier domain
+    fn features<G: FrameSource<Pixel = Luma<u8>>>(
+        &self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+        angle: f32,
+    ) -> Vec<Complex<f32>> {
+        let patch = patch(frame, center, size, angle);
+
+        // like preprocess(), but the angles wrap around: only the radius axis gets a cosine window
+        let mut values: Vec<f32> = patch.pixels().map(|p| (p[0] as f32 + 1.0).ln()).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        values.iter_mut().for_each(|v| *v -= mean);
+        let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm != 0.0 {
+            values.iter_mut().for_each(|v| *v /= norm);
+        }
+
+        let windowed: Vec<f32> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let x = i as u32 % LOG_POLAR_SIZE;
+                let window = (PI * x as f32 / (LOG_POLAR_SIZE - 1) as f32).sin();
+                v * window
+            })
+            .collect();
+        self.real_fft.process(&windowed)
+    }
+
+    // what the filter has learned (see MosseTracker::state()), with the top as its only feature dimension
+    pub(crate) fn state(&self) -> ShapeFilterState {
+        ShapeFilterState {
+            top: [&self.top]
+                .into_iter()
+                .filter(|top| !top.is_empty())
+                .cloned()
+                .collect(),
+            bottom: self.bottom.clone(),
+        }
+    }
+
+    // whether a state can be restored into a log-polar filter
+    pub(crate) fn fits(state: &ShapeFilterState) -> bool {
+        let length = (LOG_POLAR_SIZE * LOG_POLAR_SIZE) as usize;
+        state.bottom.len() == length
+            && state.top.len() <= 1
+            && state.top.iter().all(|top| top.len() == length)
+    }
+
+    // restore what the filter has learned (from a state that fits)
+    pub(crate) fn restore(&mut self, state: ShapeFilterState) {
+        self.top = state.top.into_iter().next().unwrap_or_default();
+        self.bottom = state.bottom;
+    }
+
+    // (re)learn the filter from scratch around the target
+    pub(crate) fn train<G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+        angle: f32,
+    ) {
+        self.top.clear();
+        self.learn(frame, center, size, angle, 1.0);
+    }
+
+    // blend the target's current appearance into the filter
+    pub(crate) fn update<G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+        angle: f32,
+    ) {
+        let eta = self.eta;
+        self.learn(frame, center, size, angle, eta);
+    }
+
+    fn learn<G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+        angle: f32,
+        eta: f32,
+    ) {
+        let features = self.features(frame, center, size, angle);
+        let top: Vec<Complex<f32>> = self
+            .target
+            .iter()
+            .zip(&features)
+            .map(|(g, f)| g * f.conj())
+            .collect();
+        let bottom: Vec<Complex<f32>> = features.iter().map(|f| f * f.conj()).collect();
+
+        if self.top.is_empty() {
+            self.top = top;
+            self.bottom = bottom;
+            return;
+        }
+
+        self.top
+            .iter_mut()
+            .zip(top)
+            .for_each(|(p, n)| *p = eta * n + (1.0 - eta) * *p);
+        self.bottom
+            .iter_mut()
+            .zip(bottom)
+            .for_each(|(p, n)| *p = eta * n + (1.0 - eta) * *p);
+    }
+
+    // the relative change in size, and the change in angle (clockwise, in radians) of the target around `center`,
+    // compared to a target of `size` at `angle`
+    pub(crate) fn estimate<G: FrameSource<Pixel = Luma<u8>>>(
+        &self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+        angle: f32,
+    ) -> (f32, f32) {
+        if self.top.is_empty() {
+            return (1.0, 0.0);
+        }
+
+        let features = self.features(frame, center, size, angle);
+        let mut response: Vec<Complex<f32>> = features
+            .iter()
+            .zip(self.top.iter().zip(&self.bottom))
+            .map(|(f, (a, b))| f * a / (b + self.regularization))
+            .collect();
+        self.inv_fft.process(&mut response);
+
+        // every column is a constant factor further out than the previous one, and every row a constant angle
+        let growth = (1.0 / MIN_RADIUS_FRACTION).ln() / (LOG_POLAR_SIZE - 1) as f32;
+        let turn = 2.0 * PI / LOG_POLAR_SIZE as f32;
+        let max_columns = (MAX_SCALE_CHANGE.ln() / growth).ceil() as i32;
+        let max_rows = (MAX_ROTATION / turn).ceil() as i32;
+
+        // the peak, within the bounds around the middle of the patch
+        let half = (LOG_POLAR_SIZE / 2) as i32;
+        let at = |columns: i32, rows: i32| {
+            let x = (half + columns).rem_euclid(LOG_POLAR_SIZE as i32);
+            let y = (half + rows).rem_euclid(LOG_POLAR_SIZE as i32);
+            response[(y * LOG_POLAR_SIZE as i32 + x) as usize].re
+        };
+        let mut peak = (0, 0);
+        for rows in -max_rows..=max_rows {
+            for columns in -max_columns..=max_columns {
+                if at(columns, rows) > at(peak.0, peak.1) {
+                    peak = (columns, rows);
+                }
+            }
+        }
+
+        // refined to sub-pixel precision
+        let (columns, rows) = peak;
+        let max = at(columns, rows);
+        let x =
+            columns as f32 + parabolic_offset(at(columns - 1, rows), max, at(columns + 1, rows));
+        let y = rows as f32 + parabolic_offset(at(columns, rows - 1), max, at(columns, rows + 1));
+
+        ((growth * x).exp(), turn * y)
+    }
+}
+
+// the log-polar patch around a target of `size` (width, height) that is rotated clockwise by `angle`.
+// The patch samples no farther than its largest radius from the center, so only that part of the frame is cropped.
+fn patch<G: FrameSource<Pixel = Luma<u8>>>(
+    frame: &G,
+    center: (u32, u32),
+    size: (f32, f32),
+    angle: f32,
+) -> GrayImage {
+    let max_radius = (size.0.min(size.1) / 2.0).max(1.0);
+    // room for the neighbours of the outermost samples (see utils::log_polar)
+    let reach = 2 * (max_radius.ceil() as u32 + 2);
+    let mut window = GrayImage::new(reach, reach);
+    frame.crop_into(reach, reach, center, &mut window);
+    let (left, top, _, _) = utils::crop_region(frame.dimensions(), reach, reach, center);
+    utils::log_polar(
+        &window,
+        ((center.0 - left) as f32, (center.1 - top) as f32),
+        (max_radius * MIN_RADIUS_FRACTION, max_radius),
+        angle,
+        (LOG_POLAR_SIZE, LOG_POLAR_SIZE),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+    use imageproc::geometric_transformations::{warp, Interpolation, Projection};
+
+    // an asymmetric blob pattern, so that both its size and its orientation show
+    fn pattern() -> GrayImage {
+        GrayImage::from_fn(128, 128, |x, y| {
+            let (dx, dy) = (x as f32 - 64.0, y as f32 - 64.0);
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist > 24.0 {
+                Luma([10])
+            } else if dx > 0.0 && dy.abs() < 6.0 {
+                Luma([240])
+            } else if dy > 4.0 {
+                Luma([150])
+            } else {
+                Luma([70])
+            }
+        })
+    }
+
+    // `image` scaled by `factor` and rotated clockwise by `angle`, about (64, 64)
+    fn transformed(image: &GrayImage, factor: f32, angle: f32) -> GrayImage {
+        let projection = Projection::translate(64.0, 64.0)
+            * Projection::rotate(angle)
+            * Projection::scale(factor, factor)
+            * Projection::translate(-64.0, -64.0);
+        warp(image, &projection, Interpolation::Bilinear, Luma([10]))
+    }
+
+    #[test]
+    fn estimates_scale_and_rotation() {
+        let mut filter = LogPolarFilter::new(0.025, 0.01);
+        filter.train(&pattern(), (64, 64), (48.0, 48.0), 0.0);
+
+        let (factor, angle) = filter.estimate(&pattern(), (64, 64), (48.0, 48.0), 0.0);
+        assert!((factor - 1.0).abs() < 0.01, "factor {}", factor);
+        assert!(angle.abs() < 0.01, "angle {}", angle);
+
+        let (factor, angle) = filter.estimate(
+            &transformed(&pattern(), 1.1, 0.2),
+            (64, 64),
+            (48.0, 48.0),
+            0.0,
+        );
+        assert!((factor - 1.1).abs() < 0.03, "factor {}", factor);
+        assert!((angle - 0.2).abs() < 0.05, "angle {}", angle);
+
+        // once the estimate is applied, there is nothing left to estimate
+        let (factor, angle) = filter.estimate(
+            &transformed(&pattern(), 1.1, 0.2),
+            (64, 64),
+            (48.0 * 1.1, 48.0 * 1.1),
+            0.2,
+        );
+        assert!((factor - 1.0).abs() < 0.03, "factor {}", factor);
+        assert!(angle.abs() < 0.05, "angle {}", angle);
+    }
+
+    #[test]
+    fn patches_only_need_the_window() {
+        let frame = pattern();
+        // in the middle, at the edge, and larger than the frame
+        for (center, size) in [
+            ((64, 64), (48.0, 40.0)),
+            ((10, 120), (30.0, 30.0)),
+            ((64, 64), (300.0, 300.0)),
+        ] {
+            let max_radius = (f32::min(size.0, size.1) / 2.0).max(1.0);
+            let whole = utils::log_polar(
+                &frame,
+                (center.0 as f32, center.1 as f32),
+                (max_radius * MIN_RADIUS_FRACTION, max_radius),
+                0.3,
+                (LOG_POLAR_SIZE, LOG_POLAR_SIZE),
+            );
+            assert_eq!(patch(&frame, center, size, 0.3), whole);
+        }
+    }
+}
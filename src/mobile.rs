@@ -0,0 +1,194 @@
+// The tracker for Android and iOS apps (with the uniffi feature): UniFFI generates Kotlin and Swift bindings of
+// Tracker from the built library (see src/bin/uniffi-bindgen.rs).
+//
+//   val tracker = Tracker(width.toUInt(), height.toUInt(), 64u)
+//   tracker.train(lumaPlane, rowStride.toUInt(), 1u, x.toUInt(), y.toUInt())
+//   val prediction = tracker.track(nextLumaPlane, rowStride.toUInt(), 1u)
+//
+// Frames are raw 8-bit pixels with rows `stride` bytes apart, as in the C API (see src/ffi.rs): the luma plane of
+// a YUV camera frame (NV21 on Android, the first plane of a CVPixelBuffer on iOS) with 1 byte per pixel, or RGBA
+// with 4.
+
+use crate::frame::RawFrame;
+use crate::{Error, MosseTracker, Prediction, TrackingStatus};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+// A tracker of one target. The bindings share it between threads, so it takes one call at a time.
+#[derive(uniffi::Object)]
+pub struct Tracker {
+    tracker: Mutex<MosseTracker>,
+}
+
+#[uniffi::export]
+impl Tracker {
+    #[uniffi::constructor]
+    pub fn new(width: u32, height: u32, window_size: u32) -> Result<Tracker, TrackerError> {
+        let tracker = MosseTracker::builder()
+            .frame_size(width, height)
+            .window_size(window_size)
+            .build()
+            .map_err(Error::from)?;
+        Ok(Tracker {
+            tracker: Mutex::new(tracker),
+        })
+    }
+
+    // train on the target centered on (x, y)
+    pub fn train(
+        &self,
+        frame: &[u8],
+        stride: u32,
+        bytes_per_pixel: u32,
+        x: u32,
+        y: u32,
+    ) -> Result<(), TrackerError> {
+        let mut tracker = self.lock();
+        let frame = raw_frame(&tracker, frame, stride, bytes_per_pixel)?;
+        Ok(tracker.train(&frame, (x, y))?)
+    }
+
+    // locate the target in the next frame, and learn from it
+    pub fn track(
+        &self,
+        frame: &[u8],
+        stride: u32,
+        bytes_per_pixel: u32,
+    ) -> Result<TrackedTarget, TrackerError> {
+        let mut tracker = self.lock();
+        let frame = raw_frame(&tracker, frame, stride, bytes_per_pixel)?;
+        Ok(TrackedTarget::from(&tracker.track(&frame)?))
+    }
+
+    // the tracker as bytes (see MosseTracker::write_to), e.g. to keep it across the restart of an activity
+    pub fn save(&self) -> Result<Vec<u8>, TrackerError> {
+        let mut bytes = Vec::new();
+        self.lock().write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // a tracker saved with save()
+    #[uniffi::constructor]
+    pub fn load(bytes: &[u8]) -> Result<Tracker, TrackerError> {
+        let tracker = MosseTracker::read_from(&mut &bytes[..])?;
+        Ok(Tracker {
+            tracker: Mutex::new(tracker),
+        })
+    }
+}
+
+impl Tracker {
+    fn lock(&self) -> MutexGuard<'_, MosseTracker> {
+        // a call that panicked left the tracker as it was before the frame, or halfway into learning it
+        self.tracker
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// The errors of Tracker, with the message of the Error: a TrackerException in Kotlin, and a TrackerError in Swift
+// (an Error of its own would shadow the exceptions and errors of the languages).
+#[derive(Debug, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum TrackerError {
+    Failed(Error),
+}
+
+impl From<Error> for TrackerError {
+    fn from(error: Error) -> Self {
+        TrackerError::Failed(error)
+    }
+}
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackerError::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+// the frame in `data`, of the size the tracker was made for
+fn raw_frame<'a>(
+    tracker: &MosseTracker,
+    data: &'a [u8],
+    stride: u32,
+    bytes_per_pixel: u32,
+) -> Result<RawFrame<'a>, Error> {
+    let settings = tracker.settings();
+    RawFrame::new(
+        data,
+        settings.width,
+        settings.height,
+        stride as usize,
+        bytes_per_pixel as usize,
+    )
+}
+
+// a Prediction for Kotlin and Swift: the center, size and confidence of the target
+#[derive(uniffi::Record, Debug, Clone, PartialEq)]
+pub struct TrackedTarget {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // clockwise, in radians
+    pub angle: f32,
+    pub psr: f32,
+    pub confidence: f32,
+    pub status: TrackingStatus,
+    pub frame_index: u32,
+}
+
+impl From<&Prediction> for TrackedTarget {
+    fn from(prediction: &Prediction) -> Self {
+        TrackedTarget {
+            x: prediction.position.0,
+            y: prediction.position.1,
+            width: prediction.size.0,
+            height: prediction.size.1,
+            angle: prediction.angle,
+            psr: prediction.psr,
+            confidence: prediction.confidence,
+            status: prediction.status,
+            frame_index: prediction.frame_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::frame;
+
+    // the luma plane of a frame, with rows padded to 160 bytes as in camera buffers
+    fn plane(center: (u32, u32)) -> Vec<u8> {
+        let mut plane = vec![0; 160 * 96];
+        for (x, y, pixel) in frame(center).enumerate_pixels() {
+            plane[y as usize * 160 + x as usize] = pixel[0];
+        }
+        plane
+    }
+
+    #[test]
+    fn tracks_camera_planes() {
+        let tracker = Tracker::new(128, 96, 32).unwrap();
+        tracker.train(&plane((60, 50)), 160, 1, 60, 50).unwrap();
+        let prediction = tracker.track(&plane((62, 51)), 160, 1).unwrap();
+        assert_eq!(prediction.status, TrackingStatus::Tracking);
+        assert!((prediction.x - 62.0).abs() <= 1.0, "{:?}", prediction);
+
+        let loaded = Tracker::load(&tracker.save().unwrap()).unwrap();
+        assert_eq!(
+            loaded.track(&plane((64, 52)), 160, 1).unwrap().frame_index,
+            2
+        );
+
+        let error = tracker.track(&plane((64, 52)), 100, 1).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            Error::FrameBuffer("the stride is shorter than a row").to_string()
+        );
+        assert!(Tracker::new(128, 96, 0).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+// Tracking results in the text format of the MOTChallenge (MOT16/17), which its evaluation tools (e.g. TrackEval
+// or py-motmetrics) score: one line per target per frame,
+//
+//   <frame>,<id>,<bb_left>,<bb_top>,<bb_width>,<bb_height>,<conf>,-1,-1,-1
+//
+// with the frames counted from 1, and the box in pixels. The confidence is Prediction::confidence. Targets the
+// tracker lost are left out, since their location is a guess.
+
+use crate::{Error, Prediction, TrackingStatus};
+use std::io::Write;
+
+#[derive(Debug)]
+pub struct MotWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> MotWriter<W> {
+    pub fn new(writer: W) -> Self {
+        MotWriter { writer }
+    }
+
+    // the predictions of frame `frame` (counted from 1), as MultiMosseTracker::track() returns them
+    pub fn write_frame(
+        &mut self,
+        frame: u32,
+        predictions: &[(u32, Prediction)],
+    ) -> Result<(), Error> {
+        for (id, prediction) in predictions {
+            if prediction.status == TrackingStatus::Lost {
+                continue;
+            }
+            let [left, top, width, height] = prediction.bbox().xywh();
+            writeln!(
+                self.writer,
+                "{},{},{:.2},{:.2},{:.2},{:.2},{:.3},-1,-1,-1",
+                frame, id, left, top, width, height, prediction.confidence
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::prediction;
+
+    #[test]
+    fn writes_mot_results() {
+        let mut writer = MotWriter::new(Vec::new());
+        writer
+            .write_frame(
+                1,
+                &[
+                    (3, prediction((40.0, 50.0), TrackingStatus::Tracking)),
+                    (7, prediction((100.5, 20.0), TrackingStatus::Occluded)),
+                ],
+            )
+            .unwrap();
+        writer
+            .write_frame(2, &[(3, prediction((42.0, 50.0), TrackingStatus::Lost))])
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "1,3,24.00,38.00,32.00,24.00,0.750,-1,-1,-1\n\
+             1,7,84.50,8.00,32.00,24.00,0.750,-1,-1,-1\n"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+// The tracker for Node.js and Electron (with the node feature), as a native addon: Tracker tracks one target on
+// frames in Buffers, where they are. The shared library is the addon, which node/mosse.js loads.
+//
+//   const tracker = new Tracker(width, height, 64);
+//   tracker.train(firstFrame, x, y);
+//   const prediction = tracker.track(nextFrame);  // { x, y, width, height, psr, confidence, status, frameIndex }
+//
+// Frames are 8-bit gray, RGB or RGBA pixels in rows without padding, as the length of the Buffer tells. Color frames
+// are tracked in grayscale.
+
+use crate::frame::RawFrame;
+use crate::{Error, MosseTracker, Prediction, TrackingStatus};
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+fn to_js(error: impl ToString) -> napi::Error {
+    napi::Error::from_reason(error.to_string())
+}
+
+#[napi]
+pub struct Tracker {
+    tracker: MosseTracker,
+}
+
+#[napi]
+impl Tracker {
+    #[napi(constructor)]
+    pub fn new(width: u32, height: u32, window_size: u32) -> napi::Result<Tracker> {
+        let tracker = MosseTracker::builder()
+            .frame_size(width, height)
+            .window_size(window_size)
+            .build()
+            .map_err(to_js)?;
+        Ok(Tracker { tracker })
+    }
+
+    // train on the target centered on (x, y)
+    #[napi]
+    pub fn train(&mut self, frame: Buffer, x: u32, y: u32) -> napi::Result<()> {
+        let frame = packed_frame(&self.tracker, &frame).map_err(to_js)?;
+        self.tracker.train(&frame, (x, y)).map_err(to_js)
+    }
+
+    // locate the target in the next frame, and learn from it
+    #[napi]
+    pub fn track(&mut self, frame: Buffer) -> napi::Result<TrackedTarget> {
+        let frame = packed_frame(&self.tracker, &frame).map_err(to_js)?;
+        let prediction = self.tracker.track(&frame).map_err(to_js)?;
+        Ok(TrackedTarget::from(&prediction))
+    }
+
+    // the tracker as bytes (see MosseTracker::write_to), e.g. to hand it to a worker thread
+    #[napi]
+    pub fn save(&self) -> napi::Result<Buffer> {
+        let mut bytes = Vec::new();
+        self.tracker.write_to(&mut bytes).map_err(to_js)?;
+        Ok(bytes.into())
+    }
+
+    // a tracker saved with save()
+    #[napi(factory)]
+    pub fn load(bytes: Buffer) -> napi::Result<Tracker> {
+        let tracker = MosseTracker::read_from(&mut &bytes[..]).map_err(to_js)?;
+        Ok(Tracker { tracker })
+    }
+}
+
+// the frame in `data`, of the size the tracker was made for: gray, RGB or RGBA by its length
+fn packed_frame<'a>(tracker: &MosseTracker, data: &'a [u8]) -> Result<RawFrame<'a>, Error> {
+    let settings = tracker.settings();
+    let (width, height) = (settings.width, settings.height);
+    let pixels = width as usize * height as usize;
+    let bytes_per_pixel = match data.len() {
+        length if pixels > 0 && length % pixels == 0 && (1..=4).contains(&(length / pixels)) => {
+            length / pixels
+        }
+        _ => {
+            return Err(Error::FrameBuffer(
+                "a frame is width x height gray, RGB or RGBA pixels",
+            ))
+        }
+    };
+    RawFrame::new(
+        data,
+        width,
+        height,
+        width as usize * bytes_per_pixel,
+        bytes_per_pixel,
+    )
+}
+
+// a Prediction for JavaScript: the center, size and confidence of the target, and its status ("tracking",
+// "occluded" or "lost")
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedTarget {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub angle: f64,
+    pub psr: f64,
+    pub confidence: f64,
+    pub status: String,
+    pub frame_index: u32,
+}
+
+impl From<&Prediction> for TrackedTarget {
+    fn from(prediction: &Prediction) -> Self {
+        TrackedTarget {
+            x: prediction.position.0 as f64,
+            y: prediction.position.1 as f64,
+            width: prediction.size.0 as f64,
+            height: prediction.size.1 as f64,
+            angle: prediction.angle as f64,
+            psr: prediction.psr as f64,
+            confidence: prediction.confidence as f64,
+            status: match prediction.status {
+                TrackingStatus::Tracking => "tracking",
+                TrackingStatus::Occluded => "occluded",
+                TrackingStatus::Lost => "lost",
+            }
+            .to_string(),
+            frame_index: prediction.frame_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+
+    #[test]
+    fn frames_are_gray_rgb_or_rgba() {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        let gray = frame((60, 50)).into_raw();
+        tracker
+            .train(&packed_frame(&tracker, &gray).unwrap(), (60, 50))
+            .unwrap();
+        for channels in [1, 3, 4] {
+            let next: Vec<u8> = frame((62, 51))
+                .into_raw()
+                .into_iter()
+                .flat_map(|value| std::iter::repeat_n(value, channels))
+                .collect();
+            let frame = packed_frame(&tracker, &next).unwrap();
+            let prediction = tracker.track_new_frame(&frame);
+            assert_eq!(prediction.location, (62, 51));
+            assert_eq!(TrackedTarget::from(&prediction).status, "tracking");
+        }
+
+        for length in [0, 128 * 96 - 1, 128 * 96 * 5] {
+            assert!(matches!(
+                packed_frame(&tracker, &vec![0; length]),
+                Err(Error::FrameBuffer(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// The one-pass evaluation (OPE) of the OTB benchmark (Wu et al., 2013): the tracker is initialized on the first
+// frame of a sequence, and runs through the rest of it without help. Its boxes are scored against the ground truth
+// with two curves:
+// - precision: the share of frames in which the center of the box is within a threshold distance (0 to 50 pixels)
+//   of the ground truth. OTB ranks trackers by the precision at 20 pixels.
+// - success: the share of frames in which the overlap (IoU) with the ground truth exceeds a threshold (0 to 1).
+//   OTB ranks trackers by the area under this curve (AUC).
+//
+// The curves are plain points, to be plotted with whatever the caller likes.
+
+use crate::vot::{reported_box, tracker_for, Sequence};
+use crate::{BBox, Error, MosseTrackerSettings};
+
+// the distance threshold OTB reports the precision at
+pub const PRECISION_THRESHOLD: f32 = 20.0;
+
+// a curve: the share of frames (in [0, 1]) at each threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    pub thresholds: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+impl Curve {
+    // the area under the curve, as OTB has it: the average of the values
+    pub fn auc(&self) -> f32 {
+        match self.values.len() {
+            0 => 0.0,
+            length => self.values.iter().sum::<f32>() / length as f32,
+        }
+    }
+
+    // the value at `threshold` (None if the curve has no point there)
+    pub fn at(&self, threshold: f32) -> Option<f32> {
+        self.thresholds
+            .iter()
+            .position(|&t| (t - threshold).abs() < 1e-6)
+            .map(|i| self.values[i])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtbReport {
+    pub precision: Curve,
+    pub success: Curve,
+    // the precision at PRECISION_THRESHOLD
+    pub precision_score: f32,
+    // the area under the success curve
+    pub success_auc: f32,
+}
+
+// Score the boxes of a tracker against the ground truth, frame by frame. Frames without ground truth (None) are
+// left out.
+pub fn evaluate(predicted: &[BBox], groundtruth: &[Option<BBox>]) -> OtbReport {
+    let (errors, overlaps): (Vec<f32>, Vec<f32>) = predicted
+        .iter()
+        .zip(groundtruth)
+        .filter_map(|(predicted, truth)| truth.map(|truth| (predicted, truth)))
+        .map(|(predicted, truth)| {
+            let (px, py) = predicted.center();
+            let (tx, ty) = truth.center();
+            ((px - tx).hypot(py - ty), predicted.iou(&truth))
+        })
+        .unzip();
+
+    let share = |count: usize| match errors.len() {
+        0 => 0.0,
+        frames => count as f32 / frames as f32,
+    };
+    let thresholds: Vec<f32> = (0..=50).map(|t| t as f32).collect();
+    let precision = Curve {
+        values: thresholds
+            .iter()
+            .map(|&t| share(errors.iter().filter(|&&e| e <= t).count()))
+            .collect(),
+        thresholds,
+    };
+    let thresholds: Vec<f32> = (0..=20).map(|t| t as f32 / 20.0).collect();
+    let success = Curve {
+        values: thresholds
+            .iter()
+            .map(|&t| share(overlaps.iter().filter(|&&o| o > t).count()))
+            .collect(),
+        thresholds,
+    };
+
+    OtbReport {
+        precision_score: precision.at(PRECISION_THRESHOLD).unwrap_or(0.0),
+        success_auc: success.auc(),
+        precision,
+        success,
+    }
+}
+
+// Run a tracker made with `settings` through a sequence from its first frame (see vot::run_reinitialization for how
+// the tracker and its boxes are made), and return its box in every frame: the first one is the ground truth.
+pub fn run_one_pass(
+    sequence: &Sequence,
+    settings: &MosseTrackerSettings,
+) -> Result<Vec<BBox>, Error> {
+    let init_box = sequence
+        .groundtruth
+        .first()
+        .copied()
+        .flatten()
+        .ok_or(Error::Format("the first frame has no ground truth"))?;
+    let mut tracker = tracker_for(&sequence.frame(0)?, init_box, settings)?;
+    let mut boxes = vec![init_box];
+    for index in 1..sequence.len() {
+        let prediction = tracker.track(&sequence.frame(index)?)?;
+        boxes.push(reported_box(&prediction, init_box, tracker.window_size()));
+    }
+    Ok(boxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_precision_and_success() {
+        let truth = BBox::from_xywh(10.0, 10.0, 20.0, 20.0);
+        let predicted = [
+            truth,
+            // 10 pixels off, and an IoU of 1/3
+            BBox::from_xywh(20.0, 10.0, 20.0, 20.0),
+            // far off
+            BBox::from_xywh(100.0, 10.0, 20.0, 20.0),
+            // no ground truth: left out
+            BBox::from_xywh(0.0, 0.0, 1.0, 1.0),
+        ];
+        let report = evaluate(&predicted, &[Some(truth), Some(truth), Some(truth), None]);
+
+        assert_eq!(report.precision.at(0.0), Some(1.0 / 3.0));
+        assert_eq!(report.precision.at(10.0), Some(2.0 / 3.0));
+        assert_eq!(report.precision_score, 2.0 / 3.0);
+        assert_eq!(report.precision.at(50.0), Some(2.0 / 3.0));
+
+        assert_eq!(report.success.at(0.0), Some(2.0 / 3.0));
+        assert_eq!(report.success.at(0.3), Some(2.0 / 3.0));
+        assert_eq!(report.success.at(0.35), Some(1.0 / 3.0));
+        assert_eq!(report.success.at(1.0), Some(0.0));
+        // 7 thresholds (0 to 0.3) at 2/3, 13 (0.35 to 0.95) at 1/3, and 1.0 at 0
+        let auc = (7.0 * 2.0 / 3.0 + 13.0 / 3.0) / 21.0;
+        assert!((report.success_auc - auc).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,553 @@
+// Saving and loading trained trackers, in a small binary file: the settings of the tracker and what it has learned
+// (see state::TrackerState). A loaded tracker continues where the saved one left off, without training again.
+//
+// The file starts with the magic bytes "MOSSE" and a format version, followed by all values in little-endian order.
+// Settings that hold your own code can't be saved: trackers with a custom preprocessor are refused, and a custom
+// augmentation pipeline is left out (the loaded tracker only needs it to train again, and then uses the default one).
+
+use crate::kalman::KalmanFilter;
+use crate::state::{FilterState, ShapeFilterState, TrackerState};
+use crate::utils::{Equalization, Preprocessing, WindowFunction};
+use crate::{
+    Error, Features, FilterInitialization, MosseTracker, MosseTrackerSettings, ResponseSigma,
+};
+use rustfft::num_complex::Complex;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 5] = b"MOSSE";
+const VERSION: u16 = 2;
+
+impl MosseTracker {
+    // save the tracker to a file (see persist)
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.write_to(&mut fs::File::create(path)?)
+    }
+
+    // load a tracker from a file written by save()
+    pub fn load(path: impl AsRef<Path>) -> Result<MosseTracker, Error> {
+        MosseTracker::read_from(&mut fs::File::open(path)?)
+    }
+
+    // see save()
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), Error> {
+        let settings = self.settings();
+        if settings.preprocessor.is_some() {
+            return Err(Error::Format(
+                "trackers with a custom preprocessor can't be saved",
+            ));
+        }
+
+        let mut encoder = Encoder::default();
+        encoder.bytes.extend_from_slice(MAGIC);
+        encoder.u16(VERSION);
+        encoder.settings(settings);
+        encoder.state(&self.state());
+        writer.write_all(&encoder.bytes)?;
+        Ok(())
+    }
+
+    // see load()
+    pub fn read_from(reader: &mut impl Read) -> Result<MosseTracker, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let mut decoder = Decoder { bytes: &bytes };
+        if decoder.take(MAGIC.len())? != MAGIC {
+            return Err(Error::Format("not a tracker file"));
+        }
+        if decoder.u16()? != VERSION {
+            return Err(Error::Format(
+                "unsupported version of the tracker file format",
+            ));
+        }
+        // the settings decide how large the tracker is: they are checked before anything is made of them, and the state
+        // is checked against them in from_state()
+        let settings = decoder.settings()?;
+        settings.validate()?;
+        let state = decoder.state()?;
+        if !decoder.bytes.is_empty() {
+            return Err(Error::Format("trailing bytes after the tracker"));
+        }
+        MosseTracker::from_state(&settings, state)
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    fn option<T>(&mut self, value: &Option<T>, mut encode: impl FnMut(&mut Self, &T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            encode(self, value);
+        }
+    }
+
+    fn pair(&mut self, value: (u32, u32)) {
+        self.u32(value.0);
+        self.u32(value.1);
+    }
+
+    fn complexes(&mut self, values: &[Complex<f32>]) {
+        self.u64(values.len() as u64);
+        for value in values {
+            self.f32(value.re);
+            self.f32(value.im);
+        }
+    }
+
+    fn settings(&mut self, settings: &MosseTrackerSettings) {
+        self.pair((settings.width, settings.height));
+        self.u32(settings.window_size);
+        self.f32(settings.learning_rate);
+        self.f32(settings.psr_threshold);
+        self.f32(settings.regularization);
+        self.bool(settings.adaptive_learning_rate);
+        self.option(&settings.occlusion_apce_ratio, |e, v| e.f32(*v));
+        self.bool(settings.redetection);
+        self.bool(settings.scale_adaptation);
+        self.f32(settings.scale_regularization);
+        self.bool(settings.aspect_ratio_adaptation);
+        self.bool(settings.rotation_estimation);
+        self.bool(settings.log_polar_estimation);
+        self.bool(settings.motion_model);
+        self.f32(settings.padding);
+        self.bool(settings.spatial_reliability);
+        self.bool(settings.background_aware);
+        self.bool(settings.boundary_masking);
+        self.window_function(settings.window_function);
+        self.preprocessing(settings.preprocessing);
+        self.features(settings.features);
+        self.option(&settings.long_term_learning_rate, |e, v| e.f32(*v));
+        self.option(&settings.anchor_interval, |e, v| e.u32(*v));
+        self.u64(settings.ensemble_size as u64);
+        self.bool(settings.initialization == FilterInitialization::Asef);
+        match settings.response_sigma {
+            ResponseSigma::Absolute(sigma) => {
+                self.bool(false);
+                self.f32(sigma);
+            }
+            ResponseSigma::Relative(fraction) => {
+                self.bool(true);
+                self.f32(fraction);
+            }
+        }
+        self.bool(settings.affine_augmentation);
+        self.bool(settings.photometric_augmentation);
+        self.option(&settings.translation_augmentation, |e, v| e.u32(*v));
+        self.bool(settings.occlusion_augmentation);
+        self.f32(settings.downscale);
+        self.u32(settings.update_interval);
+    }
+
+    fn window_function(&mut self, window_function: WindowFunction) {
+        let (tag, alpha) = match window_function {
+            WindowFunction::Cosine => (0, 0.0),
+            WindowFunction::Hann => (1, 0.0),
+            WindowFunction::Tukey(alpha) => (2, alpha),
+            WindowFunction::Blackman => (3, 0.0),
+            WindowFunction::None => (4, 0.0),
+        };
+        self.bytes.push(tag);
+        self.f32(alpha);
+    }
+
+    fn preprocessing(&mut self, preprocessing: Preprocessing) {
+        let (tag, tiles, clip_limit) = match preprocessing.equalization {
+            Equalization::None => (0, 0, 0.0),
+            Equalization::Global => (1, 0, 0.0),
+            Equalization::Adaptive { tiles, clip_limit } => (2, tiles, clip_limit),
+        };
+        self.bytes.push(tag);
+        self.u32(tiles);
+        self.f32(clip_limit);
+        self.f32(preprocessing.gamma);
+        self.bool(preprocessing.log_transform);
+        self.bool(preprocessing.mean_subtraction);
+        self.bool(preprocessing.unit_norm);
+    }
+
+    fn features(&mut self, features: Features) {
+        let (tag, cell_size) = match features {
+            Features::Intensity => (0, 0),
+            Features::GradientMagnitude => (1, 0),
+            Features::Gradients => (2, 0),
+            Features::Hog { cell_size } => (3, cell_size),
+            Features::ColorNames => (4, 0),
+        };
+        self.bytes.push(tag);
+        self.u32(cell_size);
+    }
+
+    fn filter(&mut self, filter: &FilterState) {
+        self.complexes(&filter.top);
+        self.complexes(&filter.bottom);
+        self.complexes(&filter.filter);
+    }
+
+    fn shape_filter(&mut self, filter: &ShapeFilterState) {
+        self.u64(filter.top.len() as u64);
+        for top in &filter.top {
+            self.complexes(top);
+        }
+        self.complexes(&filter.bottom);
+    }
+
+    fn state(&mut self, state: &TrackerState) {
+        self.pair(state.frame_size);
+        self.u32(state.window_size);
+        self.filter(&state.filter);
+        self.option(&state.long_term, Self::filter);
+        self.option(&state.anchor, Self::filter);
+        self.u32(state.frames_since_anchor);
+        self.u64(state.ensemble.len() as u64);
+        for member in &state.ensemble {
+            self.filter(member);
+        }
+        self.pair(state.center);
+        self.f32(state.scale);
+        self.f32(state.stretch);
+        self.f32(state.angle);
+        self.option(&state.scale_filter, Self::shape_filter);
+        self.option(&state.aspect_filter, Self::shape_filter);
+        self.option(&state.log_polar_filter, Self::shape_filter);
+        self.option(&state.motion_model, |e, kalman| {
+            kalman.state.iter().for_each(|v| e.f32(*v));
+            kalman.covariance.iter().flatten().for_each(|v| e.f32(*v));
+            e.f32(kalman.process_noise);
+            e.f32(kalman.measurement_noise);
+        });
+        self.f32(state.last_psr);
+        self.f32(state.last_apce);
+        self.f32(state.mean_apce);
+        self.u32(state.learned_frames);
+        self.bool(state.occluded);
+        self.u32(state.frames_tracked);
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < count {
+            return Err(Error::Format("the tracker file ends early"));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::Format("invalid flag in the tracker file")),
+        }
+    }
+
+    fn option<T>(
+        &mut self,
+        mut decode: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        Ok(match self.bool()? {
+            true => Some(decode(self)?),
+            false => None,
+        })
+    }
+
+    fn pair(&mut self) -> Result<(u32, u32), Error> {
+        Ok((self.u32()?, self.u32()?))
+    }
+
+    // the length of a sequence of items of at least `item_size` bytes, checked against the rest of the file
+    fn length(&mut self, item_size: usize) -> Result<usize, Error> {
+        let length = self.u64()?;
+        if length > (self.bytes.len() / item_size) as u64 {
+            return Err(Error::Format("the tracker file ends early"));
+        }
+        Ok(length as usize)
+    }
+
+    fn complexes(&mut self) -> Result<Vec<Complex<f32>>, Error> {
+        let length = self.length(8)?;
+        (0..length)
+            .map(|_| Ok(Complex::new(self.f32()?, self.f32()?)))
+            .collect()
+    }
+
+    fn settings(&mut self) -> Result<MosseTrackerSettings, Error> {
+        let (width, height) = self.pair()?;
+        Ok(MosseTrackerSettings {
+            width,
+            height,
+            window_size: self.u32()?,
+            learning_rate: self.f32()?,
+            psr_threshold: self.f32()?,
+            regularization: self.f32()?,
+            adaptive_learning_rate: self.bool()?,
+            occlusion_apce_ratio: self.option(Self::f32)?,
+            redetection: self.bool()?,
+            scale_adaptation: self.bool()?,
+            scale_regularization: self.f32()?,
+            aspect_ratio_adaptation: self.bool()?,
+            rotation_estimation: self.bool()?,
+            log_polar_estimation: self.bool()?,
+            motion_model: self.bool()?,
+            padding: self.f32()?,
+            spatial_reliability: self.bool()?,
+            background_aware: self.bool()?,
+            boundary_masking: self.bool()?,
+            window_function: self.window_function()?,
+            preprocessing: self.preprocessing()?,
+            features: self.features()?,
+            preprocessor: None,
+            long_term_learning_rate: self.option(Self::f32)?,
+            anchor_interval: self.option(Self::u32)?,
+            ensemble_size: self.u64()? as usize,
+            initialization: match self.bool()? {
+                true => FilterInitialization::Asef,
+                false => FilterInitialization::Mosse,
+            },
+            response_sigma: match self.bool()? {
+                true => ResponseSigma::Relative(self.f32()?),
+                false => ResponseSigma::Absolute(self.f32()?),
+            },
+            affine_augmentation: self.bool()?,
+            photometric_augmentation: self.bool()?,
+            translation_augmentation: self.option(Self::u32)?,
+            occlusion_augmentation: self.bool()?,
+            augmentation: None,
+            downscale: self.f32()?,
+            update_interval: self.u32()?,
+        })
+    }
+
+    fn window_function(&mut self) -> Result<WindowFunction, Error> {
+        let tag = self.u8()?;
+        let alpha = self.f32()?;
+        Ok(match tag {
+            0 => WindowFunction::Cosine,
+            1 => WindowFunction::Hann,
+            2 => WindowFunction::Tukey(alpha),
+            3 => WindowFunction::Blackman,
+            4 => WindowFunction::None,
+            _ => return Err(Error::Format("unknown window function in the tracker file")),
+        })
+    }
+
+    fn preprocessing(&mut self) -> Result<Preprocessing, Error> {
+        let tag = self.u8()?;
+        let tiles = self.u32()?;
+        let clip_limit = self.f32()?;
+        Ok(Preprocessing {
+            equalization: match tag {
+                0 => Equalization::None,
+                1 => Equalization::Global,
+                2 => Equalization::Adaptive { tiles, clip_limit },
+                _ => return Err(Error::Format("unknown equalization in the tracker file")),
+            },
+            gamma: self.f32()?,
+            log_transform: self.bool()?,
+            mean_subtraction: self.bool()?,
+            unit_norm: self.bool()?,
+        })
+    }
+
+    fn features(&mut self) -> Result<Features, Error> {
+        let tag = self.u8()?;
+        let cell_size = self.u32()?;
+        Ok(match tag {
+            0 => Features::Intensity,
+            1 => Features::GradientMagnitude,
+            2 => Features::Gradients,
+            3 => Features::Hog { cell_size },
+            4 => Features::ColorNames,
+            _ => return Err(Error::Format("unknown features in the tracker file")),
+        })
+    }
+
+    fn filter(&mut self) -> Result<FilterState, Error> {
+        Ok(FilterState {
+            top: self.complexes()?,
+            bottom: self.complexes()?,
+            filter: self.complexes()?,
+        })
+    }
+
+    fn shape_filter(&mut self) -> Result<ShapeFilterState, Error> {
+        let dimensions = self.length(8)?;
+        Ok(ShapeFilterState {
+            top: (0..dimensions)
+                .map(|_| self.complexes())
+                .collect::<Result<_, _>>()?,
+            bottom: self.complexes()?,
+        })
+    }
+
+    fn kalman_filter(&mut self) -> Result<KalmanFilter, Error> {
+        let mut state = [0.0; 4];
+        for value in state.iter_mut() {
+            *value = self.f32()?;
+        }
+        let mut covariance = [[0.0; 4]; 4];
+        for value in covariance.iter_mut().flatten() {
+            *value = self.f32()?;
+        }
+        Ok(KalmanFilter {
+            state,
+            covariance,
+            process_noise: self.f32()?,
+            measurement_noise: self.f32()?,
+        })
+    }
+
+    fn state(&mut self) -> Result<TrackerState, Error> {
+        Ok(TrackerState {
+            frame_size: self.pair()?,
+            window_size: self.u32()?,
+            filter: self.filter()?,
+            long_term: self.option(Self::filter)?,
+            anchor: self.option(Self::filter)?,
+            frames_since_anchor: self.u32()?,
+            ensemble: {
+                let members = self.length(24)?;
+                (0..members)
+                    .map(|_| self.filter())
+                    .collect::<Result<_, _>>()?
+            },
+            center: self.pair()?,
+            scale: self.f32()?,
+            stretch: self.f32()?,
+            angle: self.f32()?,
+            scale_filter: self.option(Self::shape_filter)?,
+            aspect_filter: self.option(Self::shape_filter)?,
+            log_polar_filter: self.option(Self::shape_filter)?,
+            motion_model: self.option(Self::kalman_filter)?,
+            last_psr: self.f32()?,
+            last_apce: self.f32()?,
+            mean_apce: self.f32()?,
+            learned_frames: self.u32()?,
+            occluded: self.bool()?,
+            frames_tracked: self.u32()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+
+    #[test]
+    fn saved_trackers_load_where_they_left_off() {
+        let settings = MosseTrackerSettings {
+            motion_model: true,
+            scale_adaptation: true,
+            window_function: WindowFunction::Tukey(0.5),
+            response_sigma: ResponseSigma::Relative(0.1),
+            ..test_settings(128, 96)
+        };
+        let mut tracker = MosseTracker::new(&settings);
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+
+        let mut bytes = Vec::new();
+        tracker.write_to(&mut bytes).unwrap();
+        let mut loaded = MosseTracker::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.state(), tracker.state());
+        assert_eq!(
+            loaded.settings().window_function,
+            WindowFunction::Tukey(0.5)
+        );
+
+        let next = frame((63, 48));
+        let pred = tracker.track_new_frame(&next);
+        assert_eq!(loaded.track_new_frame(&next).position, pred.position);
+
+        // broken files are errors, not panics
+        let read = |bytes: &[u8]| MosseTracker::read_from(&mut &bytes[..]);
+        assert_eq!(
+            read(&bytes[..bytes.len() / 2]).unwrap_err(),
+            Error::Format("the tracker file ends early")
+        );
+        assert_eq!(
+            read(b"PNG..").unwrap_err(),
+            Error::Format("not a tracker file")
+        );
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = 99;
+        assert!(read(&newer).is_err());
+
+        // a header asking for a huge tracker: the window has to fit the frame, and the state the window
+        let window_size = MAGIC.len() + 2 + 8;
+        let mut huge = bytes.clone();
+        huge[window_size..window_size + 4].copy_from_slice(&60000u32.to_le_bytes());
+        assert!(matches!(read(&huge), Err(Error::Settings(_))));
+        for offset in [MAGIC.len() + 2, MAGIC.len() + 6, window_size] {
+            huge[offset..offset + 4].copy_from_slice(&60000u32.to_le_bytes());
+        }
+        assert!(matches!(read(&huge), Err(Error::FrameSize { .. })));
+        let mut header = Encoder::default();
+        header.settings(tracker.settings());
+        let state = MAGIC.len() + 2 + header.bytes.len();
+        for offset in [state, state + 4, state + 8] {
+            huge[offset..offset + 4].copy_from_slice(&60000u32.to_le_bytes());
+        }
+        assert_eq!(
+            read(&huge).unwrap_err(),
+            Error::IncompatibleState("filter size")
+        );
+    }
+}
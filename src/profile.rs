@@ -0,0 +1,195 @@
+// How long the stages of tracking a frame take, for finding out which stage to optimize for a workload.
+// Profiling is off until MosseTracker::enable_profiling() (or on_timings()): a tracker that doesn't profile doesn't
+// read the clock either.
+//
+// A frame runs from one track_new_frame() to the next, so its timings include the update() of the frame.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    // cutting the windows out of the frame (and resizing and rotating them)
+    Crop,
+    // turning the windows into the features the filters work on (see features::Preprocessor)
+    Preprocess,
+    // the forward and inverse FFTs
+    Fft,
+    // multiplying the window spectra with the filters, and summing the channels
+    Correlation,
+    // learning from the frame: the filter math of update(), without its crop, preprocessing and FFT
+    Update,
+}
+
+// the time spent in every stage while tracking a frame. The stages don't overlap, and what is in none of them
+// (e.g. the scale filter, or finding the peak of the response) isn't counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageTimings {
+    // the frame these are the timings of (see Prediction::frame_index)
+    pub frame_index: u32,
+    pub crop: Duration,
+    pub preprocess: Duration,
+    pub fft: Duration,
+    pub correlation: Duration,
+    pub update: Duration,
+}
+
+impl StageTimings {
+    pub fn get(&self, stage: Stage) -> Duration {
+        match stage {
+            Stage::Crop => self.crop,
+            Stage::Preprocess => self.preprocess,
+            Stage::Fft => self.fft,
+            Stage::Correlation => self.correlation,
+            Stage::Update => self.update,
+        }
+    }
+
+    // the time spent in all stages together
+    pub fn total(&self) -> Duration {
+        self.crop + self.preprocess + self.fft + self.correlation + self.update
+    }
+
+    fn add(&mut self, stage: Stage, duration: Duration) {
+        let time = match stage {
+            Stage::Crop => &mut self.crop,
+            Stage::Preprocess => &mut self.preprocess,
+            Stage::Fft => &mut self.fft,
+            Stage::Correlation => &mut self.correlation,
+            Stage::Update => &mut self.update,
+        };
+        *time += duration;
+    }
+}
+
+// called with the timings of every frame, once it is done
+pub type TimingsCallback = Box<dyn FnMut(&StageTimings) + Send>;
+
+#[derive(Default)]
+pub(crate) struct Profiler {
+    enabled: bool,
+    // the timings of the current frame. The stages are timed from methods that only borrow the tracker.
+    timings: Mutex<StageTimings>,
+    // whether the current frame is yet to be reported to the callbacks
+    unreported: bool,
+    callbacks: Vec<TimingsCallback>,
+}
+
+impl Profiler {
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub(crate) fn push(&mut self, callback: TimingsCallback) {
+        self.callbacks.push(callback);
+    }
+
+    // the start of a stage (None when not profiling)
+    pub(crate) fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    // the end of a stage that started at `start`
+    pub(crate) fn record(&self, stage: Stage, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.lock().add(stage, start.elapsed());
+        }
+    }
+
+    // start timing frame `frame_index`, after reporting the last one if that hasn't happened yet
+    pub(crate) fn next_frame(&mut self, frame_index: u32) {
+        self.report();
+        *self.lock() = StageTimings {
+            frame_index,
+            ..Default::default()
+        };
+        self.unreported = self.enabled;
+    }
+
+    // call the callbacks with the timings of the current frame (once per frame)
+    pub(crate) fn report(&mut self) {
+        if !self.unreported {
+            return;
+        }
+        self.unreported = false;
+        let timings = *self.lock();
+        for callback in &mut self.callbacks {
+            callback(&timings);
+        }
+    }
+
+    pub(crate) fn timings(&self) -> Option<StageTimings> {
+        self.enabled.then(|| *self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StageTimings> {
+        // the timings are plain numbers, which a panic can't leave half written
+        self.timings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profiler")
+            .field("enabled", &self.enabled)
+            .field("timings", &*self.lock())
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+    use crate::MosseTracker;
+    use std::sync::Arc;
+
+    fn tracker() -> MosseTracker {
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+        tracker
+    }
+
+    #[test]
+    fn times_the_stages_of_every_frame() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.timings(), None);
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let recorded = reported.clone();
+        tracker.on_timings(move |timings| recorded.lock().unwrap().push(*timings));
+        tracker.track(&frame((62, 50))).unwrap();
+        // the timings of a frame are reported once update() is done with it
+        let timings = tracker.timings().unwrap();
+        assert_eq!(*reported.lock().unwrap(), vec![timings]);
+        assert_eq!(timings.frame_index, 1);
+        for stage in [
+            Stage::Crop,
+            Stage::Preprocess,
+            Stage::Fft,
+            Stage::Correlation,
+            Stage::Update,
+        ] {
+            assert!(timings.get(stage) > Duration::ZERO, "{:?}", stage);
+        }
+
+        // a frame without an update is reported when the next one starts
+        tracker.track_new_frame(&frame((63, 50)));
+        assert_eq!(reported.lock().unwrap().len(), 1);
+        assert_eq!(tracker.timings().unwrap().update, Duration::ZERO);
+        tracker.track_new_frame(&frame((64, 50)));
+        let frames: Vec<u32> = reported
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.frame_index)
+            .collect();
+        assert_eq!(frames, vec![1, 2]);
+
+        tracker.disable_profiling();
+        assert_eq!(tracker.timings(), None);
+    }
+}
@@ -0,0 +1,181 @@
+// Logs what the trackers see to Rerun (with the rerun feature), to scrub through a run in the Rerun viewer instead of
+// dumping PNGs: the frames, the box of every target on them, its response map and filter, and its PSR, confidence
+// and per-stage timings as time series. Everything is on the "frame" timeline.
+//
+//   let recorder = Recorder::new(rerun::RecordingStreamBuilder::new("mosse").spawn()?);
+//   for (index, frame) in frames.enumerate() {
+//       let predictions = multi_tracker.track(&frame)?;
+//       recorder.frame(index as i64, &frame)?;
+//       for (id, prediction) in &predictions {
+//           recorder.target(*id, multi_tracker.get(*id).unwrap(), prediction)?;
+//       }
+//   }
+//
+// The boxes are children of the frame ("frame/targets/<id>"), so the viewer draws them on it, colored by status as
+// draw::Overlay does. The rest of a target is under "targets/<id>". A MosseTracker is a target of any id.
+
+use crate::draw::OverlayColor;
+use crate::{Error, FrameSource, MosseTracker, Prediction, StageTimings, TrackingStatus};
+use image::Rgb;
+use rerun::{AsComponents, Boxes2D, Color, ColorModel, Image, RecordingStream, Scalars};
+use std::io;
+
+pub struct Recorder {
+    stream: RecordingStream,
+}
+
+impl Recorder {
+    // log to `stream`, e.g. one that spawns a viewer, or saves a .rrd file (see rerun::RecordingStreamBuilder)
+    pub fn new(stream: RecordingStream) -> Self {
+        Recorder { stream }
+    }
+
+    pub fn stream(&self) -> &RecordingStream {
+        &self.stream
+    }
+
+    // the frame `index` (in grayscale, as the trackers see it), which what is logged next belongs to
+    pub fn frame(&self, index: i64, frame: &impl FrameSource) -> Result<(), Error> {
+        self.stream.set_time_sequence("frame", index);
+        let (width, height) = frame.dimensions();
+        self.stream
+            .log(
+                "frame",
+                &Image::from_l8(frame.to_luma().into_raw(), [width, height]),
+            )
+            .map_err(to_error)
+    }
+
+    // the prediction of `tracker` (the target `id`) in the frame logged last. The timings are logged if the tracker
+    // profiles (see MosseTracker::enable_profiling).
+    pub fn target(
+        &self,
+        id: u32,
+        tracker: &MosseTracker,
+        prediction: &Prediction,
+    ) -> Result<(), Error> {
+        let [x, y, width, height] = prediction.bbox().xywh();
+        let Rgb([r, g, b]) = Rgb::color(prediction.status);
+        let status = match prediction.status {
+            TrackingStatus::Tracking => "tracking",
+            TrackingStatus::Occluded => "occluded",
+            TrackingStatus::Lost => "lost",
+        };
+        self.stream
+            .log(
+                format!("frame/targets/{}", id),
+                &Boxes2D::from_mins_and_sizes([(x, y)], [(width, height)])
+                    .with_labels([format!("#{} {} {:.2}", id, status, prediction.confidence)])
+                    .with_colors([Color::from_rgb(r, g, b)]),
+            )
+            .map_err(to_error)?;
+
+        let window_size = tracker.window_size();
+        let response = tracker.response_map();
+        if !response.is_empty() {
+            self.log_target(
+                id,
+                "response",
+                &Image::from_elements(response, [window_size, window_size], ColorModel::L),
+            )?;
+        }
+        self.log_target(
+            id,
+            "filter",
+            &Image::from_elements(
+                &tracker.filter_template(),
+                [window_size, window_size],
+                ColorModel::L,
+            ),
+        )?;
+        // NaN (e.g. in a frame the target was initialized in) is a gap in the series
+        self.log_target(id, "psr", &Scalars::single(prediction.psr as f64))?;
+        self.log_target(
+            id,
+            "confidence",
+            &Scalars::single(prediction.confidence as f64),
+        )?;
+
+        if let Some(timings) = tracker.timings() {
+            self.timings(id, &timings)?;
+        }
+        Ok(())
+    }
+
+    // the time the stages of a frame took (see profile::StageTimings), in milliseconds
+    pub fn timings(&self, id: u32, timings: &StageTimings) -> Result<(), Error> {
+        for (stage, duration) in [
+            ("crop", timings.crop),
+            ("preprocess", timings.preprocess),
+            ("fft", timings.fft),
+            ("correlation", timings.correlation),
+            ("update", timings.update),
+        ] {
+            self.log_target(
+                id,
+                &format!("timings/{}", stage),
+                &Scalars::single(duration.as_secs_f64() * 1000.0),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn log_target(&self, id: u32, path: &str, archetype: &impl AsComponents) -> Result<(), Error> {
+        self.stream
+            .log(format!("targets/{}/{}", id, path), archetype)
+            .map_err(to_error)
+    }
+}
+
+fn to_error(error: rerun::RecordingStreamError) -> Error {
+    Error::Io {
+        kind: io::ErrorKind::Other,
+        message: format!("rerun: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{frame, test_settings};
+    use rerun::log::{Chunk, LogMsg};
+
+    #[test]
+    fn logs_frames_and_targets() {
+        let (stream, storage) = rerun::RecordingStreamBuilder::new("mosse_test")
+            .memory()
+            .unwrap();
+        let recorder = Recorder::new(stream);
+        let mut tracker = MosseTracker::new(&test_settings(128, 96));
+        tracker.enable_profiling();
+        tracker.train(&frame((60, 50)), (60, 50)).unwrap();
+
+        for (index, center) in [(60, 50), (62, 51), (64, 52)].into_iter().enumerate() {
+            let frame = frame(center);
+            let prediction = tracker.track_new_frame(&frame);
+            recorder.frame(index as i64, &frame).unwrap();
+            recorder.target(3, &tracker, &prediction).unwrap();
+        }
+
+        let logged: Vec<String> = storage
+            .take()
+            .iter()
+            .filter_map(|message| match message {
+                LogMsg::ArrowMsg(_, message) => Chunk::from_arrow_msg(message).ok(),
+                _ => None,
+            })
+            .map(|chunk| chunk.entity_path().to_string())
+            .collect();
+        for path in [
+            "/frame",
+            "/frame/targets/3",
+            "/targets/3/response",
+            "/targets/3/filter",
+            "/targets/3/psr",
+            "/targets/3/confidence",
+            "/targets/3/timings/fft",
+        ] {
+            assert!(logged.iter().any(|logged| logged == path), "{}", path);
+        }
+    }
+}
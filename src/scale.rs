@@ -0,0 +1,334 @@
+// A one-dimensional scale correlation filter, after Danelljan et al. (2014),
+// "Accurate Scale Estimation for Robust Visual Tracking" (DSST).
+//
+// The target is sampled at NUM_SCALES sizes around its current size. Every sample is resized to a small
+// fixed patch and preprocessed, so each pixel of that patch becomes a feature dimension with one value per scale.
+// Each feature dimension gets its own filter along the scale axis, and their responses are summed.
+//
+// The same filter estimates changes in aspect ratio, when the samples are stretched along one axis
+// and squeezed along the other instead of resized uniformly.
+
+use crate::state::ShapeFilterState;
+use crate::utils::preprocess;
+use crate::{fft, scaled_window_crop, FrameSource};
+use image::Luma;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use std::cmp::Ordering;
+
+// number of scale samples, centered on the current scale
+const NUM_SCALES: usize = 17;
+// relative size difference between consecutive scale samples
+const SCALE_STEP: f32 = 1.02;
+// edge size of the square patch every scale sample is resized to
+const SCALE_MODEL_SIZE: u32 = 32;
+// Raw pixel features are very sensitive to small misalignments of the target. A strong regularization turns the
+// filter into a smooth correlation that moves the scale estimate by a step or two per frame, instead of jumping around.
+pub(crate) const DEFAULT_SCALE_REGULARIZATION: f32 = 1.0;
+
+// how the scale samples relate to the target size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScaleAxis {
+    // the sample with factor f is f times as wide and f times as high
+    Uniform,
+    // the sample with factor f is f times as wide and 1/f times as high
+    Aspect,
+}
+
+pub(crate) struct ScaleFilter {
+    // the 'top' of the filter per feature dimension (G * Fd*), and the 'bottom' shared by all of them (sum of Fd * Fd*)
+    top: Vec<Vec<Complex<f32>>>,
+    bottom: Vec<Complex<f32>>,
+
+    // FFT of the desired output: a 1-D Gaussian peak at the current scale
+    target: Vec<Complex<f32>>,
+
+    // the scale factors relative to the current scale, smallest first
+    factors: Vec<f32>,
+    axis: ScaleAxis,
+
+    eta: f32,
+    regularization: f32,
+
+    fft: fft::Plan,
+    inv_fft: fft::Plan,
+}
+
+impl std::fmt::Debug for ScaleFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScaleFilter")
+            .field("top", &self.top)
+            .field("bottom", &self.bottom)
+            .field("target", &self.target)
+            .field("factors", &self.factors)
+            .field("axis", &self.axis)
+            .field("eta", &self.eta)
+            .field("regularization", &self.regularization)
+            .finish()
+    }
+}
+
+impl ScaleFilter {
+    pub(crate) fn new(learning_rate: f32, regularization: f32, axis: ScaleAxis) -> ScaleFilter {
+        let (fft, inv_fft) = fft::plans(NUM_SCALES);
+
+        let middle = (NUM_SCALES / 2) as f32;
+        let factors = (0..NUM_SCALES)
+            .map(|s| SCALE_STEP.powf(s as f32 - middle))
+            .collect();
+
+        // DSST uses a Gaussian with a standard deviation of sqrt(S) / 4 scale samples
+        let sigma = (NUM_SCALES as f32).sqrt() / 4.0;
+        let mut target: Vec<Complex<f32>> = (0..NUM_SCALES)
+            .map(|s| {
+                let dist = s as f32 - middle;
+                Complex::new((-0.5 * dist * dist / (sigma * sigma)).exp(), 0.0)
+            })
+            .collect();
+        fft.process(&mut target);
+
+        ScaleFilter {
+            top: Vec::new(),
+            bottom: vec![Complex::zero(); NUM_SCALES],
+            target,
+            factors,
+            axis,
+            eta: learning_rate,
+            regularization,
+            fft,
+            inv_fft,
+        }
+    }
+
+    // the scale-domain spectrum of every feature dimension, sampled around a target of `size` (width, height)
+    fn features<G: FrameSource<Pixel = Luma<u8>>>(
+        &self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+    ) -> Vec<Vec<Complex<f32>>> {
+        let dimensions = (SCALE_MODEL_SIZE * SCALE_MODEL_SIZE) as usize;
+        let mut features = vec![vec![Complex::zero(); NUM_SCALES]; dimensions];
+
+        for (s, factor) in self.factors.iter().enumerate() {
+            let (width_factor, height_factor) = match self.axis {
+                ScaleAxis::Uniform => (*factor, *factor),
+                ScaleAxis::Aspect => (*factor, 1.0 / factor),
+            };
+            let sample = scaled_window_crop(
+                frame,
+                ((size.0 * width_factor).round() as u32).max(1),
+                ((size.1 * height_factor).round() as u32).max(1),
+                center,
+                SCALE_MODEL_SIZE,
+                SCALE_MODEL_SIZE,
+            );
+            // a Hann window along the scale axis suppresses the wrap-around between the smallest and largest sample
+            let hann = (std::f32::consts::PI * (s + 1) as f32 / (NUM_SCALES + 1) as f32)
+                .sin()
+                .powi(2);
+            for (d, value) in preprocess(&sample).into_iter().enumerate() {
+                features[d][s] = Complex::new(hann * value, 0.0);
+            }
+        }
+
+        // FFT along the scale axis of every feature dimension
+        features.iter_mut().for_each(|f| self.fft.process(f));
+
+        features
+    }
+
+    // what the filter has learned (see MosseTracker::state())
+    pub(crate) fn state(&self) -> ShapeFilterState {
+        ShapeFilterState {
+            top: self.top.clone(),
+            bottom: self.bottom.clone(),
+        }
+    }
+
+    // whether a state can be restored into a scale filter
+    pub(crate) fn fits(state: &ShapeFilterState) -> bool {
+        let dimensions = (SCALE_MODEL_SIZE * SCALE_MODEL_SIZE) as usize;
+        state.bottom.len() == NUM_SCALES
+            && (state.top.is_empty() || state.top.len() == dimensions)
+            && state.top.iter().all(|top| top.len() == NUM_SCALES)
+    }
+
+    // restore what the filter has learned (from a state that fits)
+    pub(crate) fn restore(&mut self, state: ShapeFilterState) {
+        self.top = state.top;
+        self.bottom = state.bottom;
+    }
+
+    // (re)learn the filter from scratch around the target
+    pub(crate) fn train<G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+    ) {
+        self.top.clear();
+        self.learn(frame, center, size, 1.0);
+    }
+
+    // blend the target's current appearance into the filter
+    pub(crate) fn update<G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+    ) {
+        let eta = self.eta;
+        self.learn(frame, center, size, eta);
+    }
+
+    fn learn<G: FrameSource<Pixel = Luma<u8>>>(
+        &mut self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+        eta: f32,
+    ) {
+        let features = self.features(frame, center, size);
+
+        let mut bottom = vec![Complex::zero(); NUM_SCALES];
+        let top: Vec<Vec<Complex<f32>>> = features
+            .iter()
+            .map(|f| {
+                bottom
+                    .iter_mut()
+                    .zip(f)
+                    .for_each(|(b, f)| *b += f * f.conj());
+                self.target
+                    .iter()
+                    .zip(f)
+                    .map(|(g, f)| g * f.conj())
+                    .collect()
+            })
+            .collect();
+
+        if self.top.is_empty() {
+            self.top = top;
+            self.bottom = bottom;
+            return;
+        }
+
+        self.top.iter_mut().zip(top).for_each(|(prev, new)| {
+            prev.iter_mut()
+                .zip(new)
+                .for_each(|(p, n)| *p = eta * n + (1.0 - eta) * *p)
+        });
+        self.bottom
+            .iter_mut()
+            .zip(bottom)
+            .for_each(|(p, n)| *p = eta * n + (1.0 - eta) * *p);
+    }
+
+    // the relative change in scale of the target around `center`, compared to `size`.
+    // For ScaleAxis::Aspect, that is the factor its width grew by (and its height shrank by).
+    pub(crate) fn estimate<G: FrameSource<Pixel = Luma<u8>>>(
+        &self,
+        frame: &G,
+        center: (u32, u32),
+        size: (f32, f32),
+    ) -> f32 {
+        if self.top.is_empty() {
+            return 1.0;
+        }
+
+        let features = self.features(frame, center, size);
+
+        // sum the responses of all feature dimensions in the Fourier domain
+        let mut response = vec![Complex::zero(); NUM_SCALES];
+        for (f, top) in features.iter().zip(&self.top) {
+            for (s, r) in response.iter_mut().enumerate() {
+                *r += f[s] * top[s];
+            }
+        }
+        response
+            .iter_mut()
+            .zip(&self.bottom)
+            .for_each(|(r, b)| *r /= b + self.regularization);
+
+        self.inv_fft.process(&mut response);
+
+        let (best, _) = response
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.re.partial_cmp(&b.1.re).unwrap_or(Ordering::Equal))
+            .unwrap(); // NUM_SCALES > 0
+
+        self.factors[best]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GrayImage;
+
+    // a bright disc with a dark core, so that its appearance clearly depends on its size
+    fn disc(radius: f32) -> GrayImage {
+        GrayImage::from_fn(128, 128, |x, y| {
+            let dist = ((x as f32 - 64.0).powi(2) + (y as f32 - 64.0).powi(2)).sqrt();
+            if dist < radius * 0.4 {
+                Luma([60])
+            } else if dist < radius {
+                Luma([220])
+            } else {
+                Luma([10])
+            }
+        })
+    }
+
+    // a bright rectangle with a dark core
+    fn rectangle(half_width: u32, half_height: u32) -> GrayImage {
+        GrayImage::from_fn(128, 128, |x, y| {
+            let (dx, dy) = (
+                (x as i32 - 64).unsigned_abs(),
+                (y as i32 - 64).unsigned_abs(),
+            );
+            if dx < half_width * 2 / 5 && dy < half_height * 2 / 5 {
+                Luma([60])
+            } else if dx < half_width && dy < half_height {
+                Luma([220])
+            } else {
+                Luma([10])
+            }
+        })
+    }
+
+    #[test]
+    fn factors_are_centered() {
+        let filter = ScaleFilter::new(0.025, DEFAULT_SCALE_REGULARIZATION, ScaleAxis::Uniform);
+        assert_eq!(filter.factors.len(), NUM_SCALES);
+        assert_eq!(filter.factors[NUM_SCALES / 2], 1.0);
+        assert!(filter.factors[0] < 1.0 && filter.factors[NUM_SCALES - 1] > 1.0);
+    }
+
+    #[test]
+    fn estimates_growth_and_shrinkage() {
+        let mut filter = ScaleFilter::new(0.025, DEFAULT_SCALE_REGULARIZATION, ScaleAxis::Uniform);
+        filter.train(&disc(20.0), (64, 64), (48.0, 48.0));
+
+        assert_eq!(filter.estimate(&disc(20.0), (64, 64), (48.0, 48.0)), 1.0);
+        assert!(filter.estimate(&disc(22.0), (64, 64), (48.0, 48.0)) > 1.0);
+        assert!(filter.estimate(&disc(18.0), (64, 64), (48.0, 48.0)) < 1.0);
+
+        // a slight misalignment should not throw off the estimate
+        assert!(filter.estimate(&disc(22.0), (62, 63), (48.0, 48.0)) > 1.0);
+    }
+
+    #[test]
+    fn estimates_aspect_changes() {
+        let mut filter = ScaleFilter::new(0.025, DEFAULT_SCALE_REGULARIZATION, ScaleAxis::Aspect);
+        filter.train(&rectangle(20, 20), (64, 64), (48.0, 48.0));
+
+        assert_eq!(
+            filter.estimate(&rectangle(20, 20), (64, 64), (48.0, 48.0)),
+            1.0
+        );
+        assert!(filter.estimate(&rectangle(22, 18), (64, 64), (48.0, 48.0)) > 1.0);
+        assert!(filter.estimate(&rectangle(18, 22), (64, 64), (48.0, 48.0)) < 1.0);
+    }
+}
@@ -0,0 +1,306 @@
+// The elementwise math of the windows and spectra, 8 values at a time (see the wide crate, which uses the SIMD
+// instructions of the target where it has them). Besides the FFTs, these loops are where the time goes.
+// The values left over at the end of a slice are done one at a time.
+// NOTE: on wasm32, wide only uses SIMD with the simd128 target feature, which .cargo/config.toml enables.
+
+use rustfft::num_complex::Complex;
+use wide::f32x8;
+
+const LANES: usize = 8;
+
+fn load(values: &[f32]) -> f32x8 {
+    let mut lanes = [0.0; LANES];
+    lanes.copy_from_slice(values);
+    f32x8::new(lanes)
+}
+
+// the real and imaginary parts of 8 complex values
+fn load_complex(values: &[Complex<f32>]) -> (f32x8, f32x8) {
+    let mut re = [0.0; LANES];
+    let mut im = [0.0; LANES];
+    for (c, value) in values.iter().enumerate() {
+        re[c] = value.re;
+        im[c] = value.im;
+    }
+    (f32x8::new(re), f32x8::new(im))
+}
+
+fn store_complex(output: &mut [Complex<f32>], re: f32x8, im: f32x8) {
+    for (value, (re, im)) in output
+        .iter_mut()
+        .zip(re.to_array().into_iter().zip(im.to_array()))
+    {
+        *value = Complex::new(re, im);
+    }
+}
+
+pub(crate) fn sum(values: &[f32]) -> f32 {
+    let chunks = values.chunks_exact(LANES);
+    let rest: f32 = chunks.remainder().iter().sum();
+    chunks
+        .fold(f32x8::splat(0.0), |sum, c| sum + load(c))
+        .reduce_add()
+        + rest
+}
+
+pub(crate) fn sum_squares(values: &[f32]) -> f32 {
+    let chunks = values.chunks_exact(LANES);
+    let rest: f32 = chunks.remainder().iter().map(|v| v * v).sum();
+    chunks
+        .fold(f32x8::splat(0.0), |sum, c| {
+            let c = load(c);
+            sum + c * c
+        })
+        .reduce_add()
+        + rest
+}
+
+// add `offset` to every value
+pub(crate) fn add(values: &mut [f32], offset: f32) {
+    let mut chunks = values.chunks_exact_mut(LANES);
+    for c in &mut chunks {
+        c.copy_from_slice(&(load(c) + offset).to_array());
+    }
+    chunks
+        .into_remainder()
+        .iter_mut()
+        .for_each(|v| *v += offset);
+}
+
+// replace every value v by ln(v + 1)
+pub(crate) fn ln_1p(values: &mut [f32]) {
+    let mut chunks = values.chunks_exact_mut(LANES);
+    for c in &mut chunks {
+        c.copy_from_slice(&(load(c) + 1.0).ln().to_array());
+    }
+    chunks
+        .into_remainder()
+        .iter_mut()
+        .for_each(|v| *v = (*v + 1.0).ln());
+}
+
+// multiply every value by `factor`
+pub(crate) fn scale(values: &mut [f32], factor: f32) {
+    let mut chunks = values.chunks_exact_mut(LANES);
+    for c in &mut chunks {
+        c.copy_from_slice(&(load(c) * factor).to_array());
+    }
+    chunks
+        .into_remainder()
+        .iter_mut()
+        .for_each(|v| *v *= factor);
+}
+
+// multiply the values by their weights. Values without a weight are left alone.
+pub(crate) fn multiply(values: &mut [f32], weights: &[f32]) {
+    let length = values.len().min(weights.len());
+    let (values, weights) = (&mut values[..length], &weights[..length]);
+    let mut chunks = values.chunks_exact_mut(LANES);
+    let mut weight_chunks = weights.chunks_exact(LANES);
+    for (c, w) in (&mut chunks).zip(&mut weight_chunks) {
+        c.copy_from_slice(&(load(c) * load(w)).to_array());
+    }
+    chunks
+        .into_remainder()
+        .iter_mut()
+        .zip(weight_chunks.remainder())
+        .for_each(|(v, w)| *v *= w);
+}
+
+// a * b, elementwise
+pub(crate) fn multiply_spectra(a: &[Complex<f32>], b: &[Complex<f32>]) -> Vec<Complex<f32>> {
+    let length = a.len().min(b.len());
+    let mut product = vec![Complex::new(0.0, 0.0); length];
+    let mut chunks = product.chunks_exact_mut(LANES);
+    for ((p, a), b) in (&mut chunks)
+        .zip(a.chunks_exact(LANES))
+        .zip(b.chunks_exact(LANES))
+    {
+        let (a_re, a_im) = load_complex(a);
+        let (b_re, b_im) = load_complex(b);
+        store_complex(p, a_re * b_re - a_im * b_im, a_re * b_im + a_im * b_re);
+    }
+    let done = length - length % LANES;
+    chunks
+        .into_remainder()
+        .iter_mut()
+        .zip(a[done..].iter().zip(&b[done..]))
+        .for_each(|(p, (a, b))| *p = a * b);
+    product
+}
+
+// top / (bottom + regularization), elementwise
+pub(crate) fn divide_spectra(
+    top: &[Complex<f32>],
+    bottom: &[Complex<f32>],
+    regularization: f32,
+) -> Vec<Complex<f32>> {
+    let mut quotient = Vec::new();
+    divide_spectra_into(top, bottom, regularization, &mut quotient);
+    quotient
+}
+
+// see divide_spectra(), writing the quotient into `quotient` (resized to fit)
+pub(crate) fn divide_spectra_into(
+    top: &[Complex<f32>],
+    bottom: &[Complex<f32>],
+    regularization: f32,
+    quotient: &mut Vec<Complex<f32>>,
+) {
+    let length = top.len().min(bottom.len());
+    quotient.resize(length, Complex::new(0.0, 0.0));
+    let mut chunks = quotient.chunks_exact_mut(LANES);
+    for ((q, a), b) in (&mut chunks)
+        .zip(top.chunks_exact(LANES))
+        .zip(bottom.chunks_exact(LANES))
+    {
+        let (a_re, a_im) = load_complex(a);
+        let (b_re, b_im) = load_complex(b);
+        let b_re = b_re + regularization;
+        let norm = b_re * b_re + b_im * b_im;
+        store_complex(
+            q,
+            (a_re * b_re + a_im * b_im) / norm,
+            (a_im * b_re - a_re * b_im) / norm,
+        );
+    }
+    let done = length - length % LANES;
+    chunks
+        .into_remainder()
+        .iter_mut()
+        .zip(top[done..].iter().zip(&bottom[done..]))
+        .for_each(|(q, (a, b))| *q = a / (b + regularization));
+}
+
+// blend the window spectrum F into the top (G F*) and bottom (F F*) of a filter at learning rate `eta`.
+// The spectrum has one window per channel, and every channel has the same desired output G (`target`).
+pub(crate) fn learn(
+    top: &mut [Complex<f32>],
+    bottom: &mut [Complex<f32>],
+    target: &[Complex<f32>],
+    spectrum: &[Complex<f32>],
+    eta: f32,
+) {
+    let keep = 1.0 - eta;
+    for ((top, bottom), spectrum) in top
+        .chunks_mut(target.len())
+        .zip(bottom.chunks_mut(target.len()))
+        .zip(spectrum.chunks(target.len()))
+    {
+        let mut chunks = top
+            .chunks_exact_mut(LANES)
+            .zip(bottom.chunks_exact_mut(LANES));
+        for ((t, b), (g, f)) in
+            (&mut chunks).zip(target.chunks_exact(LANES).zip(spectrum.chunks_exact(LANES)))
+        {
+            let (g_re, g_im) = load_complex(g);
+            let (f_re, f_im) = load_complex(f);
+            let (t_re, t_im) = load_complex(t);
+            let (b_re, b_im) = load_complex(b);
+            // G F* = (g_re f_re + g_im f_im) + i (g_im f_re - g_re f_im), and F F* = |F|^2 is real
+            store_complex(
+                t,
+                (g_re * f_re + g_im * f_im) * eta + t_re * keep,
+                (g_im * f_re - g_re * f_im) * eta + t_im * keep,
+            );
+            store_complex(
+                b,
+                (f_re * f_re + f_im * f_im) * eta + b_re * keep,
+                b_im * keep,
+            );
+        }
+        let done = top.len() - top.len() % LANES;
+        for ((t, b), (g, f)) in top[done..]
+            .iter_mut()
+            .zip(&mut bottom[done..])
+            .zip(target[done..].iter().zip(&spectrum[done..]))
+        {
+            *t = eta * (g * f.conj()) + keep * *t;
+            *b = eta * (f * f.conj()) + keep * *b;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complex(length: usize, seed: usize) -> Vec<Complex<f32>> {
+        (0..length)
+            .map(|i| {
+                Complex::new(
+                    ((i * 7 + seed) % 13) as f32 - 6.0,
+                    ((i * 5 + seed) % 11) as f32 - 5.0,
+                )
+            })
+            .collect()
+    }
+
+    fn assert_close(a: &[Complex<f32>], b: &[Complex<f32>]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).norm() <= 1e-4 * (1.0 + b.norm()), "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn real_math_matches_scalar_math() {
+        let values: Vec<f32> = (0..21).map(|i| i as f32 * 0.5 - 3.0).collect();
+        assert_eq!(sum(&values), values.iter().sum::<f32>());
+        assert_eq!(
+            sum_squares(&values),
+            values.iter().map(|v| v * v).sum::<f32>()
+        );
+
+        let mut shifted = values.clone();
+        add(&mut shifted, 2.0);
+        scale(&mut shifted, 3.0);
+        // the last value has no weight
+        multiply(&mut shifted, &[0.5; 20]);
+        let expected: Vec<f32> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v + 2.0) * 3.0 * if i < 20 { 0.5 } else { 1.0 })
+            .collect();
+        assert_eq!(shifted, expected);
+    }
+
+    #[test]
+    fn logarithms_match_scalar_logarithms() {
+        let mut values: Vec<f32> = (0..=255).map(|v| v as f32).collect();
+        ln_1p(&mut values);
+        for (v, ln) in values.iter().enumerate() {
+            let expected = (v as f32 + 1.0).ln();
+            assert!((ln - expected).abs() <= 1e-5 * expected.max(1.0), "{}", v);
+        }
+    }
+
+    #[test]
+    fn spectrum_math_matches_scalar_math() {
+        let (a, b) = (complex(19, 1), complex(19, 4));
+        let product: Vec<_> = a.iter().zip(&b).map(|(a, b)| a * b).collect();
+        assert_close(&multiply_spectra(&a, &b), &product);
+        let quotient: Vec<_> = a.iter().zip(&b).map(|(a, b)| a / (b + 0.1)).collect();
+        assert_close(&divide_spectra(&a, &b, 0.1), &quotient);
+
+        // two channels of 10
+        let target = complex(10, 2);
+        let spectrum = complex(20, 3);
+        let (mut top, mut bottom) = (complex(20, 5), complex(20, 6));
+        let expected_top: Vec<_> = target
+            .iter()
+            .cycle()
+            .zip(&spectrum)
+            .zip(&top)
+            .map(|((g, f), t)| 0.2 * (g * f.conj()) + 0.8 * t)
+            .collect();
+        let expected_bottom: Vec<_> = spectrum
+            .iter()
+            .zip(&bottom)
+            .map(|(f, b)| 0.2 * (f * f.conj()) + 0.8 * b)
+            .collect();
+        learn(&mut top, &mut bottom, &target, &spectrum, 0.2);
+        assert_close(&top, &expected_top);
+        assert_close(&bottom, &expected_bottom);
+    }
+}
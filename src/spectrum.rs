@@ -0,0 +1,367 @@
+// The spectra a tracker keeps from frame to frame: its filter, and the top (Ai) and bottom (Bi) parts it is divided
+// from. By default they are stored in f32, like all other math. Two features change that:
+// - f16 stores them in half precision (IEEE 754 binary16), which halves the memory of a tracker. They are decoded to
+//   f32 for the math, into buffers that are reused from frame to frame.
+// - f64 stores them in double precision, and learns (see learn()) in double precision: over tens of thousands of
+//   updates, the rounding errors of the running averages in f32 add up. The rest of the math stays in f32.
+// f64 wins when both are enabled. The long-term, anchor and ensemble filters (FilterState) are kept in f32.
+// NOTE: in f16, the values are stored relative to the largest of every block of them, since the bottom (|F|^2) easily
+// exceeds the largest f16 (65504), while the high frequencies hold values many times smaller than the low ones.
+
+#[cfg(not(feature = "f64"))]
+use crate::simd;
+#[cfg(all(feature = "f16", not(feature = "f64")))]
+use half::f16;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+#[cfg(any(feature = "f16", feature = "f64"))]
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::ops::Deref;
+
+// the values that share a scale in f16
+#[cfg(all(feature = "f16", not(feature = "f64")))]
+const BLOCK: usize = 64;
+
+#[derive(Clone, PartialEq)]
+pub(crate) struct Spectrum {
+    #[cfg(not(any(feature = "f16", feature = "f64")))]
+    values: Vec<Complex<f32>>,
+
+    // the real and imaginary parts in f16, as fractions of the scale of their block of BLOCK values
+    #[cfg(all(feature = "f16", not(feature = "f64")))]
+    values: Vec<[f16; 2]>,
+    #[cfg(all(feature = "f16", not(feature = "f64")))]
+    scales: Vec<f32>,
+
+    #[cfg(feature = "f64")]
+    values: Vec<Complex<f64>>,
+}
+
+impl Spectrum {
+    pub(crate) fn zeros(length: usize) -> Self {
+        Spectrum::from(vec![Complex::zero(); length])
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    // the values in f32
+    #[cfg(not(any(feature = "f16", feature = "f64")))]
+    pub(crate) fn get(&self) -> Values<'_> {
+        Values::Borrowed(&self.values)
+    }
+
+    #[cfg(any(feature = "f16", feature = "f64"))]
+    pub(crate) fn get(&self) -> Values<'_> {
+        let mut values = lend();
+        self.decode_into(&mut values);
+        Values::Decoded(values)
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<Complex<f32>> {
+        self.get().to_vec()
+    }
+
+    // change the values in f32, and store the result
+    #[cfg(not(any(feature = "f16", feature = "f64")))]
+    pub(crate) fn update<R>(&mut self, change: impl FnOnce(&mut Vec<Complex<f32>>) -> R) -> R {
+        change(&mut self.values)
+    }
+
+    #[cfg(any(feature = "f16", feature = "f64"))]
+    pub(crate) fn update<R>(&mut self, change: impl FnOnce(&mut Vec<Complex<f32>>) -> R) -> R {
+        let mut values = lend();
+        self.decode_into(&mut values);
+        let result = change(&mut values);
+        self.encode(&values);
+        give_back(values);
+        result
+    }
+
+    #[cfg(all(feature = "f16", not(feature = "f64")))]
+    fn decode_into(&self, values: &mut Vec<Complex<f32>>) {
+        values.clear();
+        values.extend(
+            self.values
+                .chunks(BLOCK)
+                .zip(&self.scales)
+                .flat_map(|(block, scale)| {
+                    block
+                        .iter()
+                        .map(move |[re, im]| Complex::new(re.to_f32(), im.to_f32()) * *scale)
+                }),
+        );
+    }
+
+    #[cfg(all(feature = "f16", not(feature = "f64")))]
+    fn encode(&mut self, values: &[Complex<f32>]) {
+        self.values.resize(values.len(), [f16::ZERO; 2]);
+        self.scales.resize(values.len().div_ceil(BLOCK), 1.0);
+        for ((stored, values), scale) in self
+            .values
+            .chunks_mut(BLOCK)
+            .zip(values.chunks(BLOCK))
+            .zip(&mut self.scales)
+        {
+            let largest = values
+                .iter()
+                .map(|v| v.re.abs().max(v.im.abs()))
+                .fold(0.0, f32::max);
+            // non-finite values are stored as they are
+            *scale = if largest > 0.0 && largest.is_finite() {
+                largest
+            } else {
+                1.0
+            };
+            for (stored, value) in stored.iter_mut().zip(values) {
+                *stored = [
+                    f16::from_f32(value.re / *scale),
+                    f16::from_f32(value.im / *scale),
+                ];
+            }
+        }
+    }
+
+    #[cfg(feature = "f64")]
+    fn decode_into(&self, values: &mut Vec<Complex<f32>>) {
+        values.clear();
+        values.extend(
+            self.values
+                .iter()
+                .map(|v| Complex::new(v.re as f32, v.im as f32)),
+        );
+    }
+
+    #[cfg(feature = "f64")]
+    fn encode(&mut self, values: &[Complex<f32>]) {
+        self.values.clear();
+        self.values.extend(
+            values
+                .iter()
+                .map(|v| Complex::new(v.re as f64, v.im as f64)),
+        );
+    }
+
+    // blend the window spectrum into the top and bottom of a filter at learning rate `eta` (see simd::learn())
+    #[cfg(not(feature = "f64"))]
+    pub(crate) fn learn(
+        top: &mut Spectrum,
+        bottom: &mut Spectrum,
+        target: &[Complex<f32>],
+        spectrum: &[Complex<f32>],
+        eta: f32,
+    ) {
+        top.update(|top| bottom.update(|bottom| simd::learn(top, bottom, target, spectrum, eta)));
+    }
+
+    #[cfg(feature = "f64")]
+    pub(crate) fn learn(
+        top: &mut Spectrum,
+        bottom: &mut Spectrum,
+        target: &[Complex<f32>],
+        spectrum: &[Complex<f32>],
+        eta: f32,
+    ) {
+        let (eta, keep) = (eta as f64, 1.0 - eta as f64);
+        let wide = |v: &Complex<f32>| Complex::new(v.re as f64, v.im as f64);
+        for ((top, bottom), spectrum) in top
+            .values
+            .chunks_mut(target.len())
+            .zip(bottom.values.chunks_mut(target.len()))
+            .zip(spectrum.chunks(target.len()))
+        {
+            for ((t, b), (g, f)) in top
+                .iter_mut()
+                .zip(bottom.iter_mut())
+                .zip(target.iter().zip(spectrum))
+            {
+                let (g, f) = (wide(g), wide(f));
+                *t = eta * (g * f.conj()) + keep * *t;
+                *b = eta * (f * f.conj()) + keep * *b;
+            }
+        }
+    }
+}
+
+#[cfg(not(any(feature = "f16", feature = "f64")))]
+impl From<Vec<Complex<f32>>> for Spectrum {
+    fn from(values: Vec<Complex<f32>>) -> Self {
+        Spectrum { values }
+    }
+}
+
+#[cfg(all(feature = "f16", not(feature = "f64")))]
+impl From<Vec<Complex<f32>>> for Spectrum {
+    fn from(values: Vec<Complex<f32>>) -> Self {
+        let mut spectrum = Spectrum {
+            values: Vec::new(),
+            scales: Vec::new(),
+        };
+        spectrum.encode(&values);
+        spectrum
+    }
+}
+
+#[cfg(feature = "f64")]
+impl From<Vec<Complex<f32>>> for Spectrum {
+    fn from(values: Vec<Complex<f32>>) -> Self {
+        let mut spectrum = Spectrum { values: Vec::new() };
+        spectrum.encode(&values);
+        spectrum
+    }
+}
+
+impl Debug for Spectrum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.get().iter()).finish()
+    }
+}
+
+// the values of a spectrum in f32 (see Spectrum::get()): the stored ones, or decoded into a buffer that goes back to
+// the buffers of the thread when dropped
+pub(crate) enum Values<'a> {
+    #[cfg_attr(any(feature = "f16", feature = "f64"), allow(dead_code))]
+    Borrowed(&'a [Complex<f32>]),
+    #[cfg(any(feature = "f16", feature = "f64"))]
+    Decoded(Vec<Complex<f32>>),
+}
+
+impl Deref for Values<'_> {
+    type Target = [Complex<f32>];
+
+    fn deref(&self) -> &[Complex<f32>] {
+        match self {
+            Values::Borrowed(values) => values,
+            #[cfg(any(feature = "f16", feature = "f64"))]
+            Values::Decoded(values) => values,
+        }
+    }
+}
+
+#[cfg(any(feature = "f16", feature = "f64"))]
+impl Drop for Values<'_> {
+    fn drop(&mut self) {
+        if let Values::Decoded(values) = self {
+            give_back(std::mem::take(values));
+        }
+    }
+}
+
+// The buffers spectra are decoded into, so that tracking a frame doesn't allocate a copy of every spectrum it reads.
+// They are shared by all trackers of the thread, so they take the memory of a few spectra per thread, not per
+// tracker.
+#[cfg(any(feature = "f16", feature = "f64"))]
+thread_local! {
+    static BUFFERS: RefCell<Vec<Vec<Complex<f32>>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(any(feature = "f16", feature = "f64"))]
+fn lend() -> Vec<Complex<f32>> {
+    BUFFERS
+        .try_with(|buffers| buffers.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+#[cfg(any(feature = "f16", feature = "f64"))]
+fn give_back(buffer: Vec<Complex<f32>>) {
+    // (not while the thread ends)
+    let _ = BUFFERS.try_with(|buffers| buffers.borrow_mut().push(buffer));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spectra_round_trip() {
+        let values: Vec<Complex<f32>> = (0..50)
+            .map(|i| Complex::new(i as f32 * 1500.0 - 30000.0, 1.0 / (i + 1) as f32))
+            .collect();
+        let spectrum = Spectrum::from(values.clone());
+        assert_eq!(spectrum.len(), values.len());
+        // half precision keeps about 3 significant digits of the largest value
+        for (stored, value) in spectrum.get().iter().zip(&values) {
+            assert!(
+                (stored - value).norm() <= 1e-3 * 45000.0,
+                "{} != {}",
+                stored,
+                value
+            );
+        }
+
+        let mut updated = spectrum.clone();
+        updated.update(|values| values.iter_mut().for_each(|v| *v = Complex::zero()));
+        assert_eq!(updated, Spectrum::zeros(values.len()));
+        assert_ne!(updated, spectrum);
+    }
+
+    #[test]
+    fn learns_running_averages() {
+        // two channels of 4, with the same desired output
+        let target = vec![Complex::new(1.0, 0.5); 4];
+        let spectrum: Vec<Complex<f32>> = (0..8)
+            .map(|i| Complex::new(i as f32 * 0.3 + 1.0, 0.2))
+            .collect();
+        let (mut top, mut bottom) = (Spectrum::zeros(8), Spectrum::zeros(8));
+        for _ in 0..5000 {
+            Spectrum::learn(&mut top, &mut bottom, &target, &spectrum, 0.01);
+        }
+
+        // after that many updates with the same frame, the averages are that frame's G F* and F F*
+        #[cfg(not(feature = "f64"))]
+        let tolerance = 1e-3;
+        #[cfg(feature = "f64")]
+        let tolerance = 1e-6;
+        for ((t, b), f) in top.get().iter().zip(bottom.get().iter()).zip(&spectrum) {
+            let (g_f, f_f) = (target[0] * f.conj(), f * f.conj());
+            assert!(
+                (t - g_f).norm() <= tolerance * g_f.norm(),
+                "{} != {}",
+                t,
+                g_f
+            );
+            assert!(
+                (b - f_f).norm() <= tolerance * f_f.norm(),
+                "{} != {}",
+                b,
+                f_f
+            );
+        }
+    }
+
+    #[cfg(all(feature = "f16", not(feature = "f64")))]
+    #[test]
+    fn keeps_small_values_next_to_large_ones() {
+        // a block of large values, and one of values a million times smaller
+        let values: Vec<Complex<f32>> = (0..2 * BLOCK)
+            .map(|i| match i < BLOCK {
+                true => Complex::new(40000.0 + i as f32, -1000.0),
+                false => Complex::new(0.03 + i as f32 * 1e-4, 0.01),
+            })
+            .collect();
+        let spectrum = Spectrum::from(values.clone());
+        for (stored, value) in spectrum.get().iter().zip(&values) {
+            assert!(
+                (stored - value).norm() <= 1e-3 * value.norm(),
+                "{} != {}",
+                stored,
+                value
+            );
+        }
+    }
+
+    #[cfg(any(feature = "f16", feature = "f64"))]
+    #[test]
+    fn decodes_into_reused_buffers() {
+        let spectrum = Spectrum::zeros(256);
+        let buffer = spectrum.get().as_ptr();
+        assert_eq!(spectrum.get().as_ptr(), buffer);
+        // while one is in use, another is lent
+        let first = spectrum.get();
+        assert_ne!(spectrum.get().as_ptr(), first.as_ptr());
+    }
+}
@@ -0,0 +1,63 @@
+// The learned state of a MosseTracker, to persist a tracker and restore it later (e.g. across process restarts).
+// With the `serde` feature, the state implements Serialize and Deserialize.
+//
+// The state holds what the tracker learned, not its settings (which may hold your own preprocessor or augmenters):
+// restore it into a tracker made with the settings it was taken from (see MosseTracker::from_state()).
+
+use crate::kalman::KalmanFilter;
+use rustfft::num_complex::Complex;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackerState {
+    // the frame size and the (padded) window size the tracker was made for
+    pub frame_size: (u32, u32),
+    pub window_size: u32,
+
+    // the filter, and the long-term, anchor and ensemble filters (if enabled)
+    pub filter: FilterState,
+    pub long_term: Option<FilterState>,
+    pub anchor: Option<FilterState>,
+    pub frames_since_anchor: u32,
+    pub ensemble: Vec<FilterState>,
+
+    // the center, scale, stretch (width/height ratio) and in-plane rotation of the target
+    pub center: (u32, u32),
+    pub scale: f32,
+    pub stretch: f32,
+    pub angle: f32,
+
+    // the filters that estimate the shape of the target, and the motion model (if enabled)
+    pub scale_filter: Option<ShapeFilterState>,
+    pub aspect_filter: Option<ShapeFilterState>,
+    pub log_polar_filter: Option<ShapeFilterState>,
+    pub motion_model: Option<KalmanFilter>,
+
+    // the confidence of the last prediction, and the occlusion detection
+    pub last_psr: f32,
+    pub last_apce: f32,
+    pub mean_apce: f32,
+    pub learned_frames: u32,
+    pub occluded: bool,
+
+    // the number of frames tracked since training
+    pub frames_tracked: u32,
+}
+
+// A correlation filter in the Fourier domain, and the top (Ai) and bottom (Bi) parts it was divided from.
+// Like the filter of MosseTracker, all three hold one window per feature channel, one after the other.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterState {
+    pub top: Vec<Complex<f32>>,
+    pub bottom: Vec<Complex<f32>>,
+    pub filter: Vec<Complex<f32>>,
+}
+
+// the top parts (one per feature dimension) and the bottom part of a scale, aspect ratio or log-polar filter
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShapeFilterState {
+    pub top: Vec<Vec<Complex<f32>>>,
+    pub bottom: Vec<Complex<f32>>,
+}
@@ -0,0 +1,1130 @@
+// Preprocessing of the windows the filters see, and training frame generators.
+//
+// Training frame generators: perturbed copies of the training window, that teach the initial filter to tolerate
+// small changes in the appearance of the target. All of them keep the window size, and fill in black where the
+// perturbation uncovers pixels outside of the window.
+//
+// The generators are wrapped in Augmenters, which an AugmentationPipeline strings together. The tracker trains
+// on the frames of its pipeline (see MosseTrackerSettings::augmentation), and anything that implements Augmenter
+// can be added to it, including closures.
+
+use image::{imageops, GrayImage, ImageBuffer, Luma, Pixel};
+use imageproc::contrast::equalize_histogram;
+use imageproc::geometric_transformations::{
+    rotate_about_center, translate, warp, Interpolation, Projection,
+};
+use imageproc::noise::gaussian_noise;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{index_to_coords, simd};
+
+// The preprocessing steps of Bolme et al.: a log transform of the pixel values against high contrast lighting,
+// normalization to mean 0 and norm 1, and a window function that fades out the edges of the window.
+// Returns the values row by row.
+pub fn preprocess(image: &GrayImage) -> Vec<f32> {
+    let (width, height) = image.dimensions();
+    preprocess_with(
+        image,
+        &Preprocessing::default(),
+        &WindowFunction::Cosine.weights(width, height),
+    )
+}
+
+// see preprocess(), writing the values into `output` (cleared first) instead of a new Vec.
+// Reusing the same `output` for every frame saves an allocation per frame.
+pub fn preprocess_into(image: &GrayImage, output: &mut Vec<f32>) {
+    let (width, height) = image.dimensions();
+    let steps = Preprocessing::default();
+    pixel_values_into(image, &steps, output);
+    normalize(output, &steps, &[]);
+    // the window, one weight at a time instead of a Vec of weights
+    for (i, p) in output.iter_mut().enumerate() {
+        let (x, y) = index_to_coords(width, i as u32);
+        *p *= WindowFunction::Cosine.weight(x, y, width, height);
+    }
+}
+
+// which of the steps of preprocess() to apply (all of them by default), and which extra steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preprocessing {
+    // spread the pixel values of the window over the full range of gray values first (see Equalization).
+    // Not one of the steps of Bolme et al., so it is off by default.
+    pub equalization: Equalization,
+    // replace every pixel value p by 255 * (p / 255)^gamma, to undo the non-linear response of a sensor.
+    // Values below 1.0 brighten the dark parts of the window, values above 1.0 darken them. 1.0 leaves it alone.
+    pub gamma: f32,
+    // replace every pixel value p by ln(p + 1), which evens out high contrast lighting.
+    // Inputs that are already normalized (e.g. preprocessed thermal frames) are better off without it.
+    pub log_transform: bool,
+    // subtract the mean of the window from every pixel
+    pub mean_subtraction: bool,
+    // scale the window to a norm of 1, so that the filter doesn't depend on the brightness of the target
+    pub unit_norm: bool,
+}
+
+impl Default for Preprocessing {
+    fn default() -> Self {
+        Preprocessing {
+            equalization: Equalization::None,
+            gamma: 1.0,
+            log_transform: true,
+            mean_subtraction: true,
+            unit_norm: true,
+        }
+    }
+}
+
+// How the pixel values of a window are equalized before preprocessing.
+// Low-contrast windows (e.g. night footage) use only a few gray values, which makes for a nearly flat filter.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Equalization {
+    #[default]
+    None,
+    // histogram equalization of the whole window
+    Global,
+    // contrast limited adaptive histogram equalization (CLAHE): the window is divided into tiles x tiles tiles,
+    // every tile is equalized separately, and the results are interpolated between the tile centers.
+    // The histograms are clipped at clip_limit times their average bin count (and the excess is spread over all
+    // bins), which keeps flat regions from turning into amplified noise. A clip limit of 2.0 to 4.0 is common for
+    // whole images, but the tiles of a tracking window hold few pixels, which calls for higher limits.
+    Adaptive {
+        tiles: u32,
+        clip_limit: f32,
+    },
+}
+
+impl Equalization {
+    // the equalized image
+    pub fn apply(&self, image: &GrayImage) -> GrayImage {
+        match *self {
+            Equalization::None => image.clone(),
+            Equalization::Global => equalize_histogram(image),
+            Equalization::Adaptive { tiles, clip_limit } => clahe(image, tiles, clip_limit),
+        }
+    }
+}
+
+// see Equalization::Adaptive
+pub fn clahe(image: &GrayImage, tiles: u32, clip_limit: f32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let tiles = tiles.clamp(1, width.min(height).max(1));
+    let (tile_width, tile_height) = (
+        (width as f32 / tiles as f32).max(1.0),
+        (height as f32 / tiles as f32).max(1.0),
+    );
+
+    // the equalization mapping of every tile, row by row
+    let mappings: Vec<[u8; 256]> = (0..tiles * tiles)
+        .map(|t| {
+            let (tx, ty) = ((t % tiles) as f32, (t / tiles) as f32);
+            let (left, top) = ((tx * tile_width) as u32, (ty * tile_height) as u32);
+            let right = (((tx + 1.0) * tile_width) as u32).min(width);
+            let bottom = (((ty + 1.0) * tile_height) as u32).min(height);
+
+            let mut histogram = [0f32; 256];
+            for y in top..bottom {
+                for x in left..right {
+                    histogram[image.get_pixel(x, y)[0] as usize] += 1.0;
+                }
+            }
+            let count: f32 = histogram.iter().sum();
+
+            // clip the histogram, and spread the excess evenly
+            let limit = (clip_limit * count / 256.0).max(1.0);
+            let excess: f32 = histogram.iter().map(|h| (h - limit).max(0.0)).sum();
+            histogram
+                .iter_mut()
+                .for_each(|h| *h = h.min(limit) + excess / 256.0);
+
+            let mut mapping = [0u8; 256];
+            let mut cumulative = 0.0;
+            for (value, h) in mapping.iter_mut().zip(histogram) {
+                cumulative += h;
+                *value = (255.0 * cumulative / count.max(1.0)).round().min(255.0) as u8;
+            }
+            mapping
+        })
+        .collect();
+
+    // interpolate between the mappings of the four tiles whose centers surround the pixel
+    let last = (tiles - 1) as f32;
+    GrayImage::from_fn(width, height, |x, y| {
+        let fx = ((x as f32 + 0.5) / tile_width - 0.5).clamp(0.0, last);
+        let fy = ((y as f32 + 0.5) / tile_height - 0.5).clamp(0.0, last);
+        let (x0, y0) = (fx.floor() as u32, fy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(tiles - 1), (y0 + 1).min(tiles - 1));
+        let (wx, wy) = (fx - x0 as f32, fy - y0 as f32);
+
+        let value = image.get_pixel(x, y)[0] as usize;
+        let map = |tx: u32, ty: u32| mappings[(ty * tiles + tx) as usize][value] as f32;
+        let upper = map(x0, y0) * (1.0 - wx) + map(x1, y0) * wx;
+        let lower = map(x0, y1) * (1.0 - wx) + map(x1, y1) * wx;
+        Luma([(upper * (1.0 - wy) + lower * wy).round() as u8])
+    })
+}
+
+// The window function preprocess() multiplies the pixels with, to fade out the edges of the window:
+// without it, the circular correlation sees a hard edge where the window wraps around.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WindowFunction {
+    // the minimum of a sine window along either axis (the tracker's original window)
+    #[default]
+    Cosine,
+    // the product of a Hann window along either axis
+    Hann,
+    // the product of a Tukey window along either axis: flat, except for a cosine taper over this fraction
+    // of the window (0.0 is no window at all, 1.0 is a Hann window)
+    Tukey(f32),
+    // the product of a Blackman window along either axis: narrower than Hann, with lower sidelobes
+    Blackman,
+    // no window at all
+    None,
+}
+
+impl WindowFunction {
+    // the weights of a width x height window, row by row
+    pub fn weights(&self, width: u32, height: u32) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = index_to_coords(width, i);
+                self.weight(x, y, width, height)
+            })
+            .collect()
+    }
+
+    // the weight of pixel (x, y) of a width x height window
+    pub fn weight(&self, x: u32, y: u32, width: u32, height: u32) -> f32 {
+        let (wx, wy) = (self.axis_weight(x, width), self.axis_weight(y, height));
+        match self {
+            WindowFunction::Cosine => wx.min(wy),
+            _ => wx * wy,
+        }
+    }
+
+    // the weight of pixel p along an axis of `length` pixels
+    fn axis_weight(&self, p: u32, length: u32) -> f32 {
+        let pi = std::f32::consts::PI;
+        let last = (length.max(2) - 1) as f32;
+        let t = p as f32 / last;
+        match *self {
+            WindowFunction::Hann => 0.5 - 0.5 * (2.0 * pi * t).cos(),
+            WindowFunction::Tukey(alpha) => {
+                let alpha = alpha.clamp(0.0, 1.0);
+                let edge = t.min(1.0 - t);
+                if edge >= alpha / 2.0 {
+                    1.0
+                } else {
+                    0.5 - 0.5 * (2.0 * pi * edge / alpha).cos()
+                }
+            }
+            WindowFunction::Blackman => {
+                (0.42 - 0.5 * (2.0 * pi * t).cos() + 0.08 * (4.0 * pi * t).cos()).max(0.0)
+            }
+            WindowFunction::Cosine => ((pi * p as f32) / last).sin(),
+            WindowFunction::None => 1.0,
+        }
+    }
+}
+
+// see preprocess(), with only the given `steps`, and `mask` (one weight per pixel, row by row) as the window
+pub fn preprocess_with(image: &GrayImage, steps: &Preprocessing, mask: &[f32]) -> Vec<f32> {
+    let mut prepped = Vec::new();
+    preprocess_with_into(image, steps, mask, &mut prepped);
+    prepped
+}
+
+// see preprocess_with() and preprocess_into()
+pub fn preprocess_with_into(
+    image: &GrayImage,
+    steps: &Preprocessing,
+    mask: &[f32],
+    output: &mut Vec<f32>,
+) {
+    pixel_values_into(image, steps, output);
+    normalize(output, steps, mask);
+}
+
+// the pixel values of `image`, after the steps of preprocess_with() that work on pixel values
+// (equalization, gamma correction and the log transform)
+pub(crate) fn pixel_values(image: &GrayImage, steps: &Preprocessing) -> Vec<f32> {
+    let mut values = Vec::new();
+    pixel_values_into(image, steps, &mut values);
+    values
+}
+
+// see pixel_values(), writing the values into `values` (cleared first).
+// Only the equalization (if enabled) allocates.
+fn pixel_values_into(image: &GrayImage, steps: &Preprocessing, values: &mut Vec<f32>) {
+    let equalized;
+    let image = if steps.equalization == Equalization::None {
+        image
+    } else {
+        equalized = steps.equalization.apply(image);
+        &equalized
+    };
+
+    // convert the pixel to u8 and then to f32
+    values.clear();
+    values.extend(image.pixels().map(|p| p[0] as f32));
+
+    if steps.gamma != 1.0 {
+        values
+            .iter_mut()
+            .for_each(|p| *p = 255.0 * (*p / 255.0).powf(steps.gamma));
+    }
+
+    // add 1, and take the natural logarithm
+    if steps.log_transform {
+        log_transform(values);
+    }
+}
+
+// replace every value v by ln(v + 1)
+pub(crate) fn log_transform(values: &mut [f32]) {
+    simd::ln_1p(values);
+}
+
+// the steps of preprocess_with() after the log transform: mean subtraction, unit norm and the window
+pub(crate) fn normalize(values: &mut [f32], steps: &Preprocessing, mask: &[f32]) {
+    // normalize to mean = 0 (subtract image-wide mean from each pixel)
+    if steps.mean_subtraction {
+        let mean: f32 = simd::sum(values) / values.len() as f32;
+        simd::add(values, -mean);
+    }
+
+    // normalize to norm = 1, if possible
+    if steps.unit_norm {
+        let norm = simd::sum_squares(values).sqrt();
+        if norm != 0.0 {
+            simd::scale(values, 1.0 / norm);
+        }
+    }
+
+    // multiply each pixel by the window
+    simd::multiply(values, mask);
+}
+
+// the channels of `image` (e.g. red, green and blue), each as a grayscale image
+pub fn channel_planes<P: Pixel<Subpixel = u8>>(image: &ImageBuffer<P, Vec<u8>>) -> Vec<GrayImage> {
+    let (width, height) = image.dimensions();
+    (0..P::CHANNEL_COUNT as usize)
+        .map(|c| {
+            GrayImage::from_fn(width, height, |x, y| {
+                Luma([image.get_pixel(x, y).channels()[c]])
+            })
+        })
+        .collect()
+}
+
+// see preprocess_with(), for every plane (channel) separately. Returns the values of the planes one after the other.
+pub fn preprocess_planes(planes: &[GrayImage], steps: &Preprocessing, mask: &[f32]) -> Vec<f32> {
+    let mut values = Vec::with_capacity(planes.len() * mask.len());
+    let mut plane_values = Vec::with_capacity(mask.len());
+    for plane in planes {
+        preprocess_with_into(plane, steps, mask, &mut plane_values);
+        values.extend_from_slice(&plane_values);
+    }
+    values
+}
+
+// Copy the width x height window around `center` out of `frame` into `output`, which is only reallocated if it
+// doesn't have the size of the window yet. The window is moved inside the frame where it would stick out.
+// A window larger than the frame is aligned with its top left corner, and black where the frame ends.
+pub fn crop_into<P: Pixel>(
+    frame: &ImageBuffer<P, Vec<P::Subpixel>>,
+    width: u32,
+    height: u32,
+    center: (u32, u32),
+    output: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+) {
+    let (left, top, inside_width, inside_height) =
+        crop_region(frame.dimensions(), width, height, center);
+    if output.dimensions() != (width, height) || (inside_width, inside_height) != (width, height) {
+        *output = ImageBuffer::new(width, height);
+    }
+
+    let channels = P::CHANNEL_COUNT as usize;
+    let row = width as usize * channels;
+    let inside_row = inside_width as usize * channels;
+    let stride = frame.width() as usize * channels;
+    for y in 0..inside_height as usize {
+        let start = (top as usize + y) * stride + left as usize * channels;
+        (**output)[y * row..y * row + inside_row]
+            .copy_from_slice(&frame.as_raw()[start..start + inside_row]);
+    }
+}
+
+// The top left corner of the width x height window around `center` in a frame of `dimensions`, and the size of
+// the part of it inside the frame (see crop_into).
+pub(crate) fn crop_region(
+    dimensions: (u32, u32),
+    width: u32,
+    height: u32,
+    center: (u32, u32),
+) -> (u32, u32, u32, u32) {
+    let left = center
+        .0
+        .saturating_sub(width / 2)
+        .min(dimensions.0.saturating_sub(width));
+    let top = center
+        .1
+        .saturating_sub(height / 2)
+        .min(dimensions.1.saturating_sub(height));
+    (left, top, width.min(dimensions.0), height.min(dimensions.1))
+}
+
+// the perturbations the tracker trains on by default (see MosseTrackerSettings).
+// Rotations are in radians, shears and tilts relative to the window size.
+// NOTE: the rotations beyond 1 radian are far more than a target turns between two frames, but they have always
+// been part of the default training set. Build a pipeline with your own Rotations to leave them out.
+pub const TRAINING_ROTATIONS: [f32; 16] = [
+    0.02, -0.02, 0.05, -0.05, 0.07, -0.07, 0.09, -0.09, 1.1, -1.1, 1.3, -1.3, 1.5, -1.5, 2.0, -2.0,
+];
+pub const TRAINING_SCALES: [f32; 4] = [0.8, 0.9, 1.1, 1.2];
+pub const TRAINING_SHEARS: [f32; 4] = [0.05, -0.05, 0.1, -0.1];
+pub const TRAINING_TILTS: [f32; 4] = [0.05, -0.05, 0.1, -0.1];
+// brightness changes and noise levels are in pixel values, contrast changes in percent
+pub const TRAINING_BRIGHTNESS: [i32; 4] = [-40, -20, 20, 40];
+pub const TRAINING_CONTRASTS: [f32; 2] = [-30.0, 30.0];
+pub const TRAINING_NOISE: [f32; 2] = [4.0, 8.0];
+// the number of shifted copies of the window (see MosseTrackerSettings::translation_augmentation)
+pub const TRAINING_TRANSLATIONS: usize = 8;
+// the number of partially occluded copies of the window (see MosseTrackerSettings::occlusion_augmentation)
+pub const TRAINING_OCCLUSIONS: usize = 6;
+
+// A training frame, and the shift (x, y) of the target in it relative to the center of the window.
+// The desired response of the frame is shifted along.
+pub type TrainingFrame = (GrayImage, (i32, i32));
+
+// produces perturbed copies of the training window
+pub trait Augmenter {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame>;
+}
+
+// any function from the window to training frames is an Augmenter
+impl<F> Augmenter for F
+where
+    F: Fn(&GrayImage) -> Vec<TrainingFrame>,
+{
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        self(window)
+    }
+}
+
+// a series of Augmenters: the frames of all of them, in order
+#[derive(Clone, Default)]
+pub struct AugmentationPipeline {
+    augmenters: Vec<Arc<dyn Augmenter + Send + Sync>>,
+}
+
+impl Debug for AugmentationPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AugmentationPipeline")
+            .field("augmenters", &self.augmenters.len())
+            .finish()
+    }
+}
+
+impl AugmentationPipeline {
+    // an empty pipeline: the tracker trains on the unperturbed window only
+    pub fn new() -> AugmentationPipeline {
+        AugmentationPipeline::default()
+    }
+
+    // append an augmenter to the pipeline
+    pub fn with(
+        mut self,
+        augmenter: impl Augmenter + Send + Sync + 'static,
+    ) -> AugmentationPipeline {
+        self.augmenters.push(Arc::new(augmenter));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.augmenters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.augmenters.is_empty()
+    }
+}
+
+impl Augmenter for AugmentationPipeline {
+    // with rayon, the augmenters run in parallel (the frames keep their order)
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        #[cfg(feature = "rayon")]
+        return self
+            .augmenters
+            .par_iter()
+            .flat_map_iter(|augmenter| augmenter.augment(window))
+            .collect();
+        #[cfg(not(feature = "rayon"))]
+        return self
+            .augmenters
+            .iter()
+            .flat_map(|augmenter| augmenter.augment(window))
+            .collect();
+    }
+}
+
+// see rotated_frames()
+#[derive(Debug, Clone)]
+pub struct Rotations(pub Vec<f32>);
+
+impl Augmenter for Rotations {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(rotated_frames(window, &self.0))
+    }
+}
+
+// see scaled_frames()
+#[derive(Debug, Clone)]
+pub struct Scales(pub Vec<f32>);
+
+impl Augmenter for Scales {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(scaled_frames(window, &self.0))
+    }
+}
+
+// see sheared_frames()
+#[derive(Debug, Clone)]
+pub struct Shears(pub Vec<f32>);
+
+impl Augmenter for Shears {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(sheared_frames(window, &self.0))
+    }
+}
+
+// see perspective_frames()
+#[derive(Debug, Clone)]
+pub struct Tilts(pub Vec<f32>);
+
+impl Augmenter for Tilts {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(perspective_frames(window, &self.0))
+    }
+}
+
+// see brightened_frames()
+#[derive(Debug, Clone)]
+pub struct Brightness(pub Vec<i32>);
+
+impl Augmenter for Brightness {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(brightened_frames(window, &self.0))
+    }
+}
+
+// see contrasted_frames()
+#[derive(Debug, Clone)]
+pub struct Contrast(pub Vec<f32>);
+
+impl Augmenter for Contrast {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(contrasted_frames(window, &self.0))
+    }
+}
+
+// see noisy_frames()
+#[derive(Debug, Clone)]
+pub struct Noise {
+    pub stddevs: Vec<f32>,
+    pub seed: u64,
+}
+
+impl Augmenter for Noise {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(noisy_frames(window, &self.stddevs, self.seed))
+    }
+}
+
+// see random_shifts() and translated_frames()
+#[derive(Debug, Clone)]
+pub struct Translations {
+    pub count: usize,
+    pub max_shift: u32,
+    pub seed: u64,
+}
+
+impl Augmenter for Translations {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        let shifts = random_shifts(self.count, self.max_shift, self.seed);
+        translated_frames(window, &shifts).collect()
+    }
+}
+
+// see occluded_frames()
+#[derive(Debug, Clone)]
+pub struct Occlusions {
+    pub count: usize,
+    pub seed: u64,
+}
+
+impl Augmenter for Occlusions {
+    fn augment(&self, window: &GrayImage) -> Vec<TrainingFrame> {
+        centered(occluded_frames(window, self.count, self.seed))
+    }
+}
+
+// The training frames of a window with several channels, given as one plane per channel: every plane is perturbed
+// separately, and the n-th frames of all planes make up the n-th training frame.
+// NOTE: this relies on `augmenter` perturbing every plane the same way, which the augmenters of this module do
+// (the random ones are seeded).
+pub fn augment_planes(
+    augmenter: &impl Augmenter,
+    planes: &[GrayImage],
+) -> Vec<(Vec<GrayImage>, (i32, i32))> {
+    let mut per_plane: Vec<Vec<TrainingFrame>> = planes
+        .iter()
+        .map(|plane| augmenter.augment(plane))
+        .collect();
+    let count = per_plane.iter().map(Vec::len).min().unwrap_or(0);
+    (0..count)
+        .map(|i| {
+            let shift = per_plane[0][i].1;
+            let frame = per_plane
+                .iter_mut()
+                .map(|frames| std::mem::take(&mut frames[i].0))
+                .collect();
+            (frame, shift)
+        })
+        .collect()
+}
+
+// frames that want the target response centered on the window
+fn centered(frames: impl Iterator<Item = GrayImage>) -> Vec<TrainingFrame> {
+    frames.map(|frame| (frame, (0, 0))).collect()
+}
+
+// the window rotated clockwise about its center, by each of `angles` (in radians)
+pub fn rotated_frames<'a>(
+    window: &'a GrayImage,
+    angles: &'a [f32],
+) -> impl Iterator<Item = GrayImage> + 'a {
+    angles.iter().map(move |rad| {
+        // Rotate an image clockwise about its center by theta radians.
+        let training_frame = rotate_about_center(window, *rad, Interpolation::Nearest, Luma([0]));
+
+        #[cfg(debug_assertions)]
+        {
+            training_frame
+                .save(format!("training_frame_rotated_theta_{}.png", rad))
+                .unwrap();
+        }
+
+        training_frame
+    })
+}
+
+// the window scaled ('zoomed') by each of `factors`
+pub fn scaled_frames<'a>(
+    window: &'a GrayImage,
+    factors: &'a [f32],
+) -> impl Iterator<Item = GrayImage> + 'a {
+    factors.iter().map(move |scalefactor| {
+        let scale = Projection::scale(*scalefactor, *scalefactor);
+
+        let scaled_training_frame = warp(window, &scale, Interpolation::Nearest, Luma([0]));
+
+        #[cfg(debug_assertions)]
+        {
+            scaled_training_frame
+                .save(format!("training_frame_scaled_{}.png", scalefactor))
+                .unwrap();
+        }
+
+        scaled_training_frame
+    })
+}
+
+// the window sheared about its center by each of `shears`, horizontally and vertically (two frames per shear).
+// A shear of s moves a pixel s pixels sideways for every pixel it is away from the center.
+pub fn sheared_frames<'a>(
+    window: &'a GrayImage,
+    shears: &'a [f32],
+) -> impl Iterator<Item = GrayImage> + 'a {
+    let (center_x, center_y) = center(window);
+    shears.iter().flat_map(move |shear| {
+        let horizontal = [1.0, *shear, -shear * center_y, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let vertical = [1.0, 0.0, 0.0, *shear, 1.0, -shear * center_x, 0.0, 0.0, 1.0];
+        [horizontal, vertical].into_iter().map(move |matrix| {
+            // a shear always has an inverse, see Projection::from_matrix()
+            let projection = Projection::from_matrix(matrix).unwrap();
+            warp(window, &projection, Interpolation::Bilinear, Luma([0]))
+        })
+    })
+}
+
+// the window seen from a slightly different viewpoint: for each of `tilts`, one edge of the window is squeezed
+// by that fraction of its length, once for the top and once for the left edge (two frames per tilt).
+// Negative tilts squeeze the opposite edge.
+pub fn perspective_frames<'a>(
+    window: &'a GrayImage,
+    tilts: &'a [f32],
+) -> impl Iterator<Item = GrayImage> + 'a {
+    let (width, height) = (window.width() as f32, window.height() as f32);
+    let corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+    tilts.iter().flat_map(move |tilt| {
+        // squeeze the top (or bottom) edge towards its middle, and likewise for the left (or right) edge
+        let (dx, dy) = (tilt.abs() * width / 2.0, tilt.abs() * height / 2.0);
+        let mut vertical = corners;
+        let mut horizontal = corners;
+        if *tilt >= 0.0 {
+            vertical[0].0 += dx;
+            vertical[1].0 -= dx;
+            horizontal[0].1 += dy;
+            horizontal[3].1 -= dy;
+        } else {
+            vertical[3].0 += dx;
+            vertical[2].0 -= dx;
+            horizontal[1].1 += dy;
+            horizontal[2].1 -= dy;
+        }
+        [vertical, horizontal].into_iter().filter_map(move |to| {
+            // None for degenerate tilts, that collapse the window
+            let projection = Projection::from_control_points(corners, to)?;
+            Some(warp(
+                window,
+                &projection,
+                Interpolation::Bilinear,
+                Luma([0]),
+            ))
+        })
+    })
+}
+
+// the window with each of `deltas` added to its pixel values, saturating at black and white
+pub fn brightened_frames<'a>(
+    window: &'a GrayImage,
+    deltas: &'a [i32],
+) -> impl Iterator<Item = GrayImage> + 'a {
+    deltas
+        .iter()
+        .map(move |delta| imageops::brighten(window, *delta))
+}
+
+// the window with its contrast adjusted by each of `contrasts` (in percent: negative values reduce the contrast)
+pub fn contrasted_frames<'a>(
+    window: &'a GrayImage,
+    contrasts: &'a [f32],
+) -> impl Iterator<Item = GrayImage> + 'a {
+    contrasts
+        .iter()
+        .map(move |contrast| imageops::contrast(window, *contrast))
+}
+
+// the window with zero-mean Gaussian noise of each of `stddevs` (in pixel values) added to it.
+// The noise is seeded with `seed` (and the position in `stddevs`), so the frames are reproducible.
+pub fn noisy_frames<'a>(
+    window: &'a GrayImage,
+    stddevs: &'a [f32],
+    seed: u64,
+) -> impl Iterator<Item = GrayImage> + 'a {
+    stddevs
+        .iter()
+        .zip(seed..)
+        .map(move |(stddev, seed)| gaussian_noise(window, 0.0, *stddev as f64, seed))
+}
+
+// the window shifted by each of `shifts` (x, y), along with that shift: the target moves off-center with it,
+// so the filter should respond off-center too
+pub fn translated_frames<'a>(
+    window: &'a GrayImage,
+    shifts: &'a [(i32, i32)],
+) -> impl Iterator<Item = (GrayImage, (i32, i32))> + 'a {
+    shifts
+        .iter()
+        .map(move |shift| (translate(window, *shift), *shift))
+}
+
+// `count` pseudo-random, non-zero shifts of at most `max_shift` pixels along either axis.
+// The same seed gives the same shifts.
+pub fn random_shifts(count: usize, max_shift: u32, seed: u64) -> Vec<(i32, i32)> {
+    if max_shift == 0 {
+        return Vec::new();
+    }
+
+    let mut rng = XorShift::new(seed);
+    let span = 2 * max_shift + 1;
+    let mut shifts = Vec::with_capacity(count);
+    while shifts.len() < count {
+        let shift = (
+            rng.below(span) as i32 - max_shift as i32,
+            rng.below(span) as i32 - max_shift as i32,
+        );
+        if shift != (0, 0) {
+            shifts.push(shift);
+        }
+    }
+    shifts
+}
+
+// `count` copies of the window with a pseudo-random rectangle pasted over part of the target (at its center).
+// The rectangles are a sixth to a third of the window in size, uniformly gray, and the same seed gives the same ones.
+pub fn occluded_frames(
+    window: &GrayImage,
+    count: usize,
+    seed: u64,
+) -> impl Iterator<Item = GrayImage> + '_ {
+    let (width, height) = window.dimensions();
+    let mut rng = XorShift::new(seed);
+    (0..count).map(move |_| {
+        let occluder_width = (width / 6 + rng.below(width / 6 + 1)).max(1);
+        let occluder_height = (height / 6 + rng.below(height / 6 + 1)).max(1);
+        // the occluder always covers part of the center of the window
+        let left = width / 2 - rng.below(occluder_width);
+        let top = height / 2 - rng.below(occluder_height);
+        let value = Luma([rng.below(256) as u8]);
+
+        let mut frame = window.clone();
+        for x in left..(left + occluder_width).min(width) {
+            for y in top..(top + occluder_height).min(height) {
+                frame.put_pixel(x, y, value);
+            }
+        }
+        frame
+    })
+}
+
+// Resample `image` on a log-polar grid around `center`: column x lies at a distance between `radii` (min, max)
+// that grows exponentially with x, and row y at an angle of `start_angle` plus y / height of a full turn
+// (clockwise, in radians). Scaling the image about the center then moves its features along the x axis,
+// and rotating it moves them (circularly) along the y axis. Pixels outside of the image are black.
+pub fn log_polar(
+    image: &GrayImage,
+    center: (f32, f32),
+    radii: (f32, f32),
+    start_angle: f32,
+    size: (u32, u32),
+) -> GrayImage {
+    let (width, height) = size;
+    let (min_radius, max_radius) = (radii.0.max(f32::EPSILON), radii.1.max(radii.0));
+    let growth = (max_radius / min_radius).ln() / (width.max(2) - 1) as f32;
+    GrayImage::from_fn(width, height, |x, y| {
+        let radius = min_radius * (growth * x as f32).exp();
+        let angle = start_angle + 2.0 * std::f32::consts::PI * y as f32 / height as f32;
+        let value = sample_bilinear(
+            image,
+            center.0 + radius * angle.cos(),
+            center.1 + radius * angle.sin(),
+        );
+        Luma([value.round() as u8])
+    })
+}
+
+// the pixel value at (x, y), interpolated between the four pixels around it
+fn sample_bilinear(image: &GrayImage, x: f32, y: f32) -> f32 {
+    let (width, height) = image.dimensions();
+    let (left, top) = (x.floor(), y.floor());
+    let (fx, fy) = (x - left, y - top);
+    let at = |x: f32, y: f32| {
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            0.0
+        } else {
+            image.get_pixel(x as u32, y as u32)[0] as f32
+        }
+    };
+    let upper = at(left, top) * (1.0 - fx) + at(left + 1.0, top) * fx;
+    let lower = at(left, top + 1.0) * (1.0 - fx) + at(left + 1.0, top + 1.0) * fx;
+    upper * (1.0 - fy) + lower * fy
+}
+
+// xorshift64*: plenty for picking a few augmentations, and reproducible
+struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // the state must never be 0
+        XorShift {
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+        }
+    }
+
+    // a pseudo-random number in 0..range
+    fn below(&mut self, range: u32) -> u32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as u32 % range.max(1)
+    }
+}
+
+fn center(window: &GrayImage) -> (f32, f32) {
+    ((window.width() / 2) as f32, (window.height() / 2) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a window with a horizontal gradient
+    fn gradient() -> GrayImage {
+        GrayImage::from_fn(32, 32, |x, _| Luma([(x * 8) as u8]))
+    }
+
+    #[test]
+    fn shear_keeps_center() {
+        let window = gradient();
+        let frames: Vec<GrayImage> = sheared_frames(&window, &[0.1, -0.1]).collect();
+        assert_eq!(frames.len(), 4);
+        for frame in &frames {
+            assert_eq!(frame.dimensions(), (32, 32));
+            assert_eq!(frame.get_pixel(16, 16), window.get_pixel(16, 16));
+        }
+        // a horizontal shear moves the top row sideways
+        assert_ne!(frames[0].get_pixel(16, 2), window.get_pixel(16, 2));
+    }
+
+    #[test]
+    fn perspective_squeezes_one_edge() {
+        let window = GrayImage::from_pixel(32, 32, Luma([200]));
+        let frames: Vec<GrayImage> = perspective_frames(&window, &[0.2]).collect();
+        assert_eq!(frames.len(), 2);
+
+        // the corners of the squeezed edge are uncovered, the opposite ones are not
+        let top_squeezed = &frames[0];
+        assert_eq!(top_squeezed.get_pixel(0, 0)[0], 0);
+        assert_eq!(top_squeezed.get_pixel(1, 30)[0], 200);
+        let left_squeezed = &frames[1];
+        assert_eq!(left_squeezed.get_pixel(0, 0)[0], 0);
+        assert_eq!(left_squeezed.get_pixel(30, 1)[0], 200);
+    }
+
+    #[test]
+    fn photometric_frames() {
+        let window = gradient();
+        let mean = |frame: &GrayImage| frame.pixels().map(|p| p[0] as f32).sum::<f32>() / 1024.0;
+
+        let brighter: Vec<GrayImage> = brightened_frames(&window, &[-20, 20]).collect();
+        assert!(mean(&brighter[0]) < mean(&window) && mean(&window) < mean(&brighter[1]));
+
+        let flatter: Vec<GrayImage> = contrasted_frames(&window, &[-50.0]).collect();
+        let spread = |frame: &GrayImage| frame.get_pixel(31, 0)[0] - frame.get_pixel(0, 0)[0];
+        assert!(spread(&flatter[0]) < spread(&window));
+
+        let noisy: Vec<GrayImage> = noisy_frames(&window, &[5.0, 5.0], 7).collect();
+        assert_ne!(noisy[0], window);
+        // every frame gets its own noise, but the same seed gives the same frames
+        assert_ne!(noisy[0], noisy[1]);
+        assert_eq!(noisy[0], noisy_frames(&window, &[5.0], 7).next().unwrap());
+    }
+
+    #[test]
+    fn translations_move_the_window() {
+        let window = gradient();
+        let shifts = random_shifts(6, 3, 42);
+        assert_eq!(shifts.len(), 6);
+        assert_eq!(shifts, random_shifts(6, 3, 42));
+        for &(dx, dy) in &shifts {
+            assert!(dx.abs() <= 3 && dy.abs() <= 3 && (dx, dy) != (0, 0));
+        }
+        assert!(random_shifts(6, 0, 42).is_empty());
+
+        let (shifted, shift) = translated_frames(&window, &[(2, 1)]).next().unwrap();
+        assert_eq!(shift, (2, 1));
+        assert_eq!(shifted.get_pixel(12, 11), window.get_pixel(10, 10));
+    }
+
+    #[test]
+    fn occluders_cover_the_center() {
+        let window = GrayImage::from_pixel(30, 30, Luma([7]));
+        let frames: Vec<GrayImage> = occluded_frames(&window, 5, 3).collect();
+        assert_eq!(frames.len(), 5);
+        for frame in &frames {
+            let covered = frame.pixels().filter(|p| p[0] != 7).count();
+            // at most a third of the window along either axis
+            assert!(covered <= 10 * 10, "{} pixels covered", covered);
+        }
+        assert!(frames.iter().any(|f| f.get_pixel(15, 15)[0] != 7));
+        assert_eq!(frames, occluded_frames(&window, 5, 3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pipeline_strings_augmenters_together() {
+        let window = gradient();
+        let flipped = |window: &GrayImage| vec![(imageops::flip_horizontal(window), (0, 0))];
+        let pipeline = AugmentationPipeline::new()
+            .with(Rotations(vec![0.05, -0.05]))
+            .with(flipped)
+            .with(Translations {
+                count: 3,
+                max_shift: 2,
+                seed: 1,
+            });
+        assert_eq!(pipeline.len(), 3);
+
+        let frames = pipeline.augment(&window);
+        assert_eq!(frames.len(), 6);
+        assert_eq!(frames[2].0, imageops::flip_horizontal(&window));
+        assert!(frames[..3].iter().all(|(_, shift)| *shift == (0, 0)));
+        assert!(frames[3..].iter().all(|(_, shift)| *shift != (0, 0)));
+
+        // pipelines nest, and an empty one adds nothing
+        let nested = AugmentationPipeline::new()
+            .with(pipeline)
+            .with(AugmentationPipeline::new());
+        assert_eq!(nested.augment(&window).len(), 6);
+    }
+
+    #[test]
+    fn planes_are_perturbed_alike() {
+        let window = image::RgbImage::from_fn(32, 32, |x, y| {
+            image::Rgb([(x * 8) as u8, (y * 8) as u8, 100])
+        });
+        let planes = channel_planes(&window);
+        assert_eq!(planes.len(), 3);
+        assert_eq!(planes[0], gradient());
+        assert_eq!(planes[2].get_pixel(5, 7)[0], 100);
+
+        let pipeline = AugmentationPipeline::new()
+            .with(Rotations(vec![0.1]))
+            .with(Translations {
+                count: 2,
+                max_shift: 2,
+                seed: 5,
+            });
+        let frames = augment_planes(&pipeline, &planes);
+        assert_eq!(frames.len(), 3);
+        for (i, (perturbed, shift)) in frames.iter().enumerate() {
+            assert_eq!(perturbed.len(), 3);
+            assert_eq!(perturbed[0], pipeline.augment(&planes[0])[i].0);
+            assert_eq!(*shift, pipeline.augment(&planes[1])[i].1);
+        }
+    }
+
+    #[test]
+    fn equalization_spreads_low_contrast() {
+        // a dim window, with a gradient of only a few gray values
+        let dim = GrayImage::from_fn(32, 32, |x, y| Luma([20 + (x + y) as u8 / 8]));
+        let range = |image: &GrayImage| {
+            let values = image.pixels().map(|p| p[0]);
+            values.clone().max().unwrap() - values.min().unwrap()
+        };
+
+        assert_eq!(Equalization::None.apply(&dim), dim);
+        assert!(range(&Equalization::Global.apply(&dim)) > 200);
+        let adaptive = Equalization::Adaptive {
+            tiles: 2,
+            clip_limit: 40.0,
+        }
+        .apply(&dim);
+        assert!(range(&adaptive) > 100, "range {}", range(&adaptive));
+
+        // equalization keeps the order of the gray values within a tile
+        assert!(adaptive.get_pixel(2, 2)[0] <= adaptive.get_pixel(6, 6)[0]);
+
+        // a flat window stays flat
+        let flat = GrayImage::from_pixel(16, 16, Luma([90]));
+        let equalized = clahe(&flat, 2, 2.0);
+        assert!(equalized.pixels().all(|p| p == equalized.get_pixel(0, 0)));
+    }
+
+    #[test]
+    fn log_polar_unrolls_rings() {
+        // a bright ring of radius 8 to 12 around the center
+        let image = GrayImage::from_fn(64, 64, |x, y| {
+            let dist = ((x as f32 - 32.0).powi(2) + (y as f32 - 32.0).powi(2)).sqrt();
+            Luma([if (8.0..12.0).contains(&dist) { 200 } else { 0 }])
+        });
+        let unrolled = log_polar(&image, (32.0, 32.0), (2.0, 30.0), 0.0, (32, 16));
+        assert_eq!(unrolled.dimensions(), (32, 16));
+
+        // the ring is a vertical band, at the same columns for every angle
+        let column = |radius: f32| ((radius / 2.0).ln() / (15.0f32).ln() * 31.0).round() as u32;
+        for y in 0..16 {
+            assert_eq!(unrolled.get_pixel(column(10.0), y)[0], 200);
+            assert_eq!(unrolled.get_pixel(column(5.0), y)[0], 0);
+            assert_eq!(unrolled.get_pixel(column(20.0), y)[0], 0);
+        }
+    }
+
+    #[test]
+    fn preprocessing_steps() {
+        let window = gradient();
+        let flat = WindowFunction::None.weights(32, 32);
+        let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+        let norm = |values: &[f32]| values.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+        let all = preprocess_with(&window, &Preprocessing::default(), &flat);
+        assert!(mean(&all).abs() < 1e-6);
+        assert!((norm(&all) - 1.0).abs() < 1e-5);
+
+        // without any of the steps, the pixel values pass through
+        let none = Preprocessing {
+            log_transform: false,
+            mean_subtraction: false,
+            unit_norm: false,
+            ..Default::default()
+        };
+        let raw = preprocess_with(&window, &none, &flat);
+        assert_eq!(raw[(3 * 32 + 5) as usize], 40.0);
+
+        // gamma correction keeps black and white, and bends the values in between
+        let gamma = |gamma| preprocess_with(&window, &Preprocessing { gamma, ..none }, &flat);
+        assert_eq!(gamma(1.0), raw);
+        assert_eq!(gamma(0.5)[0], 0.0);
+        assert!((gamma(2.0)[10] - 255.0 * (80.0f32 / 255.0).powi(2)).abs() < 1e-3);
+        assert!(gamma(0.5)[10] > raw[10]);
+
+        // without the log transform, the values stay proportional to the pixel values
+        let linear = preprocess_with(
+            &window,
+            &Preprocessing {
+                log_transform: false,
+                ..Default::default()
+            },
+            &flat,
+        );
+        let step = |values: &[f32], x: usize| values[x + 1] - values[x];
+        assert!((step(&linear, 2) - step(&linear, 20)).abs() < 1e-6);
+        assert!(step(&all, 2) > step(&all, 20));
+
+        // preprocess() applies all steps and the cosine window
+        let cosine = WindowFunction::Cosine.weights(32, 32);
+        assert_eq!(
+            preprocess(&window),
+            preprocess_with(&window, &Preprocessing::default(), &cosine)
+        );
+    }
+
+    #[test]
+    fn into_reuses_buffers() {
+        let window = gradient();
+        let mut values = Vec::new();
+        preprocess_into(&window, &mut values);
+        assert_eq!(values, preprocess(&window));
+
+        // a second window of the same size fits into the same buffer
+        let capacity = values.capacity();
+        let pointer = values.as_ptr();
+        let other = GrayImage::from_fn(32, 32, |_, y| Luma([(y * 5) as u8]));
+        preprocess_into(&other, &mut values);
+        assert_eq!(values, preprocess(&other));
+        assert_eq!((values.capacity(), values.as_ptr()), (capacity, pointer));
+
+        // crops match imageops::crop, and are moved inside the frame at its edges
+        let frame = GrayImage::from_fn(40, 30, |x, y| Luma([(x + 40 * y) as u8]));
+        let mut crop = GrayImage::new(8, 6);
+        let pointer = crop.as_ptr();
+        crop_into(&frame, 8, 6, (20, 10), &mut crop);
+        assert_eq!(crop, imageops::crop_imm(&frame, 16, 7, 8, 6).to_image());
+        crop_into(&frame, 8, 6, (39, 0), &mut crop);
+        assert_eq!(crop, imageops::crop_imm(&frame, 32, 0, 8, 6).to_image());
+        assert_eq!(crop.as_ptr(), pointer);
+
+        // a window larger than the frame is padded with black
+        let mut large = GrayImage::new(1, 1);
+        crop_into(&frame, 50, 32, (20, 15), &mut large);
+        assert_eq!(large.dimensions(), (50, 32));
+        assert_eq!(large.get_pixel(39, 29), frame.get_pixel(39, 29));
+        assert_eq!(large.get_pixel(45, 10)[0], 0);
+        assert_eq!(large.get_pixel(10, 31)[0], 0);
+    }
+}
@@ -0,0 +1,433 @@
+// Video files and cameras as frames (with the video and camera features), decoded by the ffmpeg command line tool:
+// ffmpeg and ffprobe have to be on the PATH. ffmpeg decodes any format it knows into 8-bit grayscale frames, which it pipes to
+// VideoFrames one after the other, so a video is never decoded into memory as a whole.
+//
+// track_video() runs a tracker through a video from start to end, and track_video_annotated() writes the video back
+// with the target drawn in (see draw::Overlay), to share the results with those who don't run the code. VideoWriter
+// encodes frames the same way the other way around, piping them to ffmpeg.
+
+use crate::draw::Overlay;
+use crate::{BBox, Error, MosseTracker, Prediction};
+use image::{DynamicImage, GrayImage, RgbImage};
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+// the frames of a video, as they are decoded
+#[derive(Debug)]
+pub struct VideoFrames<R: Read = ChildStdout> {
+    reader: R,
+    width: u32,
+    height: u32,
+    // the ffmpeg that decodes the video, stopped when the frames are dropped
+    decoder: Option<Child>,
+}
+
+impl VideoFrames {
+    pub fn open(path: impl AsRef<Path>) -> Result<VideoFrames, Error> {
+        VideoFrames::decode(&["-i".as_ref(), path.as_ref().as_os_str()])
+    }
+
+    // The live frames of a camera (with the camera feature), e.g. "/dev/video0" on Linux (V4L2), "0" on macOS
+    // (AVFoundation) or "video=<name of the camera>" on Windows (DirectShow). They come at the frame rate and size the
+    // camera is set to: frames that aren't taken from the iterator in time queue up in the pipe.
+    #[cfg(feature = "camera")]
+    pub fn camera(device: &str) -> Result<VideoFrames, Error> {
+        let format = match std::env::consts::OS {
+            "linux" => "v4l2",
+            "macos" => "avfoundation",
+            "windows" => "dshow",
+            _ => {
+                return Err(Error::Unsupported(
+                    "ffmpeg has no camera input on this platform",
+                ))
+            }
+        };
+        VideoFrames::decode(&[
+            "-f".as_ref(),
+            format.as_ref(),
+            "-i".as_ref(),
+            device.as_ref(),
+        ])
+    }
+
+    // The frames of the input of ffmpeg `input` (its options and -i), as they are stored: ffmpeg would turn the
+    // frames of videos with rotation metadata (e.g. from phones) upright, which swaps their width and height against
+    // the size ffprobe reports.
+    fn decode(input: &[&OsStr]) -> Result<VideoFrames, Error> {
+        let (width, height) = video_size(input)?;
+        let mut decoder = Command::new("ffmpeg")
+            .args(["-v", "error", "-noautorotate"])
+            .args(input)
+            .args(["-f", "rawvideo", "-pix_fmt", "gray", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let reader = decoder.stdout.take().expect("the output is piped");
+        Ok(VideoFrames {
+            reader,
+            width,
+            height,
+            decoder: Some(decoder),
+        })
+    }
+}
+
+impl<R: Read> VideoFrames<R> {
+    // the frames of raw 8-bit grayscale video (width x height bytes per frame, without padding), e.g. the output of
+    // `ffmpeg -f rawvideo -pix_fmt gray` or of a camera
+    pub fn from_raw(reader: R, width: u32, height: u32) -> VideoFrames<R> {
+        VideoFrames {
+            reader,
+            width,
+            height,
+            decoder: None,
+        }
+    }
+
+    // (width, height)
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+impl<R: Read> VideoFrames<R> {
+    // whether the decoder got through the video, once its output ends
+    fn finish(&mut self) -> Result<(), Error> {
+        match self.decoder.take() {
+            Some(mut decoder) => match decoder.wait()? {
+                status if status.success() => Ok(()),
+                status => Err(Error::Io {
+                    kind: io::ErrorKind::InvalidData,
+                    message: format!("ffmpeg could not decode the video ({})", status),
+                }),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for VideoFrames<R> {
+    type Item = Result<GrayImage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut frame = vec![0; self.width as usize * self.height as usize];
+        let mut read = 0;
+        while read < frame.len() {
+            match self.reader.read(&mut frame[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Some(Err(error.into())),
+            }
+        }
+        match read {
+            // the end of the video, unless ffmpeg failed
+            0 => self.finish().err().map(Err),
+            read if read < frame.len() => Some(Err(Error::Format(
+                "the video ends in the middle of a frame",
+            ))),
+            _ => GrayImage::from_raw(self.width, self.height, frame).map(Ok),
+        }
+    }
+}
+
+impl<R: Read> Drop for VideoFrames<R> {
+    fn drop(&mut self) {
+        if let Some(decoder) = &mut self.decoder {
+            // the video may not be at its end yet
+            let _ = decoder.kill();
+            let _ = decoder.wait();
+        }
+    }
+}
+
+// An encoder of RGB frames into a video file, by ffmpeg. The format is the one of the extension of the file: e.g.
+// .mp4 is H.264, and .gif an animated GIF (with a palette made for the video, so it keeps its colors).
+#[derive(Debug)]
+pub struct VideoWriter<W: Write = ChildStdin> {
+    // None once the video is finished
+    writer: Option<W>,
+    width: u32,
+    height: u32,
+    // the ffmpeg that encodes the video
+    encoder: Option<Child>,
+}
+
+impl VideoWriter {
+    pub fn create(
+        path: impl AsRef<Path>,
+        width: u32,
+        height: u32,
+        frame_rate: f32,
+    ) -> Result<VideoWriter, Error> {
+        let path = path.as_ref();
+        let is_gif = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        let filter = match is_gif {
+            true => "split[a][b];[a]palettegen[p];[b][p]paletteuse",
+            // the usual H.264 profiles take frames of even sizes only
+            false => "pad=ceil(iw/2)*2:ceil(ih/2)*2,format=yuv420p",
+        };
+        let mut encoder = Command::new("ffmpeg")
+            .args(["-v", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-framerate", &frame_rate.to_string(), "-i", "-"])
+            .args(["-vf", filter])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        let writer = encoder.stdin.take().expect("the input is piped");
+        Ok(VideoWriter {
+            writer: Some(writer),
+            width,
+            height,
+            encoder: Some(encoder),
+        })
+    }
+}
+
+impl<W: Write> VideoWriter<W> {
+    // a writer of raw 24-bit RGB video (width x height x 3 bytes per frame, without padding), e.g. for the input of
+    // `ffmpeg -f rawvideo -pix_fmt rgb24`
+    pub fn to_raw(writer: W, width: u32, height: u32) -> VideoWriter<W> {
+        VideoWriter {
+            writer: Some(writer),
+            width,
+            height,
+            encoder: None,
+        }
+    }
+
+    pub fn write_frame(&mut self, frame: &RgbImage) -> Result<(), Error> {
+        if frame.dimensions() != (self.width, self.height) {
+            return Err(Error::FrameSize {
+                expected: (self.width, self.height),
+                actual: frame.dimensions(),
+            });
+        }
+        match &mut self.writer {
+            Some(writer) => Ok(writer.write_all(frame.as_raw())?),
+            None => Err(Error::Format("the video is finished")),
+        }
+    }
+
+    // End the video, and wait for ffmpeg to write the rest of it. Dropping the writer does the same, but without
+    // telling whether the video is complete.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        if let Some(mut encoder) = self.encoder.take() {
+            if !encoder.wait()?.success() {
+                return Err(Error::Io {
+                    kind: io::ErrorKind::Other,
+                    message: "ffmpeg could not encode the video".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for VideoWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+// the (width, height) of the first video stream of an input of ffmpeg, from ffprobe
+fn video_size(input: &[&OsStr]) -> Result<(u32, u32), Error> {
+    let size = probe(input, "stream=width,height")?;
+    let (width, height) = size
+        .split_once('x')
+        .ok_or(Error::Format("the file has no video stream"))?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(Error::Format("ffprobe reported no size for the video")),
+    }
+}
+
+// the frames per second of the first video stream of an input of ffmpeg, from ffprobe
+fn frame_rate(input: &[&OsStr]) -> Result<f32, Error> {
+    // a fraction, e.g. 30000/1001
+    let rate = probe(input, "stream=avg_frame_rate")?;
+    let (numerator, denominator) = rate.split_once('/').unwrap_or((&rate, "1"));
+    match (numerator.parse::<f32>(), denominator.parse::<f32>()) {
+        (Ok(numerator), Ok(denominator)) if numerator > 0.0 && denominator > 0.0 => {
+            Ok(numerator / denominator)
+        }
+        _ => Err(Error::Format(
+            "ffprobe reported no frame rate for the video",
+        )),
+    }
+}
+
+// the `entries` of the first video stream, separated by x
+fn probe(input: &[&OsStr], entries: &str) -> Result<String, Error> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", entries, "-of", "csv=p=0:s=x"])
+        .args(input)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::Io {
+            kind: io::ErrorKind::InvalidData,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Track the target in `init_box` of the first frame of a video through all of it, with the default settings and
+// a window of the larger side of the box. The predictions are those of the frames after the first one.
+pub fn track_video(path: impl AsRef<Path>, init_box: BBox) -> Result<Vec<Prediction>, Error> {
+    track_frames(VideoFrames::open(path)?, init_box, |_, _| Ok(()))
+}
+
+// Track a video like track_video(), and write it to `output` with the box and trail of the target drawn in, at the
+// frame rate of the video (see VideoWriter for the formats). The frames are gray, as the tracker sees them.
+pub fn track_video_annotated(
+    path: impl AsRef<Path>,
+    init_box: BBox,
+    output: impl AsRef<Path>,
+) -> Result<Vec<Prediction>, Error> {
+    let input = ["-i".as_ref(), path.as_ref().as_os_str()];
+    let frames = VideoFrames::decode(&input)?;
+    let (width, height) = frames.dimensions();
+    let mut writer = VideoWriter::create(output, width, height, frame_rate(&input)?)?;
+    let predictions = annotate_frames(frames, init_box, &mut writer)?;
+    writer.finish()?;
+    Ok(predictions)
+}
+
+fn annotate_frames<R: Read, W: Write>(
+    frames: VideoFrames<R>,
+    init_box: BBox,
+    writer: &mut VideoWriter<W>,
+) -> Result<Vec<Prediction>, Error> {
+    let mut overlay = Overlay::new();
+    track_frames(frames, init_box, |frame, prediction| {
+        let mut annotated = DynamicImage::ImageLuma8(frame.clone()).to_rgb8();
+        if let Some(prediction) = prediction {
+            overlay.draw(&mut annotated, &[(0, prediction.clone())]);
+        }
+        writer.write_frame(&annotated)
+    })
+}
+
+// `on_frame` sees every frame with its prediction (None for the first frame)
+fn track_frames<R: Read>(
+    mut frames: VideoFrames<R>,
+    init_box: BBox,
+    mut on_frame: impl FnMut(&GrayImage, Option<&Prediction>) -> Result<(), Error>,
+) -> Result<Vec<Prediction>, Error> {
+    let (width, height) = frames.dimensions();
+    let first = frames
+        .next()
+        .ok_or(Error::Format("the video has no frames"))??;
+    let mut tracker = MosseTracker::builder()
+        .frame_size(width, height)
+        .window_size(init_box.width.max(init_box.height).round() as u32)
+        .build()?;
+    tracker.retrain(&first, init_box)?;
+    on_frame(&first, None)?;
+    frames
+        .map(|frame| {
+            let frame = frame?;
+            let prediction = tracker.track(&frame)?;
+            on_frame(&frame, Some(&prediction))?;
+            Ok(prediction)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::frame;
+
+    #[test]
+    fn tracks_raw_video() {
+        let video: Vec<u8> = (0..6)
+            .flat_map(|i| frame((40 + 3 * i, 50)).into_raw())
+            .collect();
+        let frames = VideoFrames::from_raw(&video[..], 128, 96);
+        let predictions =
+            track_frames(frames, BBox::from_cxcywh(40.0, 50.0, 32.0, 32.0), |_, _| {
+                Ok(())
+            })
+            .unwrap();
+        let locations: Vec<(u32, u32)> = predictions.iter().map(|p| p.location).collect();
+        assert_eq!(
+            locations,
+            vec![(43, 50), (46, 50), (49, 50), (52, 50), (55, 50)]
+        );
+
+        // a video cut off in the middle of a frame
+        let mut frames = VideoFrames::from_raw(&video[..128 * 96 + 100], 128, 96);
+        assert!(frames.next().unwrap().is_ok());
+        assert!(matches!(frames.next(), Some(Err(Error::Format(_)))));
+
+        // with or without ffmpeg
+        assert!(VideoFrames::open("does-not-exist.mp4").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn decoder_failures_are_errors() {
+        // a decoder that gives up after a frame
+        let mut decoder = Command::new("sh")
+            .args(["-c", "head -c 12 /dev/zero; exit 3"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut frames = VideoFrames {
+            reader: decoder.stdout.take().unwrap(),
+            width: 4,
+            height: 3,
+            decoder: Some(decoder),
+        };
+        assert!(frames.next().unwrap().is_ok());
+        assert!(matches!(frames.next(), Some(Err(Error::Io { .. }))));
+        assert!(frames.next().is_none());
+    }
+
+    #[test]
+    fn writes_annotated_raw_video() {
+        let video: Vec<u8> = (0..4)
+            .flat_map(|i| frame((40 + 3 * i, 50)).into_raw())
+            .collect();
+        let mut output = Vec::new();
+        let mut writer = VideoWriter::to_raw(&mut output, 128, 96);
+        let predictions = annotate_frames(
+            VideoFrames::from_raw(&video[..], 128, 96),
+            BBox::from_cxcywh(40.0, 50.0, 32.0, 32.0),
+            &mut writer,
+        )
+        .unwrap();
+        assert!(matches!(
+            writer.write_frame(&RgbImage::new(64, 48)),
+            Err(Error::FrameSize { .. })
+        ));
+        writer.finish().unwrap();
+
+        assert_eq!(predictions.len(), 3);
+        let frames: Vec<RgbImage> = output
+            .chunks(128 * 96 * 3)
+            .map(|frame| RgbImage::from_raw(128, 96, frame.to_vec()).unwrap())
+            .collect();
+        assert_eq!(frames.len(), 4);
+        // the first frame as it is, the others with the box of the target (centered on 49,50 in the last one)
+        assert_eq!(frames[0].get_pixel(33, 34), &image::Rgb([20, 20, 20]));
+        assert_eq!(frames[3].get_pixel(33, 34), &image::Rgb([0, 255, 0]));
+    }
+}
@@ -0,0 +1,256 @@
+// VOT sequences (the dataset format of votchallenge.net), and the re-initialization protocol VOT scores short-term
+// trackers with (VOT2013 to VOT2019): the tracker starts on the ground truth box, and is counted as failed as soon
+// as its box doesn't overlap the ground truth at all. It is then initialized again 5 frames later.
+// - accuracy: the average overlap (IoU) of the boxes with the ground truth, leaving out the first 10 frames after
+//   every initialization, in which a tracker is still biased towards the box it was given.
+// - robustness: the number of failures.
+//
+// To be scored by the VOT toolkit itself, see examples/votchallenge.
+
+use crate::{BBox, Error, MosseTracker, MosseTrackerSettings, Prediction};
+use image::DynamicImage;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// the frames after a failure before the tracker is initialized again
+pub const SKIP_AFTER_FAILURE: usize = 5;
+// the frames after an initialization that the accuracy leaves out
+pub const BURN_IN: usize = 10;
+
+// a sequence: its frames, and the box of the target in every one of them
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    pub frames: Vec<PathBuf>,
+    // None where the target is not visible (NaN in groundtruth.txt)
+    pub groundtruth: Vec<Option<BBox>>,
+}
+
+impl Sequence {
+    // A sequence directory: groundtruth.txt, and the frames in color/ (VOT2017 and later) or next to it, in the order
+    // of their names. The ground truth is rectangles (x,y,width,height), or polygons (x1,y1,...,x4,y4), which are
+    // taken as their bounding boxes.
+    pub fn load(directory: impl AsRef<Path>) -> Result<Sequence, Error> {
+        let directory = directory.as_ref();
+        let groundtruth = fs::read_to_string(directory.join("groundtruth.txt"))?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_region)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let color = directory.join("color");
+        let images = if color.is_dir() {
+            color
+        } else {
+            directory.to_path_buf()
+        };
+        let mut frames: Vec<PathBuf> = fs::read_dir(images)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        frames.retain(|path| image::ImageFormat::from_path(path).is_ok());
+        frames.sort();
+
+        if frames.len() != groundtruth.len() {
+            return Err(Error::Format("the sequence has a box for every frame"));
+        }
+        Ok(Sequence {
+            frames,
+            groundtruth,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn frame(&self, index: usize) -> Result<DynamicImage, Error> {
+        image::open(&self.frames[index]).map_err(|error| Error::Io {
+            kind: io::ErrorKind::InvalidData,
+            message: format!("{}: {}", self.frames[index].display(), error),
+        })
+    }
+}
+
+fn parse_region(line: &str) -> Result<Option<BBox>, Error> {
+    let values = line
+        .split(',')
+        .map(|value| value.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Error::Format("a region is a rectangle or a polygon of numbers"))?;
+    if values.iter().any(|value| !value.is_finite()) {
+        return Ok(None);
+    }
+    match values.len() {
+        4 => Ok(Some(BBox::from_xywh(
+            values[0], values[1], values[2], values[3],
+        ))),
+        length if length >= 6 && length % 2 == 0 => {
+            let (mut min, mut max) = (
+                (f32::INFINITY, f32::INFINITY),
+                (f32::NEG_INFINITY, f32::NEG_INFINITY),
+            );
+            for point in values.chunks(2) {
+                min = (min.0.min(point[0]), min.1.min(point[1]));
+                max = (max.0.max(point[0]), max.1.max(point[1]));
+            }
+            Ok(Some(BBox::from_x1y1x2y2(min.0, min.1, max.0, max.1)))
+        }
+        _ => Err(Error::Format(
+            "a region is a rectangle or a polygon of numbers",
+        )),
+    }
+}
+
+// the outcome of a sequence under the re-initialization protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct VotReport {
+    // the average overlap with the ground truth, over the frames after the burn-in
+    pub accuracy: f32,
+    // how often the tracker lost the target
+    pub failures: usize,
+    // the overlap in every frame: None for the frames of an initialization, the frames skipped after a failure, and
+    // the frames without ground truth
+    pub overlaps: Vec<Option<f32>>,
+    // the frames the tracker failed in
+    pub failed_frames: Vec<usize>,
+}
+
+// Run a tracker through a sequence under the re-initialization protocol. The tracker is made with `settings` on
+// every initialization, with a window of the larger side of the ground truth box; its box is the one of the
+// initialization, moved and scaled with the target.
+pub fn run_reinitialization(
+    sequence: &Sequence,
+    settings: &MosseTrackerSettings,
+) -> Result<VotReport, Error> {
+    let mut overlaps = vec![None; sequence.len()];
+    let mut failed_frames = Vec::new();
+    let mut accuracy = (0.0, 0);
+
+    let mut index = 0;
+    while index < sequence.len() {
+        // the next frame to initialize on
+        let init_box = match sequence.groundtruth[index] {
+            Some(init_box) => init_box,
+            None => {
+                index += 1;
+                continue;
+            }
+        };
+        let frame = sequence.frame(index)?;
+        let mut tracker = tracker_for(&frame, init_box, settings)?;
+        let init = index;
+        index += 1;
+
+        while index < sequence.len() {
+            let prediction = tracker.track(&sequence.frame(index)?)?;
+            let truth = match sequence.groundtruth[index] {
+                Some(truth) => truth,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let overlap = reported_box(&prediction, init_box, tracker.window_size()).iou(&truth);
+            if overlap <= 0.0 {
+                failed_frames.push(index);
+                index += SKIP_AFTER_FAILURE;
+                break;
+            }
+            overlaps[index] = Some(overlap);
+            if index - init >= BURN_IN {
+                accuracy = (accuracy.0 + overlap, accuracy.1 + 1);
+            }
+            index += 1;
+        }
+    }
+
+    Ok(VotReport {
+        accuracy: if accuracy.1 > 0 {
+            accuracy.0 / accuracy.1 as f32
+        } else {
+            0.0
+        },
+        failures: failed_frames.len(),
+        overlaps,
+        failed_frames,
+    })
+}
+
+pub(crate) fn tracker_for(
+    frame: &DynamicImage,
+    init_box: BBox,
+    settings: &MosseTrackerSettings,
+) -> Result<MosseTracker, Error> {
+    let window_size = init_box.width.max(init_box.height).round().max(1.0) as u32;
+    let mut tracker = MosseTracker::builder()
+        .with(|own| *own = settings.clone())
+        .frame_size(frame.width(), frame.height())
+        .window_size(window_size)
+        .build()?;
+    tracker.retrain(frame, init_box.clamp(frame.width(), frame.height()))?;
+    Ok(tracker)
+}
+
+// the box of the initialization around the predicted position, scaled like the target
+pub(crate) fn reported_box(prediction: &Prediction, init_box: BBox, window_size: u32) -> BBox {
+    let (sx, sy) = (
+        prediction.size.0 / window_size as f32,
+        prediction.size.1 / window_size as f32,
+    );
+    BBox::from_cxcywh(
+        prediction.position.0,
+        prediction.position.1,
+        init_box.width * sx,
+        init_box.height * sy,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::frame;
+
+    #[test]
+    fn runs_the_reinitialization_protocol() {
+        // the target moves steadily, and jumps across the frame once
+        let centers: Vec<(u32, u32)> = (0..30)
+            .map(|i| match i < 15 {
+                true => (30 + i, 40),
+                false => (90 + i, 60),
+            })
+            .collect();
+        let directory = std::env::temp_dir().join(format!("mosse-vot-{}", std::process::id()));
+        fs::create_dir_all(directory.join("color")).unwrap();
+        let mut groundtruth = String::new();
+        for (i, &center) in centers.iter().enumerate() {
+            frame(center)
+                .save(directory.join("color").join(format!("{:08}.png", i + 1)))
+                .unwrap();
+            // polygons, as in VOT2016
+            let (x, y) = (center.0 as f32 - 16.0, center.1 as f32 - 16.0);
+            let corners = [x, y, x + 32.0, y, x + 32.0, y + 32.0, x, y + 32.0];
+            groundtruth += &format!("{:?}\n", corners).replace(['[', ']', ' '], "");
+        }
+        fs::write(directory.join("groundtruth.txt"), groundtruth).unwrap();
+
+        let sequence = Sequence::load(&directory).unwrap();
+        assert_eq!(sequence.len(), 30);
+        assert_eq!(
+            sequence.groundtruth[0],
+            Some(BBox::from_xywh(14.0, 24.0, 32.0, 32.0))
+        );
+
+        let report = run_reinitialization(&sequence, &MosseTrackerSettings::default()).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        // the jump is a failure, after which the tracker skips 5 frames, and starts over on frame 20
+        assert_eq!(report.failed_frames, vec![15]);
+        assert_eq!(report.failures, 1);
+        assert!(report.overlaps[16..=20].iter().all(Option::is_none));
+        assert!(report.overlaps[21..].iter().all(Option::is_some));
+        assert!(report.accuracy > 0.8, "{:?}", report);
+    }
+}
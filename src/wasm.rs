@@ -1,5 +1,11 @@
-use crate::{MosseTrackerSettings, MultiMosseTracker};
-use image::Rgba;
+// The tracker for JavaScript (with the wasm feature, on wasm32): MultiMosseTrackerJS tracks PNG-encoded frames and
+// draws the predictions, MosseTrackerJS tracks one target on the pixels of a canvas.
+
+use crate::{
+    FrameView, Grayscale, MosseTracker, MosseTrackerSettings, MultiMosseTracker, Prediction,
+    TrackingStatus,
+};
+use image::{Rgb, Rgba};
 use imageproc::drawing::{draw_cross_mut, draw_hollow_rect_mut, draw_text_mut};
 use imageproc::rect::Rect;
 use rusttype::{Font, Scale};
@@ -23,6 +29,7 @@ impl MultiMosseTrackerJS {
             regularization: 0.002,
             learning_rate: 0.05,
             psr_threshold,
+            ..Default::default()
         };
         let desperation_threshold = 4;
         let multi_tracker = MultiMosseTracker::new(settings, desperation_threshold);
@@ -32,16 +39,19 @@ impl MultiMosseTrackerJS {
     }
 
     #[wasm_bindgen]
-    pub fn set_target(&mut self, x: u32, y: u32, img_data: &[u8]) {
-        let img = image::load_from_memory_with_format(img_data, image::ImageFormat::Png).unwrap();
+    pub fn set_target(&mut self, x: u32, y: u32, img_data: &[u8]) -> Result<(), JsValue> {
+        let img = image::load_from_memory_with_format(img_data, image::ImageFormat::Png)
+            .map_err(to_js)?;
         self.tracker
-            .add_or_replace_target(1, (x, y), &img.to_luma8());
+            .add_or_replace_target(1, (x, y), &img)
+            .map_err(to_js)
     }
 
     #[wasm_bindgen]
-    pub fn track(&mut self, img_data: &[u8]) -> Vec<u8> {
-        let image = image::load_from_memory_with_format(img_data, image::ImageFormat::Png).unwrap();
-        let predictions = self.tracker.track(&image.to_luma8());
+    pub fn track(&mut self, img_data: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let image = image::load_from_memory_with_format(img_data, image::ImageFormat::Png)
+            .map_err(to_js)?;
+        let predictions = self.tracker.track(&image).map_err(to_js)?;
         let mut img_copy = image.to_rgba8();
         for (obj_id, pred) in predictions.iter() {
             let mut color = Rgba([125u8, 255u8, 0u8, 0u8]);
@@ -73,8 +83,8 @@ impl MultiMosseTrackerJS {
             draw_text_mut(
                 &mut img_copy,
                 Rgba([125u8, 255u8, 0u8, 0u8]),
-                (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2)).try_into().unwrap(),
+                pred.location.0.saturating_sub(window_size / 2) as i32,
+                pred.location.1.saturating_sub(window_size / 2) as i32,
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("#{}", obj_id),
@@ -83,10 +93,8 @@ impl MultiMosseTrackerJS {
             draw_text_mut(
                 &mut img_copy,
                 color,
-                (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2) + FONT_SCALE as u32)
-                    .try_into()
-                    .unwrap(),
+                pred.location.0.saturating_sub(window_size / 2) as i32,
+                (pred.location.1.saturating_sub(window_size / 2) + FONT_SCALE as u32) as i32,
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("PSR: {:.2}", pred.psr),
@@ -99,7 +107,115 @@ impl MultiMosseTrackerJS {
                 &mut std::io::Cursor::new(&mut image_data),
                 image::ImageFormat::Png,
             )
-            .unwrap();
-        image_data
+            .map_err(to_js)?;
+        Ok(image_data)
+    }
+}
+
+fn to_js(error: impl ToString) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+// A tracker for one target on RGBA frames, such as the `data` (a Uint8ClampedArray) of the ImageData of a canvas.
+// The alpha channel is ignored, and the frames are tracked in grayscale.
+#[wasm_bindgen]
+pub struct MosseTrackerJS {
+    tracker: MosseTracker,
+    width: u32,
+    height: u32,
+}
+
+#[wasm_bindgen]
+impl MosseTrackerJS {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32, window_size: u32) -> Result<MosseTrackerJS, JsValue> {
+        let tracker = MosseTracker::builder()
+            .frame_size(width, height)
+            .window_size(window_size)
+            .build()
+            .map_err(to_js)?;
+        Ok(MosseTrackerJS {
+            tracker,
+            width,
+            height,
+        })
+    }
+
+    // train on the target centered on (x, y)
+    #[wasm_bindgen]
+    pub fn train(&mut self, data: &[u8], x: u32, y: u32) -> Result<(), JsValue> {
+        let frame = self.frame(data)?;
+        self.tracker
+            .train(&Grayscale(&frame), (x, y))
+            .map_err(to_js)
+    }
+
+    // locate the target in the next frame, and learn from it
+    #[wasm_bindgen]
+    pub fn track(&mut self, data: &[u8]) -> Result<PredictionJS, JsValue> {
+        let frame = self.frame(data)?;
+        let prediction = self.tracker.track(&Grayscale(&frame)).map_err(to_js)?;
+        Ok(PredictionJS::from(&prediction))
+    }
+
+    // The tracker as bytes (see MosseTracker::save), e.g. to move it to a Web Worker: the Uint8Array can be
+    // transferred with postMessage(bytes, [bytes.buffer]), and load() continues tracking where this one left off.
+    #[wasm_bindgen]
+    pub fn save(&self) -> Result<Vec<u8>, JsValue> {
+        let mut bytes = Vec::new();
+        self.tracker.write_to(&mut bytes).map_err(to_js)?;
+        Ok(bytes)
+    }
+
+    // a tracker saved with save()
+    #[wasm_bindgen]
+    pub fn load(bytes: &[u8]) -> Result<MosseTrackerJS, JsValue> {
+        let tracker = MosseTracker::read_from(&mut &bytes[..]).map_err(to_js)?;
+        let settings = tracker.settings();
+        let (width, height) = (settings.width, settings.height);
+        Ok(MosseTrackerJS {
+            tracker,
+            width,
+            height,
+        })
+    }
+}
+
+impl MosseTrackerJS {
+    fn frame<'a>(&self, data: &'a [u8]) -> Result<FrameView<'a, Rgb<u8>>, JsValue> {
+        FrameView::new(data, self.width, self.height, self.width as usize * 4, 4).map_err(to_js)
+    }
+}
+
+// a Prediction for JavaScript: an object with the center, size and confidence of the target
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct PredictionJS {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub angle: f32,
+    pub psr: f32,
+    pub confidence: f32,
+    pub lost: bool,
+    pub occluded: bool,
+    pub frame_index: u32,
+}
+
+impl From<&Prediction> for PredictionJS {
+    fn from(prediction: &Prediction) -> Self {
+        PredictionJS {
+            x: prediction.position.0,
+            y: prediction.position.1,
+            width: prediction.size.0,
+            height: prediction.size.1,
+            angle: prediction.angle,
+            psr: prediction.psr,
+            confidence: prediction.confidence,
+            lost: prediction.status == TrackingStatus::Lost,
+            occluded: prediction.status == TrackingStatus::Occluded,
+            frame_index: prediction.frame_index,
+        }
     }
 }